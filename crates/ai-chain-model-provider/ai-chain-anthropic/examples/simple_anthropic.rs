@@ -0,0 +1,31 @@
+use ai_chain::executor;
+use ai_chain::options;
+use ai_chain::options::Options;
+use ai_chain::prompt;
+use ai_chain::traits::Executor;
+use ai_chain::Parameters;
+use ai_chain_anthropic::model::Model;
+use std::error::Error;
+
+/// This example demonstrates how to use the ai-chain-anthropic crate to chat with Claude.
+///
+/// Usage: ANTHROPIC_API_KEY=... cargo run --package ai-chain-anthropic --example simple_anthropic
+///
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let opts = options!(
+        Model: Model::Claude3Haiku,
+        MaxTokens: 200usize,
+        Temperature: 0.7
+    );
+    let exec = executor!(anthropic, opts)?;
+    let prompt = prompt!(
+        "You are a robot assistant for making personalized greetings",
+        "Make a personalized greeting for Joe"
+    )
+    .format(&Parameters::new())?;
+    let res = exec.execute(Options::empty(), &prompt).await?;
+    println!("{}", res);
+    println!("{:?}", exec.usage());
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! Request and response types of the Messages API.
+use ai_chain::prompt::{ChatRole, Data, Prompt};
+use ai_chain::tools::{ToolDescription, ToolInvocationInput};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub(crate) struct Message {
+    pub role: &'static str,
+    pub content: String,
+}
+
+/// A tool offered to the model, in the shape the Messages API expects.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    /// A JSON schema describing the tool's input object.
+    pub input_schema: Value,
+}
+
+impl From<&ToolDescription> for ToolDefinition {
+    /// Every input key of the tool becomes a required string property described by its purpose.
+    fn from(tool: &ToolDescription) -> Self {
+        let properties: Map<String, Value> = tool
+            .input_format
+            .parts
+            .iter()
+            .map(|part| {
+                (
+                    part.key.clone(),
+                    json!({ "type": "string", "description": part.purpose }),
+                )
+            })
+            .collect();
+        let required: Vec<&String> = properties.keys().collect();
+        let input_schema = json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        });
+        Self {
+            name: tool.name.clone(),
+            description: format!("{} {}", tool.description, tool.description_context)
+                .trim()
+                .to_string(),
+            input_schema,
+        }
+    }
+}
+
+impl From<ToolDescription> for ToolDefinition {
+    fn from(tool: ToolDescription) -> Self {
+        Self::from(&tool)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct MessagesRequest<'a> {
+    pub model: String,
+    pub max_tokens: usize,
+    pub messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub tools: &'a [ToolDefinition],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum ContentBlock {
+    Text { text: String },
+    ToolUse { name: String, input: Value },
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub(crate) struct ApiUsage {
+    #[serde(default)]
+    pub input_tokens: usize,
+    #[serde(default)]
+    pub output_tokens: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct MessagesResponse {
+    pub content: Vec<ContentBlock>,
+    #[serde(default)]
+    pub usage: ApiUsage,
+}
+
+impl MessagesResponse {
+    /// Joins the text blocks of the response, rendering tool calls as tool invocations.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .map(|block| match block {
+                ContentBlock::Text { text } => text.clone(),
+                ContentBlock::ToolUse { name, input } => render_tool_use(name, input),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ApiError {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ErrorResponse {
+    pub error: ApiError,
+}
+
+/// Renders a `tool_use` block as the YAML tool invocation understood by
+/// `ToolCollection::process_chat_input`.
+pub(crate) fn render_tool_use(name: &str, input: &Value) -> String {
+    let invocation = ToolInvocationInput {
+        command: name.to_string(),
+        input: serde_yaml::to_value(input).unwrap_or(serde_yaml::Value::Null),
+    };
+    let yaml = serde_yaml::to_string(&invocation).unwrap_or_default();
+    format!("\n```yaml\n{}```\n", yaml)
+}
+
+/// The user turn put in front of a conversation that starts with the assistant.
+const LEADING_USER_TURN: &str = "Continue the conversation.";
+
+/// Splits a prompt into the system prompt and the alternating user/assistant turns.
+///
+/// System messages are joined into the system prompt wherever they appear, consecutive messages
+/// of the same role are merged and a conversation that doesn't start with the user gets a
+/// placeholder user turn, since the API requires messages to alternate starting with `user` and
+/// rejects empty content.
+pub(crate) fn format_prompt(prompt: &Prompt) -> (Option<String>, Vec<Message>) {
+    let chat = match prompt {
+        Data::Text(text) => {
+            return (
+                None,
+                vec![Message {
+                    role: "user",
+                    content: text.clone(),
+                }],
+            )
+        }
+        Data::Chat(chat) => chat,
+    };
+
+    let mut system: Vec<&str> = Vec::new();
+    let mut messages: Vec<Message> = Vec::new();
+    for message in chat.iter() {
+        let role = match message.role() {
+            ChatRole::System => {
+                system.push(message.body());
+                continue;
+            }
+            ChatRole::Assistant => "assistant",
+            ChatRole::User | ChatRole::Other(_) => "user",
+        };
+        match messages.last_mut() {
+            Some(last) if last.role == role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(message.body());
+            }
+            _ => messages.push(Message {
+                role,
+                content: message.body().clone(),
+            }),
+        }
+    }
+    if messages.first().map(|m| m.role) != Some("user") {
+        messages.insert(
+            0,
+            Message {
+                role: "user",
+                content: LEADING_USER_TURN.to_string(),
+            },
+        );
+    }
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    (system, messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use ai_chain::prompt::{ChatMessage, ChatMessageCollection};
+
+    use super::*;
+
+    #[test]
+    fn test_format_prompt_extracts_system_and_merges_turns() {
+        let chat = ChatMessageCollection::for_vector(vec![
+            ChatMessage::system("Be brief.".to_string()),
+            ChatMessage::user("Hi".to_string()),
+            ChatMessage::new(ChatRole::Other("tool".to_string()), "result".to_string()),
+            ChatMessage::assistant("Hello".to_string()),
+            ChatMessage::system("Answer in English.".to_string()),
+        ]);
+        let (system, messages) = format_prompt(&Data::Chat(chat));
+        assert_eq!(system.unwrap(), "Be brief.\n\nAnswer in English.");
+        assert_eq!(
+            messages,
+            vec![
+                Message {
+                    role: "user",
+                    content: "Hi\n\nresult".to_string()
+                },
+                Message {
+                    role: "assistant",
+                    content: "Hello".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_prompt_starts_assistant_first_chat_with_user() {
+        let chat = ChatMessageCollection::for_vector(vec![
+            ChatMessage::system("Be brief.".to_string()),
+            ChatMessage::assistant("How can I help?".to_string()),
+            ChatMessage::user("Hi".to_string()),
+        ]);
+        let (_, messages) = format_prompt(&Data::Chat(chat));
+        assert_eq!(
+            messages,
+            vec![
+                Message {
+                    role: "user",
+                    content: LEADING_USER_TURN.to_string()
+                },
+                Message {
+                    role: "assistant",
+                    content: "How can I help?".to_string()
+                },
+                Message {
+                    role: "user",
+                    content: "Hi".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_tool_use_is_a_tool_invocation() {
+        let rendered = render_tool_use("bash", &json!({"cmd": "ls"}));
+        let invocation: Vec<ToolInvocationInput> = ai_chain::parsing::find_yaml(&rendered).unwrap();
+        assert_eq!(invocation[0].command, "bash");
+        assert_eq!(invocation[0].input["cmd"], "ls");
+    }
+}
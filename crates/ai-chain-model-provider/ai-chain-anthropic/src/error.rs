@@ -0,0 +1,14 @@
+use thiserror::Error;
+
+/// Errors from talking to the Anthropic Messages API.
+#[derive(Debug, Error)]
+pub enum AnthropicError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Anthropic API returned {kind}: {message}")]
+    Api { kind: String, message: String },
+    #[error("Malformed event stream: {0}")]
+    Stream(String),
+}
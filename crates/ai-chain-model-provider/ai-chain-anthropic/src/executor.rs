@@ -0,0 +1,490 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+
+use ai_chain::options::{Opt, OptDiscriminants, Options, OptionsCascade};
+use ai_chain::output::{Output, StreamSegment};
+use ai_chain::prompt::{ChatMessage, ChatMessageCollection, ChatRole, Prompt};
+use ai_chain::tokens::{PromptTokensError, TokenCollection, TokenCount, Tokenizer, TokenizerError};
+use ai_chain::traits::{ExecutorCreationError, ExecutorError};
+
+use crate::api::{format_prompt, ErrorResponse, MessagesRequest, MessagesResponse, ToolDefinition};
+use crate::error::AnthropicError;
+use crate::model::Model;
+use crate::stream::EventDecoder;
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// The Messages API requires `max_tokens`; this is used when `Opt::MaxTokens` isn't set.
+const DEFAULT_MAX_TOKENS: usize = 4096;
+
+/// Token usage reported by the API, summed over every request made by an executor.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub requests: usize,
+    pub input_tokens: usize,
+    pub output_tokens: usize,
+}
+
+impl Usage {
+    fn record(&mut self, input_tokens: usize, output_tokens: usize) {
+        self.requests += 1;
+        self.input_tokens += input_tokens;
+        self.output_tokens += output_tokens;
+    }
+}
+
+/// The `Executor` for Claude models, talking to the Anthropic Messages API.
+#[derive(Clone)]
+pub struct Executor {
+    client: reqwest::Client,
+    /// The per-invocation options for this executor.
+    options: Options,
+    api_key: String,
+    base_url: String,
+    tools: Vec<ToolDefinition>,
+    usage: Arc<Mutex<Usage>>,
+}
+
+impl Executor {
+    /// Sends requests to `base_url` instead of `https://api.anthropic.com`.
+    pub fn with_base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Offers `tools` to the model.
+    ///
+    /// When the model decides to call a tool, the call is returned in the output as a YAML tool
+    /// invocation, so it can be handed straight to `ToolCollection::process_chat_input`.
+    pub fn with_tools<I, T>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<ToolDefinition>,
+    {
+        self.tools.extend(tools.into_iter().map(Into::into));
+        self
+    }
+
+    /// Returns the token usage of all requests made so far.
+    pub fn usage(&self) -> Usage {
+        *self.usage.lock().unwrap()
+    }
+
+    fn get_model_from_invocation_options(&self, opts: &OptionsCascade) -> Model {
+        let Some(Opt::Model(model)) = opts.get(OptDiscriminants::Model) else {
+            return Model::default();
+        };
+        Model::from_str(&model.to_name()).unwrap()
+    }
+
+    fn cascade<'a>(&'a self, opts: Option<&'a Options>) -> OptionsCascade<'a> {
+        let mut v: Vec<&'a Options> = vec![&self.options];
+        if let Some(o) = opts {
+            v.push(o);
+        }
+        OptionsCascade::from_vec(v)
+    }
+
+    fn create_request(&self, opts: &OptionsCascade, prompt: &Prompt) -> MessagesRequest<'_> {
+        let (system, messages) = format_prompt(prompt);
+        let mut request = MessagesRequest {
+            model: self.get_model_from_invocation_options(opts).to_string(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            messages,
+            system,
+            stop_sequences: Vec::new(),
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            stream: opts.is_streaming(),
+            tools: &self.tools,
+        };
+        if let Some(Opt::MaxTokens(max_tokens)) = opts.get(OptDiscriminants::MaxTokens) {
+            request.max_tokens = *max_tokens;
+        }
+        if let Some(Opt::StopSequence(stop)) = opts.get(OptDiscriminants::StopSequence) {
+            request.stop_sequences = stop.clone();
+        }
+        if let Some(Opt::Temperature(temperature)) = opts.get(OptDiscriminants::Temperature) {
+            request.temperature = Some(*temperature);
+        }
+        if let Some(Opt::TopP(top_p)) = opts.get(OptDiscriminants::TopP) {
+            request.top_p = Some(*top_p);
+        }
+        if let Some(Opt::TopK(top_k)) = opts.get(OptDiscriminants::TopK) {
+            request.top_k = Some(*top_k);
+        }
+        request
+    }
+
+    async fn send(
+        &self,
+        request: &MessagesRequest<'_>,
+    ) -> Result<reqwest::Response, AnthropicError> {
+        let response = self
+            .client
+            .post(format!(
+                "{}/v1/messages",
+                self.base_url.trim_end_matches('/')
+            ))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(request)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().await?;
+        Err(match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(ErrorResponse { error }) => AnthropicError::Api {
+                kind: error.kind,
+                message: error.message,
+            },
+            Err(_) => AnthropicError::Api {
+                kind: status.to_string(),
+                message: body,
+            },
+        })
+    }
+
+    fn stream_to_output(&self, response: reqwest::Response) -> Output {
+        let (sender, output) = Output::new_stream();
+        let usage = self.usage.clone();
+        tokio::spawn(async move {
+            let mut decoder = EventDecoder::default();
+            let mut body = response.bytes_stream();
+            while let Some(chunk) = body.next().await {
+                let segments = chunk
+                    .map_err(AnthropicError::from)
+                    .and_then(|chunk| decoder.push(&chunk));
+                match segments {
+                    Ok(segments) => {
+                        for segment in segments {
+                            if sender.send(segment).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let _ =
+                            sender.send(StreamSegment::Err(ExecutorError::InnerError(e.into())));
+                        return;
+                    }
+                }
+            }
+            if let Err(e) = decoder.finish() {
+                let _ = sender.send(StreamSegment::Err(ExecutorError::InnerError(e.into())));
+                return;
+            }
+            usage
+                .lock()
+                .unwrap()
+                .record(decoder.usage.input_tokens, decoder.usage.output_tokens);
+        });
+        output
+    }
+}
+
+#[async_trait]
+impl ai_chain::traits::Executor for Executor {
+    type StepTokenizer<'a> = AnthropicTokenizer;
+
+    /// Creates a new `Executor` with the given options.
+    ///
+    /// The API key is taken from `Opt::ApiKey` or the `ANTHROPIC_API_KEY` environment variable and
+    /// the base url from `ANTHROPIC_BASE_URL` if it is set.
+    fn new_with_options(options: Options) -> Result<Self, ExecutorCreationError> {
+        let opts = OptionsCascade::new().with_options(&options);
+        let api_key = match opts.get(OptDiscriminants::ApiKey) {
+            Some(Opt::ApiKey(api_key)) => api_key.clone(),
+            _ => std::env::var("ANTHROPIC_API_KEY").map_err(|_| {
+                ExecutorCreationError::FieldRequiredError("ANTHROPIC_API_KEY".to_string())
+            })?,
+        };
+        let base_url =
+            std::env::var("ANTHROPIC_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Ok(Self {
+            client: reqwest::Client::new(),
+            options,
+            api_key,
+            base_url,
+            tools: Vec::new(),
+            usage: Arc::new(Mutex::new(Usage::default())),
+        })
+    }
+
+    async fn execute(&self, options: &Options, prompt: &Prompt) -> Result<Output, ExecutorError> {
+        let opts = self.cascade(Some(options));
+        let request = self.create_request(&opts, prompt);
+        let response = self
+            .send(&request)
+            .await
+            .map_err(|e| ExecutorError::InnerError(e.into()))?;
+        if request.stream {
+            return Ok(self.stream_to_output(response));
+        }
+        let response: MessagesResponse = response
+            .json()
+            .await
+            .map_err(|e| ExecutorError::InnerError(e.into()))?;
+        self.usage
+            .lock()
+            .unwrap()
+            .record(response.usage.input_tokens, response.usage.output_tokens);
+        let mut col = ChatMessageCollection::new();
+        col.add_message(ChatMessage::new(ChatRole::Assistant, response.text()));
+        Ok(Output::new_immediate(col.into()))
+    }
+
+    fn tokens_used(
+        &self,
+        options: &Options,
+        prompt: &Prompt,
+    ) -> Result<TokenCount, PromptTokensError> {
+        let tokenizer = self
+            .get_tokenizer(options)
+            .map_err(|_| PromptTokensError::UnableToCompute)?;
+        let (system, messages) = format_prompt(prompt);
+        let mut tokens_used = 0;
+        for text in system
+            .iter()
+            .chain(messages.iter().map(|message| &message.content))
+        {
+            tokens_used += tokenizer
+                .tokenize_str(text)
+                .map_err(|_| PromptTokensError::UnableToCompute)?
+                .len();
+        }
+        Ok(TokenCount::new(
+            self.max_tokens_allowed(options),
+            tokens_used as i32,
+        ))
+    }
+
+    /// Get the context size from `Opt::MaxContextSize` or else from the model.
+    fn max_tokens_allowed(&self, options: &Options) -> i32 {
+        let opts = self.cascade(Some(options));
+        let context_size = match opts.get(OptDiscriminants::MaxContextSize) {
+            Some(Opt::MaxContextSize(size)) => *size,
+            _ => self.get_model_from_invocation_options(&opts).context_size(),
+        };
+        context_size.try_into().unwrap_or(i32::MAX)
+    }
+
+    fn answer_prefix(&self, _prompt: &Prompt) -> Option<String> {
+        None
+    }
+
+    fn get_tokenizer(&self, _options: &Options) -> Result<AnthropicTokenizer, TokenizerError> {
+        Ok(AnthropicTokenizer::new())
+    }
+}
+
+/// Anthropic doesn't publish the Claude 3 tokenizer, so token counts are approximated with the
+/// `cl100k_base` encoding.
+pub struct AnthropicTokenizer {
+    bpe: tiktoken_rs::CoreBPE,
+}
+
+impl AnthropicTokenizer {
+    pub fn new() -> Self {
+        Self {
+            bpe: tiktoken_rs::cl100k_base().expect("cl100k_base is bundled with tiktoken-rs"),
+        }
+    }
+}
+
+impl Default for AnthropicTokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tokenizer for AnthropicTokenizer {
+    fn tokenize_str(&self, doc: &str) -> Result<TokenCollection, TokenizerError> {
+        Ok(self.bpe.encode_ordinary(doc).into())
+    }
+
+    fn to_string(&self, tokens: TokenCollection) -> Result<String, TokenizerError> {
+        self.bpe
+            .decode(tokens.as_usize()?)
+            .map_err(|_| TokenizerError::ToStringError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ai_chain::options;
+    use ai_chain::prompt::Data;
+    use ai_chain::tools::tools::BashTool;
+    use ai_chain::tools::Tool;
+    use ai_chain::traits::Executor as _;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    use super::*;
+
+    fn executor(server: &mockito::Server, options: Options) -> Executor {
+        let mut builder = Options::builder();
+        builder.add_option(Opt::ApiKey("test-key".to_string()));
+        let mut exec = Executor::new_with_options(builder.build())
+            .unwrap()
+            .with_base_url(server.url());
+        exec.options = options;
+        exec
+    }
+
+    fn chat_prompt() -> Prompt {
+        Data::Chat(ChatMessageCollection::for_vector(vec![
+            ChatMessage::system("You are terse.".to_string()),
+            ChatMessage::user("Say hi".to_string()),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn test_execute_sends_system_prompt_and_options() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .match_header("x-api-key", "test-key")
+            .match_header("anthropic-version", ANTHROPIC_VERSION)
+            .match_body(Matcher::PartialJson(json!({
+                "model": "claude-3-haiku-20240307",
+                "max_tokens": 100,
+                "system": "You are terse.",
+                "messages": [{"role": "user", "content": "Say hi"}],
+                "stop_sequences": ["\n\nHuman:"],
+                "temperature": 0.5,
+            })))
+            .with_body(
+                json!({
+                    "id": "msg_1",
+                    "type": "message",
+                    "role": "assistant",
+                    "content": [{"type": "text", "text": "Hi."}],
+                    "stop_reason": "end_turn",
+                    "usage": {"input_tokens": 12, "output_tokens": 3}
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let exec = executor(
+            &server,
+            options!(
+                Model: Model::Claude3Haiku,
+                MaxTokens: 100usize,
+                StopSequence: vec!["\n\nHuman:".to_string()],
+                Temperature: 0.5
+            ),
+        );
+        let output = exec
+            .execute(Options::empty(), &chat_prompt())
+            .await
+            .unwrap()
+            .to_immediate()
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(output.primary_textual_output().unwrap(), "Hi.");
+        assert_eq!(
+            exec.usage(),
+            Usage {
+                requests: 1,
+                input_tokens: 12,
+                output_tokens: 3
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_streams_segments_and_tool_use() {
+        let events = [
+            json!({"type": "message_start", "message": {"id": "msg_1", "role": "assistant", "content": [], "usage": {"input_tokens": 20, "output_tokens": 1}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Let me "}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "check."}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "BashTool", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"cmd\": "}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "\"ls\"}"}}),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 15}}),
+            json!({"type": "message_stop"}),
+        ];
+        let body: String = events
+            .iter()
+            .map(|e| format!("event: {}\ndata: {}\n\n", e["type"].as_str().unwrap(), e))
+            .collect();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .match_body(Matcher::PartialJson(json!({
+                "stream": true,
+                "tools": [{"name": "BashTool"}],
+            })))
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let exec =
+            executor(&server, options!(Stream: true)).with_tools([BashTool::new().description()]);
+        let mut stream = exec
+            .execute(Options::empty(), &chat_prompt())
+            .await
+            .unwrap()
+            .as_stream()
+            .await
+            .unwrap();
+
+        let mut role = None;
+        let mut text = String::new();
+        while let Some(segment) = stream.next().await {
+            match segment {
+                StreamSegment::Role(r) => role = Some(r),
+                StreamSegment::Content(c) => text.push_str(&c),
+                StreamSegment::Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        mock.assert_async().await;
+        assert_eq!(role, Some(ChatRole::Assistant));
+        assert!(text.starts_with("Let me check."));
+        let invocation: Vec<ai_chain::tools::ToolInvocationInput> =
+            ai_chain::parsing::find_yaml(&text).unwrap();
+        assert_eq!(invocation[0].command, "BashTool");
+        assert_eq!(invocation[0].input["cmd"], "ls");
+        assert_eq!(exec.usage().input_tokens, 20);
+        assert_eq!(exec.usage().output_tokens, 15);
+    }
+
+    #[tokio::test]
+    async fn test_execute_surfaces_api_errors() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/messages")
+            .with_status(400)
+            .with_body(
+                json!({"type": "error", "error": {"type": "invalid_request_error", "message": "max_tokens: Field required"}})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let exec = executor(&server, Options::default());
+        let err = exec
+            .execute(Options::empty(), &Data::text("Hi".to_string()))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("invalid_request_error"));
+    }
+}
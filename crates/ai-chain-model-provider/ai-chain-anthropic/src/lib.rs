@@ -0,0 +1,17 @@
+//! # ai-chain-anthropic
+//!
+//! Use Anthropic's Claude models with `ai-chain` through the [Messages API](https://docs.anthropic.com/en/api/messages).
+//!
+//! The executor reads the API key from `ANTHROPIC_API_KEY` (or `Opt::ApiKey`) and talks to
+//! `https://api.anthropic.com` unless `ANTHROPIC_BASE_URL` is set. System messages in a chat prompt
+//! are lifted into the request's `system` field, `Opt::Stream(true)` streams the answer as
+//! `StreamSegment`s and tools registered with [`Executor::with_tools`] are offered to the model.
+mod api;
+mod error;
+mod executor;
+pub mod model;
+mod stream;
+
+pub use api::ToolDefinition;
+pub use error::AnthropicError;
+pub use executor::{AnthropicTokenizer, Executor, Usage};
@@ -0,0 +1,93 @@
+use ai_chain::options::{ModelRef, Opt};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumString;
+
+/// The `Model` enum represents the Claude models available through the Messages API.
+///
+/// See <https://docs.anthropic.com/en/docs/about-claude/models> for more information.
+///
+/// # Example
+///
+/// ```
+/// use ai_chain_anthropic::model::Model;
+///
+/// let sonnet = Model::Claude35Sonnet;
+/// let custom_model = Model::Other("claude-3-5-sonnet-latest".to_string());
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize, EnumString, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Model {
+    /// Claude 3.5 Sonnet, the most intelligent model.
+    #[default]
+    #[strum(serialize = "claude-3-5-sonnet-20240620")]
+    Claude35Sonnet,
+
+    /// Claude 3 Opus, a powerful model for highly complex tasks.
+    #[strum(serialize = "claude-3-opus-20240229")]
+    Claude3Opus,
+
+    /// Claude 3 Sonnet, balancing intelligence and speed.
+    #[strum(serialize = "claude-3-sonnet-20240229")]
+    Claude3Sonnet,
+
+    /// Claude 3 Haiku, the fastest and most compact model.
+    #[strum(serialize = "claude-3-haiku-20240307")]
+    Claude3Haiku,
+
+    /// A variant that allows you to specify a custom model name as a string, in case new models
+    /// are introduced or you have access to specialized models.
+    #[strum(default)]
+    Other(String),
+}
+
+impl Model {
+    /// The context window of the model in tokens.
+    pub fn context_size(&self) -> usize {
+        200_000
+    }
+}
+
+impl std::fmt::Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Model::Claude35Sonnet => "claude-3-5-sonnet-20240620",
+            Model::Claude3Opus => "claude-3-opus-20240229",
+            Model::Claude3Sonnet => "claude-3-sonnet-20240229",
+            Model::Claude3Haiku => "claude-3-haiku-20240307",
+            Model::Other(model) => model,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Conversion from Model to ModelRef
+impl From<Model> for ModelRef {
+    fn from(value: Model) -> Self {
+        ModelRef::from_model_name(value.to_string())
+    }
+}
+
+/// Conversion from Model to Option
+impl From<Model> for Opt {
+    fn from(value: Model) -> Self {
+        Opt::Model(value.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_from_str_round_trip() {
+        let model = Model::from_str("claude-3-haiku-20240307").unwrap();
+        assert_eq!(model, Model::Claude3Haiku);
+        assert_eq!(model.to_string(), "claude-3-haiku-20240307");
+        assert_eq!(
+            Model::from_str("claude-next").unwrap(),
+            Model::Other("claude-next".to_string())
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Decoding of the server-sent events emitted by a streamed Messages API request.
+use ai_chain::output::StreamSegment;
+use ai_chain::prompt::ChatRole;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::api::{render_tool_use, ApiError, ApiUsage};
+use crate::error::AnthropicError;
+
+#[derive(Debug, Deserialize)]
+struct StartMessage {
+    #[serde(default)]
+    usage: ApiUsage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StartBlock {
+    Text {
+        #[serde(default)]
+        text: String,
+    },
+    ToolUse {
+        name: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta {
+        text: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event {
+    MessageStart {
+        message: StartMessage,
+    },
+    ContentBlockStart {
+        content_block: StartBlock,
+    },
+    ContentBlockDelta {
+        delta: BlockDelta,
+    },
+    ContentBlockStop {},
+    MessageDelta {
+        #[serde(default)]
+        usage: ApiUsage,
+    },
+    MessageStop {},
+    Ping {},
+    Error {
+        error: ApiError,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+/// Turns the raw bytes of an event stream into `StreamSegment`s.
+///
+/// Text deltas are forwarded as they arrive. Tool calls are buffered until their block ends and
+/// are then emitted as a single rendered tool invocation.
+#[derive(Default)]
+pub(crate) struct EventDecoder {
+    buffer: Vec<u8>,
+    tool: Option<(String, String)>,
+    pub usage: ApiUsage,
+}
+
+impl EventDecoder {
+    /// Feeds a chunk of the response body, returning the segments of all events it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<StreamSegment>, AnthropicError> {
+        // JSON payloads escape carriage returns, so dropping them normalizes CRLF line endings.
+        self.buffer.extend(chunk.iter().filter(|b| **b != b'\r'));
+        let mut segments = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let event: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            let event = String::from_utf8(event)
+                .map_err(|_| AnthropicError::Stream("event is not valid UTF-8".to_string()))?;
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(str::trim_start)
+                .collect();
+            if !data.is_empty() {
+                segments.extend(self.handle(&data.join("\n"))?);
+            }
+        }
+        Ok(segments)
+    }
+
+    /// Checks that the stream didn't end in the middle of an event.
+    pub fn finish(&self) -> Result<(), AnthropicError> {
+        if self.buffer.iter().all(u8::is_ascii_whitespace) {
+            Ok(())
+        } else {
+            Err(AnthropicError::Stream(
+                "the stream ended in the middle of an event".to_string(),
+            ))
+        }
+    }
+
+    fn handle(&mut self, data: &str) -> Result<Vec<StreamSegment>, AnthropicError> {
+        let event: Event = serde_json::from_str(data)
+            .map_err(|e| AnthropicError::Stream(format!("invalid event `{}`: {}", data, e)))?;
+        let segments = match event {
+            Event::MessageStart { message } => {
+                self.usage.input_tokens = message.usage.input_tokens;
+                self.usage.output_tokens = message.usage.output_tokens;
+                vec![StreamSegment::Role(ChatRole::Assistant)]
+            }
+            Event::ContentBlockStart { content_block } => match content_block {
+                StartBlock::Text { text } if !text.is_empty() => {
+                    vec![StreamSegment::Content(text)]
+                }
+                StartBlock::ToolUse { name } => {
+                    self.tool = Some((name, String::new()));
+                    vec![]
+                }
+                _ => vec![],
+            },
+            Event::ContentBlockDelta { delta } => match delta {
+                BlockDelta::TextDelta { text } => vec![StreamSegment::Content(text)],
+                BlockDelta::InputJsonDelta { partial_json } => {
+                    if let Some((_, json)) = self.tool.as_mut() {
+                        json.push_str(&partial_json);
+                    }
+                    vec![]
+                }
+                BlockDelta::Other => vec![],
+            },
+            Event::ContentBlockStop {} => match self.tool.take() {
+                Some((name, json)) => {
+                    let input: Value = if json.trim().is_empty() {
+                        Value::Object(Default::default())
+                    } else {
+                        serde_json::from_str(&json).map_err(|e| {
+                            AnthropicError::Stream(format!(
+                                "invalid input for tool `{}`: {}",
+                                name, e
+                            ))
+                        })?
+                    };
+                    vec![StreamSegment::Content(render_tool_use(&name, &input))]
+                }
+                None => vec![],
+            },
+            Event::MessageDelta { usage } => {
+                self.usage.output_tokens = usage.output_tokens;
+                vec![]
+            }
+            Event::Error { error } => {
+                return Err(AnthropicError::Api {
+                    kind: error.kind,
+                    message: error.message,
+                })
+            }
+            Event::MessageStop {} | Event::Ping {} | Event::Unknown => vec![],
+        };
+        Ok(segments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(segments: Vec<StreamSegment>) -> Vec<String> {
+        segments
+            .into_iter()
+            .filter_map(|s| match s {
+                StreamSegment::Content(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decoder_handles_events_split_across_chunks() {
+        let mut decoder = EventDecoder::default();
+        let first = decoder
+            .push(
+                b"event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,",
+            )
+            .unwrap();
+        assert!(first.is_empty());
+        let second = decoder
+            .push(b"\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\nevent: ping\ndata: {\"type\": \"ping\"}\n\n")
+            .unwrap();
+        assert_eq!(contents(second), vec!["Hi".to_string()]);
+    }
+
+    #[test]
+    fn test_decoder_keeps_multibyte_characters_split_across_chunks() {
+        let mut decoder = EventDecoder::default();
+        let event = "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"你好\"}}\r\n\r\n";
+        let (head, tail) = event.as_bytes().split_at(event.find('你').unwrap() + 1);
+        assert!(decoder.push(head).unwrap().is_empty());
+        assert_eq!(
+            contents(decoder.push(tail).unwrap()),
+            vec!["你好".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_decoder_reports_stream_errors() {
+        let mut decoder = EventDecoder::default();
+        let err = decoder
+            .push(b"event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n")
+            .unwrap_err();
+        assert!(matches!(err, AnthropicError::Api { kind, .. } if kind == "overloaded_error"));
+    }
+
+    #[test]
+    fn test_decoder_reports_malformed_events() {
+        let mut decoder = EventDecoder::default();
+        let err = decoder.push(b"data: {\"type\":\n\n").unwrap_err();
+        assert!(matches!(err, AnthropicError::Stream(_)));
+
+        let mut decoder = EventDecoder::default();
+        decoder
+            .push(b"data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"tool_use\",\"name\":\"bash\"}}\n\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"cmd\"}}\n\n")
+            .unwrap();
+        let err = decoder
+            .push(b"data: {\"type\":\"content_block_stop\",\"index\":0}\n\n")
+            .unwrap_err();
+        assert!(matches!(err, AnthropicError::Stream(_)));
+
+        let mut decoder = EventDecoder::default();
+        decoder.push(b"data: {\"type\":\"ping\"}\n\n").unwrap();
+        assert!(decoder.finish().is_ok());
+        decoder.push(b"data: {\"type\":").unwrap();
+        assert!(matches!(decoder.finish(), Err(AnthropicError::Stream(_))));
+    }
+}
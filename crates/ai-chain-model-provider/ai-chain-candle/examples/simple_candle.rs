@@ -0,0 +1,50 @@
+use ai_chain::executor;
+use ai_chain::options;
+use ai_chain::options::{ModelRef, Options};
+use ai_chain::output::StreamSegment;
+use futures::StreamExt;
+use std::io::Write;
+use std::{env::args, error::Error};
+
+use ai_chain::{prompt::Data, traits::Executor};
+
+/// This example demonstrates how to use the ai-chain-candle crate to stream text from a GGUF model
+/// running on the CPU.
+///
+/// Usage: cargo run --release --package ai-chain-candle --example simple_candle path/to/model.gguf
+///
+/// An optional second argument can be used to customize the prompt passed to the model.
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let raw_args: Vec<String> = args().collect();
+    let (model_path, prompt) = match &raw_args.len() {
+        2 => (raw_args[1].as_str(), "Rust is a cool programming language because"),
+        3 => (raw_args[1].as_str(), raw_args[2].as_str()),
+        _ => panic!("Usage: cargo run --release --example simple_candle <path to model> <optional prompt>"),
+    };
+
+    let exec = executor!(
+        candle,
+        options!(
+            Model: ModelRef::from_path(model_path),
+            MaxTokens: 128_usize,
+            Stream: true
+        )
+    )?;
+    let mut stream = exec
+        .execute(Options::empty(), &Data::Text(String::from(prompt)))
+        .await?
+        .as_stream()
+        .await?;
+    print!("{}", prompt);
+    while let Some(segment) = stream.next().await {
+        match segment {
+            StreamSegment::Content(text) => print!("{}", text),
+            StreamSegment::Err(e) => return Err(e.into()),
+            StreamSegment::Role(_) => {}
+        }
+        std::io::stdout().flush()?;
+    }
+    println!();
+    Ok(())
+}
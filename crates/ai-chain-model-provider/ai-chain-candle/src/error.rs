@@ -0,0 +1,16 @@
+use thiserror::Error;
+
+/// Errors from loading or running a GGUF model.
+#[derive(Debug, Error)]
+pub enum CandleError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Candle(#[from] candle_core::Error),
+    #[error("unsupported model architecture `{0}`")]
+    UnsupportedArchitecture(String),
+    #[error("unsupported tokenizer model `{0}`")]
+    UnsupportedTokenizer(String),
+    #[error("missing or invalid GGUF metadata `{0}`")]
+    Metadata(String),
+}
@@ -0,0 +1,351 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+
+use ai_chain::options;
+use ai_chain::options::{Opt, OptDiscriminants, Options, OptionsCascade};
+use ai_chain::output::{Output, StreamSegment};
+use ai_chain::prompt::{ChatRole, ChatTemplate, ChatTemplateError, Data, Prompt};
+use ai_chain::tokens::{PromptTokensError, TokenCount, TokenizerError};
+use ai_chain::traits::{ExecutorCreationError, ExecutorError};
+
+use crate::generate::{generate, GenerationParams};
+use crate::model::Model;
+use crate::tokenizer::CandleTokenizer;
+
+lazy_static! {
+    static ref DEFAULT_OPTIONS: Options = options!(
+        TopK: 40_i32,
+        TopP: 0.95,
+        RepeatPenalty: 1.1,
+        RepeatPenaltyLastN: 64_usize,
+        Temperature: 0.8
+    );
+}
+
+/// The `Executor` for quantized GGUF models running on the CPU.
+///
+/// The model is loaded from the path given by `Opt::Model`. Llama, Qwen and Gemma architectures
+/// are supported; the tokenizer is built from the vocabulary stored in the file.
+#[derive(Clone)]
+pub struct Executor {
+    model: Arc<Model>,
+    /// The per-invocation options for this executor.
+    options: Options,
+}
+
+impl Executor {
+    fn cascade<'a>(&'a self, opts: Option<&'a Options>) -> OptionsCascade<'a> {
+        let mut v: Vec<&'a Options> = vec![&DEFAULT_OPTIONS, &self.options];
+        if let Some(o) = opts {
+            v.push(o);
+        }
+        OptionsCascade::from_vec(v)
+    }
+
+    /// The context length from `Opt::MaxContextSize`, capped at what the model was trained for.
+    fn context_length(&self, opts: &OptionsCascade) -> usize {
+        match opts.get(OptDiscriminants::MaxContextSize) {
+            Some(Opt::MaxContextSize(size)) => (*size).min(self.model.context_length),
+            _ => self.model.context_length,
+        }
+    }
+
+    /// Encodes the prompt. Chat prompts are rendered with `Opt::ChatTemplate`, or the template
+    /// detected from the model file, and fall back to `Prompt::to_text` without either.
+    fn encode(
+        &self,
+        opts: &OptionsCascade,
+        prompt: &Prompt,
+    ) -> Result<(Vec<u32>, Option<ChatTemplate>), ChatTemplateError> {
+        let template = match opts.get(OptDiscriminants::ChatTemplate) {
+            Some(Opt::ChatTemplate(template)) => Some(template.clone()),
+            _ => self.model.chat_template.clone(),
+        };
+        match (prompt, template) {
+            (Data::Chat(_), Some(template)) => {
+                let text = template.format_prompt(prompt)?;
+                // The template writes its own BOS token when it has one.
+                let tokens = self
+                    .model
+                    .vocabulary
+                    .encode(&text, template.bos_token().is_empty());
+                Ok((tokens, Some(template)))
+            }
+            _ => Ok((self.model.vocabulary.encode(&prompt.to_text(), true), None)),
+        }
+    }
+}
+
+#[async_trait]
+impl ai_chain::traits::Executor for Executor {
+    type StepTokenizer<'a> = CandleTokenizer;
+
+    fn new_with_options(options: Options) -> Result<Self, ExecutorCreationError> {
+        let opts = OptionsCascade::new().with_options(&options);
+        let Some(Opt::Model(model)) = opts.get(OptDiscriminants::Model) else {
+            return Err(ExecutorCreationError::FieldRequiredError(
+                "model_path".to_string(),
+            ));
+        };
+        let model = Model::load(model.to_path())
+            .map_err(|e| ExecutorCreationError::InnerError(Box::new(e)))?;
+        Ok(Self {
+            model: Arc::new(model),
+            options,
+        })
+    }
+
+    /// Generates on a blocking thread, so tokens can be streamed while they are sampled. Chat
+    /// prompts that are rendered with a chat template also stop at its stop sequences.
+    async fn execute(&self, options: &Options, prompt: &Prompt) -> Result<Output, ExecutorError> {
+        let opts = self.cascade(Some(options));
+        let context_length = self.context_length(&opts);
+        let mut params = GenerationParams::from_options(&opts);
+        let (tokens, template) = self
+            .encode(&opts, prompt)
+            .map_err(|e| ExecutorError::InnerError(e.into()))?;
+        if let Some(template) = template {
+            params
+                .stop_sequences
+                .extend(template.stop_sequences().iter().cloned());
+        }
+        if tokens.len() >= context_length {
+            return Err(ExecutorError::ContextTooSmall);
+        }
+
+        let (sender, output) = Output::new_stream();
+        if let Data::Chat(_) = prompt {
+            let _ = sender.send(StreamSegment::Role(ChatRole::Assistant));
+        }
+        let model = self.model.clone();
+        let seed = rand::random();
+        tokio::task::spawn_blocking(move || {
+            let mut weights = model.weights.lock().unwrap_or_else(|e| e.into_inner());
+            let result = generate(
+                &mut weights,
+                &model.vocabulary,
+                context_length,
+                tokens,
+                params,
+                seed,
+                |text| sender.send(StreamSegment::Content(text)).is_ok(),
+            );
+            if let Err(e) = result {
+                let _ = sender.send(StreamSegment::Err(ExecutorError::InnerError(e.into())));
+            }
+        });
+
+        if opts.is_streaming() {
+            Ok(output)
+        } else {
+            Ok(Output::new_immediate(
+                output.to_immediate().await?.as_content(),
+            ))
+        }
+    }
+
+    fn tokens_used(
+        &self,
+        options: &Options,
+        prompt: &Prompt,
+    ) -> Result<TokenCount, PromptTokensError> {
+        let opts = self.cascade(Some(options));
+        let (tokens, _) = self
+            .encode(&opts, prompt)
+            .map_err(|_| PromptTokensError::UnableToCompute)?;
+        let tokens_used = tokens.len() as i32;
+        Ok(TokenCount::new(
+            self.max_tokens_allowed(options),
+            tokens_used,
+        ))
+    }
+
+    fn max_tokens_allowed(&self, options: &Options) -> i32 {
+        let opts = self.cascade(Some(options));
+        self.context_length(&opts).try_into().unwrap_or(i32::MAX)
+    }
+
+    fn answer_prefix(&self, _prompt: &Prompt) -> Option<String> {
+        None
+    }
+
+    fn get_tokenizer(&self, _options: &Options) -> Result<CandleTokenizer, TokenizerError> {
+        Ok(CandleTokenizer::new(self.model.vocabulary.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use ai_chain::options::{ModelRef, TokenBias};
+    use ai_chain::traits::Executor as _;
+    use candle_core::quantized::{gguf_file, GgmlDType, QTensor};
+    use candle_core::{Device, Tensor};
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::tokenizer::tests::sentencepiece_metadata;
+
+    /// Writes a tiny Llama model with deterministic weights and returns its path.
+    fn tiny_llama(name: &str) -> PathBuf {
+        let (vocab, embedding, hidden) = (17usize, 8usize, 16usize);
+        let tensor = |rows: usize, cols: usize, salt: f32| {
+            let values: Vec<f32> = (0..rows * cols)
+                .map(|i| ((i as f32 + salt) * 0.37).sin() * 0.5)
+                .collect();
+            let tensor = Tensor::from_vec(values, (rows, cols), &Device::Cpu).unwrap();
+            QTensor::quantize(&tensor, GgmlDType::F32).unwrap()
+        };
+        let ones = |len: usize| {
+            let tensor = Tensor::ones(len, candle_core::DType::F32, &Device::Cpu).unwrap();
+            QTensor::quantize(&tensor, GgmlDType::F32).unwrap()
+        };
+        let tensors = vec![
+            ("token_embd.weight", tensor(vocab, embedding, 0.0)),
+            ("output_norm.weight", ones(embedding)),
+            ("output.weight", tensor(vocab, embedding, 1.0)),
+            ("blk.0.attn_q.weight", tensor(embedding, embedding, 2.0)),
+            ("blk.0.attn_k.weight", tensor(embedding, embedding, 3.0)),
+            ("blk.0.attn_v.weight", tensor(embedding, embedding, 4.0)),
+            (
+                "blk.0.attn_output.weight",
+                tensor(embedding, embedding, 5.0),
+            ),
+            ("blk.0.ffn_gate.weight", tensor(hidden, embedding, 6.0)),
+            ("blk.0.ffn_up.weight", tensor(hidden, embedding, 7.0)),
+            ("blk.0.ffn_down.weight", tensor(embedding, hidden, 8.0)),
+            ("blk.0.attn_norm.weight", ones(embedding)),
+            ("blk.0.ffn_norm.weight", ones(embedding)),
+        ];
+        let mut metadata = sentencepiece_metadata();
+        metadata.insert(
+            "general.architecture".to_string(),
+            gguf_file::Value::String("llama".to_string()),
+        );
+        for (key, value) in [
+            ("llama.attention.head_count", 2u32),
+            ("llama.attention.head_count_kv", 2),
+            ("llama.block_count", 1),
+            ("llama.embedding_length", embedding as u32),
+            ("llama.rope.dimension_count", (embedding / 2) as u32),
+            ("llama.context_length", 64),
+        ] {
+            metadata.insert(key.to_string(), gguf_file::Value::U32(value));
+        }
+        metadata.insert(
+            "llama.attention.layer_norm_rms_epsilon".to_string(),
+            gguf_file::Value::F32(1e-5),
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "ai-chain-candle-{}-{}.gguf",
+            name,
+            std::process::id()
+        ));
+        let mut file = std::fs::File::create(&path).unwrap();
+        let metadata: Vec<(&str, &gguf_file::Value)> =
+            metadata.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        let tensors: Vec<(&str, &QTensor)> = tensors.iter().map(|(k, v)| (*k, v)).collect();
+        gguf_file::write(&mut file, &metadata, &tensors).unwrap();
+        path
+    }
+
+    fn executor(name: &str, options: Options) -> Executor {
+        let path = tiny_llama(name);
+        let mut exec = Executor::new_with_options(options!(
+            Model: ModelRef::from_path(path.to_str().unwrap())
+        ))
+        .unwrap();
+        std::fs::remove_file(path).unwrap();
+        exec.options = options;
+        exec
+    }
+
+    #[tokio::test]
+    async fn test_greedy_generation_is_deterministic_and_bounded() {
+        let bias = TokenBias::new(vec![(2i32.into(), f32::NEG_INFINITY)]);
+        let exec = executor("greedy", options!(Temperature: 0.0, TokenBias: bias));
+        let prompt = Data::text("hello".to_string());
+        let mut outputs = Vec::new();
+        for max_tokens in [0_usize, 3, 6, 6] {
+            let output = exec
+                .execute(&options!(MaxTokens: max_tokens), &prompt)
+                .await
+                .unwrap();
+            outputs.push(output.to_immediate().await.unwrap().as_content().to_text());
+        }
+        assert_eq!(outputs[0], "");
+        assert!(!outputs[1].is_empty());
+        assert!(outputs[2].starts_with(&outputs[1]));
+        assert_eq!(outputs[2], outputs[3]);
+        assert_eq!(exec.max_tokens_allowed(Options::empty()), 64);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_yields_segments_and_respects_context_size() {
+        let exec = executor(
+            "stream",
+            options!(Temperature: 0.0, MaxTokens: 4_usize, Stream: true),
+        );
+        let mut stream = exec
+            .execute(Options::empty(), &Data::text("hello".to_string()))
+            .await
+            .unwrap()
+            .as_stream()
+            .await
+            .unwrap();
+        while let Some(segment) = stream.next().await {
+            assert!(matches!(segment, StreamSegment::Content(_)));
+        }
+
+        let err = exec
+            .execute(
+                &options!(MaxContextSize: 2_usize),
+                &Data::text("hello hello".to_string()),
+            )
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, ExecutorError::ContextTooSmall));
+    }
+
+    #[tokio::test]
+    async fn test_chat_prompts_are_rendered_with_the_chat_template() {
+        let exec = executor("template", options!(Temperature: 0.0, MaxTokens: 4_usize));
+        let text = exec
+            .execute(Options::empty(), &Data::text("hello".to_string()))
+            .await
+            .unwrap()
+            .to_immediate()
+            .await
+            .unwrap()
+            .as_content()
+            .to_text();
+
+        let template = ChatTemplate::new("{% for m in messages %}{{ m.content }}{% endfor %}");
+        let chat = Data::Chat(ai_chain::prompt::ChatMessageCollection::for_vector(vec![
+            ai_chain::prompt::ChatMessage::user("hello".to_string()),
+        ]));
+        let options = options!(ChatTemplate: template);
+        let reply = exec
+            .execute(&options, &chat)
+            .await
+            .unwrap()
+            .to_immediate()
+            .await
+            .unwrap()
+            .as_content();
+        assert_eq!(reply.extract_last_body(), Some(&text));
+        assert_eq!(
+            exec.tokens_used(&options, &chat)
+                .unwrap()
+                .tokens_remaining(),
+            exec.tokens_used(Options::empty(), &Data::text("hello".to_string()))
+                .unwrap()
+                .tokens_remaining()
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! The token generation loop: sampling, stop sequences and incremental decoding.
+use ai_chain::options::{Opt, OptDiscriminants, OptionsCascade};
+use candle_core::{Device, Tensor};
+use candle_transformers::generation::{LogitsProcessor, Sampling};
+
+use crate::error::CandleError;
+use crate::model::Weights;
+use crate::tokenizer::Vocabulary;
+
+/// The sampling and stopping parameters of one generation, read from `Opt`s.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GenerationParams {
+    pub max_tokens: usize,
+    pub temperature: f32,
+    pub top_k: Option<usize>,
+    pub top_p: Option<f32>,
+    pub repeat_penalty: f32,
+    pub repeat_last_n: usize,
+    pub stop_sequences: Vec<String>,
+    pub token_bias: Vec<(u32, f32)>,
+}
+
+impl GenerationParams {
+    pub fn from_options(opts: &OptionsCascade) -> Self {
+        let mut params = Self {
+            max_tokens: usize::MAX,
+            temperature: 0.8,
+            top_k: None,
+            top_p: None,
+            repeat_penalty: 1.0,
+            repeat_last_n: 64,
+            stop_sequences: Vec::new(),
+            token_bias: Vec::new(),
+        };
+        if let Some(Opt::MaxTokens(max_tokens)) = opts.get(OptDiscriminants::MaxTokens) {
+            params.max_tokens = *max_tokens;
+        }
+        if let Some(Opt::Temperature(temperature)) = opts.get(OptDiscriminants::Temperature) {
+            params.temperature = *temperature;
+        }
+        if let Some(Opt::TopK(top_k)) = opts.get(OptDiscriminants::TopK) {
+            params.top_k = usize::try_from(*top_k).ok().filter(|k| *k > 0);
+        }
+        if let Some(Opt::TopP(top_p)) = opts.get(OptDiscriminants::TopP) {
+            params.top_p = Some(*top_p).filter(|p| *p < 1.0);
+        }
+        if let Some(Opt::RepeatPenalty(penalty)) = opts.get(OptDiscriminants::RepeatPenalty) {
+            params.repeat_penalty = *penalty;
+        }
+        if let Some(Opt::RepeatPenaltyLastN(last_n)) =
+            opts.get(OptDiscriminants::RepeatPenaltyLastN)
+        {
+            params.repeat_last_n = *last_n;
+        }
+        if let Some(Opt::StopSequence(stop)) = opts.get(OptDiscriminants::StopSequence) {
+            params.stop_sequences = stop.iter().filter(|s| !s.is_empty()).cloned().collect();
+        }
+        if let Some(Opt::TokenBias(bias)) = opts.get(OptDiscriminants::TokenBias) {
+            params.token_bias = bias
+                .iter()
+                .filter_map(|(token, bias)| {
+                    let id = token
+                        .to_i32()
+                        .and_then(|id| u32::try_from(id).ok())
+                        .or_else(|| token.to_usize().and_then(|id| u32::try_from(id).ok()))?;
+                    Some((id, *bias))
+                })
+                .collect();
+        }
+        params
+    }
+
+    fn sampling(&self) -> Sampling {
+        if self.temperature <= 0.0 {
+            return Sampling::ArgMax;
+        }
+        let temperature = self.temperature as f64;
+        match (self.top_k, self.top_p) {
+            (None, None) => Sampling::All { temperature },
+            (Some(k), None) => Sampling::TopK { k, temperature },
+            (None, Some(p)) => Sampling::TopP {
+                p: p as f64,
+                temperature,
+            },
+            (Some(k), Some(p)) => Sampling::TopKThenTopP {
+                k,
+                p: p as f64,
+                temperature,
+            },
+        }
+    }
+}
+
+/// Picks the next token from the logits of the model.
+pub(crate) struct Sampler {
+    processor: LogitsProcessor,
+    params: GenerationParams,
+}
+
+impl Sampler {
+    pub fn new(params: GenerationParams, seed: u64) -> Self {
+        Self {
+            processor: LogitsProcessor::from_sampling(seed, params.sampling()),
+            params,
+        }
+    }
+
+    /// Applies the token bias and the repeat penalty over the last tokens of `context`, then
+    /// samples.
+    pub fn sample(&mut self, mut logits: Vec<f32>, context: &[u32]) -> Result<u32, CandleError> {
+        for (id, bias) in &self.params.token_bias {
+            if let Some(logit) = logits.get_mut(*id as usize) {
+                *logit += bias;
+            }
+        }
+        let len = logits.len();
+        let mut logits = Tensor::from_vec(logits, len, &Device::Cpu)?;
+        if self.params.repeat_penalty != 1.0 {
+            let start = context.len().saturating_sub(self.params.repeat_last_n);
+            logits = candle_transformers::utils::apply_repeat_penalty(
+                &logits,
+                self.params.repeat_penalty,
+                &context[start..],
+            )?;
+        }
+        Ok(self.processor.sample(&logits)?)
+    }
+}
+
+/// Holds back generated text that might be the start of a stop sequence.
+pub(crate) struct StopSequences {
+    stops: Vec<String>,
+    pending: String,
+}
+
+impl StopSequences {
+    pub fn new(stops: Vec<String>) -> Self {
+        Self {
+            stops,
+            pending: String::new(),
+        }
+    }
+
+    /// Adds generated text, returning the text that can be released and whether a stop sequence
+    /// was generated. Text from the stop sequence on is never released.
+    pub fn push(&mut self, text: &str) -> (String, bool) {
+        self.pending.push_str(text);
+        let stop = self
+            .stops
+            .iter()
+            .filter_map(|stop| self.pending.find(stop.as_str()))
+            .min();
+        if let Some(pos) = stop {
+            self.pending.truncate(pos);
+            return (std::mem::take(&mut self.pending), true);
+        }
+        let held = self
+            .stops
+            .iter()
+            .flat_map(|stop| stop.char_indices().skip(1).map(move |(i, _)| &stop[..i]))
+            .filter(|prefix| self.pending.ends_with(prefix))
+            .map(str::len)
+            .max()
+            .unwrap_or(0);
+        let released = self.pending[..self.pending.len() - held].to_string();
+        self.pending.drain(..released.len());
+        (released, false)
+    }
+
+    /// Releases the text held back at the end of the generation.
+    pub fn finish(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Collects the bytes of generated tokens and releases them once they form valid UTF-8.
+#[derive(Default)]
+pub(crate) struct Utf8Buffer {
+    bytes: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.bytes.extend_from_slice(bytes);
+        let mut text = String::new();
+        loop {
+            match std::str::from_utf8(&self.bytes) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    self.bytes.clear();
+                    return text;
+                }
+                Err(e) => {
+                    let valid_up_to = e.valid_up_to();
+                    text.push_str(std::str::from_utf8(&self.bytes[..valid_up_to]).unwrap());
+                    match e.error_len() {
+                        // An incomplete character at the end, wait for the next token.
+                        None => {
+                            self.bytes.drain(..valid_up_to);
+                            return text;
+                        }
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            self.bytes.drain(..valid_up_to + len);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Generates a continuation of `prompt`, passing each piece of text to `on_text` as soon as it is
+/// decoded. Generation ends at an end-of-generation token, a stop sequence, `max_tokens`, a full
+/// context window or when `on_text` returns `false`. The prompt must be shorter than the context.
+pub(crate) fn generate<F>(
+    weights: &mut Weights,
+    vocabulary: &Vocabulary,
+    context_length: usize,
+    prompt: Vec<u32>,
+    params: GenerationParams,
+    seed: u64,
+    mut on_text: F,
+) -> Result<(), CandleError>
+where
+    F: FnMut(String) -> bool,
+{
+    let max_tokens = params.max_tokens;
+    let mut stops = StopSequences::new(params.stop_sequences.clone());
+    let mut sampler = Sampler::new(params, seed);
+    let mut utf8 = Utf8Buffer::default();
+    let mut tokens = prompt;
+
+    weights.clear_kv_cache();
+    let mut logits = weights.forward(&tokens, 0)?;
+    for _ in 0..max_tokens {
+        let next = sampler.sample(logits, &tokens)?;
+        if vocabulary.is_end_of_generation(next) {
+            break;
+        }
+        tokens.push(next);
+        let (text, stopped) = stops.push(&utf8.push(&vocabulary.token_bytes(next)));
+        if (!text.is_empty() && !on_text(text)) || stopped {
+            return Ok(());
+        }
+        if tokens.len() >= context_length {
+            break;
+        }
+        logits = weights.forward(&[next], tokens.len() - 1)?;
+    }
+    let rest = stops.finish();
+    if !rest.is_empty() {
+        on_text(rest);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ai_chain::options;
+
+    use super::*;
+
+    #[test]
+    fn test_stop_sequences_hold_back_partial_matches() {
+        let mut stops = StopSequences::new(vec!["\nUser:".to_string()]);
+        assert_eq!(stops.push("Hello"), ("Hello".to_string(), false));
+        assert_eq!(stops.push(" there\nUs"), (" there".to_string(), false));
+        assert_eq!(stops.push("ually"), ("\nUsually".to_string(), false));
+        assert_eq!(stops.push("\nUser: hi"), (String::new(), true));
+    }
+
+    #[test]
+    fn test_utf8_buffer_waits_for_complete_characters() {
+        let mut buffer = Utf8Buffer::default();
+        let bytes = "你好".as_bytes();
+        assert_eq!(buffer.push(&bytes[..2]), "");
+        assert_eq!(buffer.push(&bytes[2..4]), "你");
+        assert_eq!(buffer.push(&bytes[4..]), "好");
+    }
+
+    #[test]
+    fn test_token_bias_and_greedy_sampling() {
+        let opts = options!(
+            Temperature: 0.0,
+            TokenBias: ai_chain::options::TokenBias::new(vec![(1i32.into(), -100.0), (2i32.into(), 5.0)])
+        );
+        let params = GenerationParams::from_options(&OptionsCascade::new().with_options(&opts));
+        assert_eq!(params.token_bias, vec![(1, -100.0), (2, 5.0)]);
+        let mut sampler = Sampler::new(params, 0);
+        assert_eq!(sampler.sample(vec![1.0, 9.0, 4.0], &[]).unwrap(), 2);
+    }
+}
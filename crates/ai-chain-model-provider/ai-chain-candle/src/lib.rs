@@ -0,0 +1,18 @@
+//! # ai-chain-candle
+//!
+//! Run quantized GGUF models on the CPU with `ai-chain`, using the pure-Rust
+//! [candle](https://github.com/huggingface/candle) inference library.
+//!
+//! Set `Opt::Model` to the path of a `.gguf` file of a Llama, Qwen or Gemma 3 model. Tokens are
+//! streamed as they are sampled when `Opt::Stream(true)` is set, and the sampling options
+//! (`Temperature`, `TopK`, `TopP`, `RepeatPenalty`, `RepeatPenaltyLastN`, `TokenBias`,
+//! `StopSequence`, `MaxTokens` and `MaxContextSize`) are taken from the options.
+mod error;
+mod executor;
+mod generate;
+mod model;
+mod tokenizer;
+
+pub use error::CandleError;
+pub use executor::Executor;
+pub use tokenizer::{CandleTokenizer, Vocabulary};
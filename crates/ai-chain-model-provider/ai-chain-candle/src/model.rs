@@ -0,0 +1,106 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use ai_chain::prompt::ChatTemplate;
+use candle_core::quantized::gguf_file;
+use candle_core::{DType, Device, Tensor};
+use candle_transformers::models::{
+    quantized_gemma3, quantized_llama, quantized_qwen2, quantized_qwen3,
+};
+
+use crate::error::CandleError;
+use crate::tokenizer::Vocabulary;
+
+/// Used when the GGUF metadata doesn't declare the context length of the model.
+const DEFAULT_CONTEXT_LENGTH: usize = 4096;
+
+/// The weights of a quantized model, by architecture, together with its KV cache.
+pub(crate) enum Weights {
+    Llama(quantized_llama::ModelWeights),
+    Qwen2(quantized_qwen2::ModelWeights),
+    Qwen3(quantized_qwen3::ModelWeights),
+    Gemma(quantized_gemma3::ModelWeights),
+}
+
+impl Weights {
+    /// Starts a new sequence. The Llama, Qwen2 and Gemma 3 models drop their cache themselves when
+    /// they are run from position 0.
+    pub fn clear_kv_cache(&mut self) {
+        if let Weights::Qwen3(model) = self {
+            model.clear_kv_cache();
+        }
+    }
+
+    /// Runs the model on `input`, the tokens following the first `index_pos` tokens of the
+    /// sequence, and returns the logits for the next token.
+    pub fn forward(&mut self, input: &[u32], index_pos: usize) -> Result<Vec<f32>, CandleError> {
+        let input = Tensor::new(input, &Device::Cpu)?.unsqueeze(0)?;
+        let logits = match self {
+            Weights::Llama(model) => model.forward(&input, index_pos)?,
+            Weights::Qwen2(model) => model.forward(&input, index_pos)?,
+            Weights::Qwen3(model) => model.forward(&input, index_pos)?,
+            Weights::Gemma(model) => model.forward(&input, index_pos)?,
+        };
+        Ok(logits.flatten_all()?.to_dtype(DType::F32)?.to_vec1()?)
+    }
+}
+
+/// A GGUF model loaded on the CPU together with its vocabulary.
+///
+/// The weights are shared by all clones of an executor, so generations run one at a time.
+pub(crate) struct Model {
+    pub weights: Mutex<Weights>,
+    pub vocabulary: Arc<Vocabulary>,
+    pub context_length: usize,
+    /// The built-in template matching the `tokenizer.chat_template` of the file, if any.
+    pub chat_template: Option<ChatTemplate>,
+}
+
+impl Model {
+    /// Loads the model at `path`, picking the implementation from `general.architecture`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CandleError> {
+        let mut file = File::open(path)?;
+        let content = gguf_file::Content::read(&mut file)?;
+        let architecture = content
+            .metadata
+            .get("general.architecture")
+            .and_then(|v| v.to_string().ok())
+            .cloned()
+            .ok_or_else(|| CandleError::Metadata("general.architecture".to_string()))?;
+        let vocabulary = Arc::new(Vocabulary::from_metadata(&content.metadata)?);
+        let context_length = content
+            .metadata
+            .get(&format!("{}.context_length", architecture))
+            .and_then(|v| v.to_u32().ok())
+            .map_or(DEFAULT_CONTEXT_LENGTH, |v| v as usize);
+        let chat_template = content
+            .metadata
+            .get("tokenizer.chat_template")
+            .and_then(|v| v.to_string().ok())
+            .and_then(|template| ChatTemplate::detect(template));
+
+        let device = Device::Cpu;
+        let weights = match architecture.as_str() {
+            "llama" => Weights::Llama(quantized_llama::ModelWeights::from_gguf(
+                content, &mut file, &device,
+            )?),
+            "qwen2" => Weights::Qwen2(quantized_qwen2::ModelWeights::from_gguf(
+                content, &mut file, &device,
+            )?),
+            "qwen3" => Weights::Qwen3(quantized_qwen3::ModelWeights::from_gguf(
+                content, &mut file, &device,
+            )?),
+            "gemma3" => Weights::Gemma(
+                quantized_gemma3::ModelWeights::from_gguf(content, &mut file, &device)?,
+            ),
+            other => return Err(CandleError::UnsupportedArchitecture(other.to_string())),
+        };
+        Ok(Self {
+            weights: Mutex::new(weights),
+            vocabulary,
+            context_length,
+            chat_template,
+        })
+    }
+}
@@ -0,0 +1,620 @@
+//! A tokenizer built from the vocabulary stored in a GGUF file.
+//!
+//! GGUF files carry their tokenizer in the `tokenizer.ggml.*` metadata. Two kinds are
+//! supported: SentencePiece vocabularies with merge scores (`llama`, used by Llama 2 and Gemma)
+//! and byte-level BPE vocabularies with merge ranks (`gpt2`, used by Llama 3 and Qwen).
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+use ai_chain::tokens::{TokenCollection, Tokenizer, TokenizerError};
+use candle_core::quantized::gguf_file::Value;
+use fancy_regex::Regex;
+
+use crate::error::CandleError;
+
+const TOKEN_TYPE_CONTROL: i32 = 3;
+const TOKEN_TYPE_USER_DEFINED: i32 = 4;
+
+/// Tokens that end a turn in common chat formats, in addition to the declared EOS/EOT tokens.
+const END_OF_TURN_TOKENS: &[&str] = &["<|eot_id|>", "<|im_end|>", "<end_of_turn>", "<|end|>"];
+
+const LLAMA3_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+const QWEN2_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+const GPT2_PATTERN: &str =
+    r"'s|'t|'re|'ve|'m|'ll|'d| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+(?!\S)|\s+";
+
+enum Kind {
+    SentencePiece {
+        scores: Vec<f32>,
+        add_space_prefix: bool,
+        byte_tokens: HashMap<u8, u32>,
+        unknown: u32,
+    },
+    BytePair {
+        ranks: HashMap<(String, String), usize>,
+        pattern: Regex,
+        byte_chars: Vec<char>,
+        char_bytes: HashMap<char, u8>,
+    },
+}
+
+/// The vocabulary of a model, able to encode text to token ids and decode them back.
+pub struct Vocabulary {
+    kind: Kind,
+    tokens: Vec<String>,
+    token_types: Vec<i32>,
+    ids: HashMap<String, u32>,
+    /// Control and user defined tokens, longest first, which are matched verbatim in the text.
+    specials: Vec<(String, u32)>,
+    bos: Option<u32>,
+    add_bos: bool,
+    end_of_generation: Vec<u32>,
+}
+
+impl Vocabulary {
+    /// Reads the vocabulary from the metadata of a GGUF file.
+    pub fn from_metadata(metadata: &HashMap<String, Value>) -> Result<Self, CandleError> {
+        let get = |key: &str| metadata.get(key);
+        let missing = |key: &str| CandleError::Metadata(key.to_string());
+        let string = |key: &str| get(key).and_then(|v| v.to_string().ok()).cloned();
+        let id = |key: &str| get(key).and_then(|v| v.to_u32().ok());
+        let flag = |key: &str| get(key).and_then(|v| v.to_bool().ok());
+
+        let model =
+            string("tokenizer.ggml.model").ok_or_else(|| missing("tokenizer.ggml.model"))?;
+        let tokens: Vec<String> = get("tokenizer.ggml.tokens")
+            .and_then(|v| v.to_vec().ok())
+            .ok_or_else(|| missing("tokenizer.ggml.tokens"))?
+            .iter()
+            .map(|v| v.to_string().cloned())
+            .collect::<Result<_, _>>()
+            .map_err(|_| missing("tokenizer.ggml.tokens"))?;
+        let token_types: Vec<i32> = match get("tokenizer.ggml.token_type") {
+            Some(types) => types
+                .to_vec()
+                .and_then(|types| types.iter().map(Value::to_i32).collect())
+                .map_err(|_| missing("tokenizer.ggml.token_type"))?,
+            None => vec![1; tokens.len()],
+        };
+        let ids: HashMap<String, u32> = tokens
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.clone(), id as u32))
+            .collect();
+
+        let kind = match model.as_str() {
+            "llama" => {
+                let scores = match get("tokenizer.ggml.scores") {
+                    Some(scores) => scores
+                        .to_vec()
+                        .and_then(|scores| scores.iter().map(Value::to_f32).collect())
+                        .map_err(|_| missing("tokenizer.ggml.scores"))?,
+                    None => vec![0.0; tokens.len()],
+                };
+                let byte_tokens = (0..=255u8)
+                    .filter_map(|b| ids.get(&format!("<0x{:02X}>", b)).map(|id| (b, *id)))
+                    .collect();
+                Kind::SentencePiece {
+                    scores,
+                    add_space_prefix: flag("tokenizer.ggml.add_space_prefix").unwrap_or(true),
+                    byte_tokens,
+                    unknown: id("tokenizer.ggml.unknown_token_id").unwrap_or(0),
+                }
+            }
+            "gpt2" => {
+                let ranks = get("tokenizer.ggml.merges")
+                    .and_then(|v| v.to_vec().ok())
+                    .ok_or_else(|| missing("tokenizer.ggml.merges"))?
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(rank, merge)| {
+                        let (left, right) = merge.to_string().ok()?.split_once(' ')?;
+                        Some(((left.to_string(), right.to_string()), rank))
+                    })
+                    .collect();
+                let pattern = match string("tokenizer.ggml.pre").as_deref() {
+                    Some("qwen2") => QWEN2_PATTERN,
+                    Some("gpt2") | None => GPT2_PATTERN,
+                    Some(_) => LLAMA3_PATTERN,
+                };
+                let byte_chars = byte_chars();
+                let char_bytes = byte_chars
+                    .iter()
+                    .enumerate()
+                    .map(|(b, c)| (*c, b as u8))
+                    .collect();
+                Kind::BytePair {
+                    ranks,
+                    pattern: Regex::new(pattern).expect("the pre-tokenizer patterns are valid"),
+                    byte_chars,
+                    char_bytes,
+                }
+            }
+            other => return Err(CandleError::UnsupportedTokenizer(other.to_string())),
+        };
+
+        let mut specials: Vec<(String, u32)> = tokens
+            .iter()
+            .zip(&token_types)
+            .enumerate()
+            .filter(|(_, (token, kind))| {
+                !token.is_empty()
+                    && (**kind == TOKEN_TYPE_CONTROL || **kind == TOKEN_TYPE_USER_DEFINED)
+            })
+            .map(|(id, (token, _))| (token.clone(), id as u32))
+            .collect();
+        specials.sort_by_key(|(token, _)| std::cmp::Reverse(token.len()));
+
+        let mut end_of_generation: Vec<u32> =
+            ["tokenizer.ggml.eos_token_id", "tokenizer.ggml.eot_token_id"]
+                .iter()
+                .filter_map(|key| id(key))
+                .chain(
+                    END_OF_TURN_TOKENS
+                        .iter()
+                        .filter_map(|t| ids.get(*t).copied()),
+                )
+                .collect();
+        end_of_generation.sort_unstable();
+        end_of_generation.dedup();
+
+        let bos = id("tokenizer.ggml.bos_token_id");
+        let add_bos = flag("tokenizer.ggml.add_bos_token").unwrap_or(model == "llama");
+        Ok(Self {
+            kind,
+            tokens,
+            token_types,
+            ids,
+            specials,
+            bos,
+            add_bos,
+            end_of_generation,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Looks up the id of a token by its text.
+    pub fn token_id(&self, token: &str) -> Option<u32> {
+        self.ids.get(token).copied()
+    }
+
+    /// Whether generating `id` means the model has finished its answer.
+    pub fn is_end_of_generation(&self, id: u32) -> bool {
+        self.end_of_generation.contains(&id)
+    }
+
+    /// Encodes `text`, prefixed with the BOS token if the model expects one and `add_bos` is set.
+    ///
+    /// Special tokens written in the text, such as `<|im_start|>`, are encoded as themselves.
+    pub fn encode(&self, text: &str, add_bos: bool) -> Vec<u32> {
+        let mut ids = Vec::new();
+        if add_bos && self.add_bos {
+            ids.extend(self.bos);
+        }
+        let mut rest = text;
+        let mut first = true;
+        while !rest.is_empty() {
+            let next_special = self
+                .specials
+                .iter()
+                .filter_map(|(token, id)| rest.find(token.as_str()).map(|pos| (pos, token, *id)))
+                .min_by_key(|(pos, _, _)| *pos);
+            let (fragment, special) = match next_special {
+                Some((pos, token, id)) => {
+                    let fragment = &rest[..pos];
+                    rest = &rest[pos + token.len()..];
+                    (fragment, Some(id))
+                }
+                None => (std::mem::take(&mut rest), None),
+            };
+            if !fragment.is_empty() {
+                self.encode_fragment(fragment, first, &mut ids);
+            }
+            ids.extend(special);
+            first = false;
+        }
+        ids
+    }
+
+    fn encode_fragment(&self, text: &str, at_start: bool, ids: &mut Vec<u32>) {
+        match &self.kind {
+            Kind::SentencePiece {
+                scores,
+                add_space_prefix,
+                byte_tokens,
+                unknown,
+            } => {
+                let mut text = text.replace(' ', "\u{2581}");
+                if *add_space_prefix && at_start {
+                    text.insert(0, '\u{2581}');
+                }
+                for piece in sentencepiece_merge(&text, &self.ids, scores) {
+                    match self.ids.get(piece) {
+                        Some(id) => ids.push(*id),
+                        None => ids.extend(
+                            piece
+                                .bytes()
+                                .map(|b| byte_tokens.get(&b).copied().unwrap_or(*unknown)),
+                        ),
+                    }
+                }
+            }
+            Kind::BytePair {
+                ranks,
+                pattern,
+                byte_chars,
+                ..
+            } => {
+                for word in pattern.find_iter(text).filter_map(Result::ok) {
+                    let word: String = word
+                        .as_str()
+                        .bytes()
+                        .map(|b| byte_chars[b as usize])
+                        .collect();
+                    for piece in byte_pair_merge(&word, ranks) {
+                        match self.ids.get(&piece) {
+                            Some(id) => ids.push(*id),
+                            None => ids
+                                .extend(piece.chars().filter_map(|c| self.ids.get(&c.to_string()))),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the bytes a token stands for. Control tokens decode to nothing.
+    ///
+    /// A single token may hold part of a multi-byte character, so the bytes of consecutive
+    /// tokens need to be joined before they are decoded as UTF-8.
+    pub fn token_bytes(&self, id: u32) -> Vec<u8> {
+        let id = id as usize;
+        let Some(token) = self.tokens.get(id) else {
+            return Vec::new();
+        };
+        if self.token_types.get(id) == Some(&TOKEN_TYPE_CONTROL) {
+            return Vec::new();
+        }
+        match &self.kind {
+            Kind::SentencePiece { .. } => match parse_byte_token(token) {
+                Some(b) => vec![b],
+                None => token.replace('\u{2581}', " ").into_bytes(),
+            },
+            Kind::BytePair { char_bytes, .. } => {
+                if self.token_types.get(id) == Some(&TOKEN_TYPE_USER_DEFINED) {
+                    return token.as_bytes().to_vec();
+                }
+                let mut bytes = Vec::with_capacity(token.len());
+                for c in token.chars() {
+                    match char_bytes.get(&c) {
+                        Some(b) => bytes.push(*b),
+                        None => bytes.extend(c.to_string().as_bytes()),
+                    }
+                }
+                bytes
+            }
+        }
+    }
+
+    /// Decodes a sequence of tokens, undoing the space prefix added by `encode`.
+    pub fn decode(&self, ids: &[u32]) -> String {
+        let bytes: Vec<u8> = ids.iter().flat_map(|id| self.token_bytes(*id)).collect();
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        match &self.kind {
+            Kind::SentencePiece {
+                add_space_prefix: true,
+                ..
+            } => match text.strip_prefix(' ') {
+                Some(stripped) => stripped.to_string(),
+                None => text,
+            },
+            _ => text,
+        }
+    }
+}
+
+/// The GPT-2 mapping from bytes to printable characters used by byte-level BPE vocabularies.
+fn byte_chars() -> Vec<char> {
+    let printable = |b: u32| {
+        (b'!' as u32..=b'~' as u32).contains(&b)
+            || (0xA1..=0xAC).contains(&b)
+            || (0xAE..=0xFF).contains(&b)
+    };
+    let mut next = 256;
+    (0..256u32)
+        .map(|b| {
+            if printable(b) {
+                char::from_u32(b).unwrap()
+            } else {
+                next += 1;
+                char::from_u32(next - 1).unwrap()
+            }
+        })
+        .collect()
+}
+
+fn parse_byte_token(token: &str) -> Option<u8> {
+    let hex = token.strip_prefix("<0x")?.strip_suffix('>')?;
+    if hex.len() != 2 {
+        return None;
+    }
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// A candidate merge of two adjacent symbols, ordered by score and then leftmost first.
+struct Bigram {
+    score: f32,
+    left: usize,
+    size: usize,
+}
+
+impl PartialEq for Bigram {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Bigram {}
+
+impl PartialOrd for Bigram {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Bigram {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.left.cmp(&self.left))
+    }
+}
+
+/// Splits `text` into pieces by repeatedly merging the adjacent pair with the best score, like
+/// the SentencePiece BPE model does. Pieces that aren't in the vocabulary are single characters.
+fn sentencepiece_merge<'a>(
+    text: &'a str,
+    ids: &HashMap<String, u32>,
+    scores: &[f32],
+) -> Vec<&'a str> {
+    // Each symbol is a byte range of `text` in a linked list of the symbols that remain.
+    let starts: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+    let mut lens: Vec<usize> = text.chars().map(char::len_utf8).collect();
+    let n = starts.len();
+    let mut prev: Vec<Option<usize>> = (0..n).map(|i| i.checked_sub(1)).collect();
+    let mut next: Vec<Option<usize>> = (0..n).map(|i| (i + 1 < n).then_some(i + 1)).collect();
+
+    let mut queue = BinaryHeap::new();
+    let try_add = |queue: &mut BinaryHeap<Bigram>,
+                   starts: &[usize],
+                   lens: &[usize],
+                   left: usize,
+                   right: usize| {
+        let size = lens[left] + lens[right];
+        let piece = &text[starts[left]..starts[left] + size];
+        if let Some(id) = ids.get(piece) {
+            queue.push(Bigram {
+                score: scores.get(*id as usize).copied().unwrap_or(0.0),
+                left,
+                size,
+            });
+        }
+    };
+    for i in 1..n {
+        try_add(&mut queue, &starts, &lens, i - 1, i);
+    }
+    while let Some(Bigram { left, size, .. }) = queue.pop() {
+        let Some(right) = next[left] else { continue };
+        // Skip candidates made stale by an earlier merge.
+        if lens[left] == 0 || lens[left] + lens[right] != size {
+            continue;
+        }
+        lens[left] = size;
+        lens[right] = 0;
+        next[left] = next[right];
+        if let Some(after) = next[right] {
+            prev[after] = Some(left);
+        }
+        if let Some(before) = prev[left] {
+            try_add(&mut queue, &starts, &lens, before, left);
+        }
+        if let Some(after) = next[left] {
+            try_add(&mut queue, &starts, &lens, left, after);
+        }
+    }
+    let mut pieces = Vec::new();
+    let mut current = (n > 0).then_some(0);
+    while let Some(i) = current {
+        pieces.push(&text[starts[i]..starts[i] + lens[i]]);
+        current = next[i];
+    }
+    pieces
+}
+
+/// Splits a pre-tokenized word into pieces by repeatedly applying the lowest ranked merge.
+fn byte_pair_merge(word: &str, ranks: &HashMap<(String, String), usize>) -> Vec<String> {
+    let mut parts: Vec<String> = word.chars().map(String::from).collect();
+    loop {
+        let best = parts
+            .windows(2)
+            .enumerate()
+            .filter_map(|(i, pair)| {
+                ranks
+                    .get(&(pair[0].clone(), pair[1].clone()))
+                    .map(|rank| (*rank, i))
+            })
+            .min();
+        let Some((_, i)) = best else { break };
+        let right = parts.remove(i + 1);
+        parts[i].push_str(&right);
+    }
+    parts
+}
+
+/// The `Tokenizer` of a GGUF model, using the model's own vocabulary.
+#[derive(Clone)]
+pub struct CandleTokenizer {
+    vocabulary: Arc<Vocabulary>,
+}
+
+impl CandleTokenizer {
+    pub fn new(vocabulary: Arc<Vocabulary>) -> Self {
+        Self { vocabulary }
+    }
+}
+
+impl Tokenizer for CandleTokenizer {
+    fn tokenize_str(&self, doc: &str) -> Result<TokenCollection, TokenizerError> {
+        Ok(self
+            .vocabulary
+            .encode(doc, false)
+            .into_iter()
+            .map(|id| id as i32)
+            .collect::<Vec<_>>()
+            .into())
+    }
+
+    fn to_string(&self, tokens: TokenCollection) -> Result<String, TokenizerError> {
+        let ids: Vec<u32> = tokens.as_i32()?.into_iter().map(|id| id as u32).collect();
+        Ok(self.vocabulary.decode(&ids))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Value {
+        Value::Array(
+            values
+                .iter()
+                .map(|v| Value::String(v.to_string()))
+                .collect(),
+        )
+    }
+
+    /// A small SentencePiece vocabulary: `<unk>`, `<s>`, `</s>`, a few pieces and byte tokens.
+    pub(crate) fn sentencepiece_metadata() -> HashMap<String, Value> {
+        let tokens = [
+            "<unk>",
+            "<s>",
+            "</s>",
+            "\u{2581}",
+            "h",
+            "e",
+            "l",
+            "o",
+            "\u{2581}h",
+            "he",
+            "ll",
+            "\u{2581}he",
+            "\u{2581}hell",
+            "\u{2581}hello",
+            "<0xE4>",
+            "<0xBD>",
+            "<0xA0>",
+        ];
+        let types: Vec<i32> = tokens
+            .iter()
+            .map(|t| match *t {
+                "<unk>" => 2,
+                "<s>" | "</s>" => 3,
+                t if t.starts_with("<0x") => 6,
+                _ => 1,
+            })
+            .collect();
+        HashMap::from([
+            (
+                "tokenizer.ggml.model".to_string(),
+                Value::String("llama".to_string()),
+            ),
+            ("tokenizer.ggml.tokens".to_string(), strings(&tokens)),
+            (
+                "tokenizer.ggml.scores".to_string(),
+                Value::Array((0..tokens.len()).map(|i| Value::F32(i as f32)).collect()),
+            ),
+            (
+                "tokenizer.ggml.token_type".to_string(),
+                Value::Array(types.into_iter().map(Value::I32).collect()),
+            ),
+            ("tokenizer.ggml.bos_token_id".to_string(), Value::U32(1)),
+            ("tokenizer.ggml.eos_token_id".to_string(), Value::U32(2)),
+        ])
+    }
+
+    fn byte_pair_metadata() -> HashMap<String, Value> {
+        let tokens = [
+            "h",
+            "e",
+            "l",
+            "o",
+            "Ġ",
+            "w",
+            "r",
+            "d",
+            "he",
+            "ll",
+            "hell",
+            "hello",
+            "Ġw",
+            "Ġwor",
+            "or",
+            "<|im_end|>",
+        ];
+        let merges = ["h e", "l l", "he ll", "hell o", "Ġ w", "o r", "Ġw or"];
+        let types: Vec<Value> = tokens
+            .iter()
+            .map(|t| Value::I32(if t.starts_with("<|") { 3 } else { 1 }))
+            .collect();
+        HashMap::from([
+            (
+                "tokenizer.ggml.model".to_string(),
+                Value::String("gpt2".to_string()),
+            ),
+            (
+                "tokenizer.ggml.pre".to_string(),
+                Value::String("qwen2".to_string()),
+            ),
+            ("tokenizer.ggml.tokens".to_string(), strings(&tokens)),
+            ("tokenizer.ggml.merges".to_string(), strings(&merges)),
+            ("tokenizer.ggml.token_type".to_string(), Value::Array(types)),
+        ])
+    }
+
+    #[test]
+    fn test_sentencepiece_merges_by_score_and_round_trips() {
+        let vocabulary = Vocabulary::from_metadata(&sentencepiece_metadata()).unwrap();
+        let ids = vocabulary.encode("hello", true);
+        assert_eq!(ids, vec![1, vocabulary.token_id("\u{2581}hello").unwrap()]);
+        assert_eq!(vocabulary.decode(&ids), "hello");
+    }
+
+    #[test]
+    fn test_sentencepiece_falls_back_to_bytes() {
+        let vocabulary = Vocabulary::from_metadata(&sentencepiece_metadata()).unwrap();
+        let ids = vocabulary.encode("he你", false);
+        assert_eq!(&ids[ids.len() - 3..], &[14, 15, 16]);
+        assert_eq!(vocabulary.decode(&ids), "he你");
+    }
+
+    #[test]
+    fn test_byte_pair_merges_by_rank_and_parses_special_tokens() {
+        let vocabulary = Vocabulary::from_metadata(&byte_pair_metadata()).unwrap();
+        let id = |t: &str| vocabulary.token_id(t).unwrap();
+        let ids = vocabulary.encode("hello word<|im_end|>", true);
+        assert_eq!(
+            ids,
+            vec![id("hello"), id("Ġwor"), id("d"), id("<|im_end|>")]
+        );
+        assert_eq!(vocabulary.decode(&ids), "hello word");
+        assert!(vocabulary.is_end_of_generation(id("<|im_end|>")));
+    }
+}
@@ -198,18 +198,33 @@ extern "C" fn stream_token(
 
 impl GemmaContext {
     pub fn generate<'a>(&mut self, prompt: String, out: mpsc::UnboundedSender<StreamSegment>) {
+        if self.model_training != gcpp_ModelTraining_GEMMA_IT {
+            self.pos = 0
+        }
+        let mut prompt_text = if self.model_training == gcpp_ModelTraining_GEMMA_IT {
+            format!("<start_of_turn>{prompt}<end_of_turn><start_of_turn>model\n")
+        } else {
+            prompt
+        };
+        if self.pos > 0 {
+            prompt_text = format!("<end_of_turn>{prompt_text}");
+        }
+        self.generate_raw(prompt_text, out)
+    }
+
+    /// Generates from a prompt that was already rendered with a chat template. The rendered
+    /// prompt holds the whole conversation, so the previous turns are dropped from the KV cache.
+    pub fn generate_templated(
+        &mut self,
+        prompt: String,
+        out: mpsc::UnboundedSender<StreamSegment>,
+    ) {
+        self.pos = 0;
+        self.generate_raw(prompt, out)
+    }
+
+    fn generate_raw(&mut self, mut prompt_text: String, out: mpsc::UnboundedSender<StreamSegment>) {
         unsafe {
-            if self.model_training != gcpp_ModelTraining_GEMMA_IT {
-                self.pos = 0
-            }
-            let mut prompt_text = if self.model_training == gcpp_ModelTraining_GEMMA_IT {
-                format!("<start_of_turn>{prompt}<end_of_turn><start_of_turn>model\n")
-            } else {
-                prompt
-            };
-            if self.pos > 0 {
-                prompt_text = format!("<end_of_turn>{prompt_text}");
-            }
             let tokens = std_vector_int_vector();
             gcpp_Gemma_Encode(
                 self.gemma,
@@ -2,7 +2,7 @@ use crate::context::GemmaContext;
 use async_trait::async_trait;
 use ai_chain::options::{Opt, OptDiscriminants, Options};
 use ai_chain::output::Output;
-use ai_chain::prompt::Prompt;
+use ai_chain::prompt::{ChatTemplate, Data, Prompt};
 use ai_chain::tokens::{
     PromptTokensError, TokenCollection, TokenCount, Tokenizer, TokenizerError,
 };
@@ -13,6 +13,34 @@ use tokio;
 pub struct Executor {
     context: Arc<Mutex<GemmaContext>>,
     stream: bool,
+    chat_template: Option<ChatTemplate>,
+}
+
+impl Executor {
+    /// Renders chat prompts with `Opt::ChatTemplate`, from the invocation options or the ones the
+    /// executor was created with. Returns `None` when the built-in Gemma formatting applies.
+    fn render_chat(
+        &self,
+        options: &Options,
+        prompt: &Prompt,
+    ) -> Result<Option<String>, ExecutorError> {
+        let template = match options.get(OptDiscriminants::ChatTemplate) {
+            Some(Opt::ChatTemplate(template)) => Some(template),
+            _ => self.chat_template.as_ref(),
+        };
+        let (Data::Chat(_), Some(template)) = (prompt, template) else {
+            return Ok(None);
+        };
+        let text = template
+            .format_prompt(prompt)
+            .map_err(|e| ExecutorError::InnerError(e.into()))?;
+        // The BOS token is added by the tokenizer, not parsed from the text.
+        Ok(Some(
+            text.strip_prefix(template.bos_token())
+                .unwrap_or(&text)
+                .to_string(),
+        ))
+    }
 }
 
 #[async_trait]
@@ -28,6 +56,10 @@ impl ExecutorTrait for Executor {
             } else {
                 false
             },
+            chat_template: match options.get(OptDiscriminants::ChatTemplate) {
+                Some(Opt::ChatTemplate(template)) => Some(template.clone()),
+                _ => None,
+            },
         })
     }
 
@@ -40,16 +72,21 @@ impl ExecutorTrait for Executor {
         let (sender, stream) = Output::new_stream();
         let context = self.context.clone();
         let prompt_text = prompt.to_string();
+        let rendered = self.render_chat(options, prompt)?;
+        let generate = move |ctx: &mut GemmaContext| match rendered {
+            Some(text) => ctx.generate_templated(text, sender),
+            None => ctx.generate(prompt_text, sender),
+        };
         if is_stream {
             tokio::task::spawn_blocking(move || {
                 if let Ok(mut ctx) = context.lock() {
-                    ctx.generate(prompt_text, sender);
+                    generate(&mut *ctx);
                 }
             });
             return Ok(stream);
         } else {
             let mut ctx = context.lock().map_err(|_| ExecutorError::InvalidOptions)?;
-            ctx.generate(prompt_text, sender);
+            generate(&mut *ctx);
         }
         stream
             .to_immediate()
@@ -23,4 +23,4 @@
 
 pub mod chatgpt;
 pub mod embeddings;
-pub use async_openai;
+pub use ai_chain_openai_compatible::async_openai;
@@ -1,13 +1,13 @@
 use async_trait::async_trait;
 use lazy_static::lazy_static;
 use llm::{
-    load_progress_callback_stdout, InferenceError, InferenceParameters, InferenceRequest, Model,
-    ModelArchitecture, ModelParameters, TokenUtf8Buffer,
+    load_progress_callback_stdout, InferenceError, InferenceParameters, InferenceRequest,
+    InferenceSession, Model, ModelArchitecture, ModelParameters, TokenUtf8Buffer,
 };
 use ai_chain::options;
 use ai_chain::options::{options_from_env, Opt, OptDiscriminants, Options, OptionsCascade};
 use ai_chain::output::Output;
-use ai_chain::prompt::Prompt;
+use ai_chain::prompt::{Data, Prompt};
 use ai_chain::tokens::{
     PromptTokensError, TokenCollection, TokenCount, Tokenizer, TokenizerError,
 };
@@ -48,6 +48,64 @@ pub enum Error {
     EndOfText,
 }
 
+impl From<InferenceError> for Error {
+    fn from(e: InferenceError) -> Self {
+        match e {
+            InferenceError::ContextFull => Error::ContextFull,
+            InferenceError::EndOfText => Error::EndOfText,
+            InferenceError::TokenizationFailed => Error::TokenizationFailed,
+            InferenceError::UserCallback(_) => {
+                panic!("user callback error should not be possible")
+            }
+        }
+    }
+}
+
+impl Executor {
+    /// Feeds `prompt` to the session and samples the reply until the end of text, a full context
+    /// window or one of `stop_sequences`, which is left out of the reply.
+    fn generate_reply(
+        &self,
+        session: &mut InferenceSession,
+        parameters: &InferenceParameters,
+        prompt: &str,
+        stop_sequences: &[String],
+    ) -> Result<String, Error> {
+        let model = self.llm.as_ref();
+        session.feed_prompt(model, parameters, prompt, &mut Default::default(), |_| {
+            Ok::<(), Infallible>(())
+        })?;
+        let mut rng = rand::thread_rng();
+        let mut token_utf8_buf = TokenUtf8Buffer::new();
+        let mut reply = String::new();
+        loop {
+            let token = match session.infer_next_token(
+                model,
+                parameters,
+                &mut Default::default(),
+                &mut rng,
+            ) {
+                Ok(token) => token,
+                Err(InferenceError::EndOfText) => break,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(text) = token_utf8_buf.push(token) {
+                reply.push_str(&text);
+                let stop = stop_sequences
+                    .iter()
+                    .filter(|stop| !stop.is_empty())
+                    .filter_map(|stop| reply.find(stop.as_str()))
+                    .min();
+                if let Some(pos) = stop {
+                    reply.truncate(pos);
+                    break;
+                }
+            }
+        }
+        Ok(reply)
+    }
+}
+
 #[async_trait]
 impl ai_chain::traits::Executor for Executor {
     type StepTokenizer<'a> = LocalLlmTokenizer<'a>;
@@ -94,44 +152,61 @@ impl ai_chain::traits::Executor for Executor {
         Ok(Executor { llm, options })
     }
 
+    /// Text prompts are fed as they are and the output starts with the prompt. Chat prompts are
+    /// rendered with `Opt::ChatTemplate` when it is set, and the output is then only the reply,
+    /// cut at the first of the stop sequences of the template and `Opt::StopSequence`.
     async fn execute(&self, options: &Options, prompt: &Prompt) -> Result<Output, ExecutorError> {
         let opts = OptionsCascade::new()
             .with_options(&DEFAULT_OPTIONS)
             .with_options(&self.options)
             .with_options(options);
+        let template = match opts.get(OptDiscriminants::ChatTemplate) {
+            Some(Opt::ChatTemplate(template)) => Some(template.clone()),
+            _ => None,
+        };
+        let mut stop_sequences = match opts.get(OptDiscriminants::StopSequence) {
+            Some(Opt::StopSequence(stop)) => stop.clone(),
+            _ => Vec::new(),
+        };
+        let parameters =
+            inference_params_from_options(opts).map_err(|_| ExecutorError::InvalidOptions)?;
         let session = &mut self.llm.start_session(Default::default());
-        let mut output = String::new();
-        session
-            .infer::<Infallible>(
-                self.llm.as_ref(),
-                &mut rand::thread_rng(),
-                &InferenceRequest {
-                    prompt: prompt.to_text().as_str(),
-                    parameters: Some(
-                        &inference_params_from_options(opts)
-                            .map_err(|_| ExecutorError::InvalidOptions)?,
-                    ),
-                    // playback_previous_tokens
-                    // maximum_token_count
-                    ..Default::default()
-                },
-                // OutputRequest
-                &mut Default::default(),
-                |t| {
-                    output.push_str(t);
-
-                    Ok(())
-                },
-            )
-            .map_err(|e| match e {
-                InferenceError::ContextFull => Error::ContextFull,
-                InferenceError::EndOfText => Error::EndOfText,
-                InferenceError::TokenizationFailed => Error::TokenizationFailed,
-                InferenceError::UserCallback(_) => {
-                    panic!("user callback error should not be possible")
-                }
-            })
-            .map_err(|e| ExecutorError::InnerError(e.into()))?;
+        let output = match (prompt, template) {
+            (Data::Chat(_), Some(template)) => {
+                let text = template
+                    .format_prompt(prompt)
+                    .map_err(|e| ExecutorError::InnerError(e.into()))?;
+                // The session adds the BOS token of the vocabulary itself.
+                let text = text.strip_prefix(template.bos_token()).unwrap_or(&text);
+                stop_sequences.extend(template.stop_sequences().iter().cloned());
+                self.generate_reply(session, &parameters, text, &stop_sequences)
+            }
+            _ => {
+                let mut output = String::new();
+                session
+                    .infer::<Infallible>(
+                        self.llm.as_ref(),
+                        &mut rand::thread_rng(),
+                        &InferenceRequest {
+                            prompt: prompt.to_text().as_str(),
+                            parameters: Some(&parameters),
+                            // playback_previous_tokens
+                            // maximum_token_count
+                            ..Default::default()
+                        },
+                        // OutputRequest
+                        &mut Default::default(),
+                        |t| {
+                            output.push_str(t);
+
+                            Ok(())
+                        },
+                    )
+                    .map_err(Error::from)
+                    .map(|_| output)
+            }
+        }
+        .map_err(|e| ExecutorError::InnerError(e.into()))?;
         Ok(Output::new_immediate(Prompt::text(output)))
     }
 
@@ -0,0 +1,29 @@
+use ai_chain::executor;
+use ai_chain::options;
+use ai_chain::options::{ModelRef, Options};
+use ai_chain::prompt;
+use ai_chain::traits::Executor;
+use ai_chain::Parameters;
+use std::error::Error;
+
+/// This example demonstrates how to use the ai-chain-ollama crate with a model served by a local
+/// Ollama server. Pull the model first with `ollama pull llama3`.
+///
+/// Usage: cargo run --package ai-chain-ollama --example simple_ollama
+///
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let opts = options!(
+        Model: ModelRef::from_model_name("llama3"),
+        Temperature: 0.7
+    );
+    let exec = executor!(ollama, opts)?;
+    let prompt = prompt!(
+        "You are a robot assistant for making personalized greetings",
+        "Make a personalized greeting for Joe"
+    )
+    .format(&Parameters::new())?;
+    let res = exec.execute(Options::empty(), &prompt).await?;
+    println!("{}", res);
+    Ok(())
+}
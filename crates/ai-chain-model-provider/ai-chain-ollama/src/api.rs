@@ -0,0 +1,171 @@
+//! Request and response types of the Ollama REST API.
+use ai_chain::options::{Opt, OptDiscriminants, OptionsCascade};
+use ai_chain::prompt::{ChatRole, Conversation};
+use serde::{Deserialize, Serialize};
+
+/// The model options understood by Ollama, see the `options` field of the API.
+#[derive(Debug, Default, Clone, Serialize, PartialEq)]
+pub(crate) struct ModelOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_last_n: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_thread: Option<usize>,
+}
+
+impl ModelOptions {
+    pub fn from_options(opts: &OptionsCascade) -> Self {
+        let mut options = Self::default();
+        if let Some(Opt::Temperature(temperature)) = opts.get(OptDiscriminants::Temperature) {
+            options.temperature = Some(*temperature);
+        }
+        if let Some(Opt::TopK(top_k)) = opts.get(OptDiscriminants::TopK) {
+            options.top_k = Some(*top_k);
+        }
+        if let Some(Opt::TopP(top_p)) = opts.get(OptDiscriminants::TopP) {
+            options.top_p = Some(*top_p);
+        }
+        if let Some(Opt::RepeatPenalty(penalty)) = opts.get(OptDiscriminants::RepeatPenalty) {
+            options.repeat_penalty = Some(*penalty);
+        }
+        if let Some(Opt::RepeatPenaltyLastN(last_n)) =
+            opts.get(OptDiscriminants::RepeatPenaltyLastN)
+        {
+            options.repeat_last_n = Some(*last_n);
+        }
+        if let Some(Opt::StopSequence(stop)) = opts.get(OptDiscriminants::StopSequence) {
+            options.stop = Some(stop.clone());
+        }
+        if let Some(Opt::MaxContextSize(num_ctx)) = opts.get(OptDiscriminants::MaxContextSize) {
+            options.num_ctx = Some(*num_ctx);
+        }
+        if let Some(Opt::MaxTokens(max_tokens)) = opts.get(OptDiscriminants::MaxTokens) {
+            options.num_predict = Some(*max_tokens);
+        }
+        if let Some(Opt::NThreads(threads)) = opts.get(OptDiscriminants::NThreads) {
+            options.num_thread = Some(*threads);
+        }
+        options
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub(crate) struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+/// Converts a conversation to chat messages. Ollama only knows the `system`, `user` and
+/// `assistant` roles, so messages with any other role are sent as user messages.
+pub(crate) fn format_chat_messages(chat: &Conversation) -> Vec<Message> {
+    chat.iter()
+        .map(|message| Message {
+            role: match message.role() {
+                ChatRole::System => "system",
+                ChatRole::Assistant => "assistant",
+                ChatRole::User | ChatRole::Other(_) => "user",
+            }
+            .to_string(),
+            content: message.body().clone(),
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<Message>,
+    pub stream: bool,
+    pub options: ModelOptions,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct GenerateRequest {
+    pub model: String,
+    pub prompt: String,
+    pub stream: bool,
+    pub options: ModelOptions,
+}
+
+/// A response, or one line of a streamed response, from either `/api/chat` or `/api/generate`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct CompletionChunk {
+    /// Set by `/api/chat`.
+    #[serde(default)]
+    pub message: Option<Message>,
+    /// Set by `/api/generate`.
+    #[serde(default)]
+    pub response: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl CompletionChunk {
+    pub fn content(&self) -> Option<&str> {
+        self.message
+            .as_ref()
+            .map(|m| m.content.as_str())
+            .or(self.response.as_deref())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct EmbeddingsRequest<'a> {
+    pub model: &'a str,
+    pub prompt: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct EmbeddingsResponse {
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ErrorResponse {
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use ai_chain::options;
+
+    use super::*;
+
+    #[test]
+    fn test_model_options_from_options() {
+        let opts = options!(
+            Temperature: 0.2,
+            TopK: 40,
+            TopP: 0.9,
+            RepeatPenalty: 1.1,
+            StopSequence: vec!["</s>".to_string()],
+            MaxContextSize: 4096usize
+        );
+        let cascade = OptionsCascade::new().with_options(&opts);
+        let options = serde_json::to_value(ModelOptions::from_options(&cascade)).unwrap();
+        assert_eq!(
+            options,
+            serde_json::json!({
+                "temperature": 0.2f32,
+                "top_k": 40,
+                "top_p": 0.9f32,
+                "repeat_penalty": 1.1f32,
+                "stop": ["</s>"],
+                "num_ctx": 4096,
+            })
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use ai_chain::traits;
+use async_trait::async_trait;
+
+use crate::api::{EmbeddingsRequest, EmbeddingsResponse, ErrorResponse};
+use crate::error::OllamaError;
+
+const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+/// Embeddings computed by an embedding model served by Ollama.
+pub struct Embeddings {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+}
+
+impl Embeddings {
+    /// Uses `model`, which must already be pulled on the server.
+    pub fn for_model<S: Into<String>>(model: S) -> Self {
+        Self {
+            model: model.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Sends requests to `base_url` instead of the address from `OLLAMA_HOST`.
+    pub fn with_base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>, OllamaError> {
+        let response = self
+            .client
+            .post(format!(
+                "{}/api/embeddings",
+                self.base_url.trim_end_matches('/')
+            ))
+            .json(&EmbeddingsRequest {
+                model: &self.model,
+                prompt: text,
+            })
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(match serde_json::from_str::<ErrorResponse>(&body) {
+                Ok(ErrorResponse { error }) => OllamaError::Server(error),
+                Err(_) => OllamaError::Server(format!("{}: {}", status, body)),
+            });
+        }
+        let response: EmbeddingsResponse = serde_json::from_str(&body)?;
+        Ok(response.embedding)
+    }
+}
+
+impl Default for Embeddings {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: crate::base_url_from_env(),
+            model: DEFAULT_EMBEDDING_MODEL.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl traits::Embeddings for Embeddings {
+    type Error = OllamaError;
+
+    /// The embeddings endpoint takes a single prompt, so texts are embedded one request at a time.
+    async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Self::Error> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in &texts {
+            embeddings.push(self.embed(text).await?);
+        }
+        Ok(embeddings)
+    }
+
+    async fn embed_query(&self, query: String) -> Result<Vec<f32>, Self::Error> {
+        self.embed(&query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ai_chain::traits::Embeddings as _;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_embed_texts_sends_one_request_per_text() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/embeddings")
+            .match_body(Matcher::PartialJson(json!({"model": "all-minilm"})))
+            .with_body(json!({"embedding": [0.5, -0.25]}).to_string())
+            .expect(2)
+            .create_async()
+            .await;
+
+        let embeddings = Embeddings::for_model("all-minilm").with_base_url(server.url());
+        let vectors = embeddings
+            .embed_texts(vec!["one".to_string(), "two".to_string()])
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(vectors, vec![vec![0.5, -0.25], vec![0.5, -0.25]]);
+    }
+}
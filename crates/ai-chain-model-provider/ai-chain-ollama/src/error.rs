@@ -0,0 +1,14 @@
+use thiserror::Error;
+
+/// Errors from talking to an Ollama server.
+#[derive(Debug, Error)]
+pub enum OllamaError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Ollama returned an error: {0}")]
+    Server(String),
+}
+
+impl ai_chain::traits::EmbeddingsError for OllamaError {}
@@ -0,0 +1,450 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+
+use ai_chain::options::{Opt, OptDiscriminants, Options, OptionsCascade};
+use ai_chain::output::{Output, StreamSegment};
+use ai_chain::prompt::{ChatMessage, ChatMessageCollection, ChatRole, Data, Prompt};
+use ai_chain::tokens::{PromptTokensError, TokenCollection, TokenCount, Tokenizer, TokenizerError};
+use ai_chain::traits::{ExecutorCreationError, ExecutorError};
+
+use crate::api::{
+    format_chat_messages, ChatRequest, CompletionChunk, ErrorResponse, GenerateRequest,
+    ModelOptions,
+};
+use crate::error::OllamaError;
+
+const DEFAULT_MODEL: &str = "llama3";
+/// Ollama's own default for `num_ctx`, used when `Opt::MaxContextSize` isn't set.
+const DEFAULT_CONTEXT_SIZE: usize = 2048;
+
+/// The `Executor` for models served by Ollama.
+///
+/// Chat prompts are sent to `/api/chat` and text prompts to `/api/generate`.
+#[derive(Clone)]
+pub struct Executor {
+    client: reqwest::Client,
+    /// The per-invocation options for this executor.
+    options: Options,
+    base_url: String,
+}
+
+impl Executor {
+    /// Sends requests to `base_url` instead of the address from `OLLAMA_HOST`.
+    pub fn with_base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    fn get_model_from_invocation_options(&self, opts: &OptionsCascade) -> String {
+        match opts.get(OptDiscriminants::Model) {
+            Some(Opt::Model(model)) => model.to_name(),
+            _ => DEFAULT_MODEL.to_string(),
+        }
+    }
+
+    fn cascade<'a>(&'a self, opts: Option<&'a Options>) -> OptionsCascade<'a> {
+        let mut v: Vec<&'a Options> = vec![&self.options];
+        if let Some(o) = opts {
+            v.push(o);
+        }
+        OptionsCascade::from_vec(v)
+    }
+
+    async fn send(
+        &self,
+        opts: &OptionsCascade<'_>,
+        prompt: &Prompt,
+    ) -> Result<reqwest::Response, OllamaError> {
+        let model = self.get_model_from_invocation_options(opts);
+        let stream = opts.is_streaming();
+        let options = ModelOptions::from_options(opts);
+        let base_url = self.base_url.trim_end_matches('/');
+        let request = match prompt {
+            Data::Chat(chat) => {
+                self.client
+                    .post(format!("{}/api/chat", base_url))
+                    .json(&ChatRequest {
+                        model,
+                        messages: format_chat_messages(chat),
+                        stream,
+                        options,
+                    })
+            }
+            Data::Text(text) => {
+                self.client
+                    .post(format!("{}/api/generate", base_url))
+                    .json(&GenerateRequest {
+                        model,
+                        prompt: text.clone(),
+                        stream,
+                        options,
+                    })
+            }
+        };
+        let response = request.send().await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().await?;
+        Err(match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(ErrorResponse { error }) => OllamaError::Server(error),
+            Err(_) => OllamaError::Server(format!("{}: {}", status, body)),
+        })
+    }
+
+    fn stream_to_output(response: reqwest::Response) -> Output {
+        let (sender, output) = Output::new_stream();
+        tokio::spawn(async move {
+            if sender
+                .send(StreamSegment::Role(ChatRole::Assistant))
+                .is_err()
+            {
+                return;
+            }
+            let mut decoder = LineDecoder::default();
+            let mut body = response.bytes_stream();
+            let mut finished = false;
+            while !finished {
+                let contents = match body.next().await {
+                    Some(chunk) => chunk
+                        .map_err(OllamaError::from)
+                        .and_then(|chunk| decoder.push(&chunk)),
+                    None => {
+                        finished = true;
+                        decoder.finish()
+                    }
+                };
+                match contents {
+                    Ok(contents) => {
+                        for content in contents {
+                            if sender.send(StreamSegment::Content(content)).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        let _ =
+                            sender.send(StreamSegment::Err(ExecutorError::InnerError(e.into())));
+                        return;
+                    }
+                }
+            }
+        });
+        output
+    }
+}
+
+/// Splits a newline-delimited JSON response into the content of its chunks.
+#[derive(Default)]
+struct LineDecoder {
+    buffer: Vec<u8>,
+}
+
+impl LineDecoder {
+    /// Feeds a chunk of the response body, returning the content of all lines it completed.
+    fn push(&mut self, chunk: &[u8]) -> Result<Vec<String>, OllamaError> {
+        self.buffer.extend_from_slice(chunk);
+        let mut contents = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            Self::decode_line(&line, &mut contents)?;
+        }
+        Ok(contents)
+    }
+
+    /// Ends the response, returning the content of a last line that had no trailing newline.
+    fn finish(&mut self) -> Result<Vec<String>, OllamaError> {
+        let line = std::mem::take(&mut self.buffer);
+        let mut contents = Vec::new();
+        Self::decode_line(&line, &mut contents)?;
+        Ok(contents)
+    }
+
+    fn decode_line(line: &[u8], contents: &mut Vec<String>) -> Result<(), OllamaError> {
+        let line = String::from_utf8_lossy(line);
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        let chunk: CompletionChunk = serde_json::from_str(&line)?;
+        if let Some(error) = chunk.error {
+            return Err(OllamaError::Server(error));
+        }
+        match chunk.content() {
+            Some(content) if !content.is_empty() => contents.push(content.to_string()),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ai_chain::traits::Executor for Executor {
+    type StepTokenizer<'a> = OllamaTokenizer;
+
+    /// Creates a new `Executor` with the given options, talking to the server named by
+    /// `OLLAMA_HOST` or else `http://localhost:11434`.
+    fn new_with_options(options: Options) -> Result<Self, ExecutorCreationError> {
+        Ok(Self {
+            client: reqwest::Client::new(),
+            options,
+            base_url: crate::base_url_from_env(),
+        })
+    }
+
+    async fn execute(&self, options: &Options, prompt: &Prompt) -> Result<Output, ExecutorError> {
+        let opts = self.cascade(Some(options));
+        let response = self
+            .send(&opts, prompt)
+            .await
+            .map_err(|e| ExecutorError::InnerError(e.into()))?;
+        if opts.is_streaming() {
+            return Ok(Self::stream_to_output(response));
+        }
+        let response: CompletionChunk = response
+            .json()
+            .await
+            .map_err(|e| ExecutorError::InnerError(e.into()))?;
+        if let Some(error) = response.error {
+            return Err(ExecutorError::InnerError(OllamaError::Server(error).into()));
+        }
+        let mut col = ChatMessageCollection::new();
+        col.add_message(ChatMessage::new(
+            ChatRole::Assistant,
+            response.content().unwrap_or_default().to_string(),
+        ));
+        Ok(Output::new_immediate(col.into()))
+    }
+
+    fn tokens_used(
+        &self,
+        options: &Options,
+        prompt: &Prompt,
+    ) -> Result<TokenCount, PromptTokensError> {
+        let tokenizer = self
+            .get_tokenizer(options)
+            .map_err(|_| PromptTokensError::UnableToCompute)?;
+        let tokens_used = tokenizer
+            .tokenize_str(&prompt.to_text())
+            .map_err(|_| PromptTokensError::UnableToCompute)?
+            .len();
+        Ok(TokenCount::new(
+            self.max_tokens_allowed(options),
+            tokens_used as i32,
+        ))
+    }
+
+    /// Get the context size from `Opt::MaxContextSize`, which is also what is sent to the server
+    /// as `num_ctx`.
+    fn max_tokens_allowed(&self, options: &Options) -> i32 {
+        let opts = self.cascade(Some(options));
+        let context_size = match opts.get(OptDiscriminants::MaxContextSize) {
+            Some(Opt::MaxContextSize(size)) => *size,
+            _ => DEFAULT_CONTEXT_SIZE,
+        };
+        context_size.try_into().unwrap_or(i32::MAX)
+    }
+
+    fn answer_prefix(&self, _prompt: &Prompt) -> Option<String> {
+        None
+    }
+
+    fn get_tokenizer(&self, _options: &Options) -> Result<OllamaTokenizer, TokenizerError> {
+        Ok(OllamaTokenizer::new())
+    }
+}
+
+/// The tokenizer of a model isn't exposed by the Ollama API, so token counts are approximated
+/// with the `cl100k_base` encoding.
+pub struct OllamaTokenizer {
+    bpe: tiktoken_rs::CoreBPE,
+}
+
+impl OllamaTokenizer {
+    pub fn new() -> Self {
+        Self {
+            bpe: tiktoken_rs::cl100k_base().expect("cl100k_base is bundled with tiktoken-rs"),
+        }
+    }
+}
+
+impl Default for OllamaTokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tokenizer for OllamaTokenizer {
+    fn tokenize_str(&self, doc: &str) -> Result<TokenCollection, TokenizerError> {
+        Ok(self.bpe.encode_ordinary(doc).into())
+    }
+
+    fn to_string(&self, tokens: TokenCollection) -> Result<String, TokenizerError> {
+        self.bpe
+            .decode(tokens.as_usize()?)
+            .map_err(|_| TokenizerError::ToStringError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ai_chain::options;
+    use ai_chain::traits::Executor as _;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    use super::*;
+
+    fn executor(server: &mockito::Server, options: Options) -> Executor {
+        Executor::new_with_options(options)
+            .unwrap()
+            .with_base_url(server.url())
+    }
+
+    #[tokio::test]
+    async fn test_execute_chat_maps_options() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/chat")
+            .match_body(Matcher::PartialJson(json!({
+                "model": "mistral",
+                "stream": false,
+                "messages": [
+                    {"role": "system", "content": "You are terse."},
+                    {"role": "user", "content": "Say hi"},
+                ],
+                "options": {"top_k": 20, "num_ctx": 8192, "stop": ["\n"]},
+            })))
+            .with_body(
+                json!({
+                    "model": "mistral",
+                    "message": {"role": "assistant", "content": "Hi."},
+                    "done": true,
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let exec = executor(
+            &server,
+            options!(
+                Model: ai_chain::options::ModelRef::from_model_name("mistral"),
+                TopK: 20,
+                MaxContextSize: 8192usize,
+                StopSequence: vec!["\n".to_string()]
+            ),
+        );
+        let prompt = Data::Chat(ChatMessageCollection::for_vector(vec![
+            ChatMessage::system("You are terse.".to_string()),
+            ChatMessage::user("Say hi".to_string()),
+        ]));
+        let output = exec
+            .execute(Options::empty(), &prompt)
+            .await
+            .unwrap()
+            .to_immediate()
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(output.primary_textual_output().unwrap(), "Hi.");
+        assert_eq!(exec.max_tokens_allowed(Options::empty()), 8192);
+    }
+
+    #[tokio::test]
+    async fn test_execute_generate_streams_ndjson() {
+        let lines = [
+            json!({"model": "llama3", "response": "Hel", "done": false}),
+            json!({"model": "llama3", "response": "lo", "done": false}),
+            json!({"model": "llama3", "response": "", "done": true, "eval_count": 2}),
+        ];
+        let body: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/generate")
+            .match_body(Matcher::PartialJson(json!({
+                "model": "llama3",
+                "prompt": "Say hello",
+                "stream": true,
+            })))
+            .with_header("content-type", "application/x-ndjson")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let exec = executor(&server, options!(Stream: true));
+        let mut stream = exec
+            .execute(Options::empty(), &Data::text("Say hello".to_string()))
+            .await
+            .unwrap()
+            .as_stream()
+            .await
+            .unwrap();
+
+        let mut role = None;
+        let mut text = String::new();
+        while let Some(segment) = stream.next().await {
+            match segment {
+                StreamSegment::Role(r) => role = Some(r),
+                StreamSegment::Content(c) => text.push_str(&c),
+                StreamSegment::Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        mock.assert_async().await;
+        assert_eq!(role, Some(ChatRole::Assistant));
+        assert_eq!(text, "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_execute_surfaces_server_errors() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/generate")
+            .with_status(404)
+            .with_body(
+                json!({"error": "model 'llama3' not found, try pulling it first"}).to_string(),
+            )
+            .create_async()
+            .await;
+
+        let exec = executor(&server, Options::default());
+        let err = exec
+            .execute(Options::empty(), &Data::text("Hi".to_string()))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("try pulling it first"));
+    }
+
+    #[test]
+    fn test_line_decoder_handles_split_lines_and_errors() {
+        let mut decoder = LineDecoder::default();
+        assert!(decoder
+            .push(b"{\"message\":{\"role\":\"assistant\",\"content\":\"Hi")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            decoder.push(b"\"},\"done\":false}\n").unwrap(),
+            vec!["Hi".to_string()]
+        );
+        let err = decoder
+            .push(b"{\"error\":\"out of memory\"}\n")
+            .unwrap_err();
+        assert!(matches!(err, OllamaError::Server(message) if message == "out of memory"));
+    }
+
+    #[test]
+    fn test_line_decoder_flushes_last_line_without_newline() {
+        let mut decoder = LineDecoder::default();
+        assert_eq!(
+            decoder
+                .push(b"{\"response\":\"Hello\",\"done\":false}\n{\"response\":\" world\",\"done\":true}")
+                .unwrap(),
+            vec!["Hello".to_string()]
+        );
+        assert_eq!(decoder.finish().unwrap(), vec![" world".to_string()]);
+        assert!(decoder.finish().unwrap().is_empty());
+    }
+}
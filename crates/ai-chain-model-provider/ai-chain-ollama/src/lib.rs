@@ -0,0 +1,25 @@
+//! # ai-chain-ollama
+//!
+//! Use models served by a local [Ollama](https://ollama.com) server with `ai-chain`.
+//!
+//! The server is expected at `http://localhost:11434` unless `OLLAMA_HOST` says otherwise. Chat
+//! prompts go to the `/api/chat` endpoint and text prompts to `/api/generate`; with
+//! `Opt::Stream(true)` the newline-delimited JSON responses are streamed as `StreamSegment`s.
+//! [`Embeddings`] uses the `/api/embeddings` endpoint.
+mod api;
+mod embeddings;
+mod error;
+mod executor;
+
+pub use embeddings::Embeddings;
+pub use error::OllamaError;
+pub use executor::{Executor, OllamaTokenizer};
+
+/// The address of the Ollama server, taken from `OLLAMA_HOST` or else the default local address.
+pub(crate) fn base_url_from_env() -> String {
+    match std::env::var("OLLAMA_HOST") {
+        Ok(host) if host.starts_with("http://") || host.starts_with("https://") => host,
+        Ok(host) if !host.is_empty() => format!("http://{}", host),
+        _ => "http://localhost:11434".to_string(),
+    }
+}
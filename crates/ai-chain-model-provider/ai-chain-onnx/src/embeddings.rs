@@ -0,0 +1,179 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use ai_chain::traits::{self, EmbeddingsError};
+use ai_chain_types::models::udf_config::OnnxConfig;
+use async_trait::async_trait;
+
+use crate::error::OnnxError;
+use crate::model::{Model, DEFAULT_MAX_LENGTH};
+
+impl EmbeddingsError for OnnxError {}
+
+/// Embeddings computed locally by a sentence-transformer model, such as `all-MiniLM-L6-v2` or
+/// `bge-small-en-v1.5`, exported to ONNX.
+///
+/// The token embeddings of the model are mean pooled over the attention mask, or taken as they are
+/// if the model already outputs one vector per text, and normalized to unit length so that dot
+/// products are cosine similarities.
+#[derive(Clone)]
+pub struct Embeddings {
+    model: Arc<Model>,
+    batch_size: usize,
+    normalize: bool,
+    query_prefix: String,
+}
+
+impl Embeddings {
+    /// Loads the model at `config.path` and the `tokenizer.json` next to it.
+    pub fn from_config(config: &OnnxConfig) -> Result<Self, OnnxError> {
+        Ok(Self::new(Model::from_config(config, DEFAULT_MAX_LENGTH)?))
+    }
+
+    /// Loads the model and the tokenizer from the given files.
+    pub fn from_files<P: AsRef<Path>, Q: AsRef<Path>>(
+        model: P,
+        tokenizer: Q,
+    ) -> Result<Self, OnnxError> {
+        Ok(Self::new(Model::from_files(
+            model,
+            tokenizer,
+            DEFAULT_MAX_LENGTH,
+        )?))
+    }
+
+    fn new(model: Model) -> Self {
+        Self {
+            model: Arc::new(model),
+            batch_size: 32,
+            normalize: true,
+            query_prefix: String::new(),
+        }
+    }
+
+    /// Sets how many texts are embedded in one run of the model.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets whether the embeddings are normalized to unit length, which they are by default.
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Sets the instruction put before queries, such as
+    /// `"Represent this sentence for searching relevant passages: "` for the BGE models.
+    pub fn with_query_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.query_prefix = prefix.into();
+        self
+    }
+
+    /// Embeds `texts`. This runs the model on the current thread.
+    pub fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, OnnxError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.batch_size) {
+            let batch = self
+                .model
+                .encoder
+                .encode(chunk.iter().map(String::as_str).collect())?;
+            let (shape, values) = self.model.run(&batch)?;
+            let pooled = match shape.as_slice() {
+                [rows, length, _] if *rows == batch.size && *length == batch.length => {
+                    mean_pool(&shape, &values, &batch.attention_mask)
+                }
+                [rows, hidden] if *rows == batch.size => {
+                    values.chunks(*hidden).map(<[f32]>::to_vec).collect()
+                }
+                _ => {
+                    return Err(OnnxError::Output(format!(
+                        "expected token or sentence embeddings, got shape {:?}",
+                        shape
+                    )))
+                }
+            };
+            embeddings.extend(pooled);
+        }
+        if self.normalize {
+            embeddings.iter_mut().for_each(|e| normalize(e));
+        }
+        Ok(embeddings)
+    }
+
+    async fn embed_blocking(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, OnnxError> {
+        let embeddings = self.clone();
+        tokio::task::spawn_blocking(move || embeddings.embed(&texts)).await?
+    }
+}
+
+/// Averages the token embeddings of each row of a `[batch, length, hidden]` output over the
+/// tokens its attention mask keeps.
+fn mean_pool(shape: &[usize], values: &[f32], attention_mask: &[i64]) -> Vec<Vec<f32>> {
+    let (length, hidden) = (shape[1], shape[2]);
+    values
+        .chunks(length * hidden)
+        .zip(attention_mask.chunks(length))
+        .map(|(tokens, mask)| {
+            let mut sum = vec![0.0; hidden];
+            let mut count = 0.0;
+            for (token, _) in tokens.chunks(hidden).zip(mask).filter(|(_, &m)| m != 0) {
+                sum.iter_mut().zip(token).for_each(|(s, v)| *s += v);
+                count += 1.0;
+            }
+            if count > 0.0 {
+                sum.iter_mut().for_each(|s| *s /= count);
+            }
+            sum
+        })
+        .collect()
+}
+
+/// Scales `vector` to unit length, unless it is zero.
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+#[async_trait]
+impl traits::Embeddings for Embeddings {
+    type Error = OnnxError;
+
+    async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Self::Error> {
+        self.embed_blocking(texts).await
+    }
+
+    async fn embed_query(&self, query: String) -> Result<Vec<f32>, Self::Error> {
+        let query = format!("{}{}", self.query_prefix, query);
+        self.embed_blocking(vec![query])
+            .await?
+            .pop()
+            .ok_or_else(|| OnnxError::Output("no embedding for the query".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean_pool_skips_padding() {
+        // Two rows of two tokens with two dimensions; the second token of the second row is
+        // padding.
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 100.0, 100.0];
+        let pooled = mean_pool(&[2, 2, 2], &values, &[1, 1, 1, 0]);
+        assert_eq!(pooled, vec![vec![2.0, 3.0], vec![5.0, 6.0]]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut vector = [3.0, 4.0];
+        normalize(&mut vector);
+        assert_eq!(vector, [0.6, 0.8]);
+        let mut zero = [0.0, 0.0];
+        normalize(&mut zero);
+        assert_eq!(zero, [0.0, 0.0]);
+    }
+}
@@ -0,0 +1,14 @@
+use thiserror::Error;
+
+/// Errors from loading or running an ONNX model.
+#[derive(Debug, Error)]
+pub enum OnnxError {
+    #[error(transparent)]
+    Ort(#[from] ort::Error),
+    #[error("tokenizer error: {0}")]
+    Tokenizer(#[from] tokenizers::Error),
+    #[error("unexpected model output: {0}")]
+    Output(String),
+    #[error("the model task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
@@ -0,0 +1,22 @@
+//! # ai-chain-onnx
+//!
+//! Run ONNX models on the CPU with `ai-chain`, using [ONNX Runtime](https://onnxruntime.ai):
+//! sentence-transformer [`Embeddings`] that work offline with any vector store, and a
+//! [`CrossEncoderReranker`] for retrieved documents.
+//!
+//! Models are located like the ONNX UDFs of `ai-chain-types`: an [`OnnxConfig`] points to the
+//! `.onnx` file, and the Hugging Face `tokenizer.json` of the model is read from the same
+//! directory.
+//!
+//! The ONNX Runtime library is loaded when the first model is, from the path in the
+//! `ORT_DYLIB_PATH` environment variable or else from the library search path.
+//!
+//! [`OnnxConfig`]: ai_chain_types::models::udf_config::OnnxConfig
+mod embeddings;
+mod error;
+mod model;
+mod reranker;
+
+pub use embeddings::Embeddings;
+pub use error::OnnxError;
+pub use reranker::CrossEncoderReranker;
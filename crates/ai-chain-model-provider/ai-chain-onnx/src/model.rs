@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use ai_chain_types::models::udf_config::OnnxConfig;
+use ort::session::Session;
+use ort::value::Tensor;
+use tokenizers::{
+    EncodeInput, PaddingParams, PaddingStrategy, Tokenizer, TruncationParams, TruncationStrategy,
+};
+
+use crate::error::OnnxError;
+
+/// The file name of the tokenizer, next to the model file.
+const TOKENIZER_FILE: &str = "tokenizer.json";
+
+/// The sequence length of BERT-based models, such as MiniLM, BGE and the `ms-marco`
+/// cross-encoders.
+pub(crate) const DEFAULT_MAX_LENGTH: usize = 512;
+
+/// The tokenized inputs of a batch, padded to the longest sequence, in row-major order.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Batch {
+    pub size: usize,
+    pub length: usize,
+    pub input_ids: Vec<i64>,
+    pub attention_mask: Vec<i64>,
+    pub token_type_ids: Vec<i64>,
+}
+
+/// A tokenizer that pads and truncates its batches for a model.
+pub(crate) struct Encoder {
+    tokenizer: Tokenizer,
+}
+
+impl Encoder {
+    pub fn new(mut tokenizer: Tokenizer, max_length: usize) -> Result<Self, OnnxError> {
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length,
+                strategy: TruncationStrategy::LongestFirst,
+                ..Default::default()
+            }))?
+            .with_padding(Some(PaddingParams {
+                strategy: PaddingStrategy::BatchLongest,
+                ..Default::default()
+            }));
+        Ok(Self { tokenizer })
+    }
+
+    pub fn encode<'s, E>(&self, inputs: Vec<E>) -> Result<Batch, OnnxError>
+    where
+        E: Into<EncodeInput<'s>> + Send,
+    {
+        let encodings = self.tokenizer.encode_batch(inputs, true)?;
+        let flatten = |get: fn(&tokenizers::Encoding) -> &[u32]| -> Vec<i64> {
+            encodings
+                .iter()
+                .flat_map(|e| get(e).iter().map(|&v| v as i64))
+                .collect()
+        };
+        Ok(Batch {
+            size: encodings.len(),
+            length: encodings.first().map_or(0, |e| e.len()),
+            input_ids: flatten(tokenizers::Encoding::get_ids),
+            attention_mask: flatten(tokenizers::Encoding::get_attention_mask),
+            token_type_ids: flatten(tokenizers::Encoding::get_type_ids),
+        })
+    }
+}
+
+/// An ONNX Runtime session together with the tokenizer of the model.
+///
+/// Sessions run one batch at a time, so the session is behind a mutex.
+pub(crate) struct Model {
+    session: Mutex<Session>,
+    pub encoder: Encoder,
+}
+
+impl Model {
+    /// Loads the model at `config.path` and the `tokenizer.json` in the same directory.
+    pub fn from_config(config: &OnnxConfig, max_length: usize) -> Result<Self, OnnxError> {
+        let model = PathBuf::from(&config.path);
+        let tokenizer = model
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(TOKENIZER_FILE);
+        Self::from_files(model, tokenizer, max_length)
+    }
+
+    pub fn from_files<P: AsRef<Path>, Q: AsRef<Path>>(
+        model: P,
+        tokenizer: Q,
+        max_length: usize,
+    ) -> Result<Self, OnnxError> {
+        let session = Session::builder()?.commit_from_file(model)?;
+        let encoder = Encoder::new(Tokenizer::from_file(tokenizer)?, max_length)?;
+        Ok(Self {
+            session: Mutex::new(session),
+            encoder,
+        })
+    }
+
+    /// Runs the model on `batch` and returns the shape and the values of its first output.
+    ///
+    /// Only the inputs the model declares are passed, so models without `token_type_ids` work.
+    pub fn run(&self, batch: &Batch) -> Result<(Vec<usize>, Vec<f32>), OnnxError> {
+        let shape = [batch.size, batch.length];
+        let mut session = self.session.lock().unwrap();
+        let mut inputs = Vec::new();
+        for input in &session.inputs {
+            let values = match input.name.as_str() {
+                "input_ids" => batch.input_ids.clone(),
+                "attention_mask" => batch.attention_mask.clone(),
+                "token_type_ids" => batch.token_type_ids.clone(),
+                name => return Err(OnnxError::Output(format!("unsupported input `{}`", name))),
+            };
+            inputs.push((input.name.clone(), Tensor::from_array((shape, values))?));
+        }
+        let outputs = session.run(inputs)?;
+        let (shape, values) = outputs[0].try_extract_tensor::<f32>()?;
+        Ok((shape.iter().map(|&d| d as usize).collect(), values.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::whitespace::Whitespace;
+
+    fn encoder(max_length: usize) -> Encoder {
+        let vocab: HashMap<String, u32> = ["[PAD]", "[UNK]", "boil", "the", "water", "kettle"]
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), id as u32))
+            .collect();
+        let model = WordLevel::builder()
+            .vocab(vocab.into_iter().collect())
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(Some(Whitespace {}));
+        Encoder::new(tokenizer, max_length).unwrap()
+    }
+
+    #[test]
+    fn test_batches_are_padded_and_truncated() {
+        let batch = encoder(3)
+            .encode(vec![
+                ("boil", "the water"),
+                ("boil", "the kettle water now"),
+            ])
+            .unwrap();
+        assert_eq!((batch.size, batch.length), (2, 3));
+        assert_eq!(batch.input_ids, vec![2, 3, 4, 2, 3, 5]);
+        assert_eq!(batch.attention_mask, vec![1; 6]);
+
+        let batch = encoder(8).encode(vec!["water", "the kettle"]).unwrap();
+        assert_eq!(batch.input_ids, vec![4, 0, 3, 5]);
+        assert_eq!(batch.attention_mask, vec![1, 0, 1, 1]);
+    }
+}
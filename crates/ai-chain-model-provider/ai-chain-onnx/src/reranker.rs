@@ -0,0 +1,99 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use ai_chain::retrieval::{Reranker, RetrieverError};
+use ai_chain::schema::Document;
+use ai_chain_types::models::udf_config::OnnxConfig;
+use async_trait::async_trait;
+
+use crate::error::OnnxError;
+use crate::model::{Model, DEFAULT_MAX_LENGTH};
+
+/// Reranks documents with a cross-encoder, a model that reads the query and a document together
+/// and outputs a relevance logit.
+///
+/// Models with a single output logit, like the `ms-marco` cross-encoders, are scored by it. For
+/// models with two logits (not relevant, relevant) the score is their difference.
+#[derive(Clone)]
+pub struct CrossEncoderReranker {
+    model: Arc<Model>,
+    batch_size: usize,
+}
+
+impl CrossEncoderReranker {
+    /// Loads the model at `config.path` and the `tokenizer.json` next to it.
+    pub fn from_config(config: &OnnxConfig) -> Result<Self, OnnxError> {
+        Ok(Self::new(Model::from_config(config, DEFAULT_MAX_LENGTH)?))
+    }
+
+    /// Loads the model and the tokenizer from the given files.
+    pub fn from_files<P: AsRef<Path>, Q: AsRef<Path>>(
+        model: P,
+        tokenizer: Q,
+    ) -> Result<Self, OnnxError> {
+        Ok(Self::new(Model::from_files(
+            model,
+            tokenizer,
+            DEFAULT_MAX_LENGTH,
+        )?))
+    }
+
+    fn new(model: Model) -> Self {
+        Self {
+            model: Arc::new(model),
+            batch_size: 16,
+        }
+    }
+
+    /// Sets how many documents are scored in one run of the model.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Scores each of `texts` against `query`. This runs the model on the current thread.
+    pub fn score_texts(&self, query: &str, texts: &[String]) -> Result<Vec<f32>, OnnxError> {
+        let mut scores = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.batch_size) {
+            let batch = self
+                .model
+                .encoder
+                .encode(chunk.iter().map(|text| (query, text.as_str())).collect())?;
+            let (shape, logits) = self.model.run(&batch)?;
+            match shape.as_slice() {
+                [rows, 1] | [rows] if *rows == chunk.len() => scores.extend(logits),
+                [rows, 2] if *rows == chunk.len() => {
+                    scores.extend(logits.chunks(2).map(|pair| pair[1] - pair[0]))
+                }
+                _ => {
+                    return Err(OnnxError::Output(format!(
+                        "expected one or two logits per document, got shape {:?}",
+                        shape
+                    )))
+                }
+            }
+        }
+        Ok(scores)
+    }
+}
+
+#[async_trait]
+impl<M> Reranker<M> for CrossEncoderReranker
+where
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync + 'static,
+{
+    async fn score(
+        &self,
+        query: &str,
+        documents: &[Document<M>],
+    ) -> Result<Vec<f32>, RetrieverError> {
+        let reranker = self.clone();
+        let query = query.to_string();
+        let texts: Vec<String> = documents.iter().map(|d| d.page_content.clone()).collect();
+        tokio::task::spawn_blocking(move || reranker.score_texts(&query, &texts))
+            .await
+            .map_err(OnnxError::from)
+            .and_then(|scores| scores)
+            .map_err(|e| RetrieverError::Reranker(e.to_string()))
+    }
+}
@@ -1,24 +1,150 @@
-use async_openai::config::{Config, OPENAI_API_BASE};
-use ai_chain::tokens::Tokenizer;
+use std::marker::PhantomData;
+use std::sync::Arc;
 
-/// config extension
-pub trait OAIConfig: Config + Send + Sync + 'static {
+use async_openai::config::{Config, OPENAI_BETA_HEADER, OPENAI_ORGANIZATION_HEADER};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use secrecy::{ExposeSecret, Secret};
 
+use super::profile::ProviderProfile;
+use super::providers::Provider;
 
+/// config extension
+pub trait OAIConfig: Config + Send + Sync + 'static {
     fn create() -> Self;
 
     fn with_api_key<S: Into<String>>(&mut self, api_key: S) -> Self;
 
-    /// To use a API base url different from default [OPENAI_API_BASE]
+    /// To use a API base url different from the one in the profile
     fn with_api_base<S: Into<String>>(&mut self, api_base: S) -> Self;
 
-    fn model_config() ->(String, Vec<String>);
+    /// The profile describing the provider this config talks to.
+    fn profile(&self) -> &ProviderProfile;
+}
 
+/// An [`OAIConfig`] driven by a [`ProviderProfile`].
+///
+/// `ProfileConfig<P>::create()` uses the built-in profile of `P`; use
+/// [`ProfileConfig::from_profile`] to talk to a provider described at runtime.
+pub struct ProfileConfig<P> {
+    profile: Arc<ProviderProfile>,
+    api_base: String,
+    api_key: Secret<String>,
+    provider: PhantomData<P>,
+}
 
-    fn tokenizer(&self)->Box<dyn Tokenizer>;
+impl<P> ProfileConfig<P> {
+    /// Creates a config for `profile`, reading the API key, and the base URL if the profile names
+    /// a variable for it, from the environment.
+    pub fn from_profile(profile: ProviderProfile) -> Self {
+        Self::from_profile_with_env(profile, |var| std::env::var(var).ok())
+    }
+
+    /// Like [`ProfileConfig::from_profile`], looking variables up with `env` instead of in the
+    /// process environment.
+    pub fn from_profile_with_env<F: Fn(&str) -> Option<String>>(
+        profile: ProviderProfile,
+        env: F,
+    ) -> Self {
+        Self {
+            api_base: profile.api_base_from(&env),
+            api_key: profile.api_key_from(&env).into(),
+            profile: Arc::new(profile),
+            provider: PhantomData,
+        }
+    }
 }
 
-trait OpenAICompatibleTokenizer: Tokenizer {
+impl<P: Provider> Default for ProfileConfig<P> {
+    fn default() -> Self {
+        Self::from_profile(P::profile())
+    }
+}
 
+impl<P> Clone for ProfileConfig<P> {
+    fn clone(&self) -> Self {
+        Self {
+            profile: self.profile.clone(),
+            api_base: self.api_base.clone(),
+            api_key: self.api_key.clone(),
+            provider: PhantomData,
+        }
+    }
 }
 
+impl<P> Config for ProfileConfig<P> {
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        headers.insert(
+            AUTHORIZATION,
+            format!("Bearer {}", self.api_key.expose_secret())
+                .as_str()
+                .parse()
+                .unwrap(),
+        );
+
+        let quirks = &self.profile.quirks;
+        if quirks.assistants_beta_header {
+            // Calls to the Assistants API require that you pass a Beta header
+            headers.insert(OPENAI_BETA_HEADER, "assistants=v1".parse().unwrap());
+        }
+        if let Some(org_id) = quirks
+            .organization_env
+            .as_ref()
+            .and_then(|var| std::env::var(var).ok())
+        {
+            headers.insert(OPENAI_ORGANIZATION_HEADER, org_id.parse().unwrap());
+        }
+        for (name, value) in &quirks.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+
+        headers
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.api_base.trim_end_matches('/'), path)
+    }
+
+    fn api_base(&self) -> &str {
+        &self.api_base
+    }
+
+    fn api_key(&self) -> &Secret<String> {
+        &self.api_key
+    }
+
+    fn query(&self) -> Vec<(&str, &str)> {
+        self.profile
+            .quirks
+            .query
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect()
+    }
+}
+
+impl<P: Provider> OAIConfig for ProfileConfig<P> {
+    fn create() -> Self {
+        Self::default()
+    }
+
+    fn with_api_key<S: Into<String>>(&mut self, api_key: S) -> Self {
+        self.api_key = Secret::from(api_key.into());
+        self.clone()
+    }
+
+    fn with_api_base<S: Into<String>>(&mut self, api_base: S) -> Self {
+        self.api_base = api_base.into();
+        self.clone()
+    }
+
+    fn profile(&self) -> &ProviderProfile {
+        &self.profile
+    }
+}
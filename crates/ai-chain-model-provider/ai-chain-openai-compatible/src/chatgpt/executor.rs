@@ -9,8 +9,6 @@ use ai_chain::options::Options;
 use ai_chain::options::OptionsCascade;
 use ai_chain::output::Output;
 use ai_chain::tokens::TokenCollection;
-use tiktoken_rs::{cl100k_base, get_bpe_from_tokenizer};
-use tiktoken_rs::tokenizer::get_tokenizer;
 
 use super::prompt::create_chat_completion_request;
 use super::prompt::format_chat_messages;
@@ -26,8 +24,8 @@ use async_trait::async_trait;
 use ai_chain::tokens::TokenCount;
 
 use std::sync::Arc;
-use tiktoken_rs::tokenizer::Tokenizer::Cl100kBase;
 use crate::chatgpt::config::OAIConfig;
+use crate::chatgpt::profile::{ProviderProfile, TokenizerKind};
 use crate::chatgpt::OpenAICompatibleInnerError;
 
 /// The `Executor` struct for the ChatGPT model. This executor uses the `async_openai` crate to communicate with the OpenAI API.
@@ -42,6 +40,14 @@ pub struct Executor<C: OAIConfig> {
 
 
 impl<C: OAIConfig> Executor<C> {
+    /// Creates a new `Executor` talking to the provider described by `config`.
+    ///
+    /// This is how providers whose [`ProviderProfile`] is only known at runtime are used, e.g.
+    /// `Executor::for_config(ProfileConfig::<Local>::from_profile(profile), options)`.
+    pub fn for_config(config: C, options: Options) -> Self {
+        Self::for_client(async_openai::Client::with_config(config), options)
+    }
+
     /// Creates a new `Executor` with the given client.
     pub fn for_client(client: async_openai::Client<C>, options: Options) -> Self {
         use ai_chain::traits::Executor as _;
@@ -52,11 +58,15 @@ impl<C: OAIConfig> Executor<C> {
 
     fn get_model_from_invocation_options(&self, opts: &OptionsCascade) -> String {
         let Some(Opt::Model(model)) = opts.get(ai_chain::options::OptDiscriminants::Model) else {
-            return C::model_config().0
+            return self.profile().default_model.clone();
         };
         model.to_name()
     }
 
+    fn profile(&self) -> &ProviderProfile {
+        self.client.config().profile()
+    }
+
     fn cascade<'a>(&'a self, opts: Option<&'a Options>) -> OptionsCascade<'a> {
         let mut v: Vec<&'a Options> = vec![&self.options];
         if let Some(o) = opts {
@@ -79,22 +89,11 @@ impl<OConfig: OAIConfig> Executor<OConfig> {
     }
 
     fn num_tokens_from_messages(
+        profile: &ProviderProfile,
         model: &str,
         messages: &[ChatCompletionRequestMessage],
     ) -> Result<usize, PromptTokensError> {
-        let mut tokenizer = Cl100kBase;
-        let (_, config_params) = OConfig::model_config();
-        if config_params.iter().any(|x| model.starts_with(x)) {
-            tokenizer = Cl100kBase;
-        } else {
-            let tokenizer1 = get_tokenizer(model).ok_or_else(|| PromptTokensError::NotAvailable)?;
-            if tokenizer1 != Cl100kBase {
-                return Err(PromptTokensError::NotAvailable);
-            }
-            tokenizer = tokenizer1;
-        }
-
-        let bpe = get_bpe_from_tokenizer(tokenizer).map_err(|_| PromptTokensError::NotAvailable)?;
+        let bpe = profile.bpe(model)?;
 
         let (tokens_per_message, tokens_per_name) = if model.starts_with("gpt-3.5") {
             (
@@ -155,7 +154,7 @@ impl<OConfig: OAIConfig> Executor<OConfig> {
 
 #[async_trait]
 impl<OConfig: OAIConfig> traits::Executor for Executor<OConfig> {
-    type StepTokenizer<'a> = OpenAITokenizer<OConfig>;
+    type StepTokenizer<'a> = OpenAITokenizer;
     /// Creates a new `Executor` with the given options.
     ///
     /// if the `OPENAI_ORG_ID` environment variable is present, it will be used as the org_ig for the OpenAI client.
@@ -167,9 +166,6 @@ impl<OConfig: OAIConfig> traits::Executor for Executor<OConfig> {
         if let Some(Opt::ApiKey(api_key)) = opts.get(ai_chain::options::OptDiscriminants::ApiKey) {
             cfg = cfg.with_api_key(api_key)
         }
-        if let Ok(base_url) = std::env::var("OPENAI_API_BASE_URL") {
-            cfg = cfg.with_api_base(base_url);
-        }
 
         let client = Arc::new(async_openai::Client::with_config(cfg));
         Ok(Self {
@@ -208,7 +204,7 @@ impl<OConfig: OAIConfig> traits::Executor for Executor<OConfig> {
             OpenAICompatibleInnerError::StringTemplateError(e) => PromptTokensError::PromptFormatFailed(e),
             _ => PromptTokensError::UnableToCompute,
         })?;
-        let tokens_used = Self::num_tokens_from_messages(&model, &messages)
+        let tokens_used = Self::num_tokens_from_messages(self.profile(), &model, &messages)
             .map_err(|_| PromptTokensError::NotAvailable)?;
 
         Ok(TokenCount::new(
@@ -220,54 +216,54 @@ impl<OConfig: OAIConfig> traits::Executor for Executor<OConfig> {
     fn max_tokens_allowed(&self, opts: &Options) -> i32 {
         let opts_cas = self.cascade(Some(opts));
         let model = self.get_model_from_invocation_options(&opts_cas);
-        tiktoken_rs::model::get_context_size(&model)
+        self.profile()
+            .context_size(&model)
             .try_into()
-            .unwrap_or(4096)
+            .unwrap_or(i32::MAX)
     }
 
     fn answer_prefix(&self, _prompt: &Prompt) -> Option<String> {
         None
     }
 
-    fn get_tokenizer(&self, options: &Options) -> Result<OpenAITokenizer<OConfig>, TokenizerError> {
-        Ok(OpenAITokenizer::new(self.cascade(Some(options))))
+    fn get_tokenizer(&self, options: &Options) -> Result<OpenAITokenizer, TokenizerError> {
+        Ok(OpenAITokenizer::new(
+            self.profile(),
+            self.cascade(Some(options)),
+        ))
     }
 }
 
 
-pub struct OpenAITokenizer<C> {
+pub struct OpenAITokenizer {
     model_name: String,
-    config: Option<C>,
+    tokenizer: TokenizerKind,
 }
 
-impl<C: OAIConfig> OpenAITokenizer<C> {
-    pub fn new(options: OptionsCascade) -> Self {
+impl OpenAITokenizer {
+    pub fn new(profile: &ProviderProfile, options: OptionsCascade) -> Self {
         let model_name = match options.get(ai_chain::options::OptDiscriminants::Model) {
             Some(Opt::Model(model_name)) => model_name.to_name(),
-            _ => C::model_config().0
+            _ => profile.default_model.clone(),
         };
-        Self::for_model_name(model_name)
+        Self::for_model_name(model_name, profile.tokenizer)
     }
     /// Creates an OpenAITokenizer for the passed in model name
-    pub fn for_model_name<S: Into<String>>(model_name: S) -> Self {
+    pub fn for_model_name<S: Into<String>>(model_name: S, tokenizer: TokenizerKind) -> Self {
         let model_name: String = model_name.into();
-        Self { model_name, config: None }
+        Self {
+            model_name,
+            tokenizer,
+        }
     }
 
     fn get_bpe_from_model(&self) -> Result<tiktoken_rs::CoreBPE, PromptTokensError> {
-        use tiktoken_rs::get_bpe_from_model;
-        let (_, config_params) = C::model_config();
-        if config_params.iter().any(|x| self.model_name.starts_with(x)) {
-            return cl100k_base().map_err(|_| PromptTokensError::NotAvailable);
-        }
-        get_bpe_from_model(&self.model_name).map_err(|_| PromptTokensError::NotAvailable)
+        self.tokenizer.bpe(&self.model_name)
     }
 }
 
-impl<C: OAIConfig> Tokenizer for OpenAITokenizer<C> {
+impl Tokenizer for OpenAITokenizer {
     fn tokenize_str(&self, doc: &str) -> Result<TokenCollection, TokenizerError> {
-        // if model.starts_with("moonshot") {
-        //     tokenizer = Cl100kBase;
         Ok(self
             .get_bpe_from_model()
             .map_err(|_| TokenizerError::TokenizationError)?
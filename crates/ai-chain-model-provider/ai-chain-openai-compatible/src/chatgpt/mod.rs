@@ -5,9 +5,12 @@ mod prompt;
 mod config;
 mod client;
 mod executor;
+mod profile;
+pub mod providers;
 
 pub use executor::*;
 pub use model::ModelTrait;
 pub use prompt::*;
 pub use config::*;
 pub use error::*;
+pub use profile::*;
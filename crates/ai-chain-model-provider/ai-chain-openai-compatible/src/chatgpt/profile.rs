@@ -0,0 +1,297 @@
+//! Declarative descriptions of OpenAI-compatible providers.
+//!
+//! A [`ProviderProfile`] captures everything that differs between vendors exposing the OpenAI chat
+//! completions API: the base URL, where the API key comes from, the models on offer and their
+//! context sizes, which BPE to count tokens with, and any protocol quirks. Adding a new vendor is a
+//! matter of writing a profile, either in code or in YAML/JSON, rather than copying a crate.
+use std::collections::BTreeMap;
+
+use ai_chain::tokens::PromptTokensError;
+use serde::{Deserialize, Serialize};
+use tiktoken_rs::CoreBPE;
+
+/// The context size used for models that are not listed in a profile.
+pub const DEFAULT_CONTEXT_SIZE: usize = 4096;
+
+/// The BPE used to count tokens for a provider's models.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenizerKind {
+    /// The `cl100k_base` encoding. Used as an approximation for most non-OpenAI vendors.
+    #[default]
+    Cl100kBase,
+    /// The `p50k_base` encoding.
+    P50kBase,
+    /// The `r50k_base` encoding.
+    R50kBase,
+    /// Look the encoding up from the model name, as OpenAI models do.
+    FromModel,
+}
+
+impl TokenizerKind {
+    /// Returns the BPE for `model` according to this tokenizer kind.
+    pub fn bpe(&self, model: &str) -> Result<CoreBPE, PromptTokensError> {
+        let bpe = match self {
+            TokenizerKind::Cl100kBase => tiktoken_rs::cl100k_base(),
+            TokenizerKind::P50kBase => tiktoken_rs::p50k_base(),
+            TokenizerKind::R50kBase => tiktoken_rs::r50k_base(),
+            TokenizerKind::FromModel => tiktoken_rs::get_bpe_from_model(model),
+        };
+        bpe.map_err(|_| PromptTokensError::NotAvailable)
+    }
+}
+
+/// A model offered by a provider.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelProfile {
+    /// The name of the model as sent to the API.
+    pub name: String,
+    /// The maximum number of tokens the model accepts, prompt and completion combined.
+    pub context_size: usize,
+}
+
+impl ModelProfile {
+    pub fn new<S: Into<String>>(name: S, context_size: usize) -> Self {
+        Self {
+            name: name.into(),
+            context_size,
+        }
+    }
+}
+
+/// Deviations from the OpenAI protocol that a provider requires.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quirks {
+    /// Send the `OpenAI-Beta: assistants=v1` header with every request.
+    pub assistants_beta_header: bool,
+    /// Environment variable holding an organization id to send as the `OpenAI-Organization` header.
+    pub organization_env: Option<String>,
+    /// Extra headers sent with every request.
+    pub headers: BTreeMap<String, String>,
+    /// Query parameters appended to every request, such as an `api-version`.
+    pub query: Vec<(String, String)>,
+}
+
+/// Describes an OpenAI-compatible provider.
+///
+/// # Example
+///
+/// ```
+/// use ai_chain_openai_compatible::chatgpt::{ModelProfile, ProviderProfile, TokenizerKind};
+///
+/// let profile = ProviderProfile::new("vllm", "http://localhost:8000/v1", "Qwen/Qwen2-7B-Instruct")
+///     .with_api_key_env("VLLM_API_KEY")
+///     .with_model(ModelProfile::new("Qwen/Qwen2-7B-Instruct", 32768))
+///     .with_tokenizer(TokenizerKind::Cl100kBase);
+///
+/// assert_eq!(profile.context_size("Qwen/Qwen2-7B-Instruct"), 32768);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderProfile {
+    /// A short, human readable name for the provider.
+    pub name: String,
+    /// The base URL requests are sent to, e.g. `https://api.openai.com/v1`.
+    pub api_base: String,
+    /// The environment variable the API key is read from.
+    #[serde(default = "default_api_key_env")]
+    pub api_key_env: String,
+    /// An environment variable that overrides `api_base` when it is set.
+    #[serde(default)]
+    pub api_base_env: Option<String>,
+    /// The model used when no `Opt::Model` is given.
+    pub default_model: String,
+    /// The models this provider is known to serve.
+    #[serde(default)]
+    pub models: Vec<ModelProfile>,
+    /// The context size for models that aren't listed in `models`.
+    #[serde(default = "default_context_size")]
+    pub default_context_size: usize,
+    /// The tokenizer used to count tokens.
+    #[serde(default)]
+    pub tokenizer: TokenizerKind,
+    /// The model used by the embeddings endpoint, if the provider offers one.
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    #[serde(default)]
+    pub quirks: Quirks,
+}
+
+fn default_api_key_env() -> String {
+    "OPENAI_API_KEY".to_string()
+}
+
+fn default_context_size() -> usize {
+    DEFAULT_CONTEXT_SIZE
+}
+
+impl ProviderProfile {
+    /// Creates a profile for the provider at `api_base` serving `default_model`.
+    pub fn new<N: Into<String>, B: Into<String>, M: Into<String>>(
+        name: N,
+        api_base: B,
+        default_model: M,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            api_base: api_base.into(),
+            api_key_env: default_api_key_env(),
+            api_base_env: None,
+            default_model: default_model.into(),
+            models: Vec::new(),
+            default_context_size: DEFAULT_CONTEXT_SIZE,
+            tokenizer: TokenizerKind::default(),
+            embedding_model: None,
+            quirks: Quirks::default(),
+        }
+    }
+
+    pub fn with_api_key_env<S: Into<String>>(mut self, api_key_env: S) -> Self {
+        self.api_key_env = api_key_env.into();
+        self
+    }
+
+    pub fn with_api_base_env<S: Into<String>>(mut self, api_base_env: S) -> Self {
+        self.api_base_env = Some(api_base_env.into());
+        self
+    }
+
+    pub fn with_model(mut self, model: ModelProfile) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    pub fn with_default_context_size(mut self, context_size: usize) -> Self {
+        self.default_context_size = context_size;
+        self
+    }
+
+    pub fn with_tokenizer(mut self, tokenizer: TokenizerKind) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    pub fn with_embedding_model<S: Into<String>>(mut self, model: S) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Returns the names of all models listed in this profile.
+    pub fn model_names(&self) -> Vec<String> {
+        self.models.iter().map(|m| m.name.clone()).collect()
+    }
+
+    /// Finds the listed model matching `model`, preferring an exact match over a prefix match so
+    /// that dated snapshots like `moonshot-v1-8k-0301` resolve to their base model.
+    pub fn model(&self, model: &str) -> Option<&ModelProfile> {
+        self.models.iter().find(|m| m.name == model).or_else(|| {
+            self.models
+                .iter()
+                .filter(|m| model.starts_with(&m.name))
+                .max_by_key(|m| m.name.len())
+        })
+    }
+
+    /// Returns the context size of `model`.
+    pub fn context_size(&self, model: &str) -> usize {
+        match self.model(model) {
+            Some(m) => m.context_size,
+            None if self.tokenizer == TokenizerKind::FromModel => {
+                tiktoken_rs::model::get_context_size(model)
+            }
+            None => self.default_context_size,
+        }
+    }
+
+    /// Returns the BPE used to count tokens for `model`.
+    pub fn bpe(&self, model: &str) -> Result<CoreBPE, PromptTokensError> {
+        self.tokenizer.bpe(model)
+    }
+
+    /// The base URL, read from `api_base_env` when the profile has one and it is set.
+    pub fn api_base_from_env(&self) -> String {
+        self.api_base_from(|var| std::env::var(var).ok())
+    }
+
+    /// Like [`api_base_from_env`](Self::api_base_from_env), looking variables up with `env`.
+    pub fn api_base_from<F: Fn(&str) -> Option<String>>(&self, env: F) -> String {
+        self.api_base_env
+            .as_deref()
+            .and_then(env)
+            .unwrap_or_else(|| self.api_base.clone())
+    }
+
+    /// The API key, read from `api_key_env`. No other variable is tried, so a key meant for one
+    /// vendor is never sent to another; the key is empty when the variable is unset.
+    pub fn api_key_from_env(&self) -> String {
+        self.api_key_from(|var| std::env::var(var).ok())
+    }
+
+    /// Like [`api_key_from_env`](Self::api_key_from_env), looking variables up with `env`.
+    pub fn api_key_from<F: Fn(&str) -> Option<String>>(&self, env: F) -> String {
+        env(&self.api_key_env).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chatgpt::providers::{Moonshot, OpenAI, Provider};
+
+    fn env(var: &str) -> Option<String> {
+        match var {
+            "OPENAI_API_KEY" => Some("sk-openai".to_string()),
+            "OPENAI_API_BASE_URL" => Some("http://localhost:1234/v1".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn openai_variables_only_apply_to_openai() {
+        let openai = OpenAI::profile();
+        assert_eq!(openai.api_base_from(env), "http://localhost:1234/v1");
+        assert_eq!(openai.api_key_from(env), "sk-openai");
+
+        let moonshot = Moonshot::profile();
+        assert_eq!(moonshot.api_base_from(env), "https://api.moonshot.cn/v1");
+        assert_eq!(moonshot.api_key_from(env), "");
+    }
+
+    #[test]
+    fn context_size_prefers_exact_then_longest_prefix() {
+        let profile = ProviderProfile::new("test", "http://localhost", "m")
+            .with_model(ModelProfile::new("m", 100))
+            .with_model(ModelProfile::new("m-long", 1000));
+        assert_eq!(profile.context_size("m"), 100);
+        assert_eq!(profile.context_size("m-long-0301"), 1000);
+        assert_eq!(profile.context_size("other"), DEFAULT_CONTEXT_SIZE);
+    }
+
+    #[test]
+    fn profile_from_yaml() {
+        let yaml = r#"
+name: vllm
+api_base: http://localhost:8000/v1
+api_key_env: VLLM_API_KEY
+default_model: llama-3-8b
+models:
+  - name: llama-3-8b
+    context_size: 8192
+tokenizer: p50k_base
+quirks:
+  headers:
+    X-Tenant: acme
+"#;
+        let profile: ProviderProfile = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(profile.api_key_env, "VLLM_API_KEY");
+        assert_eq!(profile.context_size("llama-3-8b"), 8192);
+        assert_eq!(profile.tokenizer, TokenizerKind::P50kBase);
+        assert_eq!(profile.quirks.headers["X-Tenant"], "acme");
+        assert!(!profile.quirks.assistants_beta_header);
+    }
+}
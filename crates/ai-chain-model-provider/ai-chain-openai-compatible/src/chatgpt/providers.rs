@@ -0,0 +1,126 @@
+//! Built-in provider profiles.
+//!
+//! Each marker type implements [`Provider`] so it can parameterize
+//! [`ProfileConfig`](super::ProfileConfig), e.g. `Executor<ProfileConfig<DeepSeek>>`.
+use super::profile::{ModelProfile, ProviderProfile, Quirks, TokenizerKind};
+
+/// A provider whose profile is known at compile time.
+pub trait Provider: Send + Sync + 'static {
+    fn profile() -> ProviderProfile;
+}
+
+/// The OpenAI API itself.
+pub struct OpenAI;
+
+impl Provider for OpenAI {
+    fn profile() -> ProviderProfile {
+        ProviderProfile::new("openai", "https://api.openai.com/v1", "gpt-3.5-turbo")
+            .with_api_base_env("OPENAI_API_BASE_URL")
+            .with_tokenizer(TokenizerKind::FromModel)
+            .with_embedding_model("text-embedding-ada-002")
+            .with_quirks(Quirks {
+                organization_env: Some("OPENAI_ORG_ID".to_string()),
+                ..Default::default()
+            })
+    }
+}
+
+/// Moonshot AI (Kimi).
+pub struct Moonshot;
+
+impl Provider for Moonshot {
+    fn profile() -> ProviderProfile {
+        ProviderProfile::new("moonshot", "https://api.moonshot.cn/v1", "moonshot-v1-8k")
+            .with_api_key_env("MOONSHOT_API_KEY")
+            .with_model(ModelProfile::new("moonshot-v1-8k", 8192))
+            .with_model(ModelProfile::new("moonshot-v1-16k", 16384))
+            .with_model(ModelProfile::new("moonshot-v1-32k", 32768))
+            .with_quirks(legacy_quirks())
+    }
+}
+
+/// Zhipu AI's GLM models.
+pub struct Glm;
+
+impl Provider for Glm {
+    fn profile() -> ProviderProfile {
+        ProviderProfile::new("glm", "https://open.bigmodel.cn/api/paas/v4", "glm-4")
+            .with_api_key_env("ZHIPUAI_API_KEY")
+            .with_model(ModelProfile::new("glm-4", 128000))
+            .with_model(ModelProfile::new("glm-4v", 2048))
+            .with_model(ModelProfile::new("glm-3-turbo", 128000))
+            .with_embedding_model("embedding-2")
+            .with_quirks(legacy_quirks())
+    }
+}
+
+/// Alibaba Cloud's Qwen models through DashScope's compatible mode.
+pub struct Qwen;
+
+impl Provider for Qwen {
+    fn profile() -> ProviderProfile {
+        ProviderProfile::new(
+            "qwen",
+            "https://dashscope.aliyuncs.com/compatible-mode/v1",
+            "qwen-turbo",
+        )
+        .with_api_key_env("DASHSCOPE_API_KEY")
+        .with_model(ModelProfile::new("qwen-turbo", 8000))
+        .with_model(ModelProfile::new("qwen-long", 10000000))
+        .with_model(ModelProfile::new("qwen-max", 8000))
+        .with_model(ModelProfile::new("qwen-max-longcontext", 30000))
+        .with_model(ModelProfile::new("qwen-plus", 32000))
+        .with_embedding_model("text-embedding-v1")
+        .with_quirks(legacy_quirks())
+    }
+}
+
+/// DeepSeek.
+pub struct DeepSeek;
+
+impl Provider for DeepSeek {
+    fn profile() -> ProviderProfile {
+        ProviderProfile::new("deepseek", "https://api.deepseek.com/v1", "deepseek-chat")
+            .with_api_key_env("DEEPSEEK_API_KEY")
+            .with_model(ModelProfile::new("deepseek-chat", 32768))
+            .with_model(ModelProfile::new("deepseek-coder", 32768))
+    }
+}
+
+/// Baichuan AI.
+pub struct Baichuan;
+
+impl Provider for Baichuan {
+    fn profile() -> ProviderProfile {
+        ProviderProfile::new("baichuan", "https://api.baichuan-ai.com/v1", "Baichuan2-Turbo")
+            .with_api_key_env("BAICHUAN_API_KEY")
+            .with_model(ModelProfile::new("Baichuan2-Turbo", 32768))
+            .with_model(ModelProfile::new("Baichuan2-Turbo-192k", 196608))
+            .with_model(ModelProfile::new("Baichuan3-Turbo", 32768))
+            .with_model(ModelProfile::new("Baichuan3-Turbo-128k", 131072))
+            .with_model(ModelProfile::new("Baichuan4", 32768))
+            .with_embedding_model("Baichuan-Text-Embedding")
+    }
+}
+
+/// A locally hosted OpenAI-compatible server such as vLLM or llama.cpp's server.
+///
+/// The server is expected at `http://localhost:8000/v1`. To point elsewhere, give the profile the
+/// base URL of the server and build the config with
+/// [`ProfileConfig::from_profile`](super::ProfileConfig::from_profile).
+pub struct Local;
+
+impl Provider for Local {
+    fn profile() -> ProviderProfile {
+        ProviderProfile::new("local", "http://localhost:8000/v1", "default")
+            .with_api_key_env("LOCAL_API_KEY")
+    }
+}
+
+/// The GLM, Moonshot and Qwen crates have always sent the Assistants beta header.
+fn legacy_quirks() -> Quirks {
+    Quirks {
+        assistants_beta_header: true,
+        ..Default::default()
+    }
+}
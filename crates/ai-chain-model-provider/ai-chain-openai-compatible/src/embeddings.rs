@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use async_openai::{
+    error::OpenAIError,
+    types::{CreateEmbeddingRequestArgs, EmbeddingInput},
+};
+use async_trait::async_trait;
+use ai_chain::traits::{self, EmbeddingsError};
+use thiserror::Error;
+
+use crate::chatgpt::OAIConfig;
+
+/// The embedding model used when a provider's profile doesn't name one.
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-ada-002";
+
+pub struct Embeddings<C: OAIConfig> {
+    client: Arc<async_openai::Client<C>>,
+    model: String,
+}
+
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub enum OpenAIEmbeddingsError {
+    #[error(transparent)]
+    Client(#[from] OpenAIError),
+    #[error("Request to OpenAI embeddings API was successful but response is empty")]
+    EmptyResponse,
+}
+
+impl EmbeddingsError for OpenAIEmbeddingsError {}
+
+#[async_trait]
+impl<C: OAIConfig> traits::Embeddings for Embeddings<C> {
+    type Error = OpenAIEmbeddingsError;
+
+    async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Self::Error> {
+        let req = CreateEmbeddingRequestArgs::default()
+            .model(self.model.clone())
+            .input(EmbeddingInput::from(texts))
+            .build()?;
+        self.client
+            .embeddings()
+            .create(req)
+            .await
+            .map(|r| r.data.into_iter().map(|e| e.embedding).collect())
+            .map_err(|e| e.into())
+    }
+
+    async fn embed_query(&self, query: String) -> Result<Vec<f32>, Self::Error> {
+        let req = CreateEmbeddingRequestArgs::default()
+            .model(self.model.clone())
+            .input(EmbeddingInput::from(query))
+            .build()?;
+        self.client
+            .embeddings()
+            .create(req)
+            .await
+            .map(|r| r.data.into_iter())?
+            .map(|e| e.embedding)
+            .next_back()
+            .ok_or(OpenAIEmbeddingsError::EmptyResponse)
+    }
+}
+
+impl<C: OAIConfig> Default for Embeddings<C> {
+    /// Uses the embedding model named by the provider's profile.
+    fn default() -> Self {
+        let config = C::create();
+        let model = config
+            .profile()
+            .embedding_model
+            .clone()
+            .unwrap_or_else(|| DEFAULT_EMBEDDING_MODEL.to_string());
+        Self::for_client(async_openai::Client::with_config(config), &model)
+    }
+}
+
+impl<C: OAIConfig> Embeddings<C> {
+    pub fn for_client(client: async_openai::Client<C>, model: &str) -> Self {
+        Self {
+            client: client.into(),
+            model: model.to_string(),
+        }
+    }
+}
@@ -22,4 +22,5 @@
 //! Happy coding, and enjoy the amazing world of LLMs with ai-chain-openai! 🥳🚀P
 
 pub mod chatgpt;
+pub mod embeddings;
 pub use async_openai;
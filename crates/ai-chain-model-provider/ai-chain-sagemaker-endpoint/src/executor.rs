@@ -3,10 +3,11 @@ use crate::model::Model;
 use async_trait::async_trait;
 
 use ai_chain::options::Opt;
+use ai_chain::options::OptDiscriminants;
 use ai_chain::options::Options;
 use ai_chain::options::OptionsCascade;
 use ai_chain::output::Output;
-use ai_chain::prompt::Prompt;
+use ai_chain::prompt::{Data, Prompt};
 use ai_chain::tokens::{
     PromptTokensError, TokenCollection, TokenCount, Tokenizer, TokenizerError,
 };
@@ -56,7 +57,29 @@ impl ai_chain::traits::Executor for Executor {
         let opts = self.cascade(Some(options));
         let model = self.get_model_from_invocation_options(&opts);
 
-        let body_blob = model.format_request(prompt, &opts);
+        // Chat prompts are rendered here, so the stop sequences of the template can be added.
+        let (prompt, template_options) = match (prompt, model.chat_template(&opts)) {
+            (Data::Chat(_), Some(template)) => {
+                let text = template
+                    .format_prompt(prompt)
+                    .map_err(|e| ExecutorError::InnerError(e.into()))?;
+                let mut stop = match opts.get(OptDiscriminants::StopSequence) {
+                    Some(Opt::StopSequence(stop)) => stop.clone(),
+                    _ => Vec::new(),
+                };
+                stop.extend(template.stop_sequences().iter().cloned());
+                let mut builder = Options::builder();
+                builder.add_option(Opt::StopSequence(stop));
+                (Prompt::text(text), Some(builder.build()))
+            }
+            _ => (prompt.clone(), None),
+        };
+        let opts = match &template_options {
+            Some(template_options) => opts.with_options(template_options),
+            None => opts,
+        };
+
+        let body_blob = model.format_request(&prompt, &opts);
 
         let result = self
             .sagemaker_client
@@ -1,7 +1,7 @@
 use aws_sdk_sagemakerruntime::operation::invoke_endpoint::InvokeEndpointOutput;
 use aws_sdk_sagemakerruntime::primitives::Blob;
 use ai_chain::options::{ModelRef, Opt, OptDiscriminants, OptionsCascade};
-use ai_chain::prompt::Prompt;
+use ai_chain::prompt::{ChatTemplate, Prompt};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use serde_with::skip_serializing_none;
@@ -133,6 +133,18 @@ impl Formatter for Model {
 }
 
 impl Model {
+    /// The template for chat prompts: `Opt::ChatTemplate` when it is set, otherwise the format the
+    /// model was tuned on, if it is known.
+    pub fn chat_template(&self, options: &OptionsCascade) -> Option<ChatTemplate> {
+        if let Some(Opt::ChatTemplate(template)) = options.get(OptDiscriminants::ChatTemplate) {
+            return Some(template.clone());
+        }
+        match self {
+            Model::Falcon7BInstruct | Model::Falcon40BInstruct => Some(ChatTemplate::falcon()),
+            _ => None,
+        }
+    }
+
     /// Convert the model to its SageMaker JumpStart default endpoint name
     pub fn to_jumpstart_endpoint_name(&self) -> String {
         match &self {
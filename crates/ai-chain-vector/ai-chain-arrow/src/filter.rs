@@ -0,0 +1,201 @@
+//! Filters on the metadata columns.
+
+use ai_chain_types::{
+    helper::json_value_to_field,
+    types::{Field, Record, Schema},
+};
+use serde_json::Value;
+
+use crate::StorageError;
+
+/// A condition on the metadata columns of a document.
+///
+/// Values are converted to the type of their column, so `Filter::gt("year", 2020)` compares
+/// integers on an `Int` column and `Filter::eq("date", "2024-01-31")` compares dates on a `Date`
+/// column. Null values match none of the comparisons.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Eq(String, Value),
+    Ne(String, Value),
+    Gt(String, Value),
+    Gte(String, Value),
+    Lt(String, Value),
+    Lte(String, Value),
+    /// The column equals one of the values.
+    In(String, Vec<Value>),
+    IsNull(String),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq(column: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Eq(column.into(), value.into())
+    }
+
+    pub fn ne(column: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Ne(column.into(), value.into())
+    }
+
+    pub fn gt(column: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Gt(column.into(), value.into())
+    }
+
+    pub fn gte(column: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Gte(column.into(), value.into())
+    }
+
+    pub fn lt(column: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Lt(column.into(), value.into())
+    }
+
+    pub fn lte(column: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Lte(column.into(), value.into())
+    }
+
+    pub fn is_in<V: Into<Value>>(
+        column: impl Into<String>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        Filter::In(column.into(), values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn is_null(column: impl Into<String>) -> Self {
+        Filter::IsNull(column.into())
+    }
+
+    /// Resolves the columns and types the values against `schema`.
+    pub(crate) fn compile(&self, schema: &Schema) -> Result<Compiled, StorageError> {
+        let compare = |column: &str, value: &Value, keep: fn(std::cmp::Ordering) -> bool| {
+            let (index, value) = typed(schema, column, value)?;
+            Ok(Compiled::Compare { index, value, keep })
+        };
+        Ok(match self {
+            Filter::Eq(c, v) => compare(c, v, |o| o.is_eq())?,
+            Filter::Ne(c, v) => compare(c, v, |o| o.is_ne())?,
+            Filter::Gt(c, v) => compare(c, v, |o| o.is_gt())?,
+            Filter::Gte(c, v) => compare(c, v, |o| o.is_ge())?,
+            Filter::Lt(c, v) => compare(c, v, |o| o.is_lt())?,
+            Filter::Lte(c, v) => compare(c, v, |o| o.is_le())?,
+            Filter::In(c, values) => Compiled::Or(
+                values
+                    .iter()
+                    .map(|v| compare(c, v, |o| o.is_eq()))
+                    .collect::<Result<_, StorageError>>()?,
+            ),
+            Filter::IsNull(c) => Compiled::IsNull(index(schema, c)?),
+            Filter::And(filters) => Compiled::And(
+                filters
+                    .iter()
+                    .map(|f| f.compile(schema))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Filter::Or(filters) => Compiled::Or(
+                filters
+                    .iter()
+                    .map(|f| f.compile(schema))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Filter::Not(filter) => Compiled::Not(Box::new(filter.compile(schema)?)),
+        })
+    }
+}
+
+fn index(schema: &Schema, column: &str) -> Result<usize, StorageError> {
+    schema
+        .fields
+        .iter()
+        .position(|f| f.name == column)
+        .ok_or_else(|| StorageError::UnknownColumn(column.to_string()))
+}
+
+fn typed(schema: &Schema, column: &str, value: &Value) -> Result<(usize, Field), StorageError> {
+    let index = index(schema, column)?;
+    let typ = schema.fields[index].typ;
+    let value = json_value_to_field(value.clone(), typ, true)
+        .map_err(|e| StorageError::Conversion(format!("filter on {}: {}", column, e)))?;
+    Ok((index, value))
+}
+
+/// A filter with its columns resolved to indexes and its values typed.
+#[derive(Debug)]
+pub(crate) enum Compiled {
+    Compare {
+        index: usize,
+        value: Field,
+        keep: fn(std::cmp::Ordering) -> bool,
+    },
+    IsNull(usize),
+    And(Vec<Compiled>),
+    Or(Vec<Compiled>),
+    Not(Box<Compiled>),
+}
+
+impl Compiled {
+    pub(crate) fn matches(&self, record: &Record) -> bool {
+        match self {
+            Compiled::Compare { index, value, keep } => match &record.values[*index] {
+                Field::Null => false,
+                field => *value != Field::Null && keep(field.cmp(value)),
+            },
+            Compiled::IsNull(index) => record.values[*index] == Field::Null,
+            Compiled::And(filters) => filters.iter().all(|f| f.matches(record)),
+            Compiled::Or(filters) => filters.iter().any(|f| f.matches(record)),
+            Compiled::Not(filter) => !filter.matches(record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_chain_types::types::{FieldDefinition, FieldType, SourceDefinition};
+
+    #[test]
+    fn test_filters_compare_typed_values() {
+        let mut schema = Schema::new();
+        schema
+            .field(
+                FieldDefinition::new(
+                    "source".to_string(),
+                    FieldType::String,
+                    true,
+                    SourceDefinition::Dynamic,
+                ),
+                false,
+            )
+            .field(
+                FieldDefinition::new(
+                    "year".to_string(),
+                    FieldType::Int,
+                    true,
+                    SourceDefinition::Dynamic,
+                ),
+                false,
+            );
+        let record = Record::new(vec![Field::String("wiki".to_string()), Field::Int(2019)]);
+        let undated = Record::new(vec![Field::String("blog".to_string()), Field::Null]);
+
+        let filter = Filter::And(vec![
+            Filter::is_in("source", ["wiki", "blog"]),
+            Filter::gte("year", 2010),
+        ])
+        .compile(&schema)
+        .unwrap();
+        assert!(filter.matches(&record));
+        assert!(!filter.matches(&undated));
+
+        let filter = Filter::Not(Box::new(Filter::lt("year", 2019)))
+            .compile(&schema)
+            .unwrap();
+        assert!(filter.matches(&record));
+        assert!(Filter::is_null("year")
+            .compile(&schema)
+            .unwrap()
+            .matches(&undated));
+
+        assert!(Filter::eq("author", "Ada").compile(&schema).is_err());
+        assert!(Filter::eq("year", "recent").compile(&schema).is_err());
+    }
+}
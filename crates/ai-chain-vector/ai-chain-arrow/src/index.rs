@@ -0,0 +1,135 @@
+//! An IVF-Flat index: the vectors are clustered with k-means, and searches only scan the lists of
+//! the clusters whose centroids are nearest to the query.
+
+use serde::{Deserialize, Serialize};
+
+/// The number of k-means iterations when the index is built.
+const ITERATIONS: usize = 10;
+
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+pub(crate) fn normalize(vector: &mut [f32]) {
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IvfIndex {
+    pub centroids: Vec<Vec<f32>>,
+    /// The ids of the rows of each cluster.
+    pub lists: Vec<Vec<u64>>,
+    /// Rows with ids from this one on were added after the index was built.
+    pub indexed_below: u64,
+}
+
+impl IvfIndex {
+    /// Clusters the normalized `vectors`, of rows `ids`, into at most `lists` lists.
+    pub fn build(
+        ids: &[u64],
+        vectors: &[f32],
+        dimension: usize,
+        lists: usize,
+        indexed_below: u64,
+    ) -> Self {
+        let rows: Vec<&[f32]> = vectors.chunks_exact(dimension).collect();
+        let k = lists.clamp(1, rows.len().max(1));
+        // Farthest-first initialization: each centroid is the row least similar to the ones
+        // picked before, which spreads them out and keeps builds repeatable.
+        let mut centroids: Vec<Vec<f32>> = Vec::with_capacity(k);
+        let mut similarity = vec![f32::NEG_INFINITY; rows.len()];
+        let mut next = 0;
+        while centroids.len() < k && next < rows.len() {
+            centroids.push(rows[next].to_vec());
+            for (s, row) in similarity.iter_mut().zip(&rows) {
+                *s = s.max(dot(row, rows[next]));
+            }
+            next = similarity
+                .iter()
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(b.1))
+                .map_or(rows.len(), |(i, _)| i);
+        }
+
+        let mut assignments = vec![0; rows.len()];
+        for _ in 0..ITERATIONS {
+            for (row, assignment) in rows.iter().zip(assignments.iter_mut()) {
+                *assignment = nearest(&centroids, row);
+            }
+            let mut sums = vec![vec![0.0f32; dimension]; centroids.len()];
+            let mut counts = vec![0usize; centroids.len()];
+            for (row, &assignment) in rows.iter().zip(&assignments) {
+                counts[assignment] += 1;
+                for (sum, x) in sums[assignment].iter_mut().zip(row.iter()) {
+                    *sum += x;
+                }
+            }
+            for ((centroid, mut sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+                // Empty clusters keep their centroid.
+                if count > 0 {
+                    normalize(&mut sum);
+                    *centroid = sum;
+                }
+            }
+        }
+
+        let mut lists = vec![Vec::new(); centroids.len()];
+        for (row, id) in rows.iter().zip(ids) {
+            lists[nearest(&centroids, row)].push(*id);
+        }
+        IvfIndex {
+            centroids,
+            lists,
+            indexed_below,
+        }
+    }
+
+    /// The lists of the `nprobe` centroids nearest to `query`.
+    pub fn probe(&self, query: &[f32], nprobe: usize) -> impl Iterator<Item = &u64> {
+        let mut scored: Vec<(f32, usize)> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (dot(query, c), i))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(nprobe)
+            .flat_map(|(_, i)| self.lists[i].iter())
+    }
+}
+
+fn nearest(centroids: &[Vec<f32>], row: &[f32]) -> usize {
+    centroids
+        .iter()
+        .enumerate()
+        .max_by(|a, b| dot(row, a.1).total_cmp(&dot(row, b.1)))
+        .map_or(0, |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clusters_separate_directions() {
+        // Two tight groups, around the x and the y axis.
+        let mut vectors = Vec::new();
+        for i in 0..10 {
+            let e = i as f32 * 0.01;
+            vectors.extend([1.0, e, 0.0, e, 1.0, 0.0]);
+        }
+        vectors.chunks_exact_mut(3).for_each(normalize);
+        let ids: Vec<u64> = (0..20).collect();
+        let index = IvfIndex::build(&ids, &vectors, 3, 2, 20);
+
+        let mut near_y: Vec<u64> = index.probe(&[0.0, 1.0, 0.0], 1).copied().collect();
+        near_y.sort();
+        assert_eq!(near_y, (0..20).filter(|i| i % 2 == 1).collect::<Vec<_>>());
+        assert_eq!(index.probe(&[0.0, 1.0, 0.0], 2).count(), 20);
+    }
+}
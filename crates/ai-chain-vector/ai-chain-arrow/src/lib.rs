@@ -0,0 +1,791 @@
+//! An embedded vector store on Arrow files.
+//!
+//! `ArrowVectorStore` keeps its documents in a directory, without any server. Each batch of added
+//! documents becomes an Arrow IPC file, a segment, with an `id`, a `page_content` and a `vector`
+//! column and a typed column per field of the metadata schema. The metadata columns go through the
+//! Arrow mapping of `ai-chain-types`, so the files can be read by any Arrow tool.
+//!
+//! A `manifest.json` lists the segments and the deleted ids; it is replaced atomically after
+//! every change, so the store is consistent even if the process stops halfway. Deleted rows are
+//! dropped from the segments by [`ArrowVectorStore::compact`].
+//!
+//! Searches are exact, or go through an IVF-Flat index once [`ArrowVectorStore::create_index`]
+//! has built one. Rows added after the index was built are always searched exactly.
+
+mod filter;
+mod index;
+mod segment;
+
+use std::{
+    collections::HashMap,
+    fs,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use ai_chain::{
+    schema::{Document, EmptyMetadata, VectorRecord},
+    traits::{Embeddings, EmbeddingsError, ExportableVectorStore, VectorStore, VectorStoreError},
+};
+use ai_chain_types::{arrow::error::ArrowError, types::Schema};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+pub use filter::Filter;
+use index::{dot, normalize, IvfIndex};
+use segment::Rows;
+
+const MANIFEST: &str = "manifest.json";
+const INDEX: &str = "index.json";
+
+/// The layout of a store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrowStoreConfig {
+    /// The length of the embeddings.
+    pub dimension: usize,
+    /// The metadata columns. Documents are converted to these through their JSON form, so the
+    /// field names are the keys of the serialized metadata.
+    pub metadata_schema: Schema,
+}
+
+impl ArrowStoreConfig {
+    pub fn new(dimension: usize, metadata_schema: Schema) -> Self {
+        ArrowStoreConfig {
+            dimension,
+            metadata_schema,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Manifest {
+    /// Increases with every change.
+    version: u64,
+    dimension: usize,
+    metadata_schema: Schema,
+    segments: Vec<String>,
+    deleted: Vec<u64>,
+    next_id: u64,
+    next_segment: u64,
+    indexed: bool,
+}
+
+/// The live rows, with their vectors as they were embedded, and the same vectors normalized for
+/// scoring.
+struct State {
+    manifest: Manifest,
+    rows: Rows,
+    normalized: Vec<f32>,
+    positions: HashMap<u64, usize>,
+    index: Option<IvfIndex>,
+}
+
+impl State {
+    fn push(&mut self, rows: Rows) {
+        let deleted: std::collections::HashSet<u64> =
+            self.manifest.deleted.iter().copied().collect();
+        let dimension = self.manifest.dimension;
+        let vectors = rows.vectors.chunks_exact(dimension);
+        for (((id, content), vector), metadata) in rows
+            .ids
+            .into_iter()
+            .zip(rows.contents)
+            .zip(vectors)
+            .zip(rows.metadata)
+        {
+            if deleted.contains(&id) {
+                continue;
+            }
+            let mut normalized = vector.to_vec();
+            normalize(&mut normalized);
+            self.positions.insert(id, self.rows.ids.len());
+            self.rows.ids.push(id);
+            self.rows.contents.push(content);
+            self.rows.vectors.extend_from_slice(vector);
+            self.normalized.extend(normalized);
+            self.rows.metadata.push(metadata);
+        }
+    }
+
+    fn remove(&mut self, ids: &[u64]) -> usize {
+        let ids: std::collections::HashSet<u64> = ids
+            .iter()
+            .copied()
+            .filter(|id| self.positions.contains_key(id))
+            .collect();
+        if ids.is_empty() {
+            return 0;
+        }
+        let dimension = self.manifest.dimension;
+        let old = std::mem::take(&mut self.rows);
+        let old_normalized = std::mem::take(&mut self.normalized);
+        self.positions.clear();
+        for ((((id, content), vector), normalized), metadata) in old
+            .ids
+            .into_iter()
+            .zip(old.contents)
+            .zip(old.vectors.chunks_exact(dimension))
+            .zip(old_normalized.chunks_exact(dimension))
+            .zip(old.metadata)
+        {
+            if !ids.contains(&id) {
+                self.positions.insert(id, self.rows.ids.len());
+                self.rows.ids.push(id);
+                self.rows.contents.push(content);
+                self.rows.vectors.extend_from_slice(vector);
+                self.normalized.extend_from_slice(normalized);
+                self.rows.metadata.push(metadata);
+            }
+        }
+        self.manifest.deleted.extend(ids.iter().copied());
+        ids.len()
+    }
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), StorageError> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, StorageError> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// A vector store in a directory of Arrow files.
+///
+/// Similarity is the cosine similarity, from -1 to 1.
+pub struct ArrowVectorStore<E, M = EmptyMetadata>
+where
+    E: Embeddings,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    directory: PathBuf,
+    state: RwLock<State>,
+    embeddings: Arc<E>,
+    nprobe: usize,
+    _marker: PhantomData<M>,
+}
+
+impl<E, M> ArrowVectorStore<E, M>
+where
+    E: Embeddings,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    /// Opens the store in `directory`, creating it if it doesn't exist. An existing store must
+    /// have the dimension and the metadata columns of `config`.
+    pub fn open<P: AsRef<Path>>(
+        directory: P,
+        config: ArrowStoreConfig,
+        embeddings: Arc<E>,
+    ) -> Result<Self, ArrowVectorStoreError<E::Error>> {
+        let directory = directory.as_ref().to_path_buf();
+        for field in &config.metadata_schema.fields {
+            if [segment::ID, segment::PAGE_CONTENT, segment::VECTOR].contains(&field.name.as_str())
+            {
+                return Err(ArrowVectorStoreError::SchemaMismatch(format!(
+                    "{} is a reserved column name",
+                    field.name
+                )));
+            }
+        }
+
+        let manifest_path = directory.join(MANIFEST);
+        let state = if manifest_path.exists() {
+            let manifest: Manifest = read_json(&manifest_path).map_err(storage)?;
+            let columns = |schema: &Schema| -> Vec<(String, _)> {
+                schema
+                    .fields
+                    .iter()
+                    .map(|f| (f.name.clone(), f.typ))
+                    .collect()
+            };
+            if manifest.dimension != config.dimension {
+                return Err(ArrowVectorStoreError::DimensionMismatch {
+                    expected: manifest.dimension,
+                    actual: config.dimension,
+                });
+            }
+            if columns(&manifest.metadata_schema) != columns(&config.metadata_schema) {
+                return Err(ArrowVectorStoreError::SchemaMismatch(
+                    "the metadata columns differ from the ones of the store".to_string(),
+                ));
+            }
+            let index = if manifest.indexed {
+                Some(read_json(&directory.join(INDEX)).map_err(storage)?)
+            } else {
+                None
+            };
+            let mut state = State {
+                manifest: manifest.clone(),
+                rows: Rows::default(),
+                normalized: Vec::new(),
+                positions: HashMap::new(),
+                index,
+            };
+            for name in &manifest.segments {
+                let rows = segment::read(
+                    &directory.join(name),
+                    manifest.dimension,
+                    &manifest.metadata_schema,
+                )
+                .map_err(storage)?;
+                state.push(rows);
+            }
+            state
+        } else {
+            fs::create_dir_all(&directory).map_err(|e| storage(e.into()))?;
+            let manifest = Manifest {
+                version: 0,
+                dimension: config.dimension,
+                metadata_schema: config.metadata_schema,
+                segments: Vec::new(),
+                deleted: Vec::new(),
+                next_id: 0,
+                next_segment: 0,
+                indexed: false,
+            };
+            write_json(&manifest_path, &manifest).map_err(storage)?;
+            State {
+                manifest,
+                rows: Rows::default(),
+                normalized: Vec::new(),
+                positions: HashMap::new(),
+                index: None,
+            }
+        };
+
+        Ok(ArrowVectorStore {
+            directory,
+            state: RwLock::new(state),
+            embeddings,
+            nprobe: 8,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Sets how many lists of the index are searched. More is slower but finds more of the
+    /// nearest documents.
+    pub fn with_nprobe(mut self, nprobe: usize) -> Self {
+        self.nprobe = nprobe;
+        self
+    }
+
+    /// The number of documents in the store.
+    pub fn len(&self) -> usize {
+        self.state.read().unwrap().rows.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn commit(&self, state: &mut State) -> Result<(), StorageError> {
+        state.manifest.version += 1;
+        write_json(&self.directory.join(MANIFEST), &state.manifest)
+    }
+
+    /// Adds documents with their embeddings, without calling the embeddings model. Returns the
+    /// ids of the documents.
+    pub fn add_embedded(
+        &self,
+        documents: Vec<Document<M>>,
+        vectors: Vec<Vec<f32>>,
+    ) -> Result<Vec<String>, ArrowVectorStoreError<E::Error>> {
+        if documents.len() != vectors.len() {
+            return Err(ArrowVectorStoreError::EmbeddingCount {
+                expected: documents.len(),
+                actual: vectors.len(),
+            });
+        }
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut state = self.state.write().unwrap();
+        let dimension = state.manifest.dimension;
+        if let Some(vector) = vectors.iter().find(|v| v.len() != dimension) {
+            return Err(ArrowVectorStoreError::DimensionMismatch {
+                expected: dimension,
+                actual: vector.len(),
+            });
+        }
+
+        let first_id = state.manifest.next_id;
+        let mut rows = Rows::default();
+        for ((document, vector), id) in documents.into_iter().zip(vectors).zip(first_id..) {
+            rows.metadata.push(
+                segment::record_from_metadata(
+                    document.metadata.as_ref(),
+                    &state.manifest.metadata_schema,
+                )
+                .map_err(storage)?,
+            );
+            rows.ids.push(id);
+            rows.contents.push(document.page_content);
+            rows.vectors.extend(vector);
+        }
+        let ids: Vec<String> = rows.ids.iter().map(|id| id.to_string()).collect();
+
+        let name = format!("segment-{}.arrow", state.manifest.next_segment);
+        segment::write(
+            &self.directory.join(&name),
+            &rows,
+            dimension,
+            &state.manifest.metadata_schema,
+        )
+        .map_err(storage)?;
+        state.manifest.segments.push(name);
+        state.manifest.next_segment += 1;
+        state.manifest.next_id = first_id + rows.ids.len() as u64;
+        self.commit(&mut state).map_err(storage)?;
+        state.push(rows);
+        Ok(ids)
+    }
+
+    /// Deletes the documents with the given ids. Returns how many were found.
+    pub fn delete(&self, ids: &[String]) -> Result<usize, ArrowVectorStoreError<E::Error>> {
+        let ids: Vec<u64> = ids.iter().filter_map(|id| id.parse().ok()).collect();
+        let mut state = self.state.write().unwrap();
+        let removed = state.remove(&ids);
+        if removed > 0 {
+            self.commit(&mut state).map_err(storage)?;
+        }
+        Ok(removed)
+    }
+
+    /// Rewrites the live documents into a single segment, dropping the deleted ones from disk.
+    pub fn compact(&self) -> Result<(), ArrowVectorStoreError<E::Error>> {
+        let mut state = self.state.write().unwrap();
+        let old_segments = std::mem::take(&mut state.manifest.segments);
+        if !state.rows.ids.is_empty() {
+            let name = format!("segment-{}.arrow", state.manifest.next_segment);
+            segment::write(
+                &self.directory.join(&name),
+                &state.rows,
+                state.manifest.dimension,
+                &state.manifest.metadata_schema,
+            )
+            .map_err(storage)?;
+            state.manifest.segments.push(name);
+            state.manifest.next_segment += 1;
+        }
+        state.manifest.deleted.clear();
+        self.commit(&mut state).map_err(storage)?;
+        for name in old_segments {
+            fs::remove_file(self.directory.join(name)).map_err(|e| storage(e.into()))?;
+        }
+        Ok(())
+    }
+
+    /// Builds an IVF-Flat index with `lists` clusters over the documents in the store. About the
+    /// square root of the number of documents is a good number of lists.
+    pub fn create_index(&self, lists: usize) -> Result<(), ArrowVectorStoreError<E::Error>> {
+        let mut state = self.state.write().unwrap();
+        let index = IvfIndex::build(
+            &state.rows.ids,
+            &state.normalized,
+            state.manifest.dimension,
+            lists,
+            state.manifest.next_id,
+        );
+        write_json(&self.directory.join(INDEX), &index).map_err(storage)?;
+        state.index = Some(index);
+        state.manifest.indexed = true;
+        self.commit(&mut state).map_err(storage)?;
+        Ok(())
+    }
+
+    /// The positions of the rows to score for `query`.
+    fn candidates(&self, state: &State, query: &[f32]) -> Vec<usize> {
+        match &state.index {
+            Some(index) => {
+                let mut positions: Vec<usize> = index
+                    .probe(query, self.nprobe)
+                    .filter_map(|id| state.positions.get(id).copied())
+                    .collect();
+                positions.extend(
+                    state
+                        .rows
+                        .ids
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, &id)| id >= index.indexed_below)
+                        .map(|(position, _)| position),
+                );
+                positions
+            }
+            None => (0..state.rows.ids.len()).collect(),
+        }
+    }
+}
+
+impl<E, M> ArrowVectorStore<E, M>
+where
+    E: Embeddings + Send + Sync,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    /// Returns the `limit` documents most similar to `query` that pass `filter`, with their
+    /// cosine similarity, most similar first.
+    pub async fn similarity_search_with_filter(
+        &self,
+        query: String,
+        limit: u32,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(Document<M>, f32)>, ArrowVectorStoreError<E::Error>> {
+        let mut query = self.embeddings.embed_query(query).await?;
+        normalize(&mut query);
+
+        let state = self.state.read().unwrap();
+        let dimension = state.manifest.dimension;
+        if query.len() != dimension {
+            return Err(ArrowVectorStoreError::DimensionMismatch {
+                expected: dimension,
+                actual: query.len(),
+            });
+        }
+        let filter = filter
+            .map(|f| f.compile(&state.manifest.metadata_schema))
+            .transpose()
+            .map_err(storage)?;
+
+        let mut scored: Vec<(f32, usize)> = self
+            .candidates(&state, &query)
+            .into_iter()
+            .filter(|&p| {
+                filter
+                    .as_ref()
+                    .is_none_or(|f| f.matches(&state.rows.metadata[p]))
+            })
+            .map(|p| {
+                let vector = &state.normalized[p * dimension..(p + 1) * dimension];
+                (dot(&query, vector), p)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(limit as usize);
+
+        scored
+            .into_iter()
+            .map(|(score, p)| {
+                let metadata = segment::metadata_from_record(
+                    &state.rows.metadata[p],
+                    &state.manifest.metadata_schema,
+                )
+                .map_err(storage)?;
+                Ok((
+                    Document {
+                        page_content: state.rows.contents[p].clone(),
+                        metadata,
+                    },
+                    score,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Errors reading or writing the files of the store.
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error("Unable to convert metadata: {0}")]
+    Conversion(String),
+    #[error("Unknown metadata column {0:?}")]
+    UnknownColumn(String),
+    #[error("Corrupt store: {0}")]
+    Corrupt(String),
+}
+
+fn storage<E>(e: StorageError) -> ArrowVectorStoreError<E>
+where
+    E: std::fmt::Debug + std::error::Error + EmbeddingsError,
+{
+    ArrowVectorStoreError::Storage(e)
+}
+
+#[derive(Debug, Error)]
+pub enum ArrowVectorStoreError<E>
+where
+    E: std::fmt::Debug + std::error::Error + EmbeddingsError,
+{
+    #[error(transparent)]
+    Embeddings(#[from] E),
+    #[error(transparent)]
+    Storage(StorageError),
+    #[error("Expected vectors of length {expected}, got one of length {actual}")]
+    DimensionMismatch { expected: usize, actual: usize },
+    #[error("Expected {expected} embeddings, got {actual}")]
+    EmbeddingCount { expected: usize, actual: usize },
+    #[error("Invalid metadata schema: {0}")]
+    SchemaMismatch(String),
+}
+
+impl<E> VectorStoreError for ArrowVectorStoreError<E> where
+    E: std::fmt::Debug + std::error::Error + EmbeddingsError
+{
+}
+
+#[async_trait]
+impl<E, M> VectorStore<E, M> for ArrowVectorStore<E, M>
+where
+    E: Embeddings + Send + Sync,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    type Error = ArrowVectorStoreError<E::Error>;
+
+    async fn add_texts(&self, texts: Vec<String>) -> Result<Vec<String>, Self::Error> {
+        let vectors = self.embeddings.embed_texts(texts.clone()).await?;
+        self.add_embedded(texts.into_iter().map(Document::new).collect(), vectors)
+    }
+
+    async fn add_documents(&self, documents: Vec<Document<M>>) -> Result<Vec<String>, Self::Error> {
+        let texts = documents.iter().map(|d| d.page_content.clone()).collect();
+        let vectors = self.embeddings.embed_texts(texts).await?;
+        self.add_embedded(documents, vectors)
+    }
+
+    async fn similarity_search(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<Document<M>>, Self::Error> {
+        Ok(self
+            .similarity_search_with_filter(query, limit, None)
+            .await?
+            .into_iter()
+            .map(|(document, _)| document)
+            .collect())
+    }
+}
+
+/// Exports the vectors as they were embedded. Imported records get new ids.
+#[async_trait]
+impl<E, M> ExportableVectorStore<E, M> for ArrowVectorStore<E, M>
+where
+    E: Embeddings + Send + Sync,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    async fn export_records(&self) -> Result<Vec<VectorRecord<M>>, Self::Error> {
+        let state = self.state.read().unwrap();
+        let rows = &state.rows;
+        rows.ids
+            .iter()
+            .zip(&rows.contents)
+            .zip(rows.vectors.chunks_exact(state.manifest.dimension))
+            .zip(&rows.metadata)
+            .map(|(((id, content), vector), metadata)| {
+                Ok(VectorRecord {
+                    id: id.to_string(),
+                    vector: vector.to_vec(),
+                    page_content: content.clone(),
+                    metadata: segment::metadata_from_record(
+                        metadata,
+                        &state.manifest.metadata_schema,
+                    )
+                    .map_err(storage)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn import_records(
+        &self,
+        records: Vec<VectorRecord<M>>,
+    ) -> Result<Vec<String>, Self::Error> {
+        let (documents, vectors) = records.into_iter().map(VectorRecord::into_document).unzip();
+        self.add_embedded(documents, vectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai_chain_types::types::{FieldDefinition, FieldType, SourceDefinition};
+
+    #[derive(Debug, Error)]
+    #[error("unreachable")]
+    struct TestError;
+
+    impl EmbeddingsError for TestError {}
+
+    /// Embeds a text as the counts of the letters `a` to `d`.
+    struct LetterEmbeddings;
+
+    fn letters(text: &str) -> Vec<f32> {
+        ['a', 'b', 'c', 'd']
+            .iter()
+            .map(|&l| text.chars().filter(|&c| c == l).count() as f32)
+            .collect()
+    }
+
+    #[async_trait]
+    impl Embeddings for LetterEmbeddings {
+        type Error = TestError;
+
+        async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, TestError> {
+            Ok(texts.iter().map(|t| letters(t)).collect())
+        }
+
+        async fn embed_query(&self, query: String) -> Result<Vec<f32>, TestError> {
+            Ok(letters(&query))
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Source {
+        source: String,
+        year: Option<i64>,
+    }
+
+    fn config() -> ArrowStoreConfig {
+        let mut schema = Schema::new();
+        schema
+            .field(
+                FieldDefinition::new(
+                    "source".to_string(),
+                    FieldType::String,
+                    false,
+                    SourceDefinition::Dynamic,
+                ),
+                false,
+            )
+            .field(
+                FieldDefinition::new(
+                    "year".to_string(),
+                    FieldType::Int,
+                    true,
+                    SourceDefinition::Dynamic,
+                ),
+                false,
+            );
+        ArrowStoreConfig::new(4, schema)
+    }
+
+    fn document(text: &str, source: &str, year: Option<i64>) -> Document<Source> {
+        Document {
+            page_content: text.to_string(),
+            metadata: Some(Source {
+                source: source.to_string(),
+                year,
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_append_delete_reopen_and_filter() {
+        let directory = std::env::temp_dir().join(format!("ai-chain-arrow-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let open = || -> ArrowVectorStore<LetterEmbeddings, Source> {
+            ArrowVectorStore::open(&directory, config(), Arc::new(LetterEmbeddings)).unwrap()
+        };
+
+        let store = open();
+        let ids = store
+            .add_documents(vec![
+                document("aaa", "wiki", Some(2001)),
+                document("aab", "blog", Some(2019)),
+            ])
+            .await
+            .unwrap();
+        store
+            .add_documents(vec![
+                document("ccd", "wiki", None),
+                Document::new("abd".to_string()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(store.delete(&[ids[0].clone()]).unwrap(), 1);
+        drop(store);
+
+        let store = open();
+        assert_eq!(store.len(), 3);
+        let found = store
+            .similarity_search_with_filter("a".to_string(), 5, None)
+            .await
+            .unwrap();
+        assert_eq!(found[0].0.page_content, "aab");
+        assert_eq!(
+            found[0].0.metadata,
+            document("aab", "blog", Some(2019)).metadata
+        );
+        assert!(found[0].1 > found[1].1);
+        assert!(found.iter().any(|(d, _)| d.metadata.is_none()));
+
+        let found = store
+            .similarity_search_with_filter(
+                "a".to_string(),
+                5,
+                Some(&Filter::And(vec![
+                    Filter::eq("source", "wiki"),
+                    Filter::is_null("year"),
+                ])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0.page_content, "ccd");
+
+        store.create_index(2).unwrap();
+        store.compact().unwrap();
+        store
+            .add_documents(vec![document("aaaa", "new", None)])
+            .await
+            .unwrap();
+        let store = open().with_nprobe(1);
+        assert_eq!(store.len(), 4);
+        let found = store.similarity_search("a".to_string(), 1).await.unwrap();
+        assert_eq!(found[0].page_content, "aaaa");
+        let segments = fs::read_dir(&directory)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().path().extension() == Some("arrow".as_ref()))
+            .count();
+        assert_eq!(segments, 2);
+
+        assert!(matches!(
+            ArrowVectorStore::<LetterEmbeddings, Source>::open(
+                &directory,
+                ArrowStoreConfig::new(3, config().metadata_schema),
+                Arc::new(LetterEmbeddings)
+            ),
+            Err(ArrowVectorStoreError::DimensionMismatch { .. })
+        ));
+
+        let records = store.export_records().await.unwrap();
+        assert_eq!(records.len(), 4);
+        // Compacting rewrote the segments without normalizing their vectors.
+        let aab = records.iter().find(|r| r.page_content == "aab").unwrap();
+        assert_eq!(aab.vector, vec![2.0, 1.0, 0.0, 0.0]);
+        assert!(matches!(
+            store.add_embedded(vec![Document::new("a".to_string())], vec![]),
+            Err(ArrowVectorStoreError::EmbeddingCount {
+                expected: 1,
+                actual: 0
+            })
+        ));
+        let copy_directory = directory.with_extension("copy");
+        let _ = fs::remove_dir_all(&copy_directory);
+        let copy: ArrowVectorStore<LetterEmbeddings, Source> =
+            ArrowVectorStore::open(&copy_directory, config(), Arc::new(LetterEmbeddings)).unwrap();
+        copy.import_records(records).await.unwrap();
+        let found = copy
+            .similarity_search_with_filter("b".to_string(), 1, Some(&Filter::eq("source", "blog")))
+            .await
+            .unwrap();
+        assert_eq!(found[0].0.page_content, "aab");
+        fs::remove_dir_all(&copy_directory).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+//! Segments are Arrow IPC files holding the rows added to the store at once.
+//!
+//! A segment has an `id`, a `page_content` and a `vector` column, followed by a column per field
+//! of the metadata schema. The metadata columns are converted with the Arrow mapping of
+//! `ai-chain-types`, and the metadata schema is kept in the metadata of the Arrow schema.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use ai_chain_types::{
+    arrow::{
+        array::{Array, ArrayRef, FixedSizeListArray, Float32Array, LargeStringArray, UInt64Array},
+        compute::concat_batches,
+        datatypes::{DataType, Field as ArrowField, Schema as ArrowSchema},
+        ipc::{reader::FileReader, writer::FileWriter},
+        record_batch::RecordBatch,
+    },
+    arrow_types::{from_arrow::map_record_batch_to_ai_chain_records, to_arrow},
+    helper::json_value_to_field,
+    json_types::field_to_json_value,
+    types::{Field, Record, Schema},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::StorageError;
+
+pub(crate) const ID: &str = "id";
+pub(crate) const PAGE_CONTENT: &str = "page_content";
+pub(crate) const VECTOR: &str = "vector";
+
+/// Rows of the store, column by column.
+#[derive(Debug, Default)]
+pub(crate) struct Rows {
+    pub ids: Vec<u64>,
+    pub contents: Vec<String>,
+    /// The vectors as they were embedded, one after the other.
+    pub vectors: Vec<f32>,
+    pub metadata: Vec<Record>,
+}
+
+/// The metadata schema with every field nullable, since documents may have no metadata.
+pub(crate) fn storage_schema(metadata_schema: &Schema) -> Schema {
+    let mut schema = metadata_schema.clone();
+    schema.fields.iter_mut().for_each(|f| f.nullable = true);
+    schema
+}
+
+/// Converts metadata to a record of `schema`. Keys that are not in the schema are dropped.
+pub(crate) fn record_from_metadata<M: Serialize>(
+    metadata: Option<&M>,
+    schema: &Schema,
+) -> Result<Record, StorageError> {
+    let Some(metadata) = metadata else {
+        return Ok(Record::nulls(schema.fields.len()));
+    };
+    let mut object = match serde_json::to_value(metadata)? {
+        serde_json::Value::Object(object) => object,
+        serde_json::Value::Null => return Ok(Record::nulls(schema.fields.len())),
+        other => {
+            return Err(StorageError::Conversion(format!(
+                "metadata must serialize to an object, got {}",
+                other
+            )))
+        }
+    };
+    let values = schema
+        .fields
+        .iter()
+        .map(|field| {
+            let value = object.remove(&field.name).unwrap_or_default();
+            json_value_to_field(value, field.typ, field.nullable)
+                .map_err(|e| StorageError::Conversion(format!("field {}: {}", field.name, e)))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Record::new(values))
+}
+
+/// Converts a record of `schema` back to metadata. Records that are all null have none.
+pub(crate) fn metadata_from_record<M: DeserializeOwned>(
+    record: &Record,
+    schema: &Schema,
+) -> Result<Option<M>, StorageError> {
+    if record.values.iter().all(|v| *v == Field::Null) {
+        return Ok(None);
+    }
+    let mut object = serde_json::Map::new();
+    for (field, value) in schema.fields.iter().zip(&record.values) {
+        let value = serde_json::to_value(field_to_json_value(value.clone()))?;
+        object.insert(field.name.clone(), value);
+    }
+    Ok(Some(serde_json::from_value(serde_json::Value::Object(
+        object,
+    ))?))
+}
+
+fn vector_field(dimension: usize) -> ArrowField {
+    ArrowField::new(
+        VECTOR,
+        DataType::FixedSizeList(
+            Arc::new(ArrowField::new("item", DataType::Float32, true)),
+            dimension as i32,
+        ),
+        false,
+    )
+}
+
+/// Writes `rows` to a new segment at `path`.
+pub(crate) fn write(
+    path: &Path,
+    rows: &Rows,
+    dimension: usize,
+    metadata_schema: &Schema,
+) -> Result<(), StorageError> {
+    let metadata_schema = storage_schema(metadata_schema);
+    let metadata_arrow = to_arrow::map_to_arrow_schema(&metadata_schema)?;
+
+    let mut fields = vec![
+        ArrowField::new(ID, DataType::UInt64, false),
+        ArrowField::new(PAGE_CONTENT, DataType::LargeUtf8, false),
+        vector_field(dimension),
+    ];
+    fields.extend(metadata_arrow.fields.iter().map(|f| f.as_ref().clone()));
+    let schema = Arc::new(ArrowSchema::new_with_metadata(
+        fields,
+        metadata_arrow.metadata.clone(),
+    ));
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from(rows.ids.clone())),
+        Arc::new(LargeStringArray::from_iter_values(&rows.contents)),
+        Arc::new(FixedSizeListArray::try_new(
+            Arc::new(ArrowField::new("item", DataType::Float32, true)),
+            dimension as i32,
+            Arc::new(Float32Array::from(rows.vectors.clone())),
+            None,
+        )?),
+    ];
+    if !metadata_schema.fields.is_empty() {
+        let batches = rows
+            .metadata
+            .iter()
+            .map(|record| to_arrow::map_record_to_arrow(record.clone(), &metadata_schema))
+            .collect::<Result<Vec<_>, _>>()?;
+        let metadata = concat_batches(&Arc::new(metadata_arrow), &batches)?;
+        columns.extend(metadata.columns().iter().cloned());
+    }
+
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    let mut writer = FileWriter::try_new(File::create(path)?, &schema)?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    writer.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T, StorageError> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<T>())
+        .ok_or_else(|| StorageError::Corrupt(format!("missing or mistyped column {}", name)))
+}
+
+/// Reads the segment at `path`.
+pub(crate) fn read(
+    path: &Path,
+    dimension: usize,
+    metadata_schema: &Schema,
+) -> Result<Rows, StorageError> {
+    let metadata_schema = storage_schema(metadata_schema);
+    let mut rows = Rows::default();
+    for batch in FileReader::try_new(File::open(path)?, None)? {
+        let batch = batch?;
+        let ids = column::<UInt64Array>(&batch, ID)?;
+        let contents = column::<LargeStringArray>(&batch, PAGE_CONTENT)?;
+        let vectors = column::<FixedSizeListArray>(&batch, VECTOR)?;
+        if vectors.value_length() as usize != dimension {
+            return Err(StorageError::Corrupt(format!(
+                "vectors of length {} in a store of dimension {}",
+                vectors.value_length(),
+                dimension
+            )));
+        }
+        let values = vectors
+            .values()
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .ok_or_else(|| StorageError::Corrupt("vectors are not f32".to_string()))?;
+
+        rows.ids.extend(ids.values().iter());
+        rows.contents
+            .extend(contents.iter().map(|c| c.unwrap_or_default().to_string()));
+        rows.vectors.extend(values.values().iter());
+        if metadata_schema.fields.is_empty() {
+            rows.metadata
+                .extend((0..batch.num_rows()).map(|_| Record::new(Vec::new())));
+        } else {
+            let indices: Vec<usize> = (3..batch.num_columns()).collect();
+            rows.metadata.extend(
+                map_record_batch_to_ai_chain_records(batch.project(&indices)?, &metadata_schema)
+                    .map_err(|e| StorageError::Conversion(e.to_string()))?,
+            );
+        }
+    }
+    Ok(rows)
+}
@@ -0,0 +1,698 @@
+//! An exact, in-memory vector store.
+//!
+//! `FlatVectorStore` compares the query with every stored vector, so its results are exact and it
+//! has no parameters to tune. A normalized copy of the vectors is kept in a single contiguous
+//! buffer, which makes scoring a dot product that the compiler vectorizes, and rows are scored in
+//! parallel batches. This is fast enough for corpora up to a few hundred thousand chunks;
+//! beyond that, an approximate index such as `ai-chain-hnsw` is the better choice.
+//!
+//! The store keeps its documents itself and can be snapshotted to a single file with
+//! [`FlatVectorStore::save`] and restored with [`FlatVectorStore::load`].
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use ai_chain::{
+    schema::{Document, EmptyMetadata, VectorRecord},
+    traits::{Embeddings, EmbeddingsError, ExportableVectorStore, VectorStore, VectorStoreError},
+};
+use async_trait::async_trait;
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+/// The number of products accumulated side by side, so they map onto SIMD registers.
+const LANES: usize = 8;
+/// The number of rows scored by one task.
+const BATCH_ROWS: usize = 1024;
+/// The first bytes of a snapshot file.
+const MAGIC: &[u8; 8] = b"AICFLAT1";
+
+/// The dot product of `a` and `b`, which have the same length.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let mut lanes = [0.0f32; LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let rest: f32 = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(x, y)| x * y)
+        .sum();
+    for (x, y) in a_chunks.zip(b_chunks) {
+        for ((lane, x), y) in lanes.iter_mut().zip(x).zip(y) {
+            *lane += x * y;
+        }
+    }
+    lanes.iter().sum::<f32>() + rest
+}
+
+/// Scales `vector` to unit length. The zero vector is left as it is.
+fn normalize(vector: &mut [f32]) {
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// A row and its score, ordered so that a `BinaryHeap` pops the lowest score first.
+#[derive(Debug, Clone, Copy)]
+struct Scored {
+    score: f32,
+    row: usize,
+}
+
+impl PartialEq for Scored {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .score
+            .total_cmp(&self.score)
+            .then(self.row.cmp(&other.row))
+    }
+}
+
+/// Keeps the `limit` best rows pushed into it.
+struct TopK {
+    limit: usize,
+    heap: BinaryHeap<Scored>,
+}
+
+impl TopK {
+    fn new(limit: usize) -> Self {
+        TopK {
+            limit,
+            heap: BinaryHeap::with_capacity(limit + 1),
+        }
+    }
+
+    /// Whether a row with `score` would be kept.
+    fn accepts(&self, score: f32) -> bool {
+        self.heap.len() < self.limit || self.heap.peek().is_some_and(|worst| score > worst.score)
+    }
+
+    fn push(&mut self, scored: Scored) {
+        self.heap.push(scored);
+        if self.heap.len() > self.limit {
+            self.heap.pop();
+        }
+    }
+
+    fn merge(mut self, other: TopK) -> TopK {
+        for scored in other.heap {
+            if self.accepts(scored.score) {
+                self.push(scored);
+            }
+        }
+        self
+    }
+
+    /// The kept rows, best first.
+    fn into_sorted_vec(self) -> Vec<Scored> {
+        self.heap.into_sorted_vec()
+    }
+}
+
+struct Rows<M>
+where
+    M: Serialize + DeserializeOwned,
+{
+    dimension: Option<usize>,
+    /// The vectors as they were added, one row of `dimension` values per document.
+    raw: Vec<f32>,
+    /// The same vectors scaled to unit length, which queries are compared with.
+    vectors: Vec<f32>,
+    documents: Vec<Document<M>>,
+}
+
+impl<M> Rows<M>
+where
+    M: Serialize + DeserializeOwned + Sync,
+{
+    /// Appends `vector`, whose length is the dimension of the rows.
+    fn push_vector(&mut self, mut vector: Vec<f32>) {
+        self.raw.extend_from_slice(&vector);
+        normalize(&mut vector);
+        self.vectors.extend_from_slice(&vector);
+    }
+
+    /// The vector of `row` as it was added.
+    fn raw_vector(&self, row: usize) -> &[f32] {
+        let dimension = self.dimension.unwrap_or(0);
+        &self.raw[row * dimension..(row + 1) * dimension]
+    }
+
+    /// Returns the rows of the `limit` vectors most similar to `query` among the documents that
+    /// pass `filter`, with their similarity, most similar first.
+    fn search<F>(&self, query: &[f32], limit: usize, filter: F) -> Vec<Scored>
+    where
+        F: Fn(&Document<M>) -> bool + Sync,
+    {
+        let Some(dimension) = self.dimension else {
+            return Vec::new();
+        };
+        if limit == 0 || dimension == 0 {
+            return Vec::new();
+        }
+        self.vectors
+            .par_chunks(dimension * BATCH_ROWS)
+            .enumerate()
+            .map(|(batch, vectors)| {
+                let mut top = TopK::new(limit);
+                for (i, vector) in vectors.chunks_exact(dimension).enumerate() {
+                    let row = batch * BATCH_ROWS + i;
+                    let score = dot(query, vector);
+                    // The filter runs only on rows that would make the cut.
+                    if top.accepts(score) && filter(&self.documents[row]) {
+                        top.push(Scored { score, row });
+                    }
+                }
+                top
+            })
+            .reduce(|| TopK::new(limit), TopK::merge)
+            .into_sorted_vec()
+    }
+
+    /// Writes the magic bytes, the length of the JSON header, the header with the dimension and
+    /// the documents, and the vectors as they were added, as little-endian `f32`s.
+    fn write_snapshot(&self, file: File) -> std::io::Result<()> {
+        let header = serde_json::to_vec(&SnapshotHeader {
+            dimension: self.dimension,
+            documents: self
+                .documents
+                .iter()
+                .map(|d| SnapshotDocument {
+                    page_content: d.page_content.as_str(),
+                    metadata: &d.metadata,
+                })
+                .collect(),
+        })?;
+
+        let mut writer = BufWriter::new(file);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(header.len() as u64).to_le_bytes())?;
+        writer.write_all(&header)?;
+        for x in &self.raw {
+            writer.write_all(&x.to_le_bytes())?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+
+    fn read_snapshot(file: File) -> std::io::Result<Self> {
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+        let mut reader = BufReader::new(file);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a flat vector store snapshot".to_string()));
+        }
+        let mut length = [0u8; 8];
+        reader.read_exact(&mut length)?;
+        let mut header = vec![0u8; u64::from_le_bytes(length) as usize];
+        reader.read_exact(&mut header)?;
+        let header: SnapshotHeaderOwned<M> = serde_json::from_slice(&header)?;
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let expected = header.dimension.unwrap_or(0) * header.documents.len() * 4;
+        if bytes.len() != expected {
+            return Err(invalid(format!(
+                "expected {} bytes of vectors, found {}",
+                expected,
+                bytes.len()
+            )));
+        }
+        let mut rows = Rows {
+            dimension: header.dimension,
+            raw: Vec::with_capacity(bytes.len() / 4),
+            vectors: Vec::with_capacity(bytes.len() / 4),
+            documents: header
+                .documents
+                .into_iter()
+                .map(|d| Document {
+                    page_content: d.page_content,
+                    metadata: d.metadata,
+                })
+                .collect(),
+        };
+        let values: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if let Some(dimension) = header.dimension.filter(|&d| d > 0) {
+            for vector in values.chunks_exact(dimension) {
+                rows.push_vector(vector.to_vec());
+            }
+        }
+        Ok(rows)
+    }
+}
+
+#[derive(Serialize)]
+struct SnapshotHeader<'a, M> {
+    dimension: Option<usize>,
+    documents: Vec<SnapshotDocument<&'a str, &'a Option<M>>>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotHeaderOwned<M> {
+    dimension: Option<usize>,
+    documents: Vec<SnapshotDocument<String, Option<M>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotDocument<S, M> {
+    page_content: S,
+    metadata: M,
+}
+
+/// A vector store that searches by comparing the query with every vector.
+///
+/// Similarity is the cosine similarity, from -1 to 1.
+pub struct FlatVectorStore<E, M = EmptyMetadata>
+where
+    E: Embeddings,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    rows: RwLock<Rows<M>>,
+    embeddings: Arc<E>,
+}
+
+impl<E, M> FlatVectorStore<E, M>
+where
+    E: Embeddings,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    pub fn new(embeddings: Arc<E>) -> Self {
+        FlatVectorStore {
+            rows: RwLock::new(Rows {
+                dimension: None,
+                raw: Vec::new(),
+                vectors: Vec::new(),
+                documents: Vec::new(),
+            }),
+            embeddings,
+        }
+    }
+
+    /// The number of documents in the store.
+    pub fn len(&self) -> usize {
+        self.rows.read().unwrap().documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The length of the vectors, once the first one has been added.
+    pub fn dimension(&self) -> Option<usize> {
+        self.rows.read().unwrap().dimension
+    }
+
+    /// Adds documents with their embeddings, without calling the embeddings model. Returns the
+    /// ids of the documents.
+    pub fn add_embedded(
+        &self,
+        documents: Vec<Document<M>>,
+        vectors: Vec<Vec<f32>>,
+    ) -> Result<Vec<String>, FlatVectorStoreError<E::Error>> {
+        if documents.len() != vectors.len() {
+            return Err(FlatVectorStoreError::EmbeddingCount {
+                expected: documents.len(),
+                actual: vectors.len(),
+            });
+        }
+        let mut rows = self.rows.write().unwrap();
+        let dimension = match (rows.dimension, vectors.first()) {
+            (Some(dimension), _) => dimension,
+            (None, Some(first)) => first.len(),
+            (None, None) => return Ok(Vec::new()),
+        };
+        if let Some(vector) = vectors.iter().find(|v| v.len() != dimension) {
+            return Err(FlatVectorStoreError::DimensionMismatch {
+                expected: dimension,
+                actual: vector.len(),
+            });
+        }
+        rows.dimension = Some(dimension);
+
+        let first_id = rows.documents.len();
+        rows.raw.reserve(vectors.len() * dimension);
+        rows.vectors.reserve(vectors.len() * dimension);
+        for vector in vectors {
+            rows.push_vector(vector);
+        }
+        rows.documents.extend(documents);
+        Ok((first_id..rows.documents.len())
+            .map(|id| id.to_string())
+            .collect())
+    }
+
+    /// Snapshots the vectors and the documents to a single file at `path`.
+    ///
+    /// The file is written next to `path` first and moved into place, so an existing snapshot is
+    /// never left half written.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), FlatVectorStoreError<E::Error>> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        File::create(&tmp)
+            .and_then(|file| self.rows.read().unwrap().write_snapshot(file))
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(FlatVectorStoreError::Snapshot)
+    }
+
+    /// Restores a store snapshotted with [`FlatVectorStore::save`]. Queries are embedded with
+    /// `embeddings`, which should be the model the snapshot was made with.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        embeddings: Arc<E>,
+    ) -> Result<Self, FlatVectorStoreError<E::Error>> {
+        Ok(FlatVectorStore {
+            rows: RwLock::new(
+                File::open(path)
+                    .and_then(Rows::read_snapshot)
+                    .map_err(FlatVectorStoreError::Snapshot)?,
+            ),
+            embeddings,
+        })
+    }
+}
+
+impl<E, M> FlatVectorStore<E, M>
+where
+    E: Embeddings + Send + Sync,
+    M: Serialize + DeserializeOwned + Send + Sync + Clone,
+{
+    /// Returns the `limit` documents most similar to `query` with their cosine similarity, most
+    /// similar first.
+    pub async fn similarity_search_with_scores(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<(Document<M>, f32)>, FlatVectorStoreError<E::Error>> {
+        self.similarity_search_with_filter(query, limit, |_| true)
+            .await
+    }
+
+    /// Like `similarity_search_with_scores`, but only returns documents for which `filter` is
+    /// true, such as those whose metadata names a given source. The search stays exact: it returns
+    /// the `limit` most similar documents that pass the filter.
+    pub async fn similarity_search_with_filter<F>(
+        &self,
+        query: String,
+        limit: u32,
+        filter: F,
+    ) -> Result<Vec<(Document<M>, f32)>, FlatVectorStoreError<E::Error>>
+    where
+        F: Fn(&Document<M>) -> bool + Sync,
+    {
+        let mut query = self.embeddings.embed_query(query).await?;
+        normalize(&mut query);
+
+        let rows = self.rows.read().unwrap();
+        if let Some(dimension) = rows.dimension.filter(|&d| d != query.len()) {
+            return Err(FlatVectorStoreError::DimensionMismatch {
+                expected: dimension,
+                actual: query.len(),
+            });
+        }
+        Ok(rows
+            .search(&query, limit as usize, filter)
+            .into_iter()
+            .map(|scored| (rows.documents[scored.row].clone(), scored.score))
+            .collect())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum FlatVectorStoreError<E>
+where
+    E: std::fmt::Debug + std::error::Error + EmbeddingsError,
+{
+    #[error(transparent)]
+    EmbeddingsError(#[from] E),
+    #[error("Expected vectors of length {expected}, got one of length {actual}")]
+    DimensionMismatch { expected: usize, actual: usize },
+    #[error("Expected {expected} embeddings, got {actual}")]
+    EmbeddingCount { expected: usize, actual: usize },
+    #[error("Unable to save or load the snapshot: {0}")]
+    Snapshot(std::io::Error),
+}
+
+impl<E> VectorStoreError for FlatVectorStoreError<E> where
+    E: std::fmt::Debug + std::error::Error + EmbeddingsError
+{
+}
+
+#[async_trait]
+impl<E, M> VectorStore<E, M> for FlatVectorStore<E, M>
+where
+    E: Embeddings + Send + Sync,
+    M: Serialize + DeserializeOwned + Send + Sync + Clone,
+{
+    type Error = FlatVectorStoreError<E::Error>;
+
+    async fn add_texts(&self, texts: Vec<String>) -> Result<Vec<String>, Self::Error> {
+        let vectors = self.embeddings.embed_texts(texts.clone()).await?;
+        self.add_embedded(texts.into_iter().map(Document::new).collect(), vectors)
+    }
+
+    async fn add_documents(&self, documents: Vec<Document<M>>) -> Result<Vec<String>, Self::Error> {
+        let texts = documents.iter().map(|d| d.page_content.clone()).collect();
+        let vectors = self.embeddings.embed_texts(texts).await?;
+        self.add_embedded(documents, vectors)
+    }
+
+    async fn similarity_search(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<Document<M>>, Self::Error> {
+        Ok(self
+            .similarity_search_with_scores(query, limit)
+            .await?
+            .into_iter()
+            .map(|(document, _)| document)
+            .collect())
+    }
+}
+
+/// Exports the vectors as they were added, so the records rank the same in stores that compare
+/// them by distance or inner product. Imported records get new ids, the positions of their rows.
+#[async_trait]
+impl<E, M> ExportableVectorStore<E, M> for FlatVectorStore<E, M>
+where
+    E: Embeddings + Send + Sync,
+    M: Serialize + DeserializeOwned + Send + Sync + Clone,
+{
+    async fn export_records(&self) -> Result<Vec<VectorRecord<M>>, Self::Error> {
+        let rows = self.rows.read().unwrap();
+        Ok(rows
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(row, document)| {
+                VectorRecord::new(
+                    row.to_string(),
+                    rows.raw_vector(row).to_vec(),
+                    document.clone(),
+                )
+            })
+            .collect())
+    }
+
+    async fn import_records(
+        &self,
+        records: Vec<VectorRecord<M>>,
+    ) -> Result<Vec<String>, Self::Error> {
+        let (documents, vectors) = records.into_iter().map(VectorRecord::into_document).unzip();
+        self.add_embedded(documents, vectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Error)]
+    #[error("unreachable")]
+    struct TestError;
+
+    impl EmbeddingsError for TestError {}
+
+    /// Embeds a text as the counts of the letters `a` to `d`.
+    struct LetterEmbeddings;
+
+    fn letters(text: &str) -> Vec<f32> {
+        ['a', 'b', 'c', 'd']
+            .iter()
+            .map(|&l| text.chars().filter(|&c| c == l).count() as f32)
+            .collect()
+    }
+
+    #[async_trait]
+    impl Embeddings for LetterEmbeddings {
+        type Error = TestError;
+
+        async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, TestError> {
+            Ok(texts.iter().map(|t| letters(t)).collect())
+        }
+
+        async fn embed_query(&self, query: String) -> Result<Vec<f32>, TestError> {
+            Ok(letters(&query))
+        }
+    }
+
+    #[test]
+    fn test_dot_matches_the_naive_sum() {
+        let a: Vec<f32> = (0..21).map(|i| i as f32 * 0.5).collect();
+        let b: Vec<f32> = (0..21).map(|i| 3.0 - i as f32).collect();
+        let naive: f32 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+        assert!((dot(&a, &b) - naive).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_search_is_exact_across_batches() {
+        let store: FlatVectorStore<LetterEmbeddings> =
+            FlatVectorStore::new(Arc::new(LetterEmbeddings));
+        let count = BATCH_ROWS * 2 + 10;
+        let vectors: Vec<Vec<f32>> = (0..count)
+            .map(|i| {
+                let t = i as f32 / count as f32 * std::f32::consts::FRAC_PI_2;
+                vec![t.cos(), t.sin()]
+            })
+            .collect();
+        let documents = (0..count).map(|i| Document::new(i.to_string())).collect();
+        store.add_embedded(documents, vectors).unwrap();
+
+        let rows = store.rows.read().unwrap();
+        let found = rows.search(&[0.0, 1.0], 3, |_| true);
+        let found: Vec<usize> = found.iter().map(|s| s.row).collect();
+        assert_eq!(found, vec![count - 1, count - 2, count - 3]);
+
+        let even = rows.search(&[0.0, 1.0], 2, |d| {
+            d.page_content.parse::<usize>().unwrap() % 2 == 0
+        });
+        let even: Vec<usize> = even.iter().map(|s| s.row).collect();
+        assert_eq!(even, vec![count - 2, count - 4]);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let store: FlatVectorStore<LetterEmbeddings, String> =
+            FlatVectorStore::new(Arc::new(LetterEmbeddings));
+        store
+            .add_documents(vec![
+                Document {
+                    page_content: "aaa".to_string(),
+                    metadata: Some("first".to_string()),
+                },
+                Document {
+                    page_content: "bbd".to_string(),
+                    metadata: None,
+                },
+            ])
+            .await
+            .unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("ai-chain-flat-{}.snapshot", std::process::id()));
+        store.save(&path).unwrap();
+        let restored: FlatVectorStore<LetterEmbeddings, String> =
+            FlatVectorStore::load(&path, Arc::new(LetterEmbeddings)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.dimension(), Some(4));
+        let found = restored
+            .similarity_search_with_scores("ab".to_string(), 1)
+            .await
+            .unwrap();
+        assert_eq!(found[0].0.page_content, "aaa");
+        assert_eq!(found[0].0.metadata.as_deref(), Some("first"));
+        let found = restored
+            .similarity_search_with_filter("a".to_string(), 2, |d| d.metadata.is_none())
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0.page_content, "bbd");
+    }
+
+    #[tokio::test]
+    async fn test_export_import_without_embedding() {
+        let store: FlatVectorStore<LetterEmbeddings, String> =
+            FlatVectorStore::new(Arc::new(LetterEmbeddings));
+        store
+            .add_texts(vec!["aab".to_string(), "dd".to_string()])
+            .await
+            .unwrap();
+        let records = store.export_records().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].id, "1");
+        assert_eq!(records[0].vector, vec![2.0, 1.0, 0.0, 0.0]);
+        assert_eq!(records[1].vector, vec![0.0, 0.0, 0.0, 2.0]);
+
+        let copy: FlatVectorStore<LetterEmbeddings, String> =
+            FlatVectorStore::new(Arc::new(LetterEmbeddings));
+        let ids = ai_chain::vector_dump::migrate(&store, &copy).await.unwrap();
+        assert_eq!(ids, vec!["0", "1"]);
+        let found = copy.similarity_search("d".to_string(), 1).await.unwrap();
+        assert_eq!(found[0].page_content, "dd");
+
+        // Snapshots keep the vectors as they were added too.
+        let path =
+            std::env::temp_dir().join(format!("ai-chain-flat-{}.export", std::process::id()));
+        copy.save(&path).unwrap();
+        let restored: FlatVectorStore<LetterEmbeddings, String> =
+            FlatVectorStore::load(&path, Arc::new(LetterEmbeddings)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let vectors = |records: Vec<VectorRecord<String>>| {
+            records.into_iter().map(|r| r.vector).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vectors(restored.export_records().await.unwrap()),
+            vectors(records)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_empty_vectors() {
+        let store: FlatVectorStore<LetterEmbeddings, String> =
+            FlatVectorStore::new(Arc::new(LetterEmbeddings));
+        store
+            .add_embedded(
+                vec![
+                    Document::new("a".to_string()),
+                    Document::new("b".to_string()),
+                ],
+                vec![Vec::new(), Vec::new()],
+            )
+            .unwrap();
+        let records = store.export_records().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].page_content, "b");
+        assert!(records[1].vector.is_empty());
+    }
+}
@@ -1,5 +1,3 @@
-use hnsw_rs::{hnswio::*, prelude::*};
-use std::path::PathBuf;
 use std::sync::Arc;
 
 use ai_chain::{
@@ -8,7 +6,7 @@ use ai_chain::{
     traits::VectorStore,
 };
 
-use ai_chain_hnsw::{HnswArgs, HnswVectorStore};
+use ai_chain_hnsw::{HnswArgs, HnswDump, HnswVectorStore};
 use tokio::sync::Mutex;
 
 #[tokio::main(flavor = "current_thread")]
@@ -59,11 +57,15 @@ async fn main() {
     println!("Loading hnsw index from file");
     embeddings = ai_chain_openai::embeddings::Embeddings::default();
 
-    let mut hnswio = HnswIo::new(PathBuf::from("../../../.."), hnsw_index_fn);
-    let hnsw_loaded = hnswio.load_hnsw::<f32, DistCosine>().unwrap();
-    let hnsw_vs =
-        HnswVectorStore::load_from_file(hnsw_loaded, Arc::new(embeddings), document_store.clone())
-            .unwrap();
+    let dump = HnswDump::read(".", &hnsw_index_fn).unwrap();
+    let readers = dump.readers(".");
+    let hnsw_vs = HnswVectorStore::load_from_file(
+        &dump,
+        &readers,
+        Arc::new(embeddings),
+        document_store.clone(),
+    )
+    .unwrap();
 
     println!("Loaded!");
 
@@ -0,0 +1,98 @@
+//! Measures the recall@10 of `HnswIndex` against a brute-force search, for each metric and a few
+//! values of `ef_search`, and how long the searches take.
+//!
+//! Run with `cargo run --release --example recall -- [points] [dimensions]`.
+use std::time::Instant;
+
+use ai_chain_hnsw::{HnswArgs, HnswIndex, Metric};
+use hnsw_rs::dist::Distance;
+
+const QUERIES: usize = 200;
+const K: usize = 10;
+
+/// Deterministic pseudo-random vectors of unit length.
+fn vectors(count: usize, dimensions: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            let vector: Vec<f32> = (0..dimensions)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+                })
+                .collect();
+            let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+            vector.into_iter().map(|v| v / norm).collect()
+        })
+        .collect()
+}
+
+fn brute_force(metric: Metric, data: &[Vec<f32>], query: &[f32]) -> Vec<usize> {
+    let mut ids: Vec<usize> = (0..data.len()).collect();
+    ids.sort_by(|&a, &b| {
+        metric
+            .eval(query, &data[a])
+            .total_cmp(&metric.eval(query, &data[b]))
+    });
+    ids.truncate(K);
+    ids
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let points: usize = args.next().map_or(20_000, |a| a.parse().unwrap());
+    let dimensions: usize = args.next().map_or(128, |a| a.parse().unwrap());
+    let data = vectors(points, dimensions, 1);
+    let queries = vectors(QUERIES, dimensions, 2);
+
+    for metric in [Metric::Cosine, Metric::L2, Metric::DotProduct] {
+        let index = HnswIndex::new(HnswArgs {
+            max_elements: points / 2,
+            metric,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        for (id, vector) in data.iter().enumerate() {
+            index.insert(vector, id);
+        }
+        println!(
+            "{:?}: inserted {} points in {} shards in {:?}",
+            metric,
+            index.len(),
+            index.shard_count(),
+            start.elapsed()
+        );
+
+        let start = Instant::now();
+        let exact: Vec<Vec<usize>> = queries
+            .iter()
+            .map(|q| brute_force(metric, &data, q))
+            .collect();
+        println!(
+            "  brute force: {:?} per query",
+            start.elapsed() / QUERIES as u32
+        );
+
+        for ef_search in [16, 32, 64, 128, 256] {
+            let start = Instant::now();
+            let hits: usize = queries
+                .iter()
+                .zip(&exact)
+                .map(|(query, exact)| {
+                    index
+                        .search(query, K, ef_search)
+                        .iter()
+                        .filter(|n| exact.contains(&n.d_id))
+                        .count()
+                })
+                .sum();
+            println!(
+                "  ef_search {:>3}: recall@{} {:.3}, {:?} per query",
+                ef_search,
+                K,
+                hits as f32 / (QUERIES * K) as f32,
+                start.elapsed() / QUERIES as u32
+            );
+        }
+    }
+}
@@ -1,31 +1,222 @@
-use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
 
-use async_trait::async_trait;
-use hnsw_rs::{hnsw::Hnsw, prelude::*};
 use ai_chain::{
     document_stores::document_store::*,
-    schema::Document,
-    traits::{Embeddings, EmbeddingsError, VectorStore, VectorStoreError},
+    schema::{Document, VectorRecord},
+    traits::{Embeddings, EmbeddingsError, ExportableVectorStore, VectorStore, VectorStoreError},
+};
+use async_trait::async_trait;
+use hnsw_rs::{
+    api::AnnT,
+    dist::{DistCosine, DistL2, Distance},
+    hnsw::{Hnsw, Neighbour},
+    hnswio::HnswIo,
 };
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 use tokio::sync::Mutex;
 
+/// The distance the index ranks vectors by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// `1 - cos(a, b)`.
+    #[default]
+    Cosine,
+    /// The euclidean distance.
+    L2,
+    /// `1 - a·b`, clamped at 0. Only meaningful for vectors of unit length, like the normalized
+    /// output of most embedding models, for which it ranks like `Cosine` but is cheaper.
+    DotProduct,
+}
+
+impl Distance<f32> for Metric {
+    fn eval(&self, va: &[f32], vb: &[f32]) -> f32 {
+        match self {
+            Metric::Cosine => DistCosine.eval(va, vb),
+            Metric::L2 => DistL2.eval(va, vb),
+            Metric::DotProduct => {
+                let dot: f32 = va.iter().zip(vb).map(|(a, b)| a * b).sum();
+                (1.0 - dot).max(0.0)
+            }
+        }
+    }
+}
+
+/// The parameters of an HNSW index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct HnswArgs {
-    max_nb_connection: usize,
-    max_elements: usize,
-    max_layer: usize,
-    ef_construction: usize,
+    /// The number of neighbours each point is connected to.
+    pub max_nb_connection: usize,
+    /// The capacity of a shard. When a shard is full, a new one is started.
+    pub max_elements: usize,
+    pub max_layer: usize,
+    /// The size of the candidate list while inserting. Higher is slower but more accurate.
+    pub ef_construction: usize,
+    /// The size of the candidate list while searching, unless given per query. It is raised to
+    /// the number of results asked for.
+    pub ef_search: usize,
+    pub metric: Metric,
 }
 
 impl Default for HnswArgs {
     fn default() -> Self {
         HnswArgs {
             max_nb_connection: 16,
-            max_elements: 100,
+            max_elements: 10_000,
             max_layer: 16,
             ef_construction: 200,
+            ef_search: 64,
+            metric: Metric::default(),
+        }
+    }
+}
+
+/// An HNSW index that grows by adding shards.
+///
+/// The HNSW graph is sized for `max_elements` points when it is created. Once a shard holds that
+/// many points, new points go to a fresh shard; searches query every shard and merge the results.
+pub struct HnswIndex<'a> {
+    args: HnswArgs,
+    shards: RwLock<Vec<Hnsw<'a, f32, Metric>>>,
+    /// The number of slots handed out to inserts, including those still in progress. Slot `n`
+    /// belongs to shard `n / max_elements`.
+    reserved: AtomicUsize,
+}
+
+impl<'a> HnswIndex<'a> {
+    pub fn new(args: HnswArgs) -> Self {
+        HnswIndex {
+            args,
+            shards: RwLock::new(Vec::new()),
+            reserved: AtomicUsize::new(0),
+        }
+    }
+
+    fn from_shards(args: HnswArgs, shards: Vec<Hnsw<'a, f32, Metric>>) -> Self {
+        // Every shard but the last one is full.
+        let reserved = shards.last().map_or(0, |last| {
+            (shards.len() - 1) * args.max_elements + last.get_nb_point()
+        });
+        HnswIndex {
+            args,
+            shards: RwLock::new(shards),
+            reserved: AtomicUsize::new(reserved),
+        }
+    }
+
+    pub fn args(&self) -> &HnswArgs {
+        &self.args
+    }
+
+    fn new_shard(&self) -> Hnsw<'a, f32, Metric> {
+        Hnsw::new(
+            self.args.max_nb_connection,
+            self.args.max_elements,
+            self.args.max_layer,
+            self.args.ef_construction,
+            self.args.metric,
+        )
+    }
+
+    /// Adds `vector` to the index under `id`.
+    pub fn insert(&self, vector: &[f32], id: usize) {
+        // Reserving the slot first keeps concurrent inserts from overfilling a shard.
+        let shard = self.reserved.fetch_add(1, Ordering::SeqCst) / self.args.max_elements.max(1);
+        if self.shards.read().unwrap().len() <= shard {
+            let mut shards = self.shards.write().unwrap();
+            while shards.len() <= shard {
+                shards.push(self.new_shard());
+            }
         }
+        self.shards.read().unwrap()[shard].insert_slice((vector, id));
+    }
+
+    /// Returns up to `limit` nearest neighbours of `vector`, nearest first.
+    pub fn search(&self, vector: &[f32], limit: usize, ef_search: usize) -> Vec<Neighbour> {
+        let ef_search = ef_search.max(limit);
+        let mut neighbours: Vec<Neighbour> = self
+            .shards
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|shard| shard.search(vector, limit, ef_search))
+            .collect();
+        neighbours.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        neighbours.truncate(limit);
+        neighbours
+    }
+
+    /// Every point of the index with its id, ordered by id.
+    pub fn points(&self) -> Vec<(usize, Vec<f32>)> {
+        let mut points: Vec<(usize, Vec<f32>)> = self
+            .shards
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|shard| shard.get_nb_point() > 0)
+            .flat_map(|shard| {
+                shard
+                    .get_point_indexation()
+                    .into_iter()
+                    .map(|point| (point.get_origin_id(), point.get_v().to_vec()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        points.sort_by_key(|(id, _)| *id);
+        points
+    }
+
+    /// The number of points in the index.
+    pub fn len(&self) -> usize {
+        self.shards
+            .read()
+            .unwrap()
+            .iter()
+            .map(|shard| shard.get_nb_point())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.read().unwrap().len()
+    }
+}
+
+/// The manifest `HnswVectorStore::dump_to_file` writes next to the shard dumps.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HnswDump {
+    pub args: HnswArgs,
+    /// The base names of the shard dumps, in order.
+    pub shards: Vec<String>,
+}
+
+impl HnswDump {
+    /// Reads the manifest `{basename}.hnsw.json` in `directory`.
+    pub fn read<P: AsRef<Path>>(directory: P, basename: &str) -> std::io::Result<Self> {
+        let path = directory.as_ref().join(format!("{}.hnsw.json", basename));
+        let file = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// The readers of the shard dumps in `directory`, to pass to `HnswVectorStore::load_from_file`.
+    pub fn readers<P: AsRef<Path>>(&self, directory: P) -> Vec<HnswIo> {
+        self.shards
+            .iter()
+            .map(|shard| HnswIo::new(directory.as_ref().to_path_buf(), shard.clone()))
+            .collect()
     }
 }
 
@@ -35,7 +226,7 @@ where
     D: DocumentStore<usize, M> + Send + Sync,
     M: Serialize + DeserializeOwned + Send + Sync,
 {
-    hnsw: Arc<Hnsw<'a, f32, DistCosine>>,
+    index: HnswIndex<'a>,
     document_store: Arc<Mutex<D>>,
     embeddings: Arc<E>,
     _marker: PhantomData<M>,
@@ -48,38 +239,59 @@ where
     M: Send + Sync + Serialize + DeserializeOwned,
 {
     pub fn new(hnsw_args: HnswArgs, embeddings: Arc<E>, document_store: Arc<Mutex<D>>) -> Self {
-        let hnsw = Hnsw::new(
-            hnsw_args.max_nb_connection,
-            hnsw_args.max_elements,
-            hnsw_args.max_layer,
-            hnsw_args.ef_construction,
-            DistCosine {},
-        );
         HnswVectorStore {
-            hnsw: Arc::new(hnsw),
+            index: HnswIndex::new(hnsw_args),
             document_store,
             embeddings,
             _marker: Default::default(),
         }
     }
 
+    pub fn index(&self) -> &HnswIndex<'a> {
+        &self.index
+    }
+
+    /// Dumps each shard to `{filename}-{n}.hnsw.graph` and `{filename}-{n}.hnsw.data` in the
+    /// current directory, and the parameters of the index to `{filename}.hnsw.json`.
     pub fn dump_to_file(
         &self,
         filename: String,
     ) -> Result<String, HnswVectorStoreError<E::Error, D::Error>> {
-        self.hnsw
-            .file_dump(&filename)
-            .map_err(|e| HnswVectorStoreError::FileDumpError(e.to_string()))
+        let mut dump = HnswDump {
+            args: self.index.args.clone(),
+            shards: Vec::new(),
+        };
+        for (n, shard) in self.index.shards.read().unwrap().iter().enumerate() {
+            let basename = shard
+                .file_dump(&format!("{}-{}", filename, n))
+                .map_err(|e| HnswVectorStoreError::FileDumpError(e.to_string()))?;
+            dump.shards.push(basename);
+        }
+        let manifest = std::fs::File::create(format!("{}.hnsw.json", filename))
+            .map_err(|e| HnswVectorStoreError::FileDumpError(e.to_string()))?;
+        serde_json::to_writer_pretty(manifest, &dump)
+            .map_err(|e| HnswVectorStoreError::FileDumpError(e.to_string()))?;
+        Ok(filename)
     }
 
+    /// Loads an index dumped with `dump_to_file`. The index borrows from the readers, which come
+    /// from [`HnswDump::readers`].
     pub fn load_from_file(
-        hnsw: Hnsw<'a, f32, DistCosine>,
+        dump: &HnswDump,
+        readers: &'a [HnswIo],
         embeddings: Arc<E>,
         document_store: Arc<Mutex<D>>,
-    ) -> Result<Self, HnswVectorStoreError<E::Error, D::Error>>
-where {
+    ) -> Result<Self, HnswVectorStoreError<E::Error, D::Error>> {
+        let shards = readers
+            .iter()
+            .map(|reader| {
+                reader
+                    .load_hnsw_with_dist(dump.args.metric)
+                    .map_err(|e| HnswVectorStoreError::FileLoadError(e.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
         Ok(HnswVectorStore {
-            hnsw: Arc::new(hnsw),
+            index: HnswIndex::from_shards(dump.args.clone(), shards),
             document_store,
             embeddings,
             _marker: Default::default(),
@@ -87,6 +299,84 @@ where {
     }
 }
 
+impl<'a, E, D, M> HnswVectorStore<'a, E, D, M>
+where
+    E: Embeddings + Send + Sync,
+    D: DocumentStore<usize, M> + Send + Sync,
+    M: Send + Sync + Serialize + DeserializeOwned,
+{
+    /// Adds documents with their embeddings, without calling the embeddings model. Returns the
+    /// ids of the documents.
+    pub async fn add_embedded(
+        &self,
+        documents: Vec<Document<M>>,
+        vectors: Vec<Vec<f32>>,
+    ) -> Result<Vec<String>, HnswVectorStoreError<E::Error, D::Error>> {
+        if documents.len() != vectors.len() {
+            return Err(HnswVectorStoreError::EmbeddingCount {
+                expected: documents.len(),
+                actual: vectors.len(),
+            });
+        }
+        let document_store_arc = self.document_store.clone();
+        let mut document_store = document_store_arc.lock().await;
+
+        let next_id = document_store
+            .next_id()
+            .await
+            .map_err(HnswVectorStoreError::DocumentStoreError)?;
+        let ids = (0..vectors.len())
+            .map(|i| next_id + i)
+            .collect::<Vec<usize>>();
+
+        let iter = vectors.into_iter().zip(documents).zip(ids.iter());
+
+        for ((vec, document), id) in iter {
+            document_store
+                .insert(&HashMap::from([(id.to_owned(), document)]))
+                .await
+                .map_err(HnswVectorStoreError::DocumentStoreError)?;
+            self.index.insert(&vec, id.to_owned());
+        }
+
+        let ids_str = ids
+            .iter()
+            .map(|&id| format!("{}", id))
+            .collect::<Vec<String>>();
+        Ok(ids_str)
+    }
+
+    /// Like `similarity_search`, with the size of the candidate list set for this query.
+    pub async fn similarity_search_with_ef(
+        &self,
+        query: String,
+        limit: u32,
+        ef_search: usize,
+    ) -> Result<Vec<Document<M>>, HnswVectorStoreError<E::Error, D::Error>> {
+        let document_store_arc = self.document_store.clone();
+        let document_store = document_store_arc.lock().await;
+
+        let embedded_query = self.embeddings.embed_query(query).await?;
+
+        let res = self
+            .index
+            .search(&embedded_query, limit as usize, ef_search);
+
+        let mut out = vec![];
+        for r in res {
+            let id = r.d_id;
+            let doc = document_store
+                .get(&id)
+                .await
+                .map_err(HnswVectorStoreError::DocumentStoreError)?
+                .ok_or_else(|| HnswVectorStoreError::RelatedDocumentNotFound(r.d_id))?;
+            out.push(doc);
+        }
+
+        Ok(out)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum HnswVectorStoreError<E, D>
 where
@@ -103,6 +393,8 @@ where
     FileDumpError(String),
     #[error("Unable to load hnsw index from file: \"{0}\"")]
     FileLoadError(String),
+    #[error("Expected {expected} embeddings, got {actual}")]
+    EmbeddingCount { expected: usize, actual: usize },
 }
 
 impl<E, D> VectorStoreError for HnswVectorStoreError<E, D>
@@ -145,7 +437,7 @@ where
                 .insert(&HashMap::from([(id.to_owned(), Document::new(text))]))
                 .await
                 .map_err(HnswVectorStoreError::DocumentStoreError)?;
-            self.hnsw.insert((&vec, id.to_owned()));
+            self.index.insert(&vec, id.to_owned());
         }
 
         let ids_str = ids
@@ -156,64 +448,231 @@ where
     }
 
     async fn add_documents(&self, documents: Vec<Document<M>>) -> Result<Vec<String>, Self::Error> {
-        let document_store_arc = self.document_store.clone();
-        let mut document_store = document_store_arc.lock().await;
-
         let texts = documents.iter().map(|d| d.page_content.clone()).collect();
         let embedding_vecs = self.embeddings.embed_texts(texts).await?;
+        self.add_embedded(documents, embedding_vecs).await
+    }
 
-        let next_id = document_store
-            .next_id()
+    async fn similarity_search(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<Document<M>>, Self::Error> {
+        let ef_search = self.index.args.ef_search;
+        self.similarity_search_with_ef(query, limit, ef_search)
             .await
-            .map_err(HnswVectorStoreError::DocumentStoreError)?;
-        let ids = (0..embedding_vecs.len())
-            .map(|i| next_id + i)
-            .collect::<Vec<usize>>();
-
-        let iter = embedding_vecs
-            .into_iter()
-            .zip(documents.into_iter())
-            .zip(ids.iter());
+    }
+}
 
-        for ((vec, document), id) in iter {
-            document_store
-                .insert(&HashMap::from([(id.to_owned(), document)]))
+/// Imported records get new ids from the document store.
+#[async_trait]
+impl<'a, E, D, M> ExportableVectorStore<E, M> for HnswVectorStore<'a, E, D, M>
+where
+    E: Embeddings + Send + Sync,
+    D: DocumentStore<usize, M> + Send + Sync,
+    M: Send + Sync + Serialize + DeserializeOwned,
+{
+    async fn export_records(&self) -> Result<Vec<VectorRecord<M>>, Self::Error> {
+        let points = self.index.points();
+        let document_store = self.document_store.lock().await;
+        let mut records = Vec::with_capacity(points.len());
+        for (id, vector) in points {
+            let document = document_store
+                .get(&id)
                 .await
-                .map_err(HnswVectorStoreError::DocumentStoreError)?;
-            self.hnsw.insert((&vec, id.to_owned()));
+                .map_err(HnswVectorStoreError::DocumentStoreError)?
+                .ok_or(HnswVectorStoreError::RelatedDocumentNotFound(id))?;
+            records.push(VectorRecord::new(id.to_string(), vector, document));
+        }
+        Ok(records)
+    }
+
+    async fn import_records(
+        &self,
+        records: Vec<VectorRecord<M>>,
+    ) -> Result<Vec<String>, Self::Error> {
+        let (documents, vectors) = records.into_iter().map(VectorRecord::into_document).unzip();
+        self.add_embedded(documents, vectors).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random vectors.
+    fn vectors(count: usize, dimensions: usize) -> Vec<Vec<f32>> {
+        let mut state: u64 = 42;
+        (0..count)
+            .map(|_| {
+                (0..dimensions)
+                    .map(|_| {
+                        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                        (state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_metrics() {
+        assert!(Metric::Cosine.eval(&[1.0, 0.0], &[2.0, 0.0]).abs() < 1e-6);
+        assert_eq!(Metric::L2.eval(&[0.0, 0.0], &[3.0, 4.0]), 5.0);
+        assert_eq!(Metric::DotProduct.eval(&[0.6, 0.8], &[0.0, 1.0]), 1.0 - 0.8);
+        assert_eq!(Metric::DotProduct.eval(&[2.0, 0.0], &[2.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_args_deserialize_with_defaults() {
+        let args: HnswArgs = serde_json::from_str(r#"{"metric": "l2", "ef_search": 64}"#).unwrap();
+        assert_eq!(args.metric, Metric::L2);
+        assert_eq!(args.ef_search, 64);
+        assert_eq!(args.max_elements, HnswArgs::default().max_elements);
+    }
+
+    /// Recall depends on the random layers hnsw_rs picks, so it is measured by the `recall`
+    /// example instead; this checks what holds for every graph.
+    #[test]
+    fn test_sharded_index_merges_shard_results() {
+        let data = vectors(500, 16);
+        let index = HnswIndex::new(HnswArgs {
+            max_elements: 200,
+            metric: Metric::L2,
+            ..Default::default()
+        });
+        for (id, vector) in data.iter().enumerate() {
+            index.insert(vector, id);
         }
+        assert_eq!(index.len(), 500);
+        assert_eq!(index.shard_count(), 3);
 
-        let ids_str = ids
+        for query in vectors(520, 16).iter().skip(500) {
+            let found = index.search(query, 10, 64);
+            assert!(!found.is_empty() && found.len() <= 10);
+            assert!(found
+                .windows(2)
+                .all(|pair| pair[0].distance <= pair[1].distance));
+            let mut ids: Vec<usize> = found.iter().map(|n| n.d_id).collect();
+            ids.sort();
+            ids.dedup();
+            assert_eq!(ids.len(), found.len());
+            for neighbour in &found {
+                let exact = Metric::L2.eval(query, &data[neighbour.d_id]);
+                assert!((neighbour.distance - exact).abs() < 1e-4);
+            }
+        }
+        assert!(index.search(&data[0], 0, 64).is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_inserts_do_not_overfill_shards() {
+        let data = vectors(200, 8);
+        let index = HnswIndex::new(HnswArgs {
+            max_elements: 10,
+            ..Default::default()
+        });
+        std::thread::scope(|scope| {
+            for (n, chunk) in data.chunks(50).enumerate() {
+                let index = &index;
+                scope.spawn(move || {
+                    for (i, vector) in chunk.iter().enumerate() {
+                        index.insert(vector, n * 50 + i);
+                    }
+                });
+            }
+        });
+        assert_eq!(index.len(), 200);
+        assert_eq!(index.shard_count(), 20);
+        assert!(index
+            .shards
+            .read()
+            .unwrap()
             .iter()
-            .map(|&id| format!("{}", id))
-            .collect::<Vec<String>>();
-        Ok(ids_str)
+            .all(|shard| shard.get_nb_point() == 10));
     }
 
-    async fn similarity_search(
-        &self,
-        query: String,
-        limit: u32,
-    ) -> Result<Vec<Document<M>>, Self::Error> {
-        let document_store_arc = self.document_store.clone();
-        let document_store = document_store_arc.lock().await;
+    #[derive(Debug, Error)]
+    #[error("unreachable")]
+    struct TestError;
 
-        let embedded_query = self.embeddings.embed_query(query).await?;
+    impl EmbeddingsError for TestError {}
 
-        let ef_search = 30;
-        let res = self.hnsw.search(&embedded_query, limit as usize, ef_search);
+    /// Embeddings that must not be called: exports and imports reuse the stored vectors.
+    struct NoEmbeddings;
 
-        let mut out = vec![];
-        for r in res {
-            let id = r.d_id;
-            let doc = document_store
-                .get(&id)
-                .await
-                .map_err(HnswVectorStoreError::DocumentStoreError)?
-                .ok_or_else(|| HnswVectorStoreError::RelatedDocumentNotFound(r.d_id))?;
-            out.push(doc);
+    #[async_trait]
+    impl Embeddings for NoEmbeddings {
+        type Error = TestError;
+
+        async fn embed_texts(&self, _: Vec<String>) -> Result<Vec<Vec<f32>>, TestError> {
+            unreachable!()
         }
 
-        Ok(out)
+        async fn embed_query(&self, _: String) -> Result<Vec<f32>, TestError> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        use ai_chain::document_stores::in_memory_document_store::InMemoryDocumentStore;
+
+        let store = || {
+            HnswVectorStore::<_, _, String>::new(
+                HnswArgs {
+                    max_elements: 2,
+                    ..Default::default()
+                },
+                Arc::new(NoEmbeddings),
+                Arc::new(Mutex::new(InMemoryDocumentStore::new())),
+            )
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let source = store();
+            let data = vectors(5, 4);
+            let documents = (0..5)
+                .map(|i| Document {
+                    page_content: format!("doc {}", i),
+                    metadata: Some(format!("source {}", i)),
+                })
+                .collect();
+            source.add_embedded(documents, data.clone()).await.unwrap();
+            assert_eq!(source.index().shard_count(), 3);
+
+            assert!(matches!(
+                source
+                    .add_embedded(vec![Document::new("extra".to_string())], Vec::new())
+                    .await,
+                Err(HnswVectorStoreError::EmbeddingCount {
+                    expected: 1,
+                    actual: 0
+                })
+            ));
+
+            let records = source.export_records().await.unwrap();
+            assert_eq!(records.len(), 5);
+            assert_eq!(records[3].id, "3");
+            assert_eq!(records[3].vector, data[3]);
+            assert_eq!(records[3].metadata.as_deref(), Some("source 3"));
+
+            let target = store();
+            let ids = target.import_records(records.clone()).await.unwrap();
+            assert_eq!(ids, vec!["0", "1", "2", "3", "4"]);
+            let points = target.index().points();
+            assert_eq!(points, data.into_iter().enumerate().collect::<Vec<_>>());
+
+            let imported = target.export_records().await.unwrap();
+            assert_eq!(imported.len(), records.len());
+            for (imported, record) in imported.iter().zip(&records) {
+                assert_eq!(imported.id, record.id);
+                assert_eq!(imported.vector, record.vector);
+                assert_eq!(imported.page_content, record.page_content);
+                assert_eq!(imported.metadata, record.metadata);
+            }
+        });
     }
 }
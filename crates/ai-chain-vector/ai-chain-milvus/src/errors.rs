@@ -21,8 +21,14 @@ where
     EmptyIndexError,
     #[error("Milvus query error")]
     QueryError,
+    #[error("Invalid id for an Int64 primary key: {0}")]
+    InvalidId(String),
     #[error("Serde Error")]
     Serde(serde_json::Error),
+    #[error("Milvus query returned {0} rows, its cap, so the export would be incomplete")]
+    QueryCapReached(usize),
+    #[error("{documents} documents were given {vectors} embeddings")]
+    EmbeddingCount { documents: usize, vectors: usize },
 }
 
 impl<E> VectorStoreError for MilvusError<E> where
@@ -1,23 +1,59 @@
-use async_trait::async_trait;
-use errors::MilvusError;
 use ai_chain::{
-    schema::Document,
-    traits::{Embeddings, VectorStore},
+    schema::{Document, VectorRecord},
+    traits::{Embeddings, ExportableVectorStore, VectorStore},
 };
+use async_trait::async_trait;
+use errors::MilvusError;
 use milvus::{
     client::Client as MilvusClient,
     collection::SearchOption,
     data::FieldColumn,
-    proto::{milvus::MutationResult, schema::i_ds::IdField},
+    proto::{
+        milvus::MutationResult,
+        schema::{i_ds::IdField, DataType},
+    },
+    schema::FieldSchema,
     value::ValueVec,
 };
 use serde::{de::DeserializeOwned, Serialize};
-use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+use std::{collections::HashMap, future::Future, marker::PhantomData, sync::Arc};
 
 pub mod errors;
 const DEFAULT_CONTENT_PAYLOAD_KEY: &str = "page_content";
 const DEFAULT_METADATA_PAYLOAD_KEY: &str = "metadata";
 
+/// Rows a single Milvus query returns at most, unless the server is configured otherwise.
+const DEFAULT_QUERY_CAP: usize = 16384;
+
+/// One entity read back by a query.
+struct Row {
+    id: String,
+    vector: Vec<f32>,
+    payload: Option<String>,
+}
+
+/// Queries the whole `Int64` key space in ranges. A range whose query comes back with `cap`
+/// rows may have been cut short, so it is split in two and both halves are queried again.
+async fn query_key_ranges<T, Err, F, Fut>(cap: usize, mut query: F) -> Result<Vec<T>, Err>
+where
+    F: FnMut(i64, i64) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, Err>>,
+{
+    let mut ranges = vec![(i64::MIN + 1, i64::MAX)];
+    let mut rows = Vec::new();
+    while let Some((low, high)) = ranges.pop() {
+        let page = query(low, high).await?;
+        if page.len() < cap || low == high {
+            rows.extend(page);
+        } else {
+            let middle = (low as i128 + high as i128).div_euclid(2) as i64;
+            ranges.push((middle + 1, high));
+            ranges.push((low, middle));
+        }
+    }
+    Ok(rows)
+}
+
 pub struct Milvus<E, M>
 where
     E: Embeddings,
@@ -29,6 +65,7 @@ where
     payload_field_name: Option<String>,
     content_payload_key: String,
     metadata_payload_key: String,
+    query_cap: usize,
     embeddings: E,
     _marker: PhantomData<M>,
 }
@@ -57,10 +94,176 @@ where
                 .unwrap_or(DEFAULT_CONTENT_PAYLOAD_KEY.to_string()),
             metadata_payload_key: metadata_payload_key
                 .unwrap_or(DEFAULT_METADATA_PAYLOAD_KEY.to_string()),
+            query_cap: DEFAULT_QUERY_CAP,
             _marker: Default::default(),
         }
     }
 
+    /// Sets the number of rows a single query returns at most (16384 unless the server is
+    /// configured otherwise). Exports rely on it to tell a full page from a complete one.
+    pub fn with_query_cap(mut self, query_cap: usize) -> Self {
+        self.query_cap = query_cap.max(1);
+        self
+    }
+
+    /// Adds documents with their embeddings, without calling the embeddings model.
+    ///
+    /// `vectors` must hold one embedding per document.
+    /// `ids` are only used when the primary key of the collection is not generated by Milvus;
+    /// they must parse as integers when the primary key is an `Int64`.
+    pub async fn add_embedded(
+        &self,
+        documents: Vec<Document<M>>,
+        vectors: Vec<Vec<f32>>,
+        ids: Option<Vec<String>>,
+    ) -> Result<Vec<String>, MilvusError<E::Error>> {
+        if documents.len() != vectors.len() {
+            return Err(MilvusError::EmbeddingCount {
+                documents: documents.len(),
+                vectors: vectors.len(),
+            });
+        }
+        let collection = self
+            .client
+            .get_collection(&self.collection_name)
+            .await
+            .map_err(errors::MilvusError::Client)?;
+
+        // Construct Milvus vector column
+        let mut columns = vec![FieldColumn::new(
+            collection
+                .schema()
+                .get_field(&self.vector_field_name)
+                .ok_or(errors::MilvusError::InvalidColumnName)?,
+            vectors.into_iter().flatten().collect::<Vec<_>>(),
+        )];
+        if let (Some(ids), Some(primary)) = (ids, collection.schema().primary_column()) {
+            if !primary.auto_id {
+                columns.push(Self::id_column(primary, ids)?);
+            }
+        }
+        // Inserting document in Milvus collection
+        // Note: To insert document metadata we need to be sure that
+        // the collection has a column `Datatype.JSON`
+        if let Some(payload_field_name) = &self.payload_field_name {
+            let payload_column_name = collection
+                .schema()
+                .get_field(payload_field_name)
+                .ok_or(errors::MilvusError::InvalidColumnName)?;
+            let payloads: Vec<String> = documents
+                .into_iter()
+                .map(|document| {
+                    let mut payload: HashMap<String, Option<String>> = HashMap::new();
+
+                    if let Some(metadata) = document.metadata {
+                        let val = serde_json::to_string(&metadata).map_err(MilvusError::Serde)?;
+
+                        payload.insert(self.metadata_payload_key.clone(), val.into());
+                    } else {
+                        payload.insert(self.metadata_payload_key.clone(), None);
+                    }
+                    payload.insert(
+                        self.content_payload_key.clone(),
+                        document.page_content.into(),
+                    );
+                    serde_json::to_string(&payload).map_err(MilvusError::Serde)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            columns.push(FieldColumn::new(payload_column_name, payloads));
+        }
+
+        let milvus_results = collection
+            .insert(columns, None)
+            .await
+            .map_err(errors::MilvusError::Client)?;
+        collection
+            .flush()
+            .await
+            .map_err(|_| errors::MilvusError::InsertionError)?;
+        self.ids_from_milvus_results(milvus_results)
+    }
+
+    fn id_column(
+        primary: &FieldSchema,
+        ids: Vec<String>,
+    ) -> Result<FieldColumn, MilvusError<E::Error>> {
+        match primary.dtype {
+            DataType::Int64 => {
+                let ids = ids
+                    .into_iter()
+                    .map(|id| id.parse::<i64>().map_err(|_| MilvusError::InvalidId(id)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(FieldColumn::new(primary, ids))
+            }
+            _ => Ok(FieldColumn::new(primary, ids)),
+        }
+    }
+
+    /// Reads the page content and metadata out of a payload written by `add_embedded`.
+    fn document_from_payload(&self, payload: &str) -> Result<Document<M>, MilvusError<E::Error>> {
+        let mut payload: HashMap<String, Option<String>> =
+            serde_json::from_str(payload).map_err(MilvusError::Serde)?;
+        let metadata = payload
+            .remove(&self.metadata_payload_key)
+            .flatten()
+            .map(|metadata| serde_json::from_str(&metadata))
+            .transpose()
+            .map_err(MilvusError::Serde)?;
+        let page_content = payload
+            .remove(&self.content_payload_key)
+            .flatten()
+            .unwrap_or_default();
+        Ok(Document {
+            page_content,
+            metadata,
+        })
+    }
+
+    /// Lines up the columns of a query result into rows.
+    fn rows_from_columns(
+        &self,
+        columns: Vec<FieldColumn>,
+        primary_name: &str,
+        dimension: usize,
+    ) -> Result<Vec<Row>, MilvusError<E::Error>> {
+        let mut ids = Vec::new();
+        let mut vectors = Vec::new();
+        let mut payloads = Vec::new();
+        for column in columns {
+            match column.value {
+                ValueVec::Long(values) if column.name == primary_name => {
+                    ids = values.iter().map(i64::to_string).collect()
+                }
+                ValueVec::String(values) if column.name == primary_name => ids = values,
+                ValueVec::Float(values) if column.name == self.vector_field_name => {
+                    vectors = values
+                        .chunks(dimension.max(1))
+                        .map(<[f32]>::to_vec)
+                        .collect()
+                }
+                ValueVec::String(values)
+                    if Some(&column.name) == self.payload_field_name.as_ref() =>
+                {
+                    payloads = values
+                }
+                _ => {}
+            }
+        }
+        if vectors.len() != ids.len() {
+            return Err(errors::MilvusError::QueryError);
+        }
+        let mut payloads = payloads.into_iter();
+        Ok(ids
+            .into_iter()
+            .zip(vectors)
+            .map(|(id, vector)| Row {
+                id,
+                vector,
+                payload: payloads.next(),
+            })
+            .collect())
+    }
+
     fn ids_from_milvus_results(
         &self,
         res: MutationResult,
@@ -111,74 +314,10 @@ where
     }
 
     async fn add_documents(&self, documents: Vec<Document<M>>) -> Result<Vec<String>, Self::Error> {
-        let collection = self
-            .client
-            .get_collection(&self.collection_name)
-            .await
-            .map_err(errors::MilvusError::Client)?;
-
         // Embedding documents' text
         let texts = documents.iter().map(|d| d.page_content.clone()).collect();
         let embedding_vecs = self.embeddings.embed_texts(texts).await?;
-
-        // Construct Milvus vector column
-        let embed_column = FieldColumn::new(
-            collection
-                .schema()
-                .get_field(&self.vector_field_name)
-                .unwrap(),
-            embedding_vecs.into_iter().flatten().collect::<Vec<_>>(),
-        );
-        // Inserting document in Milvus collection
-        // Note: To insert document metadata we need to be sure that
-        // the collection has a column `Datatype.JSON`
-        match &self.payload_field_name {
-            Some(payload_field_name) => {
-                let payload_column_name = collection
-                    .schema()
-                    .get_field(&payload_field_name)
-                    .ok_or(errors::MilvusError::InvalidColumnName)?;
-                let payloads: Vec<String> = documents
-                    .into_iter()
-                    .map(|document| {
-                        // Construct the
-                        let mut payload: HashMap<String, Option<String>> = HashMap::new();
-
-                        if let Some(metadata) = document.metadata {
-                            let val =
-                                serde_json::to_string(&metadata).map_err(Self::Error::Serde)?;
-
-                            payload.insert(self.metadata_payload_key.clone(), val.into());
-                        } else {
-                            payload.insert(self.metadata_payload_key.clone(), None);
-                        }
-                        payload.insert(
-                            self.content_payload_key.clone(),
-                            document.page_content.clone().into(),
-                        );
-                        let payload =
-                            serde_json::to_string(&payload).map_err(Self::Error::Serde)?;
-                        Ok(payload)
-                    })
-                    .collect::<Result<Vec<_>, errors::MilvusError<_>>>()?;
-                let payload_column = FieldColumn::new(payload_column_name, payloads);
-                let milvus_results = collection
-                    .insert(vec![embed_column, payload_column], None)
-                    .await
-                    .unwrap();
-
-                collection
-                    .flush()
-                    .await
-                    .map_err(|_| errors::MilvusError::InsertionError)?;
-
-                self.ids_from_milvus_results(milvus_results)
-            }
-            None => {
-                let milvus_results = collection.insert(vec![embed_column], None).await.unwrap();
-                self.ids_from_milvus_results(milvus_results)
-            }
-        }
+        self.add_embedded(documents, embedding_vecs, None).await
     }
 
     async fn similarity_search(
@@ -223,28 +362,10 @@ where
                 for res in results {
                     for field in res.field.iter().filter(|f| &f.name == out_field) {
                         match &field.value {
-                            ValueVec::String(val) => {
-                                let payload: HashMap<String, Option<String>> =
-                                    serde_json::from_str(&val[0])
-                                        .map_err(errors::MilvusError::Serde)?;
-
-                                let _metadata: Option<String> = payload // XXX: temp fix since the
-                                                                       // var is not used rn
-                                    .get(&self.metadata_payload_key)
-                                    .unwrap()
-                                    .clone()
-                                    .into();
-
-                                let page_content = payload
-                                    .get(&self.content_payload_key)
-                                    .unwrap()
-                                    .clone()
-                                    .unwrap_or("".to_string());
-
-                                docs.push(Document {
-                                    page_content: page_content,
-                                    metadata: None,
-                                });
+                            ValueVec::String(payloads) => {
+                                for payload in payloads {
+                                    docs.push(self.document_from_payload(payload)?);
+                                }
                             }
                             _ => return Err(errors::MilvusError::QueryError),
                         }
@@ -256,3 +377,127 @@ where
         }
     }
 }
+
+#[async_trait]
+impl<E, M> ExportableVectorStore<E, M> for Milvus<E, M>
+where
+    E: Embeddings + Send + Sync,
+    M: Send + Sync + Serialize + DeserializeOwned,
+{
+    /// Queries every entity of the collection. Milvus caps the rows a query returns, so
+    /// collections with an `Int64` primary key are read in key ranges, halving any range whose
+    /// query reaches the cap. `VarChar` keys cannot be split that way; their export fails with
+    /// [`MilvusError::QueryCapReached`] rather than coming back incomplete.
+    async fn export_records(&self) -> Result<Vec<VectorRecord<M>>, Self::Error> {
+        let collection = self
+            .client
+            .get_collection(&self.collection_name)
+            .await
+            .map_err(errors::MilvusError::Client)?;
+        let primary = collection
+            .schema()
+            .primary_column()
+            .ok_or(errors::MilvusError::InvalidColumnName)?;
+        let dimension = collection
+            .schema()
+            .get_field(&self.vector_field_name)
+            .ok_or(errors::MilvusError::InvalidColumnName)?
+            .dim as usize;
+        let query = |expr: String| {
+            let collection = &collection;
+            async move {
+                let columns = collection
+                    .query(expr, Vec::<String>::new())
+                    .await
+                    .map_err(errors::MilvusError::Client)?;
+                self.rows_from_columns(columns, &primary.name, dimension)
+            }
+        };
+
+        let rows = match primary.dtype {
+            DataType::Int64 => {
+                query_key_ranges(self.query_cap, |low, high| {
+                    query(format!(
+                        "{name} >= {low} && {name} <= {high}",
+                        name = primary.name
+                    ))
+                })
+                .await?
+            }
+            _ => {
+                let rows = query(format!("{} != \"\"", primary.name)).await?;
+                if rows.len() >= self.query_cap {
+                    return Err(errors::MilvusError::QueryCapReached(rows.len()));
+                }
+                rows
+            }
+        };
+
+        rows.into_iter()
+            .map(|row| {
+                let document = match &row.payload {
+                    Some(payload) => self.document_from_payload(payload)?,
+                    None => Document::new(String::new()),
+                };
+                Ok(VectorRecord::new(row.id, row.vector, document))
+            })
+            .collect()
+    }
+
+    async fn import_records(
+        &self,
+        records: Vec<VectorRecord<M>>,
+    ) -> Result<Vec<String>, Self::Error> {
+        let mut ids = Vec::with_capacity(records.len());
+        let mut documents = Vec::with_capacity(records.len());
+        let mut vectors = Vec::with_capacity(records.len());
+        for record in records {
+            ids.push(record.id.clone());
+            let (document, vector) = record.into_document();
+            documents.push(document);
+            vectors.push(vector);
+        }
+        self.add_embedded(documents, vectors, Some(ids)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_query_key_ranges_reads_past_the_cap() {
+        let keys: Vec<i64> = (0..50)
+            .map(|i| i * 7_919 - 100_000)
+            .chain([i64::MIN + 1, i64::MAX, -1, 0x7fff_0000_0000_0000])
+            .collect();
+        let mut queries = 0;
+        let rows = query_key_ranges(4, |low, high| {
+            queries += 1;
+            // Like Milvus, answer with at most `cap` rows and in no particular order.
+            let page: Vec<i64> = keys
+                .iter()
+                .rev()
+                .copied()
+                .filter(|key| (low..=high).contains(key))
+                .take(4)
+                .collect();
+            async move { Ok::<_, ()>(page) }
+        })
+        .await
+        .unwrap();
+
+        let mut expected = keys.clone();
+        expected.sort_unstable();
+        let mut rows = rows;
+        rows.sort_unstable();
+        assert_eq!(rows, expected);
+        assert!(queries > 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_key_ranges_stops_at_errors() {
+        let result = query_key_ranges(4, |_, _| async { Err::<Vec<i64>, _>("down") }).await;
+        assert_eq!(result, Err("down"));
+    }
+}
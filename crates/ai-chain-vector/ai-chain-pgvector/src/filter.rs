@@ -0,0 +1,234 @@
+//! Filters on the JSONB metadata of the documents.
+
+use serde_json::{Map, Value};
+
+/// A condition on the metadata of a document.
+///
+/// Paths name a key of the metadata, with dots between the keys of nested objects, like
+/// `author.name`. Documents without metadata, or without the key, match none of the comparisons.
+/// Values are compared as a whole: `Eq("tags", "a")` does not match `{"tags": ["a", "b"]}`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// The value at the path equals the given one.
+    Eq(String, Value),
+    /// The value at the path does not equal the given one.
+    Ne(String, Value),
+    /// The value at the path is a number greater than the given one.
+    Gt(String, f64),
+    Gte(String, f64),
+    Lt(String, f64),
+    Lte(String, f64),
+    /// The value at the path equals one of the given ones.
+    In(String, Vec<Value>),
+    /// The metadata has a value at the path.
+    Exists(String),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    pub fn eq(path: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Eq(path.into(), value.into())
+    }
+
+    pub fn ne(path: impl Into<String>, value: impl Into<Value>) -> Self {
+        Filter::Ne(path.into(), value.into())
+    }
+
+    pub fn gt(path: impl Into<String>, value: f64) -> Self {
+        Filter::Gt(path.into(), value)
+    }
+
+    pub fn gte(path: impl Into<String>, value: f64) -> Self {
+        Filter::Gte(path.into(), value)
+    }
+
+    pub fn lt(path: impl Into<String>, value: f64) -> Self {
+        Filter::Lt(path.into(), value)
+    }
+
+    pub fn lte(path: impl Into<String>, value: f64) -> Self {
+        Filter::Lte(path.into(), value)
+    }
+
+    pub fn is_in<V: Into<Value>>(
+        path: impl Into<String>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        Filter::In(path.into(), values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn exists(path: impl Into<String>) -> Self {
+        Filter::Exists(path.into())
+    }
+}
+
+/// A value bound to a placeholder of the query.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Bind {
+    Text(String),
+    Path(Vec<String>),
+    Float(f64),
+}
+
+/// Turns filters into SQL conditions on the `metadata` column, with their values bound to
+/// placeholders numbered from `first_placeholder`.
+pub(crate) struct FilterSql {
+    next_placeholder: usize,
+    pub(crate) binds: Vec<Bind>,
+}
+
+impl FilterSql {
+    pub(crate) fn new(first_placeholder: usize) -> Self {
+        FilterSql {
+            next_placeholder: first_placeholder,
+            binds: Vec::new(),
+        }
+    }
+
+    fn bind(&mut self, bind: Bind) -> String {
+        let placeholder = format!("${}", self.next_placeholder);
+        self.next_placeholder += 1;
+        self.binds.push(bind);
+        placeholder
+    }
+
+    /// The condition for `filter`. It is never NULL, so it can be negated safely.
+    pub(crate) fn condition(&mut self, filter: &Filter) -> String {
+        match filter {
+            Filter::Eq(path, value) => self.equals(path, value),
+            Filter::Ne(path, value) => {
+                let exists = self.exists(path);
+                format!("({} AND NOT {})", exists, self.equals(path, value))
+            }
+            Filter::Gt(path, value) => self.compare(path, ">", *value),
+            Filter::Gte(path, value) => self.compare(path, ">=", *value),
+            Filter::Lt(path, value) => self.compare(path, "<", *value),
+            Filter::Lte(path, value) => self.compare(path, "<=", *value),
+            Filter::In(path, values) => self.join(
+                values.iter().map(|v| Filter::Eq(path.clone(), v.clone())),
+                "OR",
+                "false",
+            ),
+            Filter::Exists(path) => self.exists(path),
+            Filter::And(filters) => self.join(filters.iter().cloned(), "AND", "true"),
+            Filter::Or(filters) => self.join(filters.iter().cloned(), "OR", "false"),
+            Filter::Not(filter) => format!("NOT ({})", self.condition(filter)),
+        }
+    }
+
+    fn exists(&mut self, path: &str) -> String {
+        let path = self.bind(Bind::Path(split_path(path)));
+        format!("(metadata #> {}::text[]) IS NOT NULL", path)
+    }
+
+    /// Equality of the value at `path`. The containment test goes first so that the GIN index on
+    /// `metadata` can narrow the rows down; it alone would also match arrays holding the value
+    /// and objects with more keys.
+    fn equals(&mut self, path: &str, value: &Value) -> String {
+        let keys = split_path(path);
+        let object = keys.iter().rev().fold(value.clone(), |value, key| {
+            Value::Object(Map::from_iter([(key.clone(), value)]))
+        });
+        let object = self.bind(Bind::Text(object.to_string()));
+        let path = self.bind(Bind::Path(keys));
+        let value = self.bind(Bind::Text(value.to_string()));
+        format!(
+            "COALESCE(metadata @> {object}::jsonb AND metadata #> {path}::text[] = {value}::jsonb, false)"
+        )
+    }
+
+    fn compare(&mut self, path: &str, operator: &str, value: f64) -> String {
+        let path = self.bind(Bind::Path(split_path(path)));
+        let value = self.bind(Bind::Float(value));
+        // The CASE keeps values that are not numbers from being cast.
+        format!(
+            "(CASE WHEN jsonb_typeof(metadata #> {path}::text[]) = 'number' \
+             THEN (metadata #>> {path}::text[])::float8 {operator} {value} ELSE false END)",
+        )
+    }
+
+    fn join(
+        &mut self,
+        filters: impl Iterator<Item = Filter>,
+        operator: &str,
+        empty: &str,
+    ) -> String {
+        let conditions: Vec<String> = filters.map(|f| self.condition(&f)).collect();
+        if conditions.is_empty() {
+            empty.to_string()
+        } else {
+            format!("({})", conditions.join(&format!(" {} ", operator)))
+        }
+    }
+}
+
+fn split_path(path: &str) -> Vec<String> {
+    path.split('.').map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_condition_binds_values_in_order() {
+        let mut sql = FilterSql::new(3);
+        let condition = sql.condition(&Filter::And(vec![
+            Filter::eq("author.name", "Ada"),
+            Filter::Not(Box::new(Filter::gte("year", 1900.0))),
+            Filter::is_in("lang", ["en", "fr"]),
+            Filter::Or(vec![]),
+        ]));
+        assert_eq!(
+            condition,
+            "(COALESCE(metadata @> $3::jsonb AND metadata #> $4::text[] = $5::jsonb, false) AND \
+             NOT ((CASE WHEN jsonb_typeof(metadata #> $6::text[]) = 'number' \
+             THEN (metadata #>> $6::text[])::float8 >= $7 ELSE false END)) AND \
+             (COALESCE(metadata @> $8::jsonb AND metadata #> $9::text[] = $10::jsonb, false) OR \
+             COALESCE(metadata @> $11::jsonb AND metadata #> $12::text[] = $13::jsonb, false)) AND \
+             false)"
+        );
+        let lang = || Bind::Path(vec!["lang".to_string()]);
+        assert_eq!(
+            sql.binds,
+            vec![
+                Bind::Text(json!({"author": {"name": "Ada"}}).to_string()),
+                Bind::Path(vec!["author".to_string(), "name".to_string()]),
+                Bind::Text(json!("Ada").to_string()),
+                Bind::Path(vec!["year".to_string()]),
+                Bind::Float(1900.0),
+                Bind::Text(json!({"lang": "en"}).to_string()),
+                lang(),
+                Bind::Text(json!("en").to_string()),
+                Bind::Text(json!({"lang": "fr"}).to_string()),
+                lang(),
+                Bind::Text(json!("fr").to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ne_requires_the_key() {
+        let mut sql = FilterSql::new(1);
+        let condition = sql.condition(&Filter::ne("author.name", "Ada"));
+        // Without the existence check, `NOT COALESCE(...)` would match documents without the key.
+        assert_eq!(
+            condition,
+            "((metadata #> $1::text[]) IS NOT NULL AND \
+             NOT COALESCE(metadata @> $2::jsonb AND metadata #> $3::text[] = $4::jsonb, false))"
+        );
+        let path = || Bind::Path(vec!["author".to_string(), "name".to_string()]);
+        assert_eq!(
+            sql.binds,
+            vec![
+                path(),
+                Bind::Text(json!({"author": {"name": "Ada"}}).to_string()),
+                path(),
+                Bind::Text(json!("Ada").to_string()),
+            ]
+        );
+    }
+}
@@ -6,43 +6,15 @@ use ai_chain::{
 };
 use ai_chain_openai::embeddings::Embeddings;
 use ai_chain_qdrant::Qdrant;
-use qdrant_client::{
-    prelude::{QdrantClient, QdrantClientConfig},
-    qdrant::{CreateCollection, Distance, VectorParams, VectorsConfig},
-};
+use qdrant_client::prelude::{QdrantClient, QdrantClientConfig};
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     // Qdrant prep
     let config = QdrantClientConfig::from_url("http://localhost:6334");
     let client = Arc::new(QdrantClient::new(Some(config)).unwrap());
+    // The collection is created on the first write if it does not exist yet.
     let collection_name = "my-collection".to_string();
-    let embedding_size = 1536;
-
-    if !client
-        .has_collection(collection_name.clone())
-        .await
-        .unwrap()
-    {
-        client
-            .create_collection(&CreateCollection {
-                collection_name: collection_name.clone(),
-                vectors_config: Some(VectorsConfig {
-                    config: Some(qdrant_client::qdrant::vectors_config::Config::Params(
-                        VectorParams {
-                            on_disk: None,
-                            size: embedding_size,
-                            distance: Distance::Cosine.into(),
-                            hnsw_config: None,
-                            quantization_config: None,
-                        },
-                    )),
-                }),
-                ..Default::default()
-            })
-            .await
-            .unwrap();
-    }
     println!("OPENAI KEY: {}", std::env::var("OPENAI_API_KEY").unwrap());
     let embeddings = ai_chain_openai::embeddings::Embeddings::default();
 
@@ -17,7 +17,6 @@ use ai_chain_qdrant::{Qdrant, QdrantError};
 
 use ai_chain_openai::embeddings::{Embeddings, OpenAIEmbeddingsError};
 use qdrant_client::prelude::{QdrantClient, QdrantClientConfig};
-use qdrant_client::qdrant::{CreateCollection, Distance, VectorParams, VectorsConfig};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 // A simple example generating a prompt with some tools.
@@ -48,33 +47,8 @@ async fn build_local_qdrant() -> Qdrant<Embeddings, EmptyMetadata> {
     // Qdrant prep
     let config = QdrantClientConfig::from_url("http://localhost:6334");
     let client = Arc::new(QdrantClient::new(Some(config)).unwrap());
+    // The collection is created on the first write if it does not exist yet.
     let collection_name = "my-collection".to_string();
-    let embedding_size = 1536;
-
-    if !client
-        .has_collection(collection_name.clone())
-        .await
-        .unwrap()
-    {
-        client
-            .create_collection(&CreateCollection {
-                collection_name: collection_name.clone(),
-                vectors_config: Some(VectorsConfig {
-                    config: Some(qdrant_client::qdrant::vectors_config::Config::Params(
-                        VectorParams {
-                            size: embedding_size,
-                            distance: Distance::Cosine.into(),
-                            hnsw_config: None,
-                            on_disk: None,
-                            quantization_config: None,
-                        },
-                    )),
-                }),
-                ..Default::default()
-            })
-            .await
-            .unwrap();
-    }
 
     let embeddings = ai_chain_openai::embeddings::Embeddings::default();
 
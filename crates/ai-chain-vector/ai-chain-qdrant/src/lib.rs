@@ -1,25 +1,35 @@
 use std::{collections::HashMap, marker::PhantomData, sync::Arc};
 
 use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
 use qdrant_client::{
     prelude::QdrantClient,
     qdrant::{
-        value::Kind, with_payload_selector::SelectorOptions, Filter, PayloadIncludeSelector,
-        PointId, PointStruct, ScoredPoint, SearchPoints, Value, Vectors, WithPayloadSelector,
+        point_id::PointIdOptions, value::Kind, vectors::VectorsOptions, vectors_config::Config,
+        with_payload_selector::SelectorOptions, CreateCollection, Distance, FieldType, Filter,
+        PayloadIncludeSelector, PointId, PointStruct, RetrievedPoint, ScoredPoint, ScrollPoints,
+        SearchPoints, Value, VectorParams, VectorParamsMap, Vectors, VectorsConfig,
+        WithPayloadSelector,
     },
 };
 use thiserror::Error;
+use tokio::sync::OnceCell;
 use uuid::Uuid;
 
 use ai_chain::{
-    schema::Document,
-    traits::{Embeddings, EmbeddingsError, VectorStore, VectorStoreError},
+    schema::{Document, VectorRecord},
+    traits::{Embeddings, EmbeddingsError, ExportableVectorStore, VectorStore, VectorStoreError},
 };
 
 use serde::{de::DeserializeOwned, Serialize};
 
 const DEFAULT_CONTENT_PAYLOAD_KEY: &str = "page_content";
 const DEFAULT_METADATA_PAYLOAD_KEY: &str = "metadata";
+const DEFAULT_BATCH_SIZE: usize = 64;
+const DEFAULT_CONCURRENCY: usize = 4;
+/// The text embedded to learn the vector size when a collection is created before any documents
+/// are added.
+const DIMENSION_PROBE: &str = "dimension probe";
 
 pub struct Qdrant<E, M>
 where
@@ -32,6 +42,12 @@ where
     content_payload_key: String,
     metadata_payload_key: String,
     filter: Option<Filter>,
+    distance: Distance,
+    vector_name: Option<String>,
+    create_collection: bool,
+    batch_size: usize,
+    concurrency: usize,
+    collection_ready: OnceCell<()>,
     _marker: PhantomData<M>,
 }
 
@@ -40,6 +56,11 @@ where
     E: Embeddings,
     M: Send + Sync + Serialize + DeserializeOwned,
 {
+    /// Creates a store on `collection_name`. `filter` is applied to the searches of the
+    /// [`VectorStore`] trait; see [`Qdrant::similarity_search_with_filter`] for per-query filters.
+    ///
+    /// The collection is created on the first write if it does not exist yet, with the vector size
+    /// of the embeddings and the distance set by [`Qdrant::with_distance`].
     pub fn new(
         client: Arc<QdrantClient>,
         collection_name: String,
@@ -57,29 +78,304 @@ where
             metadata_payload_key: metadata_payload_key
                 .unwrap_or(DEFAULT_METADATA_PAYLOAD_KEY.to_string()),
             filter,
+            distance: Distance::Cosine,
+            vector_name: None,
+            create_collection: true,
+            batch_size: DEFAULT_BATCH_SIZE,
+            concurrency: DEFAULT_CONCURRENCY,
+            collection_ready: OnceCell::new(),
             _marker: Default::default(),
         }
     }
 
+    /// Sets the distance of collections created by the store. Defaults to cosine.
+    pub fn with_distance(mut self, distance: Distance) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// Stores and searches the named vector `name` instead of the collection's default vector.
+    pub fn with_vector_name(mut self, name: impl Into<String>) -> Self {
+        self.vector_name = Some(name.into());
+        self
+    }
+
+    /// Whether to create the collection if it is missing on the first write. Defaults to true.
+    pub fn with_create_collection(mut self, create_collection: bool) -> Self {
+        self.create_collection = create_collection;
+        self
+    }
+
+    /// Sets how many points are sent per upsert request. Defaults to 64.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets how many upsert requests may be in flight at once. Defaults to 4.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn collection_name(&self) -> &str {
+        &self.collection_name
+    }
+
+    /// Creates the collection if it does not exist, embedding a probe text to learn the vector
+    /// size. Returns whether the collection was created.
+    pub async fn ensure_collection(&self) -> Result<bool, QdrantError<E::Error>> {
+        if self.collection_exists().await? {
+            return Ok(false);
+        }
+        let probe = self
+            .embeddings
+            .embed_query(DIMENSION_PROBE.to_string())
+            .await?;
+        self.create_collection(probe.len() as u64).await?;
+        Ok(true)
+    }
+
+    /// Creates an index on the metadata key `key`, which speeds up filters on it. Nested keys
+    /// are separated by dots.
+    pub async fn create_payload_index(
+        &self,
+        key: &str,
+        field_type: FieldType,
+    ) -> Result<(), QdrantError<E::Error>> {
+        self.client
+            .create_field_index_blocking(
+                &self.collection_name,
+                format!("{}.{}", self.metadata_payload_key, key),
+                field_type,
+                None,
+                None,
+            )
+            .await
+            .map_err(QdrantError::Client)?;
+        Ok(())
+    }
+
+    /// Adds `documents` under the given ids, which must be unsigned integers or UUIDs. Points
+    /// that already have one of the ids are replaced.
+    pub async fn add_documents_with_ids(
+        &self,
+        documents: Vec<Document<M>>,
+        ids: Vec<String>,
+    ) -> Result<Vec<String>, QdrantError<E::Error>> {
+        let texts = documents.iter().map(|d| d.page_content.clone()).collect();
+        let embedding_vecs = self.embeddings.embed_texts(texts).await?;
+        self.add_embedded(documents, embedding_vecs, ids).await
+    }
+
+    /// Adds documents with their embeddings under the given ids, without calling the embeddings
+    /// model.
+    pub async fn add_embedded(
+        &self,
+        documents: Vec<Document<M>>,
+        vectors: Vec<Vec<f32>>,
+        ids: Vec<String>,
+    ) -> Result<Vec<String>, QdrantError<E::Error>> {
+        if documents.len() != ids.len() {
+            return Err(QdrantError::IdCount {
+                documents: documents.len(),
+                ids: ids.len(),
+            });
+        }
+        if documents.len() != vectors.len() {
+            return Err(QdrantError::EmbeddingCount {
+                documents: documents.len(),
+                vectors: vectors.len(),
+            });
+        }
+        let size = vectors.first().map_or(0, Vec::len) as u64;
+
+        let points = vectors
+            .into_iter()
+            .zip(documents)
+            .zip(&ids)
+            .map(|((vec, document), id)| {
+                let metadata = match document.metadata {
+                    Some(metadata) => serde_json::to_value(metadata)
+                        .map_err(QdrantError::Serde)?
+                        .into(),
+                    None => Value { kind: None },
+                };
+                Ok(self.point(id, vec, document.page_content, Some(metadata)))
+            })
+            .collect::<Result<Vec<_>, QdrantError<E::Error>>>()?;
+        self.upsert(points, size).await?;
+        Ok(ids)
+    }
+
+    /// Searches for the documents nearest to `query` that match `filter`, which replaces the
+    /// filter the store was created with.
+    pub async fn similarity_search_with_filter(
+        &self,
+        query: String,
+        limit: u32,
+        filter: Option<Filter>,
+    ) -> Result<Vec<Document<M>>, QdrantError<E::Error>> {
+        let embedded_query = self.embeddings.embed_query(query).await?;
+        let res = self
+            .client
+            .search_points(&SearchPoints {
+                timeout: None,
+                shard_key_selector: None,
+                sparse_indices: None,
+                collection_name: self.collection_name.clone(),
+                vector: embedded_query,
+                filter,
+                limit: limit.into(),
+                with_payload: Some(WithPayloadSelector {
+                    selector_options: Some(SelectorOptions::Include(PayloadIncludeSelector {
+                        fields: vec![
+                            self.content_payload_key.clone(),
+                            self.metadata_payload_key.clone(),
+                        ],
+                    })),
+                }),
+                params: None,
+                score_threshold: None,
+                offset: None,
+                vector_name: self.vector_name.clone(),
+                with_vectors: None,
+                read_consistency: None,
+            })
+            .await
+            .map_err(QdrantError::Client)?;
+
+        res.result
+            .into_iter()
+            .map(|r| self.try_document_from_scored_point(r))
+            .collect()
+    }
+
+    async fn collection_exists(&self) -> Result<bool, QdrantError<E::Error>> {
+        self.client
+            .collection_exists(&self.collection_name)
+            .await
+            .map_err(QdrantError::Client)
+    }
+
+    async fn create_collection(&self, size: u64) -> Result<(), QdrantError<E::Error>> {
+        self.client
+            .create_collection(&CreateCollection {
+                collection_name: self.collection_name.clone(),
+                vectors_config: Some(self.vectors_config(size)),
+                ..Default::default()
+            })
+            .await
+            .map_err(QdrantError::Client)?;
+        Ok(())
+    }
+
+    fn vectors_config(&self, size: u64) -> VectorsConfig {
+        let params = VectorParams {
+            size,
+            distance: self.distance.into(),
+            ..Default::default()
+        };
+        let config = match &self.vector_name {
+            Some(name) => Config::ParamsMap(VectorParamsMap {
+                map: HashMap::from([(name.clone(), params)]),
+            }),
+            None => Config::Params(params),
+        };
+        VectorsConfig {
+            config: Some(config),
+        }
+    }
+
+    fn point(
+        &self,
+        id: &str,
+        vector: Vec<f32>,
+        text: String,
+        metadata: Option<Value>,
+    ) -> PointStruct {
+        let mut payload = HashMap::new();
+        payload.insert(self.content_payload_key.clone(), text.into());
+        if let Some(metadata) = metadata {
+            payload.insert(self.metadata_payload_key.clone(), metadata);
+        }
+        let vectors = match &self.vector_name {
+            Some(name) => Vectors::from(HashMap::from([(name.clone(), vector)])),
+            None => Vectors::from(vector),
+        };
+        PointStruct {
+            id: Some(point_id(id)),
+            payload,
+            vectors: Some(vectors),
+        }
+    }
+
+    /// Upserts `points`, whose vectors have `size` dimensions, in batches. The collection is
+    /// created first if needed.
+    async fn upsert(
+        &self,
+        points: Vec<PointStruct>,
+        size: u64,
+    ) -> Result<(), QdrantError<E::Error>> {
+        if points.is_empty() {
+            return Ok(());
+        }
+        if self.create_collection {
+            self.collection_ready
+                .get_or_try_init(|| async {
+                    if !self.collection_exists().await? {
+                        self.create_collection(size).await?;
+                    }
+                    Ok::<_, QdrantError<E::Error>>(())
+                })
+                .await?;
+        }
+
+        let mut batches = Vec::new();
+        let mut points = points.into_iter().peekable();
+        while points.peek().is_some() {
+            batches.push(points.by_ref().take(self.batch_size).collect::<Vec<_>>());
+        }
+        futures::stream::iter(batches)
+            .map(|batch| {
+                self.client
+                    .upsert_points(&self.collection_name, None, batch, None)
+            })
+            .buffer_unordered(self.concurrency)
+            .try_for_each(|_| async { Ok(()) })
+            .await
+            .map_err(QdrantError::Client)
+    }
+
     fn try_document_from_scored_point(
         &self,
         scored_point: ScoredPoint,
     ) -> Result<Document<M>, QdrantError<E::Error>> {
-        let metadata = scored_point.payload.get(&self.metadata_payload_key);
+        self.try_document_from_payload(scored_point.id, &scored_point.payload)
+    }
+
+    fn try_document_from_payload(
+        &self,
+        point_id: Option<PointId>,
+        payload: &HashMap<String, Value>,
+    ) -> Result<Document<M>, QdrantError<E::Error>> {
+        let metadata = payload.get(&self.metadata_payload_key);
         let metadata: Option<M> = match metadata.cloned() {
+            Some(Value {
+                kind: None | Some(Kind::NullValue(_)),
+            }) => None,
             Some(val) => {
                 let j = serde_json::to_value(val).map_err(QdrantError::Serde)?;
                 Some(serde_json::from_value(j).map_err(QdrantError::Serde)?)
             }
             None => None,
         };
-        let page_content = scored_point
-            .payload
+        let page_content = payload
             .get(&self.content_payload_key)
             .ok_or::<QdrantError<E::Error>>(
                 ConversionError::PayloadKeyNotFound {
                     payload_key: self.content_payload_key.clone(),
-                    point_id: scored_point.id.clone(),
+                    point_id: point_id.clone(),
                 }
                 .into(),
             )?
@@ -87,7 +383,7 @@ where
             .clone()
             .ok_or::<QdrantError<E::Error>>(
                 ConversionError::InvalidPageContent {
-                    point_id: scored_point.id.clone(),
+                    point_id: point_id.clone(),
                 }
                 .into(),
             )?;
@@ -97,12 +393,36 @@ where
                 metadata,
             })
         } else {
-            Err(ConversionError::InvalidPageContent {
-                point_id: scored_point.id,
-            }
-            .into())
+            Err(ConversionError::InvalidPageContent { point_id }.into())
         }
     }
+
+    fn try_record_from_retrieved_point(
+        &self,
+        point: RetrievedPoint,
+    ) -> Result<VectorRecord<M>, QdrantError<E::Error>> {
+        let document = self.try_document_from_payload(point.id.clone(), &point.payload)?;
+        let vector = match point.vectors.and_then(|v| v.vectors_options) {
+            Some(VectorsOptions::Vector(vector)) if self.vector_name.is_none() => Some(vector.data),
+            Some(VectorsOptions::Vectors(mut named)) => self
+                .vector_name
+                .as_ref()
+                .and_then(|name| named.vectors.remove(name))
+                .map(|vector| vector.data),
+            _ => None,
+        };
+        let id = match point
+            .id
+            .as_ref()
+            .and_then(|id| id.point_id_options.as_ref())
+        {
+            Some(PointIdOptions::Num(num)) => num.to_string(),
+            Some(PointIdOptions::Uuid(uuid)) => uuid.clone(),
+            None => String::new(),
+        };
+        let vector = vector.ok_or(ConversionError::MissingVector { point_id: point.id })?;
+        Ok(VectorRecord::new(id, vector, document))
+    }
 }
 
 #[derive(Debug, Error)]
@@ -116,6 +436,8 @@ pub enum ConversionError {
     InvalidPageContent { point_id: Option<PointId> },
     #[error("Could not convert metadata. Point ID: {point_id:?}")]
     InvalidMetadata { point_id: Option<PointId> },
+    #[error("The point has no vector of the store. Point ID: {point_id:?}")]
+    MissingVector { point_id: Option<PointId> },
 }
 
 #[derive(Debug, Error)]
@@ -131,6 +453,10 @@ where
     ConversionError(#[from] ConversionError),
     #[error("Serde Error")]
     Serde(serde_json::Error),
+    #[error("{documents} documents were given {ids} ids")]
+    IdCount { documents: usize, ids: usize },
+    #[error("{documents} documents were given {vectors} embeddings")]
+    EmbeddingCount { documents: usize, vectors: usize },
 }
 
 impl<E> VectorStoreError for QdrantError<E> where
@@ -148,113 +474,316 @@ where
 
     async fn add_texts(&self, texts: Vec<String>) -> Result<Vec<String>, Self::Error> {
         let embedding_vecs = self.embeddings.embed_texts(texts.clone()).await?;
+        let size = embedding_vecs.first().map_or(0, Vec::len) as u64;
 
         let ids = (0..embedding_vecs.len())
             .map(|_| Uuid::new_v4().to_string())
             .collect::<Vec<String>>();
         let points = embedding_vecs
             .into_iter()
-            .zip(texts.into_iter())
-            .zip(ids.iter())
-            .map(|((vec, text), uuid)| {
-                let mut payload = HashMap::new();
-                payload.insert(self.content_payload_key.clone(), text.into());
-                PointStruct {
-                    id: Some(uuid.to_string().into()),
-                    payload,
-                    vectors: Some(Vectors::from(vec)),
-                }
-            })
+            .zip(texts)
+            .zip(&ids)
+            .map(|((vec, text), id)| self.point(id, vec, text, None))
             .collect();
-        self.client
-            .upsert_points(&self.collection_name, None, points, None)
-            .await
-            .map_err(QdrantError::Client)?;
+        self.upsert(points, size).await?;
         Ok(ids)
     }
 
     async fn add_documents(&self, documents: Vec<Document<M>>) -> Result<Vec<String>, Self::Error> {
-        let texts = documents.iter().map(|d| d.page_content.clone()).collect();
-        let embedding_vecs = self.embeddings.embed_texts(texts).await?;
-
-        let ids = (0..embedding_vecs.len())
+        let ids = documents
+            .iter()
             .map(|_| Uuid::new_v4().to_string())
-            .collect::<Vec<String>>();
+            .collect();
+        self.add_documents_with_ids(documents, ids).await
+    }
 
-        let points: Result<Vec<PointStruct>, Self::Error> = embedding_vecs
-            .into_iter()
-            .zip(documents.into_iter())
-            .zip(ids.iter())
-            .map(|((vec, document), uuid)| {
-                let mut payload: HashMap<String, Value> = HashMap::new();
-
-                if let Some(metadata) = document.metadata {
-                    let val = serde_json::to_value(metadata).map_err(Self::Error::Serde)?;
-                    payload.insert(self.metadata_payload_key.clone(), val.into());
-                } else {
-                    payload.insert(self.metadata_payload_key.clone(), Value { kind: None });
-                }
-                payload.insert(
-                    self.content_payload_key.clone(),
-                    document.page_content.clone().into(),
-                );
-                Ok(PointStruct {
-                    id: Some(uuid.to_string().into()),
-                    payload,
-                    vectors: Some(Vectors::from(vec)),
+    async fn similarity_search(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<Document<M>>, Self::Error> {
+        self.similarity_search_with_filter(query, limit, self.filter.clone())
+            .await
+    }
+}
+
+/// Records keep their ids if they are all unsigned integers or UUIDs, which are the ids Qdrant
+/// accepts; otherwise they get new UUIDs.
+#[async_trait]
+impl<E, M> ExportableVectorStore<E, M> for Qdrant<E, M>
+where
+    E: Embeddings + Send + Sync,
+    M: Send + Sync + Serialize + DeserializeOwned,
+{
+    async fn export_records(&self) -> Result<Vec<VectorRecord<M>>, Self::Error> {
+        let mut records = Vec::new();
+        let mut offset = None;
+        loop {
+            let page = self
+                .client
+                .scroll(&ScrollPoints {
+                    collection_name: self.collection_name.clone(),
+                    offset,
+                    limit: Some(self.batch_size as u32),
+                    with_payload: Some(true.into()),
+                    with_vectors: Some(true.into()),
+                    ..Default::default()
                 })
+                .await
+                .map_err(QdrantError::Client)?;
+            for point in page.result {
+                records.push(self.try_record_from_retrieved_point(point)?);
+            }
+            match page.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => return Ok(records),
+            }
+        }
+    }
+
+    async fn import_records(
+        &self,
+        records: Vec<VectorRecord<M>>,
+    ) -> Result<Vec<String>, Self::Error> {
+        let keep_ids = records
+            .iter()
+            .all(|r| r.id.parse::<u64>().is_ok() || Uuid::parse_str(&r.id).is_ok());
+        let ids = records
+            .iter()
+            .map(|r| match keep_ids {
+                true => r.id.clone(),
+                false => Uuid::new_v4().to_string(),
             })
             .collect();
+        let (documents, vectors) = records.into_iter().map(VectorRecord::into_document).unzip();
+        self.add_embedded(documents, vectors, ids).await
+    }
+}
+
+/// Integer ids are sent as numbers, anything else as a UUID.
+fn point_id(id: &str) -> PointId {
+    match id.parse::<u64>() {
+        Ok(num) => num.into(),
+        Err(_) => id.to_string().into(),
+    }
+}
 
-        let points = points?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qdrant_client::{prelude::QdrantClientConfig, qdrant::Condition};
+    use serde::Deserialize;
 
-        self.client
-            .upsert_points(self.collection_name.clone(), None, points, None)
-            .await
-            .map_err(QdrantError::Client)?;
+    #[derive(Debug, Error)]
+    #[error("unreachable")]
+    struct TestError;
 
-        Ok(ids)
+    impl EmbeddingsError for TestError {}
+
+    /// Embeds a text as the counts of the letters `a` to `c`.
+    struct LetterEmbeddings;
+
+    fn letters(text: &str) -> Vec<f32> {
+        ['a', 'b', 'c']
+            .iter()
+            .map(|&l| text.chars().filter(|&c| c == l).count() as f32)
+            .collect()
     }
 
-    async fn similarity_search(
-        &self,
-        query: String,
-        limit: u32,
-    ) -> Result<Vec<Document<M>>, Self::Error> {
-        let embedded_query = self.embeddings.embed_query(query).await?;
-        let res = self
-            .client
-            .search_points(&SearchPoints {
-                timeout: None,
-                shard_key_selector: None,
-                sparse_indices: None,
-                collection_name: self.collection_name.clone(),
-                vector: embedded_query,
-                filter: self.filter.clone(),
-                limit: limit.into(),
-                with_payload: Some(WithPayloadSelector {
-                    selector_options: Some(SelectorOptions::Include(PayloadIncludeSelector {
-                        fields: vec![
-                            self.content_payload_key.clone(),
-                            self.metadata_payload_key.clone(),
-                        ],
-                    })),
+    #[async_trait]
+    impl Embeddings for LetterEmbeddings {
+        type Error = TestError;
+
+        async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, TestError> {
+            Ok(texts.iter().map(|t| letters(t)).collect())
+        }
+
+        async fn embed_query(&self, query: String) -> Result<Vec<f32>, TestError> {
+            Ok(letters(&query))
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Source {
+        source: String,
+    }
+
+    fn store(url: &str, collection_name: &str) -> Qdrant<LetterEmbeddings, Source> {
+        let client = QdrantClient::new(Some(QdrantClientConfig::from_url(url))).unwrap();
+        Qdrant::new(
+            Arc::new(client),
+            collection_name.to_string(),
+            LetterEmbeddings,
+            None,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_points_use_the_vector_name_and_given_ids() {
+        let store = store("http://localhost:6334", "test").with_vector_name("text");
+
+        let point = store.point("42", vec![1.0, 2.0], "abc".to_string(), None);
+        assert_eq!(
+            point.id.unwrap().point_id_options,
+            Some(PointIdOptions::Num(42))
+        );
+        match point.vectors.unwrap().vectors_options {
+            Some(VectorsOptions::Vectors(named)) => {
+                assert_eq!(named.vectors["text"].data, vec![1.0, 2.0])
+            }
+            other => panic!("expected named vectors, got {:?}", other),
+        }
+        assert!(!point.payload.contains_key(DEFAULT_METADATA_PAYLOAD_KEY));
+
+        let uuid = Uuid::new_v4().to_string();
+        assert_eq!(
+            point_id(&uuid).point_id_options,
+            Some(PointIdOptions::Uuid(uuid))
+        );
+
+        match store.vectors_config(3).config {
+            Some(Config::ParamsMap(params)) => {
+                assert_eq!(params.map["text"].size, 3);
+                assert_eq!(params.map["text"].distance, Distance::Cosine as i32);
+            }
+            other => panic!("expected a params map, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_null_metadata_reads_as_none() {
+        let store = store("http://localhost:6334", "test");
+        let point = store.point(
+            "1",
+            vec![0.0],
+            "abc".to_string(),
+            Some(Value { kind: None }),
+        );
+        let document = store
+            .try_document_from_scored_point(ScoredPoint {
+                id: point.id,
+                payload: point.payload,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(document.page_content, "abc");
+        assert_eq!(document.metadata, None);
+    }
+
+    #[test]
+    fn test_records_from_retrieved_points() {
+        let text_store = store("http://localhost:6334", "test").with_vector_name("text");
+        let metadata = serde_json::json!({ "source": "wiki" }).into();
+        let point = text_store.point("7", vec![1.0, 2.0], "abc".to_string(), Some(metadata));
+        let retrieved = RetrievedPoint {
+            id: point.id,
+            payload: point.payload,
+            vectors: point.vectors,
+            shard_key: None,
+        };
+
+        let record = text_store
+            .try_record_from_retrieved_point(retrieved.clone())
+            .unwrap();
+        assert_eq!(record.id, "7");
+        assert_eq!(record.vector, vec![1.0, 2.0]);
+        assert_eq!(
+            record.metadata,
+            Some(Source {
+                source: "wiki".to_string()
+            })
+        );
+
+        let other = store("http://localhost:6334", "test")
+            .with_vector_name("image")
+            .try_record_from_retrieved_point(retrieved);
+        assert!(matches!(
+            other,
+            Err(QdrantError::ConversionError(
+                ConversionError::MissingVector { .. }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_embedded_checks_the_counts() {
+        let store = store("http://localhost:6334", "test");
+        let documents = vec![
+            Document::new("a".to_string()),
+            Document::new("b".to_string()),
+        ];
+        let ids = vec!["1".to_string(), "2".to_string()];
+        let result = store
+            .add_embedded(documents, vec![vec![1.0, 0.0, 0.0]], ids)
+            .await;
+        assert!(matches!(
+            result,
+            Err(QdrantError::EmbeddingCount {
+                documents: 2,
+                vectors: 1
+            })
+        ));
+    }
+
+    /// Needs a running Qdrant, at `QDRANT_URL` or on localhost:
+    /// `cargo test -p ai-chain-qdrant -- --ignored`.
+    #[tokio::test]
+    #[ignore]
+    async fn test_against_qdrant() {
+        let url = std::env::var("QDRANT_URL").unwrap_or("http://localhost:6334".to_string());
+        let collection_name = format!("ai_chain_test_{}", std::process::id());
+        let store = store(&url, &collection_name)
+            .with_distance(Distance::Euclid)
+            .with_batch_size(2);
+
+        let documents = ["aab", "bbc", "cca", "abc"]
+            .iter()
+            .zip(["wiki", "blog", "wiki", "blog"])
+            .map(|(text, source)| Document {
+                page_content: text.to_string(),
+                metadata: Some(Source {
+                    source: source.to_string(),
                 }),
-                params: None,
-                score_threshold: None,
-                offset: None,
-                vector_name: None,
-                with_vectors: None,
-                read_consistency: None,
             })
+            .collect();
+        let ids = vec!["1", "2", "3", "4"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        store.add_documents_with_ids(documents, ids).await.unwrap();
+        store
+            .create_payload_index("source", FieldType::Keyword)
             .await
-            .map_err(QdrantError::Client)?;
+            .unwrap();
+        assert!(!store.ensure_collection().await.unwrap());
 
-        let mut out = vec![];
-        for r in res.result.into_iter() {
-            let val = self.try_document_from_scored_point(r)?;
-            out.push(val);
-        }
-        Ok(out)
+        // The points are written asynchronously.
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        let found = store
+            .similarity_search_with_filter(
+                "bbc".to_string(),
+                1,
+                Some(Filter::must([Condition::matches(
+                    "metadata.source",
+                    "wiki".to_string(),
+                )])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(found[0].page_content, "aab");
+
+        let records = store.export_records().await.unwrap();
+        assert_eq!(records.len(), 4);
+        assert!(records
+            .iter()
+            .any(|r| r.id == "2" && r.vector == vec![0.0, 2.0, 1.0]));
+
+        store
+            .client
+            .delete_collection(&collection_name)
+            .await
+            .unwrap();
     }
 }
@@ -0,0 +1,710 @@
+//! Persistent chat histories, keyed by session id.
+//!
+//! A `ChatHistoryStore` keeps the messages of many conversations, and metadata about each session
+//! such as its system prompt, so a `conversation::Chain` can be picked up again later, possibly by
+//! another process. Three stores are provided:
+//! - `InMemoryChatHistoryStore` for tests and single-process servers.
+//! - `FileChatHistoryStore`, which keeps one `serialization::Envelope` file per session, in JSON or
+//!   YAML.
+//! - `SqlChatHistoryStore`, which keeps all sessions in one table of a PostgreSQL, MySQL or SQLite
+//!   database through `sqlx`. Enable the `sqlite` feature, or the driver features of `sqlx`, for
+//!   the databases you use.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::any::AnyPoolOptions;
+use sqlx::{AnyPool, Row};
+use tokio::sync::{Mutex, RwLock};
+
+use crate::prompt::{ChatMessage, ChatRole};
+use crate::serialization::Envelope;
+
+/// An error raised by a `ChatHistoryStore`.
+#[derive(thiserror::Error, Debug)]
+pub enum ChatHistoryError {
+    #[error("invalid session id `{0}`")]
+    InvalidSessionId(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("SQL error: {0}")]
+    Sql(#[from] sqlx::Error),
+}
+
+/// A message of a stored conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub message: ChatMessage<String>,
+    /// When the message was added, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+}
+
+impl StoredMessage {
+    /// Wraps `message`, timestamped now and without metadata.
+    pub fn new(message: ChatMessage<String>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Self {
+            message,
+            timestamp,
+            metadata: HashMap::new(),
+        }
+    }
+
+    pub fn with_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Stores the messages of conversations by session id.
+#[async_trait]
+pub trait ChatHistoryStore: Send + Sync {
+    /// Returns the messages of a session, oldest first. Unknown sessions are empty.
+    async fn load(&self, session_id: &str) -> Result<Vec<StoredMessage>, ChatHistoryError>;
+
+    /// Appends messages to a session, creating it if needed.
+    async fn append(
+        &self,
+        session_id: &str,
+        messages: Vec<StoredMessage>,
+    ) -> Result<(), ChatHistoryError>;
+
+    /// Returns the metadata of a session. Unknown sessions have none.
+    async fn load_metadata(
+        &self,
+        session_id: &str,
+    ) -> Result<HashMap<String, String>, ChatHistoryError>;
+
+    /// Sets metadata of a session, creating it if needed. Keys missing from `metadata` keep their
+    /// values.
+    async fn set_metadata(
+        &self,
+        session_id: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), ChatHistoryError>;
+
+    /// Deletes a session and its metadata.
+    async fn clear(&self, session_id: &str) -> Result<(), ChatHistoryError>;
+
+    /// Lists the ids of the stored sessions.
+    async fn sessions(&self) -> Result<Vec<String>, ChatHistoryError>;
+}
+
+/// Keeps chat histories in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryChatHistoryStore {
+    sessions: RwLock<HashMap<String, Vec<StoredMessage>>>,
+    metadata: RwLock<HashMap<String, HashMap<String, String>>>,
+}
+
+impl InMemoryChatHistoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ChatHistoryStore for InMemoryChatHistoryStore {
+    async fn load(&self, session_id: &str) -> Result<Vec<StoredMessage>, ChatHistoryError> {
+        Ok(self
+            .sessions
+            .read()
+            .await
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn append(
+        &self,
+        session_id: &str,
+        messages: Vec<StoredMessage>,
+    ) -> Result<(), ChatHistoryError> {
+        self.sessions
+            .write()
+            .await
+            .entry(session_id.to_string())
+            .or_default()
+            .extend(messages);
+        Ok(())
+    }
+
+    async fn load_metadata(
+        &self,
+        session_id: &str,
+    ) -> Result<HashMap<String, String>, ChatHistoryError> {
+        Ok(self
+            .metadata
+            .read()
+            .await
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn set_metadata(
+        &self,
+        session_id: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), ChatHistoryError> {
+        self.metadata
+            .write()
+            .await
+            .entry(session_id.to_string())
+            .or_default()
+            .extend(metadata);
+        Ok(())
+    }
+
+    async fn clear(&self, session_id: &str) -> Result<(), ChatHistoryError> {
+        self.sessions.write().await.remove(session_id);
+        self.metadata.write().await.remove(session_id);
+        Ok(())
+    }
+
+    async fn sessions(&self) -> Result<Vec<String>, ChatHistoryError> {
+        let mut sessions: Vec<String> = self.sessions.read().await.keys().cloned().collect();
+        for session_id in self.metadata.read().await.keys() {
+            if !sessions.contains(session_id) {
+                sessions.push(session_id.clone());
+            }
+        }
+        Ok(sessions)
+    }
+}
+
+/// The file format of a `FileChatHistoryStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistoryFileFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+
+impl HistoryFileFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            HistoryFileFormat::Json => "json",
+            HistoryFileFormat::Yaml => "yaml",
+        }
+    }
+}
+
+/// Keeps each session in its own file, `<session id>.json` or `<session id>.yaml`, holding an
+/// `Envelope` with the session id and the metadata of the session in its metadata.
+///
+/// Session ids may only contain ASCII letters, digits, `-`, `_` and `.`, and can't start with `.`.
+#[derive(Debug)]
+pub struct FileChatHistoryStore {
+    directory: PathBuf,
+    format: HistoryFileFormat,
+    /// Serializes the read-modify-write cycles of `append` and `set_metadata`.
+    lock: Mutex<()>,
+}
+
+impl FileChatHistoryStore {
+    /// Creates a store keeping JSON files in `directory`, which is created on the first write.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Self {
+            directory: directory.into(),
+            format: HistoryFileFormat::default(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn with_format(mut self, format: HistoryFileFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    fn path(&self, session_id: &str) -> Result<PathBuf, ChatHistoryError> {
+        let valid = !session_id.is_empty()
+            && !session_id.starts_with('.')
+            && session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(ChatHistoryError::InvalidSessionId(session_id.to_string()));
+        }
+        Ok(self
+            .directory
+            .join(format!("{}.{}", session_id, self.format.extension())))
+    }
+
+    async fn read(
+        &self,
+        session_id: &str,
+    ) -> Result<Envelope<Vec<StoredMessage>>, ChatHistoryError> {
+        let contents = match tokio::fs::read(self.path(session_id)?).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Envelope::new(Vec::new()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(match self.format {
+            HistoryFileFormat::Json => serde_json::from_slice(&contents)?,
+            HistoryFileFormat::Yaml => serde_yaml::from_slice(&contents)?,
+        })
+    }
+
+    async fn write(
+        &self,
+        session_id: &str,
+        mut envelope: Envelope<Vec<StoredMessage>>,
+    ) -> Result<(), ChatHistoryError> {
+        envelope
+            .metadata
+            .insert(SESSION_ID_KEY.to_string(), session_id.to_string());
+        let contents = match self.format {
+            HistoryFileFormat::Json => serde_json::to_string(&envelope)?,
+            HistoryFileFormat::Yaml => serde_yaml::to_string(&envelope)?,
+        };
+        tokio::fs::create_dir_all(&self.directory).await?;
+        tokio::fs::write(self.path(session_id)?, contents).await?;
+        Ok(())
+    }
+}
+
+/// The key of the session id in the envelope of a `FileChatHistoryStore` file.
+const SESSION_ID_KEY: &str = "session_id";
+
+#[async_trait]
+impl ChatHistoryStore for FileChatHistoryStore {
+    async fn load(&self, session_id: &str) -> Result<Vec<StoredMessage>, ChatHistoryError> {
+        Ok(self.read(session_id).await?.data)
+    }
+
+    async fn append(
+        &self,
+        session_id: &str,
+        messages: Vec<StoredMessage>,
+    ) -> Result<(), ChatHistoryError> {
+        let _guard = self.lock.lock().await;
+        let mut envelope = self.read(session_id).await?;
+        envelope.data.extend(messages);
+        self.write(session_id, envelope).await
+    }
+
+    async fn load_metadata(
+        &self,
+        session_id: &str,
+    ) -> Result<HashMap<String, String>, ChatHistoryError> {
+        let mut metadata = self.read(session_id).await?.metadata;
+        metadata.remove(SESSION_ID_KEY);
+        Ok(metadata)
+    }
+
+    async fn set_metadata(
+        &self,
+        session_id: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), ChatHistoryError> {
+        let _guard = self.lock.lock().await;
+        let mut envelope = self.read(session_id).await?;
+        envelope.metadata.extend(metadata);
+        self.write(session_id, envelope).await
+    }
+
+    async fn clear(&self, session_id: &str) -> Result<(), ChatHistoryError> {
+        let _guard = self.lock.lock().await;
+        match tokio::fs::remove_file(self.path(session_id)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn sessions(&self) -> Result<Vec<String>, ChatHistoryError> {
+        let mut entries = match tokio::fs::read_dir(&self.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut sessions = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some(self.format.extension()) {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    sessions.push(stem.to_string());
+                }
+            }
+        }
+        Ok(sessions)
+    }
+}
+
+fn role_to_str(role: &ChatRole) -> &str {
+    match role {
+        ChatRole::System => "system",
+        ChatRole::User => "user",
+        ChatRole::Assistant => "assistant",
+        ChatRole::Other(role) => role,
+    }
+}
+
+fn role_from_str(role: &str) -> ChatRole {
+    match role {
+        "system" => ChatRole::System,
+        "user" => ChatRole::User,
+        "assistant" => ChatRole::Assistant,
+        other => ChatRole::Other(other.to_string()),
+    }
+}
+
+/// Keeps chat histories in the `chat_history` table of a SQL database, and the metadata of the
+/// sessions in the `chat_session_metadata` table.
+///
+/// The tables are created by `migrate`. Metadata is stored as JSON. Queries use `$1` placeholders,
+/// rewritten to `?` when the pool connects to MySQL or MariaDB.
+///
+/// # Example
+/// ```no_run
+/// # async fn run() -> Result<(), ai_chain::chains::conversation::ChatHistoryError> {
+/// use ai_chain::chains::conversation::SqlChatHistoryStore;
+/// let store = SqlChatHistoryStore::connect("sqlite://history.db?mode=rwc").await?;
+/// store.migrate().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SqlChatHistoryStore {
+    pool: AnyPool,
+    /// Whether the database takes `?` placeholders instead of `$1`.
+    question_marks: bool,
+}
+
+impl SqlChatHistoryStore {
+    /// Uses an existing pool.
+    pub fn new(pool: AnyPool) -> Self {
+        let scheme = pool.connect_options().database_url.scheme().to_string();
+        Self {
+            pool,
+            question_marks: matches!(scheme.as_str(), "mysql" | "mariadb"),
+        }
+    }
+
+    /// Connects to the database at `url` with the drivers that are enabled.
+    pub async fn connect(url: &str) -> Result<Self, ChatHistoryError> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new().connect(url).await?;
+        Ok(Self::new(pool))
+    }
+
+    /// Returns `query` with the placeholders of this database.
+    fn sql(&self, query: &str) -> String {
+        if self.question_marks {
+            question_mark_placeholders(query)
+        } else {
+            query.to_string()
+        }
+    }
+
+    /// Creates the `chat_history` and `chat_session_metadata` tables if they don't exist.
+    pub async fn migrate(&self) -> Result<(), ChatHistoryError> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS chat_history (
+                session_id VARCHAR(255) NOT NULL,
+                position BIGINT NOT NULL,
+                role VARCHAR(255) NOT NULL,
+                body TEXT NOT NULL,
+                timestamp BIGINT NOT NULL,
+                metadata TEXT NOT NULL,
+                PRIMARY KEY (session_id, position)
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS chat_session_metadata (
+                session_id VARCHAR(255) NOT NULL,
+                meta_key VARCHAR(255) NOT NULL,
+                meta_value TEXT NOT NULL,
+                PRIMARY KEY (session_id, meta_key)
+            )",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ChatHistoryStore for SqlChatHistoryStore {
+    async fn load(&self, session_id: &str) -> Result<Vec<StoredMessage>, ChatHistoryError> {
+        let rows = sqlx::query(&self.sql(
+            "SELECT role, body, timestamp, metadata FROM chat_history
+             WHERE session_id = $1 ORDER BY position",
+        ))
+        .bind(session_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                let role: String = row.try_get("role")?;
+                let metadata: String = row.try_get("metadata")?;
+                Ok(StoredMessage {
+                    message: ChatMessage::new(role_from_str(&role), row.try_get("body")?),
+                    timestamp: row.try_get::<i64, _>("timestamp")? as u64,
+                    metadata: serde_json::from_str(&metadata)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn append(
+        &self,
+        session_id: &str,
+        messages: Vec<StoredMessage>,
+    ) -> Result<(), ChatHistoryError> {
+        let mut transaction = self.pool.begin().await?;
+        let next: i64 = sqlx::query(&self.sql(
+            "SELECT COALESCE(MAX(position) + 1, 0) AS next FROM chat_history WHERE session_id = $1",
+        ))
+        .bind(session_id)
+        .fetch_one(&mut *transaction)
+        .await?
+        .try_get("next")?;
+        let insert = self.sql(
+            "INSERT INTO chat_history (session_id, position, role, body, timestamp, metadata)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        );
+        for (i, stored) in messages.iter().enumerate() {
+            sqlx::query(&insert)
+                .bind(session_id)
+                .bind(next + i as i64)
+                .bind(role_to_str(stored.message.role()))
+                .bind(stored.message.body().as_str())
+                .bind(stored.timestamp as i64)
+                .bind(serde_json::to_string(&stored.metadata)?)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn load_metadata(
+        &self,
+        session_id: &str,
+    ) -> Result<HashMap<String, String>, ChatHistoryError> {
+        let rows =
+            sqlx::query(&self.sql(
+                "SELECT meta_key, meta_value FROM chat_session_metadata WHERE session_id = $1",
+            ))
+            .bind(session_id)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Ok((row.try_get("meta_key")?, row.try_get("meta_value")?)))
+            .collect::<Result<_, sqlx::Error>>()?)
+    }
+
+    async fn set_metadata(
+        &self,
+        session_id: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), ChatHistoryError> {
+        // Upserts are spelled differently by every database, so existing keys are replaced.
+        let mut transaction = self.pool.begin().await?;
+        let delete =
+            self.sql("DELETE FROM chat_session_metadata WHERE session_id = $1 AND meta_key = $2");
+        let insert = self.sql(
+            "INSERT INTO chat_session_metadata (session_id, meta_key, meta_value)
+             VALUES ($1, $2, $3)",
+        );
+        for (key, value) in &metadata {
+            sqlx::query(&delete)
+                .bind(session_id)
+                .bind(key.as_str())
+                .execute(&mut *transaction)
+                .await?;
+            sqlx::query(&insert)
+                .bind(session_id)
+                .bind(key.as_str())
+                .bind(value.as_str())
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn clear(&self, session_id: &str) -> Result<(), ChatHistoryError> {
+        let mut transaction = self.pool.begin().await?;
+        for table in ["chat_history", "chat_session_metadata"] {
+            sqlx::query(&self.sql(&format!("DELETE FROM {table} WHERE session_id = $1")))
+                .bind(session_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn sessions(&self) -> Result<Vec<String>, ChatHistoryError> {
+        let rows = sqlx::query(
+            "SELECT session_id FROM chat_history
+             UNION SELECT session_id FROM chat_session_metadata",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| row.try_get("session_id"))
+            .collect::<Result<_, _>>()?)
+    }
+}
+
+/// Replaces the `$1`-style placeholders of `query`, which are numbered in bind order, with `?`.
+fn question_mark_placeholders(query: &str) -> String {
+    let mut rewritten = String::with_capacity(query.len());
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '$' && chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+            while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                chars.next();
+            }
+            rewritten.push('?');
+        } else {
+            rewritten.push(c);
+        }
+    }
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages() -> Vec<StoredMessage> {
+        vec![
+            StoredMessage::new(ChatMessage::user("Hi!".to_string())).with_metadata("user", "42"),
+            StoredMessage::new(ChatMessage::new(
+                ChatRole::Other("tool".to_string()),
+                "ok".to_string(),
+            )),
+        ]
+    }
+
+    async fn round_trip<S: ChatHistoryStore>(store: &S) {
+        store.append("a", messages()).await.unwrap();
+        store.append("a", messages()).await.unwrap();
+        store.append("b", messages()).await.unwrap();
+        store
+            .set_metadata(
+                "a",
+                HashMap::from([("prompt".to_string(), "old".to_string())]),
+            )
+            .await
+            .unwrap();
+        store
+            .set_metadata(
+                "a",
+                HashMap::from([
+                    ("prompt".to_string(), "sys".to_string()),
+                    ("user".to_string(), "42".to_string()),
+                ]),
+            )
+            .await
+            .unwrap();
+        store
+            .set_metadata(
+                "c",
+                HashMap::from([("prompt".to_string(), "c".to_string())]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.load_metadata("a").await.unwrap(),
+            HashMap::from([
+                ("prompt".to_string(), "sys".to_string()),
+                ("user".to_string(), "42".to_string()),
+            ])
+        );
+        assert!(store.load_metadata("b").await.unwrap().is_empty());
+
+        let loaded = store.load("a").await.unwrap();
+        assert_eq!(loaded.len(), 4);
+        assert_eq!(loaded[0].message.body(), "Hi!");
+        assert_eq!(
+            loaded[0].metadata.get("user").map(String::as_str),
+            Some("42")
+        );
+        assert!(loaded[0].timestamp > 0);
+        assert_eq!(
+            loaded[3].message.role(),
+            &ChatRole::Other("tool".to_string())
+        );
+
+        let mut sessions = store.sessions().await.unwrap();
+        sessions.sort();
+        assert_eq!(sessions, vec!["a", "b", "c"]);
+        store.clear("a").await.unwrap();
+        store.clear("c").await.unwrap();
+        assert!(store.load("a").await.unwrap().is_empty());
+        assert!(store.load_metadata("a").await.unwrap().is_empty());
+        assert_eq!(store.sessions().await.unwrap(), vec!["b"]);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        round_trip(&InMemoryChatHistoryStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_file_store() {
+        for format in [HistoryFileFormat::Json, HistoryFileFormat::Yaml] {
+            let directory = std::env::temp_dir().join(format!(
+                "ai-chain-history-{}-{:?}",
+                std::process::id(),
+                format
+            ));
+            let store = FileChatHistoryStore::new(&directory).with_format(format);
+            round_trip(&store).await;
+            assert!(matches!(
+                store.load("../a").await,
+                Err(ChatHistoryError::InvalidSessionId(_))
+            ));
+            std::fs::remove_dir_all(directory).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sql_store() {
+        sqlx::any::install_default_drivers();
+        // Every connection to `sqlite::memory:` opens a different database.
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let mut store = SqlChatHistoryStore::new(pool);
+        assert!(!store.question_marks);
+        store.migrate().await.unwrap();
+        round_trip(&store).await;
+
+        // SQLite takes both styles, so the MySQL queries can be run against it too.
+        store.question_marks = true;
+        round_trip(&store).await;
+    }
+
+    #[test]
+    fn test_question_mark_placeholders() {
+        assert_eq!(
+            question_mark_placeholders("VALUES ($1, $2, $10) WHERE price = '$'"),
+            "VALUES (?, ?, ?) WHERE price = '$'"
+        );
+    }
+}
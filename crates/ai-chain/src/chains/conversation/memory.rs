@@ -0,0 +1,678 @@
+//! Memory strategies deciding which part of the conversation is sent with each message.
+//!
+//! A `conversation::Chain` hands every finished exchange to its `Memory` and asks it for the
+//! history to send before the next prompt. System messages are always kept; what happens to the
+//! rest depends on the strategy:
+//! - `BufferWindowMemory` keeps the last few messages.
+//! - `TokenBufferMemory` keeps as many recent messages as fit in a token budget.
+//! - `SummaryBufferMemory` compresses the messages that no longer fit into a running summary.
+//! - `VectorRetrievalMemory` moves old messages to a `VectorStore` and recalls the relevant ones.
+
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::options::Options;
+use crate::prompt::{ChatMessage, ChatMessageCollection, ChatRole, Prompt};
+use crate::tokens::{Tokenizer, TokenizerError};
+use crate::traits::{Embeddings, Executor, ExecutorError, VectorStore};
+
+/// An error raised by a `Memory`.
+#[derive(thiserror::Error, Debug)]
+pub enum MemoryError {
+    #[error("TokenizerError: {0}")]
+    Tokenizer(#[from] TokenizerError),
+    #[error("ExecutorError: {0}")]
+    Executor(#[from] ExecutorError),
+    #[error("the summarizer returned no output")]
+    NoSummary,
+    #[error("VectorStoreError: {0}")]
+    VectorStore(String),
+}
+
+/// Stores the messages of a conversation and picks the history to send with the next prompt.
+#[async_trait]
+pub trait Memory: Send {
+    /// Prepares the memory for `prompt` before `load` is called. Memories that look things up do
+    /// it here.
+    async fn prepare(&mut self, _prompt: &Prompt) -> Result<(), MemoryError> {
+        Ok(())
+    }
+
+    /// Returns the history to send before the next prompt, within `max_tokens` tokens as counted
+    /// by `tokenizer`.
+    fn load<T: Tokenizer>(
+        &self,
+        tokenizer: &T,
+        max_tokens: i32,
+    ) -> Result<ChatMessageCollection<String>, MemoryError>;
+
+    /// Adds messages as they are, for instance to seed the conversation with a system prompt.
+    fn add_messages(&mut self, messages: ChatMessageCollection<String>);
+
+    /// Records a finished exchange: the prompt that was sent and the reply.
+    async fn save(&mut self, messages: ChatMessageCollection<String>) -> Result<(), MemoryError> {
+        self.add_messages(messages);
+        Ok(())
+    }
+
+    /// Forgets everything.
+    fn clear(&mut self);
+}
+
+fn is_system(message: &ChatMessage<String>) -> bool {
+    message.role() == &ChatRole::System
+}
+
+fn count_tokens<T: Tokenizer>(tokenizer: &T, text: &str) -> Result<i32, TokenizerError> {
+    Ok(tokenizer.tokenize_str(text)?.len() as i32)
+}
+
+/// Fits `messages` into `max_tokens`: system messages are always kept and the other messages are
+/// dropped oldest first. `extra`, if any, is also always kept and goes before the first kept
+/// message that isn't a system message.
+fn fit_to_budget<T: Tokenizer>(
+    tokenizer: &T,
+    messages: &ChatMessageCollection<String>,
+    extra: Option<ChatMessage<String>>,
+    max_tokens: i32,
+) -> Result<ChatMessageCollection<String>, MemoryError> {
+    let mut used = 0;
+    for message in messages
+        .iter()
+        .filter(|m| is_system(m))
+        .chain(extra.as_ref())
+    {
+        used += count_tokens(tokenizer, message.body())?;
+    }
+    let mut keep: Vec<bool> = messages.iter().map(is_system).collect();
+    for (i, message) in messages.iter().enumerate().rev() {
+        if is_system(message) {
+            continue;
+        }
+        let tokens = count_tokens(tokenizer, message.body())?;
+        if used + tokens > max_tokens {
+            break;
+        }
+        used += tokens;
+        keep[i] = true;
+    }
+
+    let mut extra = extra;
+    let mut fitted = ChatMessageCollection::new();
+    for (message, keep) in messages.iter().zip(keep) {
+        if !keep {
+            continue;
+        }
+        if !is_system(message) {
+            if let Some(extra) = extra.take() {
+                fitted.add_message(extra);
+            }
+        }
+        fitted.add_message(message.clone());
+    }
+    if let Some(extra) = extra {
+        fitted.add_message(extra);
+    }
+    Ok(fitted)
+}
+
+/// Removes the oldest messages that aren't system messages until at most `keep` of them are left,
+/// and returns them.
+fn evict_oldest(
+    messages: &mut ChatMessageCollection<String>,
+    keep: usize,
+) -> Vec<ChatMessage<String>> {
+    let mut others = messages.iter().filter(|m| !is_system(m)).count();
+    let mut remaining = ChatMessageCollection::new();
+    let mut evicted = Vec::new();
+    for message in messages.iter() {
+        if others > keep && !is_system(message) {
+            evicted.push(message.clone());
+            others -= 1;
+        } else {
+            remaining.add_message(message.clone());
+        }
+    }
+    *messages = remaining;
+    evicted
+}
+
+fn transcript(messages: &[ChatMessage<String>]) -> String {
+    messages
+        .iter()
+        .map(|m| m.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Keeps the system messages and the last `window` other messages.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BufferWindowMemory {
+    messages: ChatMessageCollection<String>,
+    window: usize,
+}
+
+impl BufferWindowMemory {
+    /// Creates a memory keeping the last `window` messages besides the system messages.
+    pub fn new(window: usize) -> Self {
+        Self {
+            messages: ChatMessageCollection::new(),
+            window,
+        }
+    }
+
+    pub fn messages(&self) -> &ChatMessageCollection<String> {
+        &self.messages
+    }
+}
+
+#[async_trait]
+impl Memory for BufferWindowMemory {
+    fn load<T: Tokenizer>(
+        &self,
+        tokenizer: &T,
+        max_tokens: i32,
+    ) -> Result<ChatMessageCollection<String>, MemoryError> {
+        fit_to_budget(tokenizer, &self.messages, None, max_tokens)
+    }
+
+    fn add_messages(&mut self, messages: ChatMessageCollection<String>) {
+        self.messages.append(messages);
+        evict_oldest(&mut self.messages, self.window);
+    }
+
+    fn clear(&mut self) {
+        self.messages = ChatMessageCollection::new();
+    }
+}
+
+/// Keeps the whole conversation and sends as much of it as fits in the context of the model,
+/// dropping the oldest messages first. System messages are always sent.
+///
+/// This is the default memory of a `conversation::Chain`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenBufferMemory {
+    messages: ChatMessageCollection<String>,
+    max_tokens: Option<i32>,
+}
+
+impl TokenBufferMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the history to `max_tokens` tokens, even when the model has room for more.
+    pub fn with_max_tokens(mut self, max_tokens: i32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn messages(&self) -> &ChatMessageCollection<String> {
+        &self.messages
+    }
+}
+
+impl From<ChatMessageCollection<String>> for TokenBufferMemory {
+    fn from(messages: ChatMessageCollection<String>) -> Self {
+        Self {
+            messages,
+            max_tokens: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Memory for TokenBufferMemory {
+    fn load<T: Tokenizer>(
+        &self,
+        tokenizer: &T,
+        max_tokens: i32,
+    ) -> Result<ChatMessageCollection<String>, MemoryError> {
+        let max_tokens = self.max_tokens.map_or(max_tokens, |m| m.min(max_tokens));
+        fit_to_budget(tokenizer, &self.messages, None, max_tokens)
+    }
+
+    fn add_messages(&mut self, messages: ChatMessageCollection<String>) {
+        self.messages.append(messages);
+    }
+
+    fn clear(&mut self) {
+        self.messages = ChatMessageCollection::new();
+    }
+}
+
+const SUMMARY_INSTRUCTIONS: &str = "You are summarizing a conversation. You will be given the \
+current summary and new lines of the conversation. Respond only with a new summary that adds the \
+new lines to the current summary.";
+
+/// Keeps the recent messages within `max_tokens` tokens and folds older messages into a running
+/// summary written by an executor. The summary is sent as a system message. Messages leave the
+/// buffer only once the summary includes them, so a failed summary is retried on the next save.
+pub struct SummaryBufferMemory<E> {
+    exec: E,
+    options: Options,
+    max_tokens: i32,
+    messages: ChatMessageCollection<String>,
+    summary: Option<String>,
+}
+
+impl<E: Executor> SummaryBufferMemory<E> {
+    /// Creates a memory that summarizes with `exec` once the recent messages, counted with the
+    /// tokenizer of `exec`, exceed `max_tokens` tokens.
+    pub fn new(exec: E, max_tokens: i32) -> Self {
+        Self {
+            exec,
+            options: Options::default(),
+            max_tokens,
+            messages: ChatMessageCollection::new(),
+            summary: None,
+        }
+    }
+
+    /// Sets the options used to run the summarizer.
+    pub fn with_options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The summary of the messages that were evicted so far.
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    pub fn messages(&self) -> &ChatMessageCollection<String> {
+        &self.messages
+    }
+
+    /// Splits the messages into those that fit in `max_tokens`, with the system messages, and
+    /// the oldest ones that have to be evicted.
+    fn split_evicted(
+        &self,
+    ) -> Result<(ChatMessageCollection<String>, Vec<ChatMessage<String>>), MemoryError> {
+        let tokenizer = self.exec.get_tokenizer(&self.options)?;
+        let mut used = 0;
+        let mut keep = 0;
+        for message in self.messages.iter().rev().filter(|m| !is_system(m)) {
+            used += count_tokens(&tokenizer, message.body())?;
+            if used > self.max_tokens {
+                break;
+            }
+            keep += 1;
+        }
+        let mut remaining = self.messages.clone();
+        let evicted = evict_oldest(&mut remaining, keep);
+        Ok((remaining, evicted))
+    }
+}
+
+#[async_trait]
+impl<E> Memory for SummaryBufferMemory<E>
+where
+    E: Executor + Send + Sync,
+{
+    fn load<T: Tokenizer>(
+        &self,
+        tokenizer: &T,
+        max_tokens: i32,
+    ) -> Result<ChatMessageCollection<String>, MemoryError> {
+        let summary = self.summary.as_ref().map(|summary| {
+            ChatMessage::system(format!("Summary of the earlier conversation:\n{}", summary))
+        });
+        fit_to_budget(tokenizer, &self.messages, summary, max_tokens)
+    }
+
+    fn add_messages(&mut self, messages: ChatMessageCollection<String>) {
+        self.messages.append(messages);
+    }
+
+    async fn save(&mut self, messages: ChatMessageCollection<String>) -> Result<(), MemoryError> {
+        self.messages.append(messages);
+        let (remaining, evicted) = self.split_evicted()?;
+        if evicted.is_empty() {
+            return Ok(());
+        }
+        let request = format!(
+            "Current summary:\n{}\n\nNew lines of conversation:\n{}\n\nNew summary:",
+            self.summary.as_deref().unwrap_or(""),
+            transcript(&evicted)
+        );
+        let prompt = Prompt::Chat(
+            ChatMessageCollection::new()
+                .with_system(SUMMARY_INSTRUCTIONS.to_string())
+                .with_user(request),
+        );
+        let summary = self
+            .exec
+            .execute(&self.options, &prompt)
+            .await?
+            .to_immediate()
+            .await?
+            .primary_textual_output()
+            .ok_or(MemoryError::NoSummary)?;
+        // The evicted messages stay in the buffer until they are part of the summary.
+        self.summary = Some(summary.trim().to_string());
+        self.messages = remaining;
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.messages = ChatMessageCollection::new();
+        self.summary = None;
+    }
+}
+
+/// Keeps the last `window` messages and moves older ones to a `VectorStore`. Before each prompt,
+/// the `limit` stored passages most similar to it are recalled and sent as a system message.
+/// Messages leave the window only once the store holds them.
+pub struct VectorRetrievalMemory<E, V> {
+    store: V,
+    window: usize,
+    limit: u32,
+    messages: ChatMessageCollection<String>,
+    recalled: Vec<String>,
+    _embeddings: PhantomData<fn() -> E>,
+}
+
+impl<E, V> VectorRetrievalMemory<E, V>
+where
+    E: Embeddings,
+    V: VectorStore<E>,
+{
+    /// Creates a memory keeping `window` recent messages and recalling up to `limit` passages.
+    pub fn new(store: V, window: usize, limit: u32) -> Self {
+        Self {
+            store,
+            window,
+            limit,
+            messages: ChatMessageCollection::new(),
+            recalled: Vec::new(),
+            _embeddings: PhantomData,
+        }
+    }
+
+    pub fn messages(&self) -> &ChatMessageCollection<String> {
+        &self.messages
+    }
+
+    /// The passages recalled for the last prompt.
+    pub fn recalled(&self) -> &[String] {
+        &self.recalled
+    }
+}
+
+#[async_trait]
+impl<E, V> Memory for VectorRetrievalMemory<E, V>
+where
+    E: Embeddings,
+    V: VectorStore<E> + Send + Sync,
+{
+    async fn prepare(&mut self, prompt: &Prompt) -> Result<(), MemoryError> {
+        let documents = self
+            .store
+            .similarity_search(prompt.to_text(), self.limit)
+            .await
+            .map_err(|e| MemoryError::VectorStore(e.to_string()))?;
+        self.recalled = documents.into_iter().map(|d| d.page_content).collect();
+        Ok(())
+    }
+
+    fn load<T: Tokenizer>(
+        &self,
+        tokenizer: &T,
+        max_tokens: i32,
+    ) -> Result<ChatMessageCollection<String>, MemoryError> {
+        let recalled = (!self.recalled.is_empty()).then(|| {
+            ChatMessage::system(format!(
+                "Relevant parts of the earlier conversation:\n\n{}",
+                self.recalled.join("\n\n")
+            ))
+        });
+        fit_to_budget(tokenizer, &self.messages, recalled, max_tokens)
+    }
+
+    fn add_messages(&mut self, messages: ChatMessageCollection<String>) {
+        self.messages.append(messages);
+    }
+
+    async fn save(&mut self, messages: ChatMessageCollection<String>) -> Result<(), MemoryError> {
+        self.messages.append(messages);
+        let mut remaining = self.messages.clone();
+        let evicted = evict_oldest(&mut remaining, self.window);
+        if !evicted.is_empty() {
+            self.store
+                .add_texts(vec![transcript(&evicted)])
+                .await
+                .map_err(|e| MemoryError::VectorStore(e.to_string()))?;
+        }
+        // The evicted messages stay in the buffer until the store holds them.
+        self.messages = remaining;
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.messages = ChatMessageCollection::new();
+        self.recalled.clear();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::chains::tests::{ByteTokenizer, NoEmbeddings, StoreError, WordStore};
+    use crate::output::Output;
+    use crate::schema::Document;
+    use crate::tokens::{PromptTokensError, TokenCount};
+    use crate::traits::ExecutorCreationError;
+
+    /// Replies with the number of messages in the prompt.
+    pub(crate) struct CountingExecutor;
+
+    #[async_trait]
+    impl Executor for CountingExecutor {
+        type StepTokenizer<'a> = ByteTokenizer;
+
+        fn new_with_options(_options: Options) -> Result<Self, ExecutorCreationError> {
+            Ok(Self)
+        }
+
+        async fn execute(
+            &self,
+            _options: &Options,
+            prompt: &Prompt,
+        ) -> Result<Output, ExecutorError> {
+            Ok(Output::new_immediate(Prompt::Chat(
+                ChatMessageCollection::new()
+                    .with_assistant(format!("summary of {} messages", prompt.to_chat().len())),
+            )))
+        }
+
+        fn tokens_used(
+            &self,
+            _options: &Options,
+            _prompt: &Prompt,
+        ) -> Result<TokenCount, PromptTokensError> {
+            Ok(TokenCount::new(100, 0))
+        }
+
+        fn max_tokens_allowed(&self, _options: &Options) -> i32 {
+            100
+        }
+
+        fn answer_prefix(&self, _prompt: &Prompt) -> Option<String> {
+            None
+        }
+
+        fn get_tokenizer(&self, _options: &Options) -> Result<ByteTokenizer, TokenizerError> {
+            Ok(ByteTokenizer)
+        }
+    }
+
+    /// Fails every request, like a summarizer whose server is down.
+    struct FailingExecutor;
+
+    #[async_trait]
+    impl Executor for FailingExecutor {
+        type StepTokenizer<'a> = ByteTokenizer;
+
+        fn new_with_options(_options: Options) -> Result<Self, ExecutorCreationError> {
+            Ok(Self)
+        }
+
+        async fn execute(
+            &self,
+            _options: &Options,
+            _prompt: &Prompt,
+        ) -> Result<Output, ExecutorError> {
+            Err(ExecutorError::InnerError("server is down".into()))
+        }
+
+        fn tokens_used(
+            &self,
+            _options: &Options,
+            _prompt: &Prompt,
+        ) -> Result<TokenCount, PromptTokensError> {
+            Ok(TokenCount::new(100, 0))
+        }
+
+        fn max_tokens_allowed(&self, _options: &Options) -> i32 {
+            100
+        }
+
+        fn answer_prefix(&self, _prompt: &Prompt) -> Option<String> {
+            None
+        }
+
+        fn get_tokenizer(&self, _options: &Options) -> Result<ByteTokenizer, TokenizerError> {
+            Ok(ByteTokenizer)
+        }
+    }
+
+    /// Fails every call, like a vector store whose server is down.
+    struct FailingStore;
+
+    #[async_trait]
+    impl VectorStore<NoEmbeddings> for FailingStore {
+        type Error = StoreError;
+
+        async fn add_texts(&self, _texts: Vec<String>) -> Result<Vec<String>, StoreError> {
+            Err(StoreError)
+        }
+
+        async fn add_documents(
+            &self,
+            _documents: Vec<Document>,
+        ) -> Result<Vec<String>, StoreError> {
+            Err(StoreError)
+        }
+
+        async fn similarity_search(
+            &self,
+            _query: String,
+            _limit: u32,
+        ) -> Result<Vec<Document>, StoreError> {
+            Err(StoreError)
+        }
+    }
+
+    fn exchange(user: &str, assistant: &str) -> ChatMessageCollection<String> {
+        ChatMessageCollection::new()
+            .with_user(user.to_string())
+            .with_assistant(assistant.to_string())
+    }
+
+    fn bodies(messages: &ChatMessageCollection<String>) -> Vec<&str> {
+        messages.iter().map(|m| m.body().as_str()).collect()
+    }
+
+    #[test]
+    fn test_token_budget_keeps_system_messages_and_drops_oldest() {
+        let mut memory = TokenBufferMemory::from(
+            ChatMessageCollection::new().with_system("be very brief".to_string()),
+        );
+        memory.add_messages(exchange("one two", "three four"));
+        memory.add_messages(exchange("five six", "seven"));
+        let history = memory.load(&ByteTokenizer, 30).unwrap();
+        assert_eq!(bodies(&history), vec!["be very brief", "five six", "seven"]);
+
+        let memory = memory.with_max_tokens(15);
+        let history = memory.load(&ByteTokenizer, 100).unwrap();
+        assert_eq!(bodies(&history), vec!["be very brief"]);
+    }
+
+    #[test]
+    fn test_window_keeps_last_messages() {
+        let mut memory = BufferWindowMemory::new(2);
+        memory.add_messages(ChatMessageCollection::new().with_system("sys".to_string()));
+        memory.add_messages(exchange("a", "b"));
+        memory.add_messages(exchange("c", "d"));
+        assert_eq!(bodies(memory.messages()), vec!["sys", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_summary_buffer_summarizes_evicted_messages() {
+        let mut memory = SummaryBufferMemory::new(CountingExecutor, 6);
+        memory.add_messages(ChatMessageCollection::new().with_system("sys".to_string()));
+        memory.save(exchange("a b", "c d")).await.unwrap();
+        assert_eq!(memory.summary(), None);
+        memory.save(exchange("e f", "g")).await.unwrap();
+        assert_eq!(memory.summary(), Some("summary of 2 messages"));
+        assert_eq!(bodies(memory.messages()), vec!["sys", "e f", "g"]);
+
+        let history = memory.load(&ByteTokenizer, 100).unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history.get_message(1).unwrap().role(), &ChatRole::System);
+        assert!(history
+            .get_message(1)
+            .unwrap()
+            .body()
+            .ends_with("summary of 2 messages"));
+    }
+
+    #[tokio::test]
+    async fn test_summary_buffer_keeps_messages_when_the_summarizer_fails() {
+        let mut memory = SummaryBufferMemory::new(FailingExecutor, 6);
+        memory.save(exchange("a b", "c d")).await.unwrap();
+        assert!(memory.save(exchange("e f", "g")).await.is_err());
+        assert_eq!(memory.summary(), None);
+        assert_eq!(bodies(memory.messages()), vec!["a b", "c d", "e f", "g"]);
+    }
+
+    #[tokio::test]
+    async fn test_vector_retrieval_recalls_evicted_messages() {
+        let mut memory = VectorRetrievalMemory::new(WordStore::default(), 2, 3);
+        memory
+            .save(exchange("my cat is Tom", "nice cat"))
+            .await
+            .unwrap();
+        memory.save(exchange("weather?", "sunny")).await.unwrap();
+        assert_eq!(bodies(memory.messages()), vec!["weather?", "sunny"]);
+
+        memory
+            .prepare(&Prompt::text("what is my cat called".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            memory.recalled(),
+            &["User: my cat is Tom\nAssistant: nice cat".to_string()]
+        );
+        let history = memory.load(&ByteTokenizer, 100).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get_message(0).unwrap().role(), &ChatRole::System);
+    }
+
+    #[tokio::test]
+    async fn test_vector_retrieval_keeps_messages_when_the_store_fails() {
+        let mut memory = VectorRetrievalMemory::new(FailingStore, 2, 3);
+        memory
+            .save(exchange("my cat is Tom", "nice cat"))
+            .await
+            .unwrap();
+        assert!(memory.save(exchange("weather?", "sunny")).await.is_err());
+        assert_eq!(
+            bodies(memory.messages()),
+            vec!["my cat is Tom", "nice cat", "weather?", "sunny"]
+        );
+    }
+}
@@ -0,0 +1,530 @@
+//! The `Chain` module models a conversation between an entity and an LLM.
+//! It manages the conversation state and provides methods for sending messages and receiving responses.
+//!
+//! It relies on the `traits::Executor` trait to execute prompts and handle LLM interactions, and
+//! on a [`Memory`] to decide which part of the conversation is sent with each message.
+
+pub mod history;
+pub mod memory;
+mod stream;
+
+pub use history::{
+    ChatHistoryError, ChatHistoryStore, FileChatHistoryStore, HistoryFileFormat,
+    InMemoryChatHistoryStore, SqlChatHistoryStore, StoredMessage,
+};
+pub use memory::{
+    BufferWindowMemory, Memory, MemoryError, SummaryBufferMemory, TokenBufferMemory,
+    VectorRetrievalMemory,
+};
+pub use stream::ChainStream;
+
+use crate::options::Options;
+use crate::output::Output;
+use crate::prompt::{
+    ChatMessageCollection, ChatRole, Data, Prompt, PromptTemplate, StringTemplateError,
+};
+use crate::step::Step;
+use crate::tokens::{PromptTokensError, TokenizerError};
+use crate::traits::{Executor, ExecutorError};
+use crate::{parameters, Parameters};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// `Chain` represents a conversation between an entity and an LLM.
+///
+/// It holds the conversation state in a [`Memory`] and provides methods for sending messages and
+/// receiving responses. By default the whole conversation is kept and as much of it as fits in the
+/// context of the model is sent, see [`TokenBufferMemory`].
+///
+/// A chain can also belong to a session of a [`ChatHistoryStore`]: `load_session` picks up a stored
+/// conversation and `persist` appends the messages exchanged since to the store. The system prompt
+/// is kept in the metadata of the session.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Chain<M = TokenBufferMemory> {
+    memory: M,
+    #[serde(default)]
+    session_id: Option<String>,
+    #[serde(default)]
+    system_prompt: Option<String>,
+    /// Whether the system prompt is in the metadata of the session.
+    #[serde(default)]
+    system_prompt_saved: bool,
+    /// The messages that haven't been persisted yet.
+    #[serde(default)]
+    unsaved: Vec<StoredMessage>,
+}
+
+impl Chain {
+    /// Constructs a new `Chain` with the given conversation state.
+    /// Self,
+    /// # Arguments
+    /// * `state` - The initial prompt state to use.
+    pub fn new(state: PromptTemplate) -> Result<Chain, StringTemplateError> {
+        state
+            .format(&parameters!())
+            .map(|state| state.to_chat())
+            .map(|state| Self::with_memory(state.into()))
+    }
+
+    /// Constructs a new `Chain` with the given conversation state by passing a ChatMessageCollection<String> (clone).
+    /// Self,
+    /// # Arguments
+    /// * `state` - The initial prompt state to use.
+    pub fn new_with_message_collection(state: &ChatMessageCollection<String>) -> Chain {
+        let system: Vec<&str> = state
+            .iter()
+            .filter(|message| message.role() == &ChatRole::System)
+            .map(|message| message.body().as_str())
+            .collect();
+        let mut chain = Self::with_memory(state.clone().into());
+        chain.system_prompt = (!system.is_empty()).then(|| system.join("\n\n"));
+        chain
+    }
+}
+
+/// The key of the system prompt in the metadata of a session.
+pub const SYSTEM_PROMPT_KEY: &str = "system_prompt";
+
+impl<M: Memory> Chain<M> {
+    /// Constructs a new `Chain` keeping the conversation in `memory`.
+    ///
+    /// Set the system prompt with `with_system_prompt`, or seed the conversation through
+    /// `Memory::add_messages`.
+    pub fn with_memory(memory: M) -> Self {
+        Self {
+            memory,
+            session_id: None,
+            system_prompt: None,
+            system_prompt_saved: false,
+            unsaved: Vec::new(),
+        }
+    }
+
+    /// Adds `prompt` to the memory as a system message. It is persisted with the session metadata,
+    /// under [`SYSTEM_PROMPT_KEY`].
+    pub fn with_system_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        let prompt = prompt.into();
+        self.memory
+            .add_messages(ChatMessageCollection::new().with_system(prompt.clone()));
+        self.system_prompt = Some(prompt);
+        self.system_prompt_saved = false;
+        self
+    }
+
+    /// Loads the conversation stored under `session_id` into `memory`, starting with the system
+    /// prompt of the session. Sessions that don't exist yet start empty.
+    pub async fn load_session<S: ChatHistoryStore>(
+        store: &S,
+        session_id: &str,
+        mut memory: M,
+    ) -> Result<Self, Error> {
+        let system_prompt = store
+            .load_metadata(session_id)
+            .await?
+            .remove(SYSTEM_PROMPT_KEY);
+        let messages = store.load(session_id).await?;
+        if let Some(prompt) = &system_prompt {
+            memory.add_messages(ChatMessageCollection::new().with_system(prompt.clone()));
+        }
+        memory.add_messages(ChatMessageCollection::for_vector(
+            messages.into_iter().map(|stored| stored.message).collect(),
+        ));
+        let mut chain = Self::with_memory(memory).with_session_id(session_id);
+        chain.system_prompt_saved = system_prompt.is_some();
+        chain.system_prompt = system_prompt;
+        Ok(chain)
+    }
+
+    /// Makes the chain part of the session `session_id`. Messages already in the memory are not
+    /// persisted, but the system prompt is.
+    pub fn with_session_id<S: Into<String>>(mut self, session_id: S) -> Self {
+        self.session_id = Some(session_id.into());
+        self.system_prompt_saved = false;
+        self
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_deref()
+    }
+
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.system_prompt.as_deref()
+    }
+
+    /// Appends the messages exchanged since the last call to the session in `store`.
+    pub async fn persist<S: ChatHistoryStore>(&mut self, store: &S) -> Result<(), Error> {
+        self.persist_with_metadata(store, HashMap::new()).await
+    }
+
+    /// Appends the messages exchanged since the last call to the session in `store`, adding
+    /// `metadata` to each of them. The system prompt is saved with the session metadata the first
+    /// time.
+    pub async fn persist_with_metadata<S: ChatHistoryStore>(
+        &mut self,
+        store: &S,
+        metadata: HashMap<String, String>,
+    ) -> Result<(), Error> {
+        let session_id = self.session_id.as_deref().ok_or(Error::NoSession)?;
+        if let (Some(prompt), false) = (&self.system_prompt, self.system_prompt_saved) {
+            store
+                .set_metadata(
+                    session_id,
+                    HashMap::from([(SYSTEM_PROMPT_KEY.to_string(), prompt.clone())]),
+                )
+                .await?;
+            self.system_prompt_saved = true;
+        }
+        let mut messages = std::mem::take(&mut self.unsaved);
+        for message in &mut messages {
+            message.metadata.extend(metadata.clone());
+        }
+        if let Err(e) = store.append(session_id, messages.clone()).await {
+            self.unsaved = messages;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut M {
+        &mut self.memory
+    }
+
+    /// Sends a message to the LLM and returns the response.
+    ///
+    /// This method sends a message to the LLM, adding it and the response to the internal state.
+    ///
+    /// # Arguments
+    /// * `step` - The step to send.
+    /// * `parameters` - The parameters to use when formatting the step.
+    /// * `exec` - The executor to use.
+    ///
+    /// # Returns
+    /// A `Result` containing the LLM's response as `E::Output` on success or an `Error` variant on failure.
+    pub async fn send_message<E: Executor>(
+        &mut self,
+        step: Step,
+        parameters: &Parameters,
+        exec: &E,
+    ) -> Result<Output, Error> {
+        let fmt = step.format(parameters)?;
+        self.send_message_raw(step.options(), &fmt, exec).await
+    }
+
+    /// Sends a message to the LLM and returns the response.
+    ///
+    /// This method takes a ready prompt and options and sends it to the LLM, adding it and the response to the internal state.
+    ///
+    /// # Arguments
+    /// * `options` - The options to use when executing the prompt.
+    /// * `prompt` - The prompt to send.
+    /// * `exec` - The executor to use.
+    ///
+    /// # Returns
+    /// A `Result` containing the LLM's response as `E::Output` on success or an `Error` variant on failure.
+    pub async fn send_message_raw<E: Executor>(
+        &mut self,
+        options: &Options,
+        prompt: &Prompt,
+        exec: &E,
+    ) -> Result<Output, Error> {
+        let prompt_with_history = self.prompt_with_history(options, prompt, exec).await?;
+
+        // Execute the prompt and retrieve the LLM's response.
+        let res = exec.execute(options, &prompt_with_history).await?;
+        let content = reply_to_chat(res.to_immediate().await?.as_content());
+
+        let mut exchange = prompt.to_chat();
+        exchange.append(content.clone());
+        let save = self.record(exchange);
+        save.await?;
+
+        Ok(Output::new_immediate(content.into()))
+    }
+
+    /// Sends a message to the LLM and streams the response.
+    ///
+    /// The returned stream forwards the segments produced by the executor; enable streaming with
+    /// `Opt::Stream(true)`, otherwise the whole response arrives at once. When the stream ends, the
+    /// message and the response are added to the internal state. If the executor fails, or the
+    /// stream is dropped before its end, the state is left as it was.
+    ///
+    /// # Arguments
+    /// * `options` - The options to use when executing the prompt.
+    /// * `prompt` - The prompt to send.
+    /// * `exec` - The executor to use.
+    pub async fn send_message_stream<E: Executor>(
+        &mut self,
+        options: &Options,
+        prompt: &Prompt,
+        exec: &E,
+    ) -> Result<ChainStream<'_, M>, Error> {
+        let prompt_with_history = self.prompt_with_history(options, prompt, exec).await?;
+        let res = exec.execute(options, &prompt_with_history).await?;
+        Ok(ChainStream::new(self, prompt.to_chat(), res))
+    }
+
+    /// Prepares the memory for `prompt` and combines the history it returns with `prompt`.
+    async fn prompt_with_history<E: Executor>(
+        &mut self,
+        options: &Options,
+        prompt: &Prompt,
+        exec: &E,
+    ) -> Result<Prompt, Error> {
+        let tok = exec.tokens_used(options, prompt)?;
+        let tokens_remaining = tok.tokens_remaining();
+        self.memory.prepare(prompt).await?;
+        let history = {
+            let tokenizer = exec.get_tokenizer(options)?;
+            self.memory.load(&tokenizer, tokens_remaining)?
+        };
+        Ok(Prompt::Chat(history).combine(prompt))
+    }
+
+    /// Hands a finished exchange to the memory and, once it is saved, marks it for persistence.
+    fn record(
+        &mut self,
+        exchange: ChatMessageCollection<String>,
+    ) -> BoxFuture<'_, Result<(), MemoryError>> {
+        let stored: Option<Vec<StoredMessage>> = self
+            .session_id
+            .is_some()
+            .then(|| exchange.iter().cloned().map(StoredMessage::new).collect());
+        Box::pin(async move {
+            self.memory.save(exchange).await?;
+            // Only exchanges the memory accepted are persisted.
+            self.unsaved.extend(stored.into_iter().flatten());
+            Ok(())
+        })
+    }
+}
+
+/// The reply of the LLM as chat messages. Replies without roles are assistant messages.
+fn reply_to_chat(reply: Data<String>) -> ChatMessageCollection<String> {
+    match reply {
+        Data::Chat(chat) => chat,
+        Data::Text(text) => ChatMessageCollection::new().with_assistant(text),
+    }
+}
+
+/// An error type representing various errors that can occur while interacting with the `Chain`.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("PromptTokensError: {0}")]
+    PromptTokens(#[from] PromptTokensError),
+    #[error("TokenizerError: {0}")]
+    Tokenizer(#[from] TokenizerError),
+    #[error("ExecutorError: {0}")]
+    Executor(#[from] ExecutorError),
+    #[error("No model output")]
+    NoModelOutput,
+    #[error("StringTemplateError: {0}")]
+    StringTemplate(#[from] crate::prompt::StringTemplateError),
+    #[error("MemoryError: {0}")]
+    Memory(#[from] MemoryError),
+    #[error("ChatHistoryError: {0}")]
+    ChatHistory(#[from] ChatHistoryError),
+    #[error("the chain is not part of a session")]
+    NoSession,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::memory::tests::CountingExecutor;
+    use super::*;
+    use crate::chains::tests::ByteTokenizer;
+    use crate::output::StreamSegment;
+    use crate::tokens::{PromptTokensError, TokenCount, Tokenizer};
+    use crate::traits::ExecutorCreationError;
+    use async_trait::async_trait;
+    use futures::StreamExt;
+
+    /// Streams "Hel", "lo", then fails if `fail` is set.
+    struct StreamingExecutor {
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Executor for StreamingExecutor {
+        type StepTokenizer<'a> = ByteTokenizer;
+
+        fn new_with_options(_options: Options) -> Result<Self, ExecutorCreationError> {
+            Ok(Self { fail: false })
+        }
+
+        async fn execute(
+            &self,
+            _options: &Options,
+            _prompt: &Prompt,
+        ) -> Result<Output, ExecutorError> {
+            let (sender, output) = Output::new_stream();
+            sender
+                .send(StreamSegment::Role(ChatRole::Assistant))
+                .unwrap();
+            sender
+                .send(StreamSegment::Content("Hel".to_string()))
+                .unwrap();
+            sender
+                .send(StreamSegment::Content("lo".to_string()))
+                .unwrap();
+            if self.fail {
+                sender
+                    .send(StreamSegment::Err(ExecutorError::InnerError(
+                        "connection reset".into(),
+                    )))
+                    .unwrap();
+            }
+            Ok(output)
+        }
+
+        fn tokens_used(
+            &self,
+            _options: &Options,
+            _prompt: &Prompt,
+        ) -> Result<TokenCount, PromptTokensError> {
+            Ok(TokenCount::new(100, 0))
+        }
+
+        fn max_tokens_allowed(&self, _options: &Options) -> i32 {
+            100
+        }
+
+        fn answer_prefix(&self, _prompt: &Prompt) -> Option<String> {
+            None
+        }
+
+        fn get_tokenizer(&self, _options: &Options) -> Result<ByteTokenizer, TokenizerError> {
+            Ok(ByteTokenizer)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streamed_replies_are_added_when_the_stream_ends() {
+        let mut chain = Chain::new_with_message_collection(&ChatMessageCollection::new());
+        let prompt = Prompt::Chat(ChatMessageCollection::new().with_user("hi".to_string()));
+
+        let mut stream = chain
+            .send_message_stream(Options::empty(), &prompt, &StreamingExecutor { fail: true })
+            .await
+            .unwrap();
+        let mut contents = Vec::new();
+        while let Some(segment) = stream.next().await {
+            match segment {
+                StreamSegment::Content(text) => contents.push(text),
+                StreamSegment::Err(_) => contents.push("<err>".to_string()),
+                StreamSegment::Role(_) => {}
+            }
+        }
+        assert_eq!(contents, vec!["Hel", "lo", "<err>"]);
+        drop(stream);
+        assert!(chain.memory().messages().is_empty());
+
+        let stream = chain
+            .send_message_stream(
+                Options::empty(),
+                &prompt,
+                &StreamingExecutor { fail: false },
+            )
+            .await
+            .unwrap();
+        assert_eq!(stream.count().await, 3);
+        let messages = chain.memory().messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages.get_message(1).unwrap().role(),
+            &ChatRole::Assistant
+        );
+        assert_eq!(messages.get_message(1).unwrap().body(), "Hello");
+    }
+
+    /// A memory that refuses every exchange.
+    struct FailingMemory;
+
+    #[async_trait]
+    impl Memory for FailingMemory {
+        fn load<T: Tokenizer>(
+            &self,
+            _tokenizer: &T,
+            _max_tokens: i32,
+        ) -> Result<ChatMessageCollection<String>, MemoryError> {
+            Ok(ChatMessageCollection::new())
+        }
+
+        fn add_messages(&mut self, _messages: ChatMessageCollection<String>) {}
+
+        async fn save(
+            &mut self,
+            _messages: ChatMessageCollection<String>,
+        ) -> Result<(), MemoryError> {
+            Err(MemoryError::NoSummary)
+        }
+
+        fn clear(&mut self) {}
+    }
+
+    #[tokio::test]
+    async fn test_exchanges_the_memory_rejects_are_not_persisted() {
+        let store = InMemoryChatHistoryStore::new();
+        let mut chain = Chain::with_memory(FailingMemory).with_session_id("s1");
+        let prompt = Prompt::Chat(ChatMessageCollection::new().with_user("hi".to_string()));
+        assert!(chain
+            .send_message_raw(Options::empty(), &prompt, &CountingExecutor)
+            .await
+            .is_err());
+        chain.persist(&store).await.unwrap();
+        assert!(store.load("s1").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chain_persists_and_loads_sessions() {
+        let store = InMemoryChatHistoryStore::new();
+        let mut chain = Chain::new_with_message_collection(
+            &ChatMessageCollection::new().with_system("sys".to_string()),
+        )
+        .with_session_id("s1");
+        let prompt = Prompt::Chat(ChatMessageCollection::new().with_user("hi".to_string()));
+        chain
+            .send_message_raw(Options::empty(), &prompt, &CountingExecutor)
+            .await
+            .unwrap();
+        chain
+            .persist_with_metadata(
+                &store,
+                HashMap::from([("user".to_string(), "7".to_string())]),
+            )
+            .await
+            .unwrap();
+
+        let stored = store.load("s1").await.unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[1].message.role(), &ChatRole::Assistant);
+        assert_eq!(stored[1].message.body(), "summary of 2 messages");
+        assert_eq!(stored[0].metadata["user"], "7");
+
+        assert_eq!(
+            store.load_metadata("s1").await.unwrap()[SYSTEM_PROMPT_KEY],
+            "sys"
+        );
+
+        let mut chain = Chain::load_session(&store, "s1", TokenBufferMemory::new())
+            .await
+            .unwrap();
+        assert_eq!(chain.system_prompt(), Some("sys"));
+        let messages = chain.memory().messages();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages.get_message(0).unwrap().role(), &ChatRole::System);
+        assert_eq!(messages.get_message(0).unwrap().body(), "sys");
+        chain
+            .send_message_raw(Options::empty(), &prompt, &CountingExecutor)
+            .await
+            .unwrap();
+        chain.persist(&store).await.unwrap();
+        let stored = store.load("s1").await.unwrap();
+        assert_eq!(stored.len(), 4);
+        assert_eq!(stored[3].message.body(), "summary of 4 messages");
+    }
+}
@@ -0,0 +1,117 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream};
+use futures::{FutureExt, Stream, StreamExt};
+
+use super::{reply_to_chat, Chain, Memory, MemoryError};
+use crate::output::{Output, SegmentCollector, StreamSegment};
+use crate::prompt::{ChatMessageCollection, Data};
+use crate::traits::ExecutorError;
+
+enum State<'a, M> {
+    Streaming {
+        chain: &'a mut Chain<M>,
+        segments: BoxStream<'static, StreamSegment>,
+    },
+    Saving(BoxFuture<'a, Result<(), MemoryError>>),
+    Done,
+}
+
+/// The response to `Chain::send_message_stream`.
+///
+/// It yields the segments of the response as the executor produces them. Once the executor is
+/// done, the exchange is added to the chain before the stream ends; an error while doing so is
+/// yielded as a last `StreamSegment::Err`. Nothing is added when the executor yields an error or
+/// when the stream is dropped early.
+pub struct ChainStream<'a, M> {
+    state: State<'a, M>,
+    prompt: ChatMessageCollection<String>,
+    collector: SegmentCollector,
+}
+
+impl<'a, M: Memory> ChainStream<'a, M> {
+    pub(super) fn new(
+        chain: &'a mut Chain<M>,
+        prompt: ChatMessageCollection<String>,
+        output: Output,
+    ) -> Self {
+        let segments = match output {
+            Output::Stream(stream) => stream.boxed(),
+            Output::Immediate(immediate) => {
+                stream::iter(segments_of(immediate.as_content())).boxed()
+            }
+        };
+        Self {
+            state: State::Streaming { chain, segments },
+            prompt,
+            collector: SegmentCollector::default(),
+        }
+    }
+}
+
+/// Splits immediate data into the segments a stream would have produced.
+fn segments_of(data: Data<String>) -> Vec<StreamSegment> {
+    match data {
+        Data::Text(text) => vec![StreamSegment::Content(text)],
+        Data::Chat(chat) => chat
+            .iter()
+            .flat_map(|message| {
+                [
+                    StreamSegment::Role(message.role().clone()),
+                    StreamSegment::Content(message.body().clone()),
+                ]
+            })
+            .collect(),
+    }
+}
+
+impl<M: Memory> Stream for ChainStream<'_, M> {
+    type Item = StreamSegment;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                State::Streaming { segments, .. } => match segments.poll_next_unpin(cx) {
+                    Poll::Ready(Some(segment)) => {
+                        let copy = match &segment {
+                            StreamSegment::Role(role) => StreamSegment::Role(role.clone()),
+                            StreamSegment::Content(text) => StreamSegment::Content(text.clone()),
+                            StreamSegment::Err(_) => {
+                                this.state = State::Done;
+                                return Poll::Ready(Some(segment));
+                            }
+                        };
+                        // Role and content segments never fail.
+                        let _ = this.collector.push(copy);
+                        return Poll::Ready(Some(segment));
+                    }
+                    Poll::Ready(None) => {
+                        let State::Streaming { chain, .. } =
+                            std::mem::replace(&mut this.state, State::Done)
+                        else {
+                            unreachable!()
+                        };
+                        let collector = std::mem::take(&mut this.collector);
+                        let mut exchange = std::mem::take(&mut this.prompt);
+                        exchange.append(reply_to_chat(collector.finish()));
+                        this.state = State::Saving(chain.record(exchange));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                State::Saving(save) => {
+                    let result = futures::ready!(save.poll_unpin(cx));
+                    this.state = State::Done;
+                    if let Err(e) = result {
+                        return Poll::Ready(Some(StreamSegment::Err(ExecutorError::InnerError(
+                            e.into(),
+                        ))));
+                    }
+                }
+                State::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
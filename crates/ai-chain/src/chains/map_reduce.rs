@@ -191,23 +191,36 @@ impl Chain {
     where
         E: Executor + 'a,
     {
-        let data: Result<Vec<_>, _> = v
-            .iter()
-            .map(|x| {
-                <E as tokens::ExecutorTokenCountExt>::split_to_fit(
-                    executor,
-                    step,
-                    x,
-                    &base_parameters,
-                    None,
-                )
-            })
-            .collect();
-        let data = data?.iter().flatten().cloned().collect();
-        Ok(data)
+        chunk_documents(&v, &base_parameters, executor, step)
     }
 }
 
+/// Splits the text of each document into chunks that fit in the context window of `step`.
+///
+/// The chunks are returned in the order of the documents. Chains that run a step per chunk, such
+/// as the refine and map-rerank chains, share this with the map-reduce chain.
+pub(crate) fn chunk_documents<E: Executor>(
+    documents: &[Parameters],
+    base_parameters: &Parameters,
+    executor: &E,
+    step: &Step,
+) -> Result<Vec<Parameters>, PromptTokensError> {
+    let data: Result<Vec<_>, _> = documents
+        .iter()
+        .map(|x| {
+            <E as tokens::ExecutorTokenCountExt>::split_to_fit(
+                executor,
+                step,
+                x,
+                base_parameters,
+                None,
+            )
+        })
+        .collect();
+    let data = data?.iter().flatten().cloned().collect();
+    Ok(data)
+}
+
 /// Implements the `StorableEntity` trait for the `Chain` struct.
 ///
 /// This implementation provides a method for extracting metadata from a `Chain` instance, in order to identify it
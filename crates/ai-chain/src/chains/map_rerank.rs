@@ -0,0 +1,221 @@
+//! The `map_rerank` module contains the `Chain` struct, which represents a map-rerank chain.
+//!
+//! A map-rerank chain runs its `map` step on every chunk of the input documents. The step answers
+//! from its chunk and rates how well the chunk answers the question, and the chain returns the
+//! answer with the highest score. The step output is parsed with [`parse_scored_answer`], so the
+//! prompt should ask for an answer followed by a `Score:` line.
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::map_reduce::chunk_documents;
+use crate::frame::{FormatAndExecuteError, Frame};
+use crate::serialization::StorableEntity;
+use crate::step::Step;
+use crate::traits::Executor;
+use crate::{prompt, Parameters};
+
+/// The `MapRerankChainError` enum represents errors that can occur when executing a map-rerank chain.
+#[derive(Error, Debug)]
+pub enum MapRerankChainError {
+    /// An error relating to the operation of the Executor.
+    #[error("FormatAndExecuteError: {0}")]
+    FormatAndExecuteError(#[from] FormatAndExecuteError),
+    /// An error relating to tokenizing the inputs.
+    #[error("TokenizeError: {0}")]
+    TokenizeError(#[from] crate::tokens::PromptTokensError),
+    #[error("The vector of input documents was empty")]
+    InputEmpty,
+    #[error("None of the answers had a score")]
+    NoScoredAnswer,
+}
+
+/// The answer for a single chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredAnswer {
+    /// The answer, without the score.
+    pub answer: String,
+    /// The score, or `None` if the output of the step had no score.
+    pub score: Option<f32>,
+}
+
+/// The result of a map-rerank chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapRerankOutput {
+    /// The answer with the highest score.
+    pub answer: String,
+    /// The score of `answer`.
+    pub score: f32,
+    /// The answer for each chunk, in the order of the chunks.
+    pub intermediate_steps: Vec<ScoredAnswer>,
+}
+
+/// Splits the output of a step into an answer and a score.
+///
+/// The score is read from the last line that starts with `Score:` (in any case), and the answer is
+/// the text before that line, without a leading `Answer:`. Scores may be written as `85` or
+/// `85/100`; anything after the number is ignored.
+pub fn parse_scored_answer(output: &str) -> ScoredAnswer {
+    let lines: Vec<&str> = output.trim().lines().collect();
+    let score_line = lines
+        .iter()
+        .rposition(|line| strip_prefix_ignore_case(line.trim(), "score:").is_some());
+    let (answer_lines, score) = match score_line {
+        Some(i) => {
+            let value = strip_prefix_ignore_case(lines[i].trim(), "score:").unwrap_or_default();
+            (&lines[..i], leading_number(value.trim()))
+        }
+        None => (&lines[..], None),
+    };
+    let answer = answer_lines.join("\n");
+    let answer = answer.trim();
+    let answer = strip_prefix_ignore_case(answer, "answer:").unwrap_or(answer);
+    ScoredAnswer {
+        answer: answer.trim().to_string(),
+        score,
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &text[prefix.len()..])
+}
+
+fn leading_number(text: &str) -> Option<f32> {
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(text.len());
+    text[..end].parse().ok()
+}
+
+/// The `Chain` struct represents a map-rerank chain, consisting of a single `map` step.
+#[derive(Serialize, Deserialize)]
+pub struct Chain {
+    map: Step,
+}
+
+impl Chain {
+    /// Constructs a new `Chain` with the given `map` step.
+    pub fn new(map: Step) -> Chain {
+        Chain { map }
+    }
+
+    /// A map-rerank chain that answers the `{{question}}` parameter from the documents.
+    pub fn for_question_answering() -> Chain {
+        Chain::new(Step::for_prompt_template(prompt!(
+            "You answer questions from a piece of context. You will be given a context and a question, and you will answer the question and rate how well the context answers it",
+            "Context:\n\n{{text}}\n\nQuestion: {{question}}\n\nRespond in the following format:\n\nAnswer: <the answer to the question>\nScore: <how fully the context answers the question, from 0 to 100>"
+        )))
+    }
+
+    /// Executes the map-rerank chain using the provided `Executor`.
+    ///
+    /// The documents are split into chunks that fit the `map` step, and the step runs on every
+    /// chunk concurrently. When several answers share the highest score, the first one wins.
+    pub async fn run<E: Executor>(
+        &self,
+        documents: Vec<Parameters>,
+        base_parameters: Parameters,
+        executor: &E,
+    ) -> Result<MapRerankOutput, MapRerankChainError> {
+        let chunks = chunk_documents(&documents, &base_parameters, executor, &self.map)?;
+        if chunks.is_empty() {
+            return Err(MapRerankChainError::InputEmpty);
+        }
+
+        let frame = Frame::new(executor, &self.map);
+        let answers = join_all(chunks.iter().map(|chunk| {
+            let parameters = base_parameters.combine(chunk);
+            let frame = &frame;
+            async move {
+                let output = frame.format_and_execute(&parameters).await?;
+                let output = output
+                    .to_immediate()
+                    .await
+                    .map_err(FormatAndExecuteError::Execute)?;
+                Ok::<_, MapRerankChainError>(parse_scored_answer(
+                    &output.primary_textual_output().unwrap_or_default(),
+                ))
+            }
+        }))
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let best = answers
+            .iter()
+            .filter_map(|a| a.score.map(|score| (a, score)))
+            .fold(
+                None,
+                |best: Option<(&ScoredAnswer, f32)>, (a, score)| match best {
+                    Some((_, best_score)) if best_score >= score => best,
+                    _ => Some((a, score)),
+                },
+            )
+            .ok_or(MapRerankChainError::NoScoredAnswer)?;
+        Ok(MapRerankOutput {
+            answer: best.0.answer.clone(),
+            score: best.1,
+            intermediate_steps: answers,
+        })
+    }
+}
+
+impl StorableEntity for Chain {
+    fn get_metadata() -> Vec<(String, String)> {
+        vec![(
+            "chain-type".to_string(),
+            "ai-chain::chains::map_rerank::Chain".to_string(),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::ScriptedExecutor;
+    use crate::parameters;
+
+    #[test]
+    fn test_parse_scored_answer() {
+        assert_eq!(
+            parse_scored_answer("Answer: Paris\nis the capital\nSCORE: 85/100"),
+            ScoredAnswer {
+                answer: "Paris\nis the capital".to_string(),
+                score: Some(85.0)
+            }
+        );
+        assert_eq!(parse_scored_answer("no idea").score, None);
+    }
+
+    #[tokio::test]
+    async fn test_map_rerank_returns_the_best_answer() {
+        let chain = Chain::new(Step::for_prompt_template(prompt!("{{text}}")));
+        let executor = ScriptedExecutor {
+            reply: |text| match text {
+                "alpha" => "Answer: a\nScore: 10".to_string(),
+                "beta" => "Answer: b\nScore: 90".to_string(),
+                _ => "not scored".to_string(),
+            },
+            max_tokens: 100,
+        };
+        let output = chain
+            .run(
+                vec![
+                    Parameters::new_with_text("alpha"),
+                    Parameters::new_with_text("beta"),
+                    Parameters::new_with_text("gamma"),
+                ],
+                parameters!(),
+                &executor,
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.answer, "b");
+        assert_eq!(output.score, 90.0);
+        assert_eq!(output.intermediate_steps.len(), 3);
+        assert_eq!(output.intermediate_steps[2].score, None);
+    }
+}
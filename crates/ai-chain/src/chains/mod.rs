@@ -8,8 +8,154 @@
 //! 1. **Sequential**: This chain type executes the steps one after another in a linear sequence. It's perfect for tasks that need a clear and simple order of execution.
 //! 2. **MapReduce**: This chain type follows the MapReduce paradigm, where the steps are divided into mapping and reducing phases. It's great for tasks that require parallel processing and data aggregation.
 //! 3. **Converstation**: This chain type models a conversation between the LLM and some other entity. It's great for tasks that require a back-and-forth between the LLM and the user.
+//! 4. **Refine**: This chain type goes through the documents in order and refines its answer with every chunk. It's great for summarizing long documents where order matters.
+//! 5. **MapRerank**: This chain type answers from every chunk, scores each answer and returns the best one. It's great for question answering over many documents.
+//! 6. **RetrievalQA**: This chain type answers questions from the documents in a vector store and cites its sources. It's great for question answering over a knowledge base.
 //! Stay tuned for more chain types, and feel free to contribute your own! 🎉
 
 pub mod conversation;
 pub mod map_reduce;
+pub mod map_rerank;
+pub mod refine;
+pub mod retrieval_qa;
 pub mod sequential;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+
+    use crate::options::Options;
+    use crate::output::Output;
+    use crate::prompt::{Data, Prompt};
+    use crate::schema::Document;
+    use crate::tokens::{
+        PromptTokensError, TokenCollection, TokenCount, Tokenizer, TokenizerError,
+    };
+    use crate::traits::{
+        Embeddings, EmbeddingsError, Executor, ExecutorCreationError, ExecutorError, VectorStore,
+        VectorStoreError,
+    };
+
+    /// Counts one token per byte.
+    pub(crate) struct ByteTokenizer;
+
+    impl Tokenizer for ByteTokenizer {
+        fn tokenize_str(&self, doc: &str) -> Result<TokenCollection, TokenizerError> {
+            Ok(doc.bytes().map(i32::from).collect::<Vec<_>>().into())
+        }
+
+        fn to_string(&self, tokens: TokenCollection) -> Result<String, TokenizerError> {
+            let bytes: Vec<u8> = tokens.as_i32()?.into_iter().map(|t| t as u8).collect();
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        }
+    }
+
+    /// Replies with `reply` applied to the text of the prompt, with a context window of
+    /// `max_tokens` bytes.
+    pub(crate) struct ScriptedExecutor {
+        pub(crate) reply: fn(&str) -> String,
+        pub(crate) max_tokens: i32,
+    }
+
+    #[async_trait]
+    impl Executor for ScriptedExecutor {
+        type StepTokenizer<'a> = ByteTokenizer;
+
+        fn new_with_options(_options: Options) -> Result<Self, ExecutorCreationError> {
+            Err(ExecutorCreationError::FieldRequiredError(
+                "reply".to_string(),
+            ))
+        }
+
+        async fn execute(
+            &self,
+            _options: &Options,
+            prompt: &Prompt,
+        ) -> Result<Output, ExecutorError> {
+            Ok(Output::new_immediate(Data::Text((self.reply)(
+                &prompt.to_text(),
+            ))))
+        }
+
+        fn tokens_used(
+            &self,
+            _options: &Options,
+            prompt: &Prompt,
+        ) -> Result<TokenCount, PromptTokensError> {
+            Ok(TokenCount::new(
+                self.max_tokens,
+                prompt.to_text().len() as i32,
+            ))
+        }
+
+        fn max_tokens_allowed(&self, _options: &Options) -> i32 {
+            self.max_tokens
+        }
+
+        fn answer_prefix(&self, _prompt: &Prompt) -> Option<String> {
+            None
+        }
+
+        fn get_tokenizer(&self, _options: &Options) -> Result<ByteTokenizer, TokenizerError> {
+            Ok(ByteTokenizer)
+        }
+    }
+
+    #[derive(thiserror::Error, Debug)]
+    #[error("store error")]
+    pub(crate) struct StoreError;
+    impl VectorStoreError for StoreError {}
+    impl EmbeddingsError for StoreError {}
+
+    pub(crate) struct NoEmbeddings;
+
+    #[async_trait]
+    impl Embeddings for NoEmbeddings {
+        type Error = StoreError;
+        async fn embed_texts(&self, _texts: Vec<String>) -> Result<Vec<Vec<f32>>, StoreError> {
+            Err(StoreError)
+        }
+        async fn embed_query(&self, _query: String) -> Result<Vec<f32>, StoreError> {
+            Err(StoreError)
+        }
+    }
+
+    /// Returns the stored texts sharing a word with the query.
+    #[derive(Default)]
+    pub(crate) struct WordStore(pub(crate) Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl VectorStore<NoEmbeddings> for WordStore {
+        type Error = StoreError;
+
+        async fn add_texts(&self, texts: Vec<String>) -> Result<Vec<String>, StoreError> {
+            self.0.lock().unwrap().extend(texts);
+            Ok(Vec::new())
+        }
+
+        async fn add_documents(
+            &self,
+            _documents: Vec<Document>,
+        ) -> Result<Vec<String>, StoreError> {
+            Err(StoreError)
+        }
+
+        async fn similarity_search(
+            &self,
+            query: String,
+            limit: u32,
+        ) -> Result<Vec<Document>, StoreError> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|text| query.split_whitespace().any(|word| text.contains(word)))
+                .take(limit as usize)
+                .map(|text| Document::new(text.clone()))
+                .collect())
+        }
+    }
+}
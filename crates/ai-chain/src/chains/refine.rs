@@ -0,0 +1,172 @@
+//! The `refine` module contains the `Chain` struct, which represents a refine chain.
+//!
+//! A refine chain walks through the input documents in order. The `initial` step answers from the
+//! first chunk, then the `refine` step runs once for every following chunk, with the answer so far
+//! in `{{existing_answer}}`, and may update it. Unlike a map-reduce chain the chunks are processed
+//! one after another, which keeps the order of long documents intact.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::map_reduce::chunk_documents;
+use crate::frame::{FormatAndExecuteError, Frame};
+use crate::serialization::StorableEntity;
+use crate::step::Step;
+use crate::traits::Executor;
+use crate::{prompt, Parameters};
+
+/// The parameter holding the answer so far in the `refine` step.
+pub const EXISTING_ANSWER_KEY: &str = "existing_answer";
+
+/// The `RefineChainError` enum represents errors that can occur when executing a refine chain.
+#[derive(Error, Debug)]
+pub enum RefineChainError {
+    /// An error relating to the operation of the Executor.
+    #[error("FormatAndExecuteError: {0}")]
+    FormatAndExecuteError(#[from] FormatAndExecuteError),
+    /// An error relating to tokenizing the inputs.
+    #[error("TokenizeError: {0}")]
+    TokenizeError(#[from] crate::tokens::PromptTokensError),
+    #[error("The vector of input documents was empty")]
+    InputEmpty,
+    #[error("A step produced no textual output")]
+    NoOutput,
+}
+
+/// The result of a refine chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefineOutput {
+    /// The final answer.
+    pub answer: String,
+    /// The answer after each chunk, in the order of the chunks. The last one is `answer`.
+    pub intermediate_steps: Vec<String>,
+}
+
+/// The `Chain` struct represents a refine chain, consisting of an `initial` step and a `refine` step.
+#[derive(Serialize, Deserialize)]
+pub struct Chain {
+    initial: Step,
+    refine: Step,
+}
+
+impl Chain {
+    /// Constructs a new `Chain` with the given `initial` and `refine` steps.
+    ///
+    /// Both steps get the chunk in `{{text}}`; the `refine` step also gets the answer so far in
+    /// `{{existing_answer}}`.
+    pub fn new(initial: Step, refine: Step) -> Chain {
+        Chain { initial, refine }
+    }
+
+    /// A refine chain that summarizes the documents.
+    pub fn for_summarization() -> Chain {
+        let initial = Step::for_prompt_template(prompt!(
+            "You are a text summarizer. You will be given a text and you will have to summarize it",
+            "Text:\n\n{{text}}\n\nPlease write a summary of the text above. Respond only with the summary."
+        ));
+        let refine = Step::for_prompt_template(prompt!(
+            "You are a text summarizer. You will be given an existing summary and more text, and you will have to refine the summary",
+            "Existing summary:\n\n{{existing_answer}}\n\nMore text:\n\n{{text}}\n\nPlease refine the existing summary with the text above. If the text is not useful, repeat the existing summary. Respond only with the summary."
+        ));
+        Chain::new(initial, refine)
+    }
+
+    /// Executes the refine chain using the provided `Executor`.
+    ///
+    /// The documents are split into chunks that fit the `refine` step and processed in order. The
+    /// chunks are sized with an empty `{{existing_answer}}`, so answers should stay short compared
+    /// to the context window.
+    pub async fn run<E: Executor>(
+        &self,
+        documents: Vec<Parameters>,
+        base_parameters: Parameters,
+        executor: &E,
+    ) -> Result<RefineOutput, RefineChainError> {
+        let chunks = chunk_documents(
+            &documents,
+            &base_parameters.with(EXISTING_ANSWER_KEY, ""),
+            executor,
+            &self.refine,
+        )?;
+        let mut chunks = chunks.into_iter();
+        let first = chunks.next().ok_or(RefineChainError::InputEmpty)?;
+
+        let initial_frame = Frame::new(executor, &self.initial);
+        let refine_frame = Frame::new(executor, &self.refine);
+
+        let mut answer = execute_to_text(&initial_frame, &base_parameters.combine(&first)).await?;
+        let mut intermediate_steps = vec![answer.clone()];
+        for chunk in chunks {
+            let parameters = base_parameters
+                .combine(&chunk)
+                .with(EXISTING_ANSWER_KEY, answer);
+            answer = execute_to_text(&refine_frame, &parameters).await?;
+            intermediate_steps.push(answer.clone());
+        }
+        Ok(RefineOutput {
+            answer,
+            intermediate_steps,
+        })
+    }
+}
+
+async fn execute_to_text<E: Executor>(
+    frame: &Frame<'_, E>,
+    parameters: &Parameters,
+) -> Result<String, RefineChainError> {
+    frame
+        .format_and_execute(parameters)
+        .await?
+        .to_immediate()
+        .await
+        .map_err(FormatAndExecuteError::Execute)?
+        .primary_textual_output()
+        .ok_or(RefineChainError::NoOutput)
+}
+
+impl StorableEntity for Chain {
+    fn get_metadata() -> Vec<(String, String)> {
+        vec![(
+            "chain-type".to_string(),
+            "ai-chain::chains::refine::Chain".to_string(),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::ScriptedExecutor;
+    use crate::parameters;
+
+    #[tokio::test]
+    async fn test_refine_updates_the_answer_chunk_by_chunk() {
+        let chain = Chain::new(
+            Step::for_prompt_template(prompt!("start:{{text}}")),
+            Step::for_prompt_template(prompt!("{{existing_answer}}+{{text}}")),
+        );
+        let executor = ScriptedExecutor {
+            reply: |prompt| prompt.trim_start_matches("start:").to_string(),
+            max_tokens: 8,
+        };
+        let output = chain
+            .run(
+                vec![
+                    Parameters::new_with_text("aaaaaaabbbbbbb"),
+                    Parameters::new_with_text("ccc"),
+                ],
+                parameters!(),
+                &executor,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            output.intermediate_steps,
+            vec!["aaaaaaa", "aaaaaaa+bbbbbbb", "aaaaaaa+bbbbbbb+ccc"]
+        );
+        assert_eq!(output.answer, "aaaaaaa+bbbbbbb+ccc");
+
+        let empty = chain.run(vec![], parameters!(), &executor).await;
+        assert!(matches!(empty, Err(RefineChainError::InputEmpty)));
+    }
+}
@@ -0,0 +1,464 @@
+//! The `retrieval_qa` module contains chains that answer questions from the documents found by a
+//! [`Retriever`], such as a `VectorStore` wrapped in a [`VectorStoreRetriever`].
+//!
+//! `Chain` retrieves the documents most relevant to the question, puts as many of them as the
+//! executor's context window allows into the prompt and returns the answer together with the
+//! documents it was given. The documents are numbered in the prompt, so the answer can cite them as
+//! `[1]`, `[2]`, and so on.
+//!
+//! `ConversationalChain` keeps a chat history and first rewrites follow-up questions such as "and
+//! in 2020?" into standalone questions, so that they retrieve the right documents.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let chain = retrieval_qa::Chain::new(store, 4).with_strategy(CombineStrategy::MapReduce);
+//! let output = chain.run("Who maintains the billing service?", &executor).await?;
+//! println!("{}", output.answer);
+//! for source in output.cited_sources() {
+//!     println!("- {}", source.page_content);
+//! }
+//! ```
+//!
+//! Any retriever can be used instead of a plain similarity search:
+//!
+//! ```ignore
+//! let retriever = MmrRetriever::new(VectorStoreRetriever::new(store), embeddings);
+//! let chain = retrieval_qa::Chain::for_retriever(retriever, 4);
+//! ```
+
+use std::marker::PhantomData;
+
+use futures::future::join_all;
+use thiserror::Error;
+
+use crate::frame::{FormatAndExecuteError, Frame};
+use crate::parsing::find_numbered_references;
+use crate::prompt::{ChatMessage, ChatMessageCollection, ChatRole, StringTemplateError};
+use crate::retrieval::{Retriever, RetrieverError, VectorStoreRetriever};
+use crate::schema::{Document, EmptyMetadata};
+use crate::step::Step;
+use crate::tokens::PromptTokensError;
+use crate::traits::{Embeddings, Executor, VectorStore};
+use crate::{prompt, Parameters};
+
+/// The `RetrievalQaError` enum represents errors that can occur when executing a retrieval QA chain.
+#[derive(Error, Debug)]
+pub enum RetrievalQaError {
+    /// An error relating to the operation of the Executor.
+    #[error("FormatAndExecuteError: {0}")]
+    FormatAndExecuteError(#[from] FormatAndExecuteError),
+    /// An error relating to tokenizing the prompt.
+    #[error("TokenizeError: {0}")]
+    TokenizeError(#[from] PromptTokensError),
+    #[error("RetrieverError: {0}")]
+    Retriever(#[from] RetrieverError),
+    #[error("A step produced no textual output")]
+    NoOutput,
+}
+
+impl From<StringTemplateError> for RetrievalQaError {
+    fn from(e: StringTemplateError) -> Self {
+        RetrievalQaError::FormatAndExecuteError(e.into())
+    }
+}
+
+/// How the retrieved documents are combined into the prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CombineStrategy {
+    /// Put the documents into the prompt as they are.
+    #[default]
+    Stuff,
+    /// First extract the parts of each document relevant to the question, then put the extracts
+    /// into the prompt. This fits more documents into the context window at the cost of one call
+    /// per document.
+    MapReduce,
+}
+
+/// The answer of a retrieval QA chain and the documents it is based on.
+#[derive(Debug)]
+pub struct RetrievalQaOutput<M = EmptyMetadata>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// The answer to the question.
+    pub answer: String,
+    /// The documents that were put into the prompt, in the order of their numbers.
+    pub sources: Vec<Document<M>>,
+    /// The indices into `sources` of the documents the answer cites.
+    pub citations: Vec<usize>,
+}
+
+impl<M> RetrievalQaOutput<M>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// The documents the answer cites.
+    pub fn cited_sources(&self) -> impl Iterator<Item = &Document<M>> {
+        self.citations.iter().map(|&i| &self.sources[i])
+    }
+}
+
+/// A chain that answers questions from the documents found by a retriever.
+///
+/// The answer step gets the numbered documents in `{{context}}` and the question in
+/// `{{question}}`. With `CombineStrategy::MapReduce`, the extract step gets each document in
+/// `{{text}}` and the question in `{{question}}`.
+pub struct Chain<R, M = EmptyMetadata> {
+    retriever: R,
+    limit: u32,
+    strategy: CombineStrategy,
+    answer: Step,
+    extract: Step,
+    _metadata: PhantomData<fn() -> M>,
+}
+
+impl<E, V, M> Chain<VectorStoreRetriever<E, V, M>, M>
+where
+    E: Embeddings,
+    V: VectorStore<E, M> + Send + Sync,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync,
+{
+    /// Creates a chain that retrieves up to `limit` documents per question from the most similar
+    /// documents in `store` and stuffs them into the prompt.
+    pub fn new(store: V, limit: u32) -> Self {
+        Self::for_retriever(VectorStoreRetriever::new(store), limit)
+    }
+
+    /// The vector store the documents are retrieved from.
+    pub fn store(&self) -> &V {
+        self.retriever.store()
+    }
+}
+
+impl<R, M> Chain<R, M>
+where
+    R: Retriever<M>,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync,
+{
+    /// Creates a chain that retrieves up to `limit` documents per question with `retriever` and
+    /// stuffs them into the prompt.
+    pub fn for_retriever(retriever: R, limit: u32) -> Self {
+        Self {
+            retriever,
+            limit,
+            strategy: CombineStrategy::default(),
+            answer: Step::for_prompt_template(prompt!(
+                "You answer questions using only the numbered documents you are given. Cite the documents you use by their number, like [1]. If the documents don't contain the answer, say that you don't know.",
+                "Documents:\n\n{{context}}\n\nQuestion: {{question}}"
+            )),
+            extract: Step::for_prompt_template(prompt!(
+                "You extract the parts of a document that are relevant to a question.",
+                "Document:\n\n{{text}}\n\nQuestion: {{question}}\n\nRespond only with the relevant parts of the document, word for word. If nothing is relevant, respond with an empty message."
+            )),
+            _metadata: PhantomData,
+        }
+    }
+
+    /// Sets how the retrieved documents are combined into the prompt.
+    pub fn with_strategy(mut self, strategy: CombineStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Replaces the step that answers the question.
+    pub fn with_answer_step(mut self, step: Step) -> Self {
+        self.answer = step;
+        self
+    }
+
+    /// Replaces the step that extracts the relevant parts of a document.
+    pub fn with_extract_step(mut self, step: Step) -> Self {
+        self.extract = step;
+        self
+    }
+
+    /// The retriever the documents are found with.
+    pub fn retriever(&self) -> &R {
+        &self.retriever
+    }
+
+    /// Answers `question` from the documents the retriever finds for it.
+    pub async fn run<X: Executor>(
+        &self,
+        question: &str,
+        executor: &X,
+    ) -> Result<RetrievalQaOutput<M>, RetrievalQaError> {
+        let documents = self.retriever.retrieve(question, self.limit).await?;
+        let (mut sources, passages): (Vec<_>, Vec<_>) = match self.strategy {
+            CombineStrategy::Stuff => documents
+                .into_iter()
+                .map(|d| {
+                    let passage = d.page_content.clone();
+                    (d, passage)
+                })
+                .unzip(),
+            // Documents with nothing relevant are left out, so they aren't numbered or returned.
+            CombineStrategy::MapReduce => {
+                let extracts = self.extract(&documents, question, executor).await?;
+                documents
+                    .into_iter()
+                    .zip(extracts)
+                    .filter(|(_, extract)| !extract.trim().is_empty())
+                    .unzip()
+            }
+        };
+
+        let base_parameters = Parameters::new().with("question", question);
+        let mut context = String::new();
+        let mut used = 0;
+        for passage in &passages {
+            let mut candidate = context.clone();
+            if !candidate.is_empty() {
+                candidate.push_str("\n\n");
+            }
+            candidate.push_str(&format!("[{}] {}", used + 1, passage));
+            let prompt = self
+                .answer
+                .format(&base_parameters.with("context", candidate.as_str()))?;
+            if !executor
+                .tokens_used(self.answer.options(), &prompt)?
+                .has_tokens_remaining()
+            {
+                break;
+            }
+            context = candidate;
+            used += 1;
+        }
+        sources.truncate(used);
+
+        let answer = Frame::new(executor, &self.answer)
+            .format_and_execute(&base_parameters.with("context", context))
+            .await?
+            .to_immediate()
+            .await
+            .map_err(FormatAndExecuteError::Execute)?
+            .primary_textual_output()
+            .ok_or(RetrievalQaError::NoOutput)?;
+        let citations = find_numbered_references(&answer, sources.len());
+        Ok(RetrievalQaOutput {
+            answer,
+            sources,
+            citations,
+        })
+    }
+
+    async fn extract<X: Executor>(
+        &self,
+        documents: &[Document<M>],
+        question: &str,
+        executor: &X,
+    ) -> Result<Vec<String>, RetrievalQaError> {
+        let frame = Frame::new(executor, &self.extract);
+        let extracts = join_all(documents.iter().map(|document| {
+            let parameters = Parameters::new_with_text(document.page_content.as_str())
+                .with("question", question);
+            let frame = &frame;
+            async move {
+                let output = frame.format_and_execute(&parameters).await?;
+                let output = output
+                    .to_immediate()
+                    .await
+                    .map_err(FormatAndExecuteError::Execute)?;
+                Ok::<_, RetrievalQaError>(output.primary_textual_output().unwrap_or_default())
+            }
+        }))
+        .await;
+        extracts.into_iter().collect()
+    }
+}
+
+/// A retrieval QA chain that keeps a chat history.
+///
+/// When there is history, the condense step rewrites the question into a standalone question
+/// before the documents are retrieved. It gets the history in `{{chat_history}}` and the question
+/// in `{{question}}`.
+pub struct ConversationalChain<R, M = EmptyMetadata> {
+    chain: Chain<R, M>,
+    condense: Step,
+    history: ChatMessageCollection<String>,
+}
+
+impl<R, M> ConversationalChain<R, M>
+where
+    R: Retriever<M>,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync,
+{
+    /// Creates a conversational chain around `chain` with an empty history.
+    pub fn new(chain: Chain<R, M>) -> Self {
+        Self {
+            chain,
+            condense: Step::for_prompt_template(prompt!(
+                "You rewrite follow-up questions into standalone questions.",
+                "Conversation:\n\n{{chat_history}}\n\nFollow-up question: {{question}}\n\nRewrite the follow-up question into a standalone question that can be understood without the conversation. Respond only with the question."
+            )),
+            history: ChatMessageCollection::new(),
+        }
+    }
+
+    /// Replaces the step that rewrites follow-up questions.
+    pub fn with_condense_step(mut self, step: Step) -> Self {
+        self.condense = step;
+        self
+    }
+
+    /// Starts from the given history instead of an empty one.
+    pub fn with_history(mut self, history: ChatMessageCollection<String>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// The questions and answers so far.
+    pub fn history(&self) -> &ChatMessageCollection<String> {
+        &self.history
+    }
+
+    /// Forgets the history.
+    pub fn clear_history(&mut self) {
+        self.history = ChatMessageCollection::new();
+    }
+
+    /// Answers `question`, taking the history into account, and adds the exchange to the history.
+    pub async fn run<X: Executor>(
+        &mut self,
+        question: &str,
+        executor: &X,
+    ) -> Result<RetrievalQaOutput<M>, RetrievalQaError> {
+        let standalone = if self.history.is_empty() {
+            question.to_string()
+        } else {
+            let parameters = Parameters::new()
+                .with("chat_history", self.history.to_string())
+                .with("question", question);
+            Frame::new(executor, &self.condense)
+                .format_and_execute(&parameters)
+                .await?
+                .to_immediate()
+                .await
+                .map_err(FormatAndExecuteError::Execute)?
+                .primary_textual_output()
+                .map(|q| q.trim().to_string())
+                .ok_or(RetrievalQaError::NoOutput)?
+        };
+        let output = self.chain.run(&standalone, executor).await?;
+        self.history
+            .add_message(ChatMessage::new(ChatRole::User, question.to_string()));
+        self.history
+            .add_message(ChatMessage::new(ChatRole::Assistant, output.answer.clone()));
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::{ScriptedExecutor, WordStore};
+
+    fn store() -> WordStore {
+        let store = WordStore::default();
+        store.0.lock().unwrap().extend([
+            "billing is maintained by alice".to_string(),
+            "search is maintained by bob".to_string(),
+            "billing runs on postgres".to_string(),
+        ]);
+        store
+    }
+
+    /// Answers with the number of every document in the prompt.
+    fn cite_everything(prompt: &str) -> String {
+        if prompt.contains("Follow-up question") {
+            return "who maintains search".to_string();
+        }
+        if prompt.contains("Respond only with the relevant parts") {
+            return "extract".to_string();
+        }
+        (1..=9)
+            .filter(|n| prompt.contains(&format!("[{}] ", n)))
+            .map(|n| format!("[{}]", n))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stuffs_documents_within_the_token_budget() {
+        let chain = Chain::new(store(), 3);
+        let executor = ScriptedExecutor {
+            reply: cite_everything,
+            max_tokens: 1000,
+        };
+        let output = chain.run("billing maintained", &executor).await.unwrap();
+        assert_eq!(output.sources.len(), 3);
+        assert_eq!(output.citations, vec![0, 1, 2]);
+
+        // Only room for the first document.
+        let executor = ScriptedExecutor {
+            reply: cite_everything,
+            max_tokens: 300,
+        };
+        let output = chain.run("billing maintained", &executor).await.unwrap();
+        assert_eq!(output.answer, "[1]");
+        assert_eq!(
+            output.cited_sources().next().unwrap().page_content,
+            "billing is maintained by alice"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_map_reduce_extracts_before_answering() {
+        let chain = Chain::new(store(), 2).with_strategy(CombineStrategy::MapReduce);
+        let executor = ScriptedExecutor {
+            reply: |prompt| {
+                if prompt.contains("Respond only with the relevant parts") {
+                    "extract".to_string()
+                } else {
+                    prompt.matches("[2] extract").count().to_string()
+                }
+            },
+            max_tokens: 1000,
+        };
+        let output = chain.run("billing", &executor).await.unwrap();
+        assert_eq!(output.answer, "1");
+        assert_eq!(output.sources.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_map_reduce_leaves_out_empty_extracts() {
+        let chain = Chain::new(store(), 3).with_strategy(CombineStrategy::MapReduce);
+        let executor = ScriptedExecutor {
+            reply: |prompt| {
+                if prompt.contains("Respond only with the relevant parts") {
+                    if prompt.contains("search is maintained") {
+                        " ".to_string()
+                    } else {
+                        "extract".to_string()
+                    }
+                } else {
+                    cite_everything(prompt)
+                }
+            },
+            max_tokens: 1000,
+        };
+        let output = chain.run("billing maintained", &executor).await.unwrap();
+        assert_eq!(output.answer, "[1][2]");
+        assert_eq!(output.sources.len(), 2);
+        assert!(output
+            .sources
+            .iter()
+            .all(|d| !d.page_content.starts_with("search")));
+    }
+
+    #[tokio::test]
+    async fn test_conversational_chain_condenses_follow_ups() {
+        let mut chain = ConversationalChain::new(Chain::new(store(), 1));
+        let executor = ScriptedExecutor {
+            reply: cite_everything,
+            max_tokens: 1000,
+        };
+        chain.run("who maintains billing", &executor).await.unwrap();
+        let output = chain.run("and search?", &executor).await.unwrap();
+        assert_eq!(
+            output.sources[0].page_content,
+            "search is maintained by bob"
+        );
+        assert_eq!(chain.history().len(), 4);
+    }
+}
@@ -5,6 +5,9 @@
 //! The `Chain` struct allows you to:
 //! - Create a new chain with a vector of `Step` instances
 //! - Execute the chain with a given set of `Parameters` and an `Executor`
+//! - Name the output of a step with `Step::with_output_key`, so that every later step can use it as a parameter
+//! - Post-process the output of a step with an `OutputParser`, set with `Step::with_output_parser`
+//! - Collect the output of every step with `Chain::run_with_outputs`
 //!
 //! The `Chain` struct is designed to work with any executor that implements the `Executor` trait, providing flexibility and extensibility.
 //!
@@ -22,6 +25,14 @@
 //!
 //! // Execute the chain with the provided parameters and executor.
 //! let result = chain.run(parameters, &executor).await;
+//!
+//! // Later steps can use the named outputs of every earlier step.
+//! let chain = Chain::new(vec![
+//!     Step::new(prompt!("Write a summary for this text: {{text}}")).with_output_key("summary"),
+//!     Step::new(prompt!("Translate to {{language}}: {{summary}}")).with_output_key("translation"),
+//! ]);
+//! let outputs = chain.run_with_outputs(parameters!("language" => "French", "text" => "..."), &executor).await?;
+//! println!("{}\n{}", outputs.get("summary").unwrap(), outputs.get("translation").unwrap());
 //! ```
 //!
 //! This module also provides serialization and deserialization support for the `Chain` struct, allowing you to store and load chains using formats like JSON, YAML, or others.
@@ -29,6 +40,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::frame::FormatAndExecuteError;
+use crate::output::parser::OutputParserError;
 use crate::output::Output;
 use crate::{
     frame::Frame, serialization::StorableEntity, step::Step, traits::Executor, Parameters,
@@ -42,9 +54,61 @@ pub enum SequentialChainError {
     FormatAndExecuteError(#[from] FormatAndExecuteError),
     #[error("The vector of steps was empty")]
     NoSteps,
+    #[error("OutputParserError: {0}")]
+    OutputParser(#[from] OutputParserError),
+}
+
+/// The output of a single step of a sequential chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepOutput {
+    /// The output key of the step, if it has one.
+    pub output_key: Option<String>,
+    /// The output of the step, after its output parser if it has one.
+    pub text: String,
+}
+
+/// The outputs of every step of a sequential chain, as returned by `Chain::run_with_outputs`.
+#[derive(Clone, Debug)]
+pub struct SequentialOutput {
+    steps: Vec<StepOutput>,
+    parameters: Parameters,
+}
+
+impl SequentialOutput {
+    /// The outputs of the steps, in the order of the steps.
+    pub fn steps(&self) -> &[StepOutput] {
+        &self.steps
+    }
+
+    /// The output of the last step with the given output key.
+    pub fn get(&self, output_key: &str) -> Option<&str> {
+        self.steps
+            .iter()
+            .rev()
+            .find(|step| step.output_key.as_deref() == Some(output_key))
+            .map(|step| step.text.as_str())
+    }
+
+    /// The output of the last step.
+    pub fn last(&self) -> &str {
+        // A chain without steps fails before producing a `SequentialOutput`.
+        &self.steps[self.steps.len() - 1].text
+    }
+
+    /// The parameters of the chain together with every named output, and the output of the last
+    /// step as `text`.
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
 }
 
 /// A sequential chain is a chain where each step is executed in order, with the output of the previous step being available to the next step.
+///
+/// The output of the previous step is available as `{{text}}`. Steps with an output key also make
+/// their output available as `{{key}}` to every later step.
+///
+/// Serializing a chain fails if one of its steps has an output parser, since parsers can't be
+/// stored.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Chain {
     steps: Vec<Step>,
@@ -66,13 +130,14 @@ impl Chain {
     ///
     /// * `step` - A `Step<E>` object that defines the single step for the chain.
     pub fn of_one(step: Step) -> Chain {
-        Chain { steps: vec![step] }
+        Chain::new(vec![step])
     }
 
     /// Executes the chain with the given parameters and executor.
     ///
     /// This method runs each step in the chain in sequence, passing the output of the previous step to the next step.
-    /// If the chain is empty, an error is returned.
+    /// If the chain is empty, an error is returned. The output of the last step is returned as it
+    /// is, so it can be streamed and is not parsed; use `run_with_outputs` for the parsed outputs.
     ///
     /// # Arguments
     ///
@@ -91,31 +156,89 @@ impl Chain {
     where
         E: Executor,
     {
-        if self.steps.is_empty() {
+        let Some((last_step, steps)) = self.steps.split_last() else {
             return Err(SequentialChainError::NoSteps);
-        }
+        };
         let mut current_params = parameters;
 
-        for step in &self.steps[..self.steps.len() - 1] {
-            let body = Frame::new(executor, step)
-                .format_and_execute(&current_params)
-                .await?
-                .to_immediate()
-                .await
-                .map_err(|err| {
-                    SequentialChainError::FormatAndExecuteError(FormatAndExecuteError::Execute(err))
-                })?
-                .as_content()
-                .extract_last_body()
-                .cloned()
-                .unwrap_or_default();
-            current_params = current_params.with_text(body);
+        for step in steps {
+            let body = run_step(step, &current_params, executor).await?;
+            current_params = with_step_output(&current_params, step, body);
         }
-        let last_step = self.steps.last().unwrap();
         Ok(Frame::new(executor, last_step)
             .format_and_execute(&current_params)
             .await?)
     }
+
+    /// Executes the chain with the given parameters and executor, and returns the output of every step.
+    ///
+    /// # Arguments
+    ///
+    /// * `parameters` - A `Parameters` object containing the input parameters for the chain.
+    /// * `executor` - A reference to an executor that implements the `Executor` trait.
+    pub async fn run_with_outputs<E>(
+        &self,
+        parameters: Parameters,
+        executor: &E,
+    ) -> Result<SequentialOutput, SequentialChainError>
+    where
+        E: Executor,
+    {
+        if self.steps.is_empty() {
+            return Err(SequentialChainError::NoSteps);
+        }
+        let mut current_params = parameters;
+        let mut steps = Vec::with_capacity(self.steps.len());
+
+        for step in &self.steps {
+            let body = run_step(step, &current_params, executor).await?;
+            current_params = with_step_output(&current_params, step, body.clone());
+            steps.push(StepOutput {
+                output_key: step.output_key().map(str::to_string),
+                text: body,
+            });
+        }
+        Ok(SequentialOutput {
+            steps,
+            parameters: current_params,
+        })
+    }
+}
+
+/// Runs a single step and parses its output.
+async fn run_step<E>(
+    step: &Step,
+    parameters: &Parameters,
+    executor: &E,
+) -> Result<String, SequentialChainError>
+where
+    E: Executor,
+{
+    let body = Frame::new(executor, step)
+        .format_and_execute(parameters)
+        .await?
+        .to_immediate()
+        .await
+        .map_err(|err| {
+            SequentialChainError::FormatAndExecuteError(FormatAndExecuteError::Execute(err))
+        })?
+        .as_content()
+        .extract_last_body()
+        .cloned()
+        .unwrap_or_default();
+    match step.output_parser() {
+        Some(parser) => Ok(parser.parse(&body).await?),
+        None => Ok(body),
+    }
+}
+
+/// Adds the output of `step` to the parameters for the next steps.
+fn with_step_output(parameters: &Parameters, step: &Step, output: String) -> Parameters {
+    let parameters = parameters.with_text(output.clone());
+    match step.output_key() {
+        Some(key) => parameters.with(key, output),
+        None => parameters,
+    }
 }
 
 impl StorableEntity for Chain {
@@ -127,3 +250,75 @@ impl StorableEntity for Chain {
         base
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::ScriptedExecutor;
+    use crate::output::parser::SimpleParser;
+    use crate::prompt;
+
+    #[tokio::test]
+    async fn test_later_steps_see_every_named_output() {
+        let chain = Chain::new(vec![
+            Step::for_prompt_template(prompt!("  {{text}} summary "))
+                .with_output_key("summary")
+                .with_output_parser(SimpleParser::new().with_trim(true)),
+            Step::for_prompt_template(prompt!("{{text}} in {{language}}")),
+            Step::for_prompt_template(prompt!("{{summary}} / {{text}}")).with_output_key("final"),
+        ]);
+        let executor = ScriptedExecutor {
+            reply: |prompt| prompt.to_string(),
+            max_tokens: 100,
+        };
+        let outputs = chain
+            .run_with_outputs(
+                Parameters::new_with_text("doc").with("language", "French"),
+                &executor,
+            )
+            .await
+            .unwrap();
+        assert_eq!(outputs.get("summary"), Some("doc summary"));
+        assert_eq!(outputs.steps()[1].text, "doc summary in French");
+        assert_eq!(outputs.last(), "doc summary / doc summary in French");
+        assert_eq!(outputs.get("final"), Some(outputs.last()));
+        assert_eq!(
+            outputs.parameters().get("language").as_deref(),
+            Some("French")
+        );
+
+        assert!(serde_json::to_string(&chain).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parsers_only_apply_to_their_own_step() {
+        let chain = Chain::new(vec![
+            Step::for_prompt_template(prompt!(" {{text}} "))
+                .with_output_key("value")
+                .with_output_parser(SimpleParser::new().with_trim(true)),
+            Step::for_prompt_template(prompt!(" {{value}} ")).with_output_key("value"),
+        ]);
+        let executor = ScriptedExecutor {
+            reply: |prompt| prompt.to_string(),
+            max_tokens: 100,
+        };
+        let outputs = chain
+            .run_with_outputs(Parameters::new_with_text("doc"), &executor)
+            .await
+            .unwrap();
+        assert_eq!(outputs.steps()[0].text, "doc");
+        assert_eq!(outputs.get("value"), Some(" doc "));
+    }
+
+    #[test]
+    fn test_chain_without_parsers_round_trips() {
+        let chain = Chain::new(vec![
+            Step::for_prompt_template(prompt!("{{text}}")).with_output_key("summary"),
+            Step::for_prompt_template(prompt!("{{summary}}")),
+        ]);
+        let stored = serde_json::to_string(&chain).unwrap();
+        let loaded: Chain = serde_json::from_str(&stored).unwrap();
+        assert_eq!(loaded.steps[0].output_key(), Some("summary"));
+        assert_eq!(loaded.steps[1].output_key(), None);
+    }
+}
@@ -1,12 +1,10 @@
-use crate::document_loaders::{process_doc_stream, LoaderError};
-use crate::{document_loaders::Loader, schemas::Document, text_splitter::TextSplitter};
+use crate::document_loaders::{process_doc_stream, LoaderError, Metadata, TextSplitter};
+use crate::{document_loaders::Loader, schema::Document};
 use async_stream::stream;
 use async_trait::async_trait;
-use csv;
 use futures::Stream;
 use serde_json::Value;
 
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Cursor, Read};
 use std::path::Path;
@@ -43,9 +41,9 @@ impl CsvLoader<BufReader<File>> {
 #[async_trait]
 impl<R: Read + Send + Sync + 'static> Loader for CsvLoader<R> {
     async fn load(
-        mut self,
+        self,
     ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<Document, LoaderError>> + Send + 'static>>,
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
         LoaderError,
     > {
         let mut reader = csv::Reader::from_reader(self.reader);
@@ -74,12 +72,11 @@ impl<R: Read + Send + Sync + 'static> Loader for CsvLoader<R> {
                 row_number += 1; // Increment the row number by 1 for each row
 
                 // Generate document with the content and metadata
-                let mut document = Document::new(content);
-                let mut metadata = HashMap::new();
+                let mut metadata = Metadata::new();
                 metadata.insert("row".to_string(), Value::from(row_number));
 
                 // Attach the metadata to the document
-                document.metadata = metadata;
+                let document = Document::new(content).with_metadata(metadata);
 
                 yield Ok(document);
             }
@@ -89,10 +86,10 @@ impl<R: Read + Send + Sync + 'static> Loader for CsvLoader<R> {
     }
 
     async fn load_and_split<TS: TextSplitter + 'static>(
-        mut self,
+        self,
         splitter: TS,
     ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<Document, LoaderError>> + Send + 'static>>,
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
         LoaderError,
     > {
         let doc_stream = self.load().await?;
@@ -133,11 +130,17 @@ Jane Smith,32,London,United Kingdom";
         assert_eq!(documents.len(), 2);
 
         let expected1 = "name: John Doe\nage: 25\ncity: New York\ncountry: United States\n";
-        assert_eq!(documents[0].metadata.get("row").unwrap(), &Value::from(1));
+        assert_eq!(
+            documents[0].metadata.as_ref().unwrap().get("row").unwrap(),
+            &Value::from(1)
+        );
         assert_eq!(documents[0].page_content, expected1);
 
         let expected2 = "name: Jane Smith\nage: 32\ncity: London\ncountry: United Kingdom\n";
-        assert_eq!(documents[1].metadata.get("row").unwrap(), &Value::from(2));
+        assert_eq!(
+            documents[1].metadata.as_ref().unwrap().get("row").unwrap(),
+            &Value::from(2)
+        );
         assert_eq!(documents[1].page_content, expected2);
     }
 
@@ -163,11 +166,17 @@ Jane Smith,32,London,United Kingdom";
         assert_eq!(documents.len(), 20);
 
         let expected1 = "name: John Doe\nage: 25\ncity: New York\ncountry: United States\n";
-        assert_eq!(documents[0].metadata.get("row").unwrap(), &Value::from(1));
+        assert_eq!(
+            documents[0].metadata.as_ref().unwrap().get("row").unwrap(),
+            &Value::from(1)
+        );
         assert_eq!(documents[0].page_content, expected1);
 
         let expected2 = "name: Jane Smith\nage: 32\ncity: London\ncountry: United Kingdom\n";
-        assert_eq!(documents[1].metadata.get("row").unwrap(), &Value::from(2));
+        assert_eq!(
+            documents[1].metadata.as_ref().unwrap().get("row").unwrap(),
+            &Value::from(2)
+        );
         assert_eq!(documents[1].page_content, expected2);
     }
 }
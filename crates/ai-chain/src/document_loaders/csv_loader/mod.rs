@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 mod csv_loader;
 pub use csv_loader::*;
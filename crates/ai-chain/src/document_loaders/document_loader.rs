@@ -1,47 +1,88 @@
-use std::pin::Pin;
+use std::{collections::HashMap, pin::Pin};
 
 use async_stream::stream;
 use async_trait::async_trait;
 use futures::Stream;
 use futures_util::{pin_mut, StreamExt};
+use serde_json::Value;
 
-use crate::{schemas::Document, text_splitter::TextSplitter};
+use crate::{schema::Document, tokens::Tokenizer};
 
 use super::LoaderError;
 
+/// The metadata loaders attach to the documents they load, such as their `source`.
+pub type Metadata = HashMap<String, Value>;
+
 #[async_trait]
 pub trait Loader: Send + Sync {
     async fn load(
         self,
     ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<Document, LoaderError>> + Send + 'static>>,
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
         LoaderError,
     >;
     async fn load_and_split<TS: TextSplitter + 'static>(
         self,
         splitter: TS,
     ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<Document, LoaderError>> + Send + 'static>>,
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
         LoaderError,
     >;
 }
 
+/// Splits the text of the documents loaded by [`Loader::load_and_split`] into chunks.
+pub trait TextSplitter: Send + Sync {
+    fn split_text(&self, text: &str) -> Result<Vec<String>, LoaderError>;
+}
+
+/// A [`TextSplitter`] making chunks of at most `max_tokens_per_chunk` tokens of `tokenizer`,
+/// each one starting `chunk_overlap` tokens before the end of the previous one.
+#[derive(Debug, Clone)]
+pub struct TokenSplitter<T> {
+    tokenizer: T,
+    max_tokens_per_chunk: usize,
+    chunk_overlap: usize,
+}
+
+impl<T: Tokenizer> TokenSplitter<T> {
+    pub fn new(tokenizer: T, max_tokens_per_chunk: usize, chunk_overlap: usize) -> Self {
+        Self {
+            tokenizer,
+            max_tokens_per_chunk,
+            chunk_overlap,
+        }
+    }
+}
+
+impl<T: Tokenizer + Send + Sync> TextSplitter for TokenSplitter<T> {
+    fn split_text(&self, text: &str) -> Result<Vec<String>, LoaderError> {
+        Ok(self
+            .tokenizer
+            .split_text(text, self.max_tokens_per_chunk, self.chunk_overlap)?)
+    }
+}
+
+/// Splits every document of `doc_stream` with `splitter`. The chunks keep the metadata of their
+/// document.
 pub(crate) async fn process_doc_stream<TS: TextSplitter + 'static>(
-    doc_stream: Pin<Box<dyn Stream<Item = Result<Document, LoaderError>> + Send>>,
+    doc_stream: Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send>>,
     splitter: TS,
-) -> impl Stream<Item = Result<Document, LoaderError>> {
+) -> impl Stream<Item = Result<Document<Metadata>, LoaderError>> {
     stream! {
         pin_mut!(doc_stream);
         while let Some(doc_result) = doc_stream.next().await {
             match doc_result {
                 Ok(doc) => {
-                    match splitter.split_documents(&[doc]).await {
-                        Ok(docs) => {
-                            for doc in docs {
-                                yield Ok(doc);
+                    match splitter.split_text(&doc.page_content) {
+                        Ok(chunks) => {
+                            for chunk in chunks {
+                                yield Ok(Document {
+                                    page_content: chunk,
+                                    metadata: doc.metadata.clone(),
+                                });
                             }
                         },
-                        Err(e) => yield Err(LoaderError::TextSplitterError(e)),
+                        Err(e) => yield Err(e),
                     }
                 }
                 Err(e) => yield Err(e),
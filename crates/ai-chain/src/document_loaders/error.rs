@@ -2,7 +2,7 @@ use std::io;
 
 use thiserror::Error;
 
-use crate::text_splitter::TextSplitterError;
+use crate::tokens::TokenizerError;
 
 #[derive(Error, Debug)]
 pub enum LoaderError {
@@ -10,7 +10,7 @@ pub enum LoaderError {
     LoadDocumentError(String),
 
     #[error("{0}")]
-    TextSplitterError(#[from] TextSplitterError),
+    TokenizerError(#[from] TokenizerError),
 
     #[error(transparent)]
     IOError(#[from] io::Error),
@@ -19,18 +19,11 @@ pub enum LoaderError {
     CSVError(#[from] csv::Error),
 
     #[error(transparent)]
-    LoPdfError(#[from] lopdf::Error),
-
-    #[error(transparent)]
-    ReadabilityError(#[from] readability::error::Error),
+    ZipError(#[from] zip::result::ZipError),
 
     #[error(transparent)]
     JoinError(#[from] tokio::task::JoinError),
 
-    #[cfg(feature = "git")]
-    #[error(transparent)]
-    DiscoveryError(#[from] gix::discover::Error),
-
     #[error("Error: {0}")]
     OtherError(String),
 }
@@ -0,0 +1,526 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read},
+    path::Path,
+    pin::Pin,
+};
+
+use async_stream::stream;
+use async_trait::async_trait;
+use futures::Stream;
+use serde_json::Value;
+
+use crate::{
+    document_loaders::{process_doc_stream, Loader, LoaderError, Metadata, TextSplitter},
+    schema::Document,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonFormat {
+    /// A single JSON value, parsed at once.
+    Json,
+    /// One JSON value per line, parsed line by line.
+    JsonLines,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    /// Every element of an array, or every value of an object.
+    Wildcard,
+}
+
+/// A path into a JSON value, in a subset of the jq and JSONPath syntax.
+///
+/// A path is a sequence of `.key`, `["key"]`, `[0]` and `[*]` segments, optionally preceded by
+/// `$`. `.*` and `[]` are the same as `[*]`, so `.items[].id`, `$.items[*].id` and `items.*.id`
+/// all select the ids of the elements of `items`. `.` or an empty path selects the value itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath(Vec<Segment>);
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self, LoaderError> {
+        let invalid = || LoaderError::OtherError(format!("Invalid JSON path: {}", path));
+        let mut segments = Vec::new();
+        let mut rest = path.trim().strip_prefix('$').unwrap_or(path.trim());
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('[') {
+                let after = after.trim_start();
+                if let Some(quote) = after.chars().next().filter(|c| matches!(c, '"' | '\'')) {
+                    // Quoted keys may contain `]`, so the closing quote is found first.
+                    let quoted = &after[1..];
+                    let end = quoted.find(quote).ok_or_else(invalid)?;
+                    segments.push(Segment::Key(quoted[..end].to_string()));
+                    rest = quoted[end + 1..]
+                        .trim_start()
+                        .strip_prefix(']')
+                        .ok_or_else(invalid)?;
+                } else {
+                    let end = after.find(']').ok_or_else(invalid)?;
+                    segments.push(match after[..end].trim() {
+                        "" | "*" => Segment::Wildcard,
+                        inner => Segment::Index(inner.parse().map_err(|_| invalid())?),
+                    });
+                    rest = &after[end + 1..];
+                }
+            } else {
+                let after = rest.strip_prefix('.').unwrap_or(rest);
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                match &after[..end] {
+                    "" if after.is_empty() || after.starts_with('[') => {}
+                    "" => return Err(invalid()),
+                    "*" => segments.push(Segment::Wildcard),
+                    key => segments.push(Segment::Key(key.to_string())),
+                }
+                rest = &after[end..];
+            }
+        }
+        Ok(Self(segments))
+    }
+
+    /// Whether the path may select more than one value.
+    pub fn is_multiple(&self) -> bool {
+        self.0.contains(&Segment::Wildcard)
+    }
+
+    /// The values the path selects in `value`. Missing keys and indexes select nothing.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut selected = vec![value];
+        for segment in &self.0 {
+            selected = selected
+                .into_iter()
+                .flat_map(|value| -> Vec<&'a Value> {
+                    match (segment, value) {
+                        (Segment::Key(key), Value::Object(object)) => {
+                            object.get(key).into_iter().collect()
+                        }
+                        (Segment::Index(index), Value::Array(array)) => {
+                            array.get(*index).into_iter().collect()
+                        }
+                        (Segment::Wildcard, Value::Array(array)) => array.iter().collect(),
+                        (Segment::Wildcard, Value::Object(object)) => object.values().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        selected
+    }
+
+    /// The value the path selects in `value`: an array of the matches if the path has a
+    /// wildcard, the match otherwise.
+    fn value(&self, value: &Value) -> Option<Value> {
+        let selected = self.select(value);
+        if self.is_multiple() {
+            Some(Value::from(
+                selected.into_iter().cloned().collect::<Vec<_>>(),
+            ))
+        } else {
+            selected.first().map(|v| (*v).clone())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum TemplatePart {
+    Text(String),
+    Path(JsonPath),
+}
+
+/// How the `page_content` of a document is made from its record.
+#[derive(Debug, Clone)]
+enum Content {
+    /// The whole record.
+    Record,
+    Path(JsonPath),
+    Template(Vec<TemplatePart>),
+}
+
+/// Strings are used as they are, other values as JSON text.
+fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, LoaderError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut path = String::new();
+                let mut quote = None;
+                loop {
+                    let c = chars.next().ok_or_else(|| {
+                        LoaderError::OtherError(format!("Unclosed `{{` in template: {}", template))
+                    })?;
+                    match (c, quote) {
+                        ('}', None) => break,
+                        ('"' | '\'', None) => quote = Some(c),
+                        (c, Some(q)) if c == q => quote = None,
+                        _ => {}
+                    }
+                    path.push(c);
+                }
+                parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                parts.push(TemplatePart::Path(JsonPath::parse(&path)?));
+            }
+            c => text.push(c),
+        }
+    }
+    parts.push(TemplatePart::Text(text));
+    Ok(parts)
+}
+
+/// Loads JSON or JSON Lines, making a document of every record a selector picks.
+///
+/// JSON Lines are read one line at a time, so files larger than memory can be loaded. Each
+/// document has a `seq_num` in its metadata, counting the records from 1, and for JSON Lines the
+/// `line` of its record.
+#[derive(Debug, Clone)]
+pub struct JsonLoader<R> {
+    reader: R,
+    format: JsonFormat,
+    records: JsonPath,
+    content: Content,
+    metadata: Vec<(String, JsonPath)>,
+    source: Option<String>,
+}
+
+impl<R: Read> JsonLoader<R> {
+    pub fn new(reader: R, format: JsonFormat) -> Self {
+        Self {
+            reader,
+            format,
+            records: JsonPath(Vec::new()),
+            content: Content::Record,
+            metadata: Vec::new(),
+            source: None,
+        }
+    }
+
+    /// The path of the records in each JSON value, such as `.messages[]`. Defaults to the value
+    /// itself.
+    pub fn with_records(mut self, selector: &str) -> Result<Self, LoaderError> {
+        self.records = JsonPath::parse(selector)?;
+        Ok(self)
+    }
+
+    /// The path of the `page_content` in each record. Defaults to the whole record, as JSON.
+    pub fn with_content_field(mut self, path: &str) -> Result<Self, LoaderError> {
+        self.content = Content::Path(JsonPath::parse(path)?);
+        Ok(self)
+    }
+
+    /// Makes the `page_content` from a template such as `"{subject}\n\n{body.text}"`, in which
+    /// every `{path}` is replaced by the value at that path in the record. `{{` and `}}` are
+    /// literal braces.
+    pub fn with_content_template(mut self, template: &str) -> Result<Self, LoaderError> {
+        self.content = Content::Template(parse_template(template)?);
+        Ok(self)
+    }
+
+    /// Stores the value at `path` in each record as `key` in the metadata. Records without a
+    /// value at `path` have no `key`.
+    pub fn with_metadata_field<S: Into<String>>(
+        mut self,
+        key: S,
+        path: &str,
+    ) -> Result<Self, LoaderError> {
+        self.metadata.push((key.into(), JsonPath::parse(path)?));
+        Ok(self)
+    }
+
+    /// Stored as `source` in the metadata of every document.
+    pub fn with_source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl JsonLoader<Cursor<Vec<u8>>> {
+    pub fn from_string<S: Into<String>>(input: S, format: JsonFormat) -> Self {
+        let input = input.into();
+        let reader = Cursor::new(input.into_bytes());
+        Self::new(reader, format)
+    }
+}
+
+impl JsonLoader<BufReader<File>> {
+    /// Files ending in `.jsonl` or `.ndjson` are read as JSON Lines, the others as JSON.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoaderError> {
+        let path = path.as_ref();
+        let format = match path.extension().and_then(|e| e.to_str()) {
+            Some("jsonl" | "ndjson") => JsonFormat::JsonLines,
+            _ => JsonFormat::Json,
+        };
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        Ok(Self::new(reader, format).with_source(path.to_string_lossy()))
+    }
+}
+
+/// Turns the records of one JSON value into documents.
+#[derive(Debug)]
+struct RecordMapper {
+    records: JsonPath,
+    content: Content,
+    metadata: Vec<(String, JsonPath)>,
+    source: Option<String>,
+    seq_num: u64,
+}
+
+impl RecordMapper {
+    fn documents(
+        &mut self,
+        value: &Value,
+        line: Option<u64>,
+    ) -> Vec<Result<Document<Metadata>, LoaderError>> {
+        let records = self.records.select(value);
+        records
+            .into_iter()
+            .map(|record| {
+                self.seq_num += 1;
+                self.document(record, line)
+            })
+            .collect()
+    }
+
+    fn document(
+        &self,
+        record: &Value,
+        line: Option<u64>,
+    ) -> Result<Document<Metadata>, LoaderError> {
+        let page_content = match &self.content {
+            Content::Record => value_to_text(record),
+            Content::Path(path) => {
+                path.value(record)
+                    .map(|v| value_to_text(&v))
+                    .ok_or_else(|| {
+                        LoaderError::LoadDocumentError(format!(
+                            "record {} has no content",
+                            self.seq_num
+                        ))
+                    })?
+            }
+            Content::Template(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    TemplatePart::Text(text) => text.clone(),
+                    TemplatePart::Path(path) => path
+                        .value(record)
+                        .map_or(String::new(), |v| value_to_text(&v)),
+                })
+                .collect(),
+        };
+
+        let mut metadata: Metadata = self
+            .metadata
+            .iter()
+            .filter_map(|(key, path)| Some((key.clone(), path.value(record)?)))
+            .collect();
+        metadata.insert("seq_num".to_string(), Value::from(self.seq_num));
+        if let Some(line) = line {
+            metadata.insert("line".to_string(), Value::from(line));
+        }
+        if let Some(source) = &self.source {
+            metadata.insert("source".to_string(), Value::from(source.as_str()));
+        }
+        Ok(Document::new(page_content).with_metadata(metadata))
+    }
+}
+
+#[async_trait]
+impl<R: Read + Send + Sync + 'static> Loader for JsonLoader<R> {
+    async fn load(
+        mut self,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let mut mapper = RecordMapper {
+            records: self.records,
+            content: self.content,
+            metadata: self.metadata,
+            source: self.source,
+            seq_num: 0,
+        };
+
+        if self.format == JsonFormat::Json {
+            let value: Value = serde_json::from_reader(self.reader)
+                .map_err(|e| LoaderError::LoadDocumentError(format!("Invalid JSON: {}", e)))?;
+            let stream = futures::stream::iter(mapper.documents(&value, None));
+            return Ok(Box::pin(stream));
+        }
+
+        let reader = BufReader::new(self.reader);
+        let stream = stream! {
+            for (index, line) in reader.lines().enumerate() {
+                let line_number = index as u64 + 1;
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<Value>(&line) {
+                    Ok(value) => {
+                        for document in mapper.documents(&value, Some(line_number)) {
+                            yield document;
+                        }
+                    }
+                    Err(e) => yield Err(LoaderError::LoadDocumentError(format!(
+                        "Invalid JSON on line {}: {}",
+                        line_number, e
+                    ))),
+                }
+            }
+        };
+        Ok(Box::pin(stream))
+    }
+
+    async fn load_and_split<TS: TextSplitter + 'static>(
+        self,
+        splitter: TS,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let doc_stream = self.load().await?;
+        let stream = process_doc_stream(doc_stream, splitter).await;
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_json_path() {
+        let value = json!({
+            "items": [{"id": 1, "tags": ["a"]}, {"id": 2, "tags": []}],
+            "with space": true,
+            "a]b": {"c}d": 3}
+        });
+        let select = |path: &str| -> Vec<Value> {
+            JsonPath::parse(path)
+                .unwrap()
+                .select(&value)
+                .into_iter()
+                .cloned()
+                .collect()
+        };
+        assert_eq!(select(".items[].id"), vec![json!(1), json!(2)]);
+        assert_eq!(select("$.items[*].id"), vec![json!(1), json!(2)]);
+        assert_eq!(select("items.*.id"), vec![json!(1), json!(2)]);
+        assert_eq!(select(".items[1].id"), vec![json!(2)]);
+        assert_eq!(select("[\"with space\"]"), vec![json!(true)]);
+        assert_eq!(select("[\"a]b\"][ 'c}d' ]"), vec![json!(3)]);
+        assert_eq!(select("."), vec![value.clone()]);
+        assert!(select(".items[5]").is_empty());
+        assert!(JsonPath::parse(".items[x]").is_err());
+        assert!(JsonPath::parse("a..b").is_err());
+        assert!(JsonPath::parse("[\"a]\"x]").is_err());
+        assert!(JsonPath::parse("[\"a]").is_err());
+    }
+
+    #[test]
+    fn test_parse_template() {
+        let value = json!({"subject": "Hi", "a}b": 1});
+        let render = |template: &str| -> String {
+            parse_template(template)
+                .unwrap()
+                .iter()
+                .map(|part| match part {
+                    TemplatePart::Text(text) => text.clone(),
+                    TemplatePart::Path(path) => path
+                        .value(&value)
+                        .map_or(String::new(), |v| value_to_text(&v)),
+                })
+                .collect()
+        };
+        assert_eq!(render("{{{subject}}}: {[\"a}b\"]}"), "{Hi}: 1");
+        assert!(parse_template("{subject").is_err());
+        assert!(parse_template("{[\"a}b\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_lines_loader() {
+        let input = r#"{"ticket": 7, "subject": "Login", "messages": [{"from": "ann", "text": "Can't log in"}, {"from": "bob", "text": "Reset it"}]}
+
+{"ticket": 8, "subject": "Billing", "messages": [{"from": "cy", "text": "Charged twice"}]}
+not json
+"#;
+        let documents = JsonLoader::from_string(input, JsonFormat::JsonLines)
+            .with_records(".messages[]")
+            .unwrap()
+            .with_content_template("{from}: {text}")
+            .unwrap()
+            .with_metadata_field("author", ".from")
+            .unwrap()
+            .load()
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(documents.len(), 4);
+        let document = documents[2].as_ref().unwrap();
+        assert_eq!(document.page_content, "cy: Charged twice");
+        let metadata = document.metadata.as_ref().unwrap();
+        assert_eq!(metadata["author"], json!("cy"));
+        assert_eq!(metadata["line"], json!(3));
+        assert_eq!(metadata["seq_num"], json!(3));
+        assert!(matches!(
+            documents[3],
+            Err(LoaderError::LoadDocumentError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_json_loader() {
+        let input = r#"{"tickets": [{"subject": "Login", "body": {"text": "Can't log in"}, "tags": ["auth"]}, {"subject": "Billing"}]}"#;
+        let documents = JsonLoader::from_string(input, JsonFormat::Json)
+            .with_records("$.tickets[*]")
+            .unwrap()
+            .with_content_field("body.text")
+            .unwrap()
+            .with_metadata_field("tags", ".tags")
+            .unwrap()
+            .load()
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(documents.len(), 2);
+        let document = documents[0].as_ref().unwrap();
+        assert_eq!(document.page_content, "Can't log in");
+        assert_eq!(document.metadata.as_ref().unwrap()["tags"], json!(["auth"]));
+        assert!(documents[1].is_err());
+
+        let documents = JsonLoader::from_string(r#"[{"a": 1}]"#, JsonFormat::Json)
+            .with_records("[]")
+            .unwrap()
+            .load()
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(documents[0].as_ref().unwrap().page_content, r#"{"a":1}"#);
+    }
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod json_loader;
+pub use json_loader::*;
@@ -0,0 +1,378 @@
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read},
+    path::Path,
+    pin::Pin,
+};
+
+use async_trait::async_trait;
+use futures::{stream, Stream};
+use markdown::{mdast::Node, Constructs, ParseOptions};
+use serde_json::Value;
+
+use crate::{
+    document_loaders::{process_doc_stream, Loader, LoaderError, Metadata, TextSplitter},
+    schema::Document,
+};
+
+/// Splits Markdown into one document per section, following the heading hierarchy.
+///
+/// The YAML or TOML front matter is lifted into the metadata of every chunk, together with a
+/// `header_path` holding the titles of the headings the chunk is under. Blocks are never cut:
+/// code blocks, tables, lists and quotes always end up whole in a single chunk.
+#[derive(Debug, Clone)]
+pub struct MarkdownSplitter {
+    split_depth: u8,
+    chunk_size: Option<usize>,
+    include_headings: bool,
+}
+
+impl Default for MarkdownSplitter {
+    fn default() -> Self {
+        Self {
+            split_depth: 6,
+            chunk_size: None,
+            include_headings: true,
+        }
+    }
+}
+
+impl MarkdownSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Headings down to this depth start a new chunk; deeper ones stay in the text of their
+    /// section. Defaults to 6, every heading.
+    pub fn with_split_depth(mut self, split_depth: u8) -> Self {
+        self.split_depth = split_depth;
+        self
+    }
+
+    /// Sections longer than `chunk_size` characters are split between their blocks. A block
+    /// longer than that, such as a large table, still makes a chunk of its own.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Whether the heading of a section starts the text of its first chunk. Defaults to true.
+    pub fn with_headings(mut self, include_headings: bool) -> Self {
+        self.include_headings = include_headings;
+        self
+    }
+
+    pub fn split(&self, markdown: &str) -> Result<Vec<Document<Metadata>>, LoaderError> {
+        let options = ParseOptions {
+            constructs: Constructs {
+                frontmatter: true,
+                ..Constructs::gfm()
+            },
+            ..ParseOptions::gfm()
+        };
+        let root = markdown::to_mdast(markdown, &options)
+            .map_err(|e| LoaderError::OtherError(format!("Invalid markdown: {}", e)))?;
+
+        let mut front_matter = Metadata::new();
+        let mut header_path: Vec<(u8, String)> = Vec::new();
+        let mut blocks: Vec<&str> = Vec::new();
+        let mut documents = Vec::new();
+        for node in root.children().into_iter().flatten() {
+            let text = node
+                .position()
+                .map_or("", |p| &markdown[p.start.offset..p.end.offset]);
+            match node {
+                Node::Yaml(yaml) => front_matter = parse_front_matter(&yaml.value)?,
+                Node::Toml(toml) => front_matter = parse_toml_front_matter(&toml.value)?,
+                Node::Heading(heading) if heading.depth <= self.split_depth => {
+                    self.push_section(&mut documents, &front_matter, &header_path, &blocks);
+                    blocks.clear();
+                    header_path.retain(|(depth, _)| *depth < heading.depth);
+                    header_path.push((heading.depth, node.to_string()));
+                    if self.include_headings {
+                        blocks.push(text);
+                    }
+                }
+                _ => blocks.push(text),
+            }
+        }
+        self.push_section(&mut documents, &front_matter, &header_path, &blocks);
+        Ok(documents)
+    }
+
+    fn push_section(
+        &self,
+        documents: &mut Vec<Document<Metadata>>,
+        front_matter: &Metadata,
+        header_path: &[(u8, String)],
+        blocks: &[&str],
+    ) {
+        // A heading directly followed by another one has no text of its own.
+        let has_body = blocks.len() > usize::from(self.include_headings && !header_path.is_empty());
+        if !has_body {
+            return;
+        }
+        let mut metadata = front_matter.clone();
+        metadata.insert(
+            "header_path".to_string(),
+            Value::from(
+                header_path
+                    .iter()
+                    .map(|(_, title)| title.clone())
+                    .collect::<Vec<_>>(),
+            ),
+        );
+
+        let mut chunk = String::new();
+        for block in blocks {
+            let too_long = self
+                .chunk_size
+                .is_some_and(|size| chunk.chars().count() + block.chars().count() + 2 > size);
+            if too_long && !chunk.is_empty() {
+                documents.push(
+                    Document::new(std::mem::take(&mut chunk)).with_metadata(metadata.clone()),
+                );
+            }
+            if !chunk.is_empty() {
+                chunk.push_str("\n\n");
+            }
+            chunk.push_str(block);
+        }
+        documents.push(Document::new(chunk).with_metadata(metadata));
+    }
+}
+
+/// Front matter that is not a mapping is kept whole under `front_matter`.
+fn parse_front_matter(yaml: &str) -> Result<Metadata, LoaderError> {
+    let value: Value = serde_yaml::from_str(yaml)
+        .map_err(|e| LoaderError::OtherError(format!("Invalid front matter: {}", e)))?;
+    Ok(match value {
+        Value::Object(object) => object.into_iter().collect(),
+        Value::Null => Metadata::new(),
+        other => Metadata::from([("front_matter".to_string(), other)]),
+    })
+}
+
+/// TOML front matter is always a table. Dates and times are kept as RFC 3339 strings.
+fn parse_toml_front_matter(toml: &str) -> Result<Metadata, LoaderError> {
+    let table: toml::Table = toml::from_str(toml)
+        .map_err(|e| LoaderError::OtherError(format!("Invalid front matter: {}", e)))?;
+    Ok(table
+        .into_iter()
+        .map(|(key, value)| (key, toml_to_json(value)))
+        .collect())
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::from(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::from(b),
+        toml::Value::Datetime(datetime) => Value::from(datetime.to_string()),
+        toml::Value::Array(array) => array.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Loads Markdown without going through pandoc, split into sections by a [`MarkdownSplitter`].
+#[derive(Debug, Clone)]
+pub struct MarkdownLoader<R> {
+    reader: R,
+    splitter: MarkdownSplitter,
+    source: Option<String>,
+}
+
+impl<R: Read> MarkdownLoader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            splitter: MarkdownSplitter::default(),
+            source: None,
+        }
+    }
+
+    pub fn with_splitter(mut self, splitter: MarkdownSplitter) -> Self {
+        self.splitter = splitter;
+        self
+    }
+
+    /// Stored as `source` in the metadata of every chunk.
+    pub fn with_source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl MarkdownLoader<Cursor<Vec<u8>>> {
+    pub fn from_string<S: Into<String>>(input: S) -> Self {
+        let input = input.into();
+        let reader = Cursor::new(input.into_bytes());
+        Self::new(reader)
+    }
+}
+
+impl MarkdownLoader<BufReader<File>> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoaderError> {
+        let source = path.as_ref().to_string_lossy().to_string();
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        Ok(Self::new(reader).with_source(source))
+    }
+}
+
+#[async_trait]
+impl<R: Read + Send + Sync + 'static> Loader for MarkdownLoader<R> {
+    async fn load(
+        mut self,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let mut markdown = String::new();
+        self.reader.read_to_string(&mut markdown)?;
+        let mut documents = self.splitter.split(&markdown)?;
+        if let Some(source) = &self.source {
+            for document in &mut documents {
+                document
+                    .metadata
+                    .get_or_insert_with(Metadata::new)
+                    .insert("source".to_string(), Value::from(source.as_str()));
+            }
+        }
+
+        let stream = stream::iter(documents.into_iter().map(Ok));
+        Ok(Box::pin(stream))
+    }
+
+    /// `splitter` is applied to the sections, so it may cut the blocks `load` keeps whole.
+    async fn load_and_split<TS: TextSplitter + 'static>(
+        self,
+        splitter: TS,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let doc_stream = self.load().await?;
+        let stream = process_doc_stream(doc_stream, splitter).await;
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    const INPUT: &str = r#"---
+title: Deploying
+tags: [ops, k8s]
+---
+
+Read this first.
+
+# Setup
+
+Install the CLI.
+
+## Configuration
+
+| key | default |
+| --- | ------- |
+| replicas | 2 |
+
+```yaml
+# not a heading
+replicas: 3
+```
+
+# Rollback
+
+Run `undo`.
+"#;
+
+    fn header_path(document: &Document<Metadata>) -> Vec<String> {
+        serde_json::from_value(document.metadata.as_ref().unwrap()["header_path"].clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_markdown_loader() {
+        let documents = MarkdownLoader::from_string(INPUT)
+            .with_source("deploying.md")
+            .load()
+            .await
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(documents.len(), 4);
+        assert_eq!(documents[0].page_content, "Read this first.");
+        assert!(header_path(&documents[0]).is_empty());
+        assert_eq!(documents[1].page_content, "# Setup\n\nInstall the CLI.");
+        assert_eq!(
+            header_path(&documents[2]),
+            vec!["Setup".to_string(), "Configuration".to_string()]
+        );
+        assert!(documents[2]
+            .page_content
+            .contains("| replicas | 2 |\n\n```yaml\n# not a heading\nreplicas: 3\n```"));
+        assert_eq!(header_path(&documents[3]), vec!["Rollback".to_string()]);
+        for document in &documents {
+            let metadata = document.metadata.as_ref().unwrap();
+            assert_eq!(metadata["title"], Value::from("Deploying"));
+            assert_eq!(metadata["tags"], serde_json::json!(["ops", "k8s"]));
+            assert_eq!(metadata["source"], Value::from("deploying.md"));
+        }
+    }
+
+    #[test]
+    fn test_toml_front_matter() {
+        let documents = MarkdownSplitter::new()
+            .split("+++\ntitle = \"Deploying\"\ndate = 2024-05-01\n[owner]\nteam = \"ops\"\n+++\n\nRead this first.")
+            .unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].page_content, "Read this first.");
+        let metadata = documents[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata["title"], Value::from("Deploying"));
+        assert_eq!(metadata["date"], Value::from("2024-05-01"));
+        assert_eq!(metadata["owner"], serde_json::json!({"team": "ops"}));
+        assert!(MarkdownSplitter::new()
+            .split("+++\ntitle =\n+++\n")
+            .is_err());
+    }
+
+    #[test]
+    fn test_split_depth_and_chunk_size() {
+        let documents = MarkdownSplitter::new()
+            .with_split_depth(1)
+            .with_headings(false)
+            .split(INPUT)
+            .unwrap();
+        assert_eq!(documents.len(), 3);
+        assert!(documents[1]
+            .page_content
+            .starts_with("Install the CLI.\n\n## Configuration"));
+
+        let documents = MarkdownSplitter::new()
+            .with_split_depth(1)
+            .with_chunk_size(40)
+            .split(INPUT)
+            .unwrap();
+        // The table and the code block are longer than a chunk, but are not cut.
+        let section: Vec<_> = documents
+            .iter()
+            .filter(|d| header_path(d) == vec!["Setup".to_string()])
+            .map(|d| d.page_content.as_str())
+            .collect();
+        assert_eq!(section.len(), 4);
+        assert!(section[2].starts_with("| key | default |"));
+        assert!(section[3].starts_with("```yaml") && section[3].ends_with("```"));
+    }
+}
@@ -0,0 +1,3 @@
+#[allow(clippy::module_inception)]
+mod markdown_loader;
+pub use markdown_loader::*;
@@ -0,0 +1,222 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+    pin::Pin,
+};
+
+use async_trait::async_trait;
+use futures::{stream, Stream};
+use serde_json::Value;
+
+use super::ooxml::{markdown_table, Element, Node, Package};
+use crate::{
+    document_loaders::{process_doc_stream, Loader, LoaderError, Metadata, TextSplitter},
+    schema::Document,
+};
+
+/// Loads the text of a Word document without going through pandoc.
+///
+/// The document is loaded as Markdown: headings become `#` headings, list items `-` items and
+/// tables Markdown tables, so the result can be split with a `MarkdownSplitter`. The `title` and
+/// `author` of the document are kept in the metadata.
+#[derive(Debug, Clone)]
+pub struct DocxLoader<R> {
+    reader: R,
+    source: Option<String>,
+}
+
+impl<R: Read + Seek> DocxLoader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            source: None,
+        }
+    }
+
+    /// Stored as `source` in the metadata of the document.
+    pub fn with_source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl DocxLoader<BufReader<File>> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoaderError> {
+        let source = path.as_ref().to_string_lossy().to_string();
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        Ok(Self::new(reader).with_source(source))
+    }
+}
+
+/// The text of a paragraph or of the runs under an element.
+fn run_text(element: &Element, text: &mut String) {
+    for node in &element.children {
+        if let Node::Element(child) = node {
+            match child.name.as_str() {
+                "t" => text.push_str(&child.text()),
+                "tab" => text.push('\t'),
+                "br" | "cr" => text.push('\n'),
+                // Deleted text, and the properties of paragraphs and runs.
+                "del" | "pPr" | "rPr" => {}
+                _ => run_text(child, text),
+            }
+        }
+    }
+}
+
+/// The heading level of a paragraph, from its outline level or its style.
+fn heading_level(paragraph: &Element) -> Option<usize> {
+    let properties = paragraph.child("pPr")?;
+    if let Some(level) = properties
+        .child("outlineLvl")
+        .and_then(|l| l.attr("val")?.parse::<usize>().ok())
+    {
+        return Some(level + 1).filter(|l| *l <= 6);
+    }
+    let style = properties.child("pStyle")?.attr("val")?.to_lowercase();
+    if style == "title" {
+        return Some(1);
+    }
+    style
+        .strip_prefix("heading")
+        .and_then(|level| level.trim().parse().ok())
+        .filter(|l| (1..=6).contains(l))
+}
+
+fn paragraph(element: &Element) -> Option<String> {
+    let mut text = String::new();
+    run_text(element, &mut text);
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let is_list_item = element
+        .child("pPr")
+        .is_some_and(|p| p.child("numPr").is_some());
+    Some(match heading_level(element) {
+        Some(level) => format!("{} {}", "#".repeat(level), text),
+        None if is_list_item => format!("- {}", text),
+        None => text.to_string(),
+    })
+}
+
+fn table(element: &Element) -> String {
+    let rows: Vec<Vec<String>> = element
+        .descendants("tr")
+        .into_iter()
+        .map(|row| {
+            row.descendants("tc")
+                .into_iter()
+                .map(|cell| {
+                    cell.descendants("p")
+                        .into_iter()
+                        .filter_map(paragraph)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect()
+        })
+        .collect();
+    markdown_table(&rows)
+}
+
+/// The blocks of the body of a document, or of a content control in it.
+fn blocks(element: &Element, blocks_text: &mut Vec<String>) {
+    for child in element.elements() {
+        match child.name.as_str() {
+            "p" => match (paragraph(child), blocks_text.last_mut()) {
+                // Consecutive list items make a single list.
+                (Some(item), Some(list))
+                    if item.starts_with("- ")
+                        && list.lines().last().is_some_and(|l| l.starts_with("- ")) =>
+                {
+                    list.push('\n');
+                    list.push_str(&item);
+                }
+                (Some(text), _) => blocks_text.push(text),
+                (None, _) => {}
+            },
+            "tbl" => blocks_text.push(table(child)),
+            "sdt" => {
+                if let Some(content) = child.child("sdtContent") {
+                    blocks(content, blocks_text);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[async_trait]
+impl<R: Read + Seek + Send + Sync + 'static> Loader for DocxLoader<R> {
+    async fn load(
+        self,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let mut package = Package::new(self.reader)?;
+        let document = package.required_part("word/document.xml")?;
+        let mut blocks_text = Vec::new();
+        if let Some(body) = document.child("body") {
+            blocks(body, &mut blocks_text);
+        }
+
+        let mut metadata = package.properties()?;
+        if let Some(source) = self.source {
+            metadata.insert("source".to_string(), Value::from(source));
+        }
+        let doc = Document::new(blocks_text.join("\n\n")).with_metadata(metadata);
+        let stream = stream::iter(vec![Ok(doc)]);
+        Ok(Box::pin(stream))
+    }
+
+    async fn load_and_split<TS: TextSplitter + 'static>(
+        self,
+        splitter: TS,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let doc_stream = self.load().await?;
+        let stream = process_doc_stream(doc_stream, splitter).await;
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_docx_loader() {
+        let path = "./src/document_loaders/test_data/sample.docx";
+        let loader = DocxLoader::from_path(path).expect("Failed to create docx loader");
+
+        let documents = loader
+            .load()
+            .await
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(documents.len(), 1);
+        let content = &documents[0].page_content;
+        assert!(content.starts_with("# Lorem ipsum\n\n# Lorem ipsum dolor sit amet"));
+        assert!(content.contains("- Nulla facilisi.\n- Aenean congue fringilla justo ut aliquam."));
+        assert!(content.contains(
+            "|  | Lorem ipsum | Lorem ipsum | Lorem ipsum |\n\
+             | --- | --- | --- | --- |\n\
+             | 1 | In eleifend velit vitae libero sollicitudin euismod. | Lorem |  |"
+        ));
+        assert_eq!(
+            documents[0].metadata.as_ref().unwrap()["source"],
+            Value::from(path)
+        );
+    }
+}
@@ -0,0 +1,10 @@
+mod ooxml;
+
+mod docx_loader;
+pub use docx_loader::*;
+
+mod xlsx_loader;
+pub use xlsx_loader::*;
+
+mod pptx_loader;
+pub use pptx_loader::*;
@@ -0,0 +1,350 @@
+//! Reading the parts of Office Open XML packages: zip archives of XML files that point to each
+//! other through relationship files.
+
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use serde_json::Value;
+use xmlparser::{ElementEnd, Token, Tokenizer};
+use zip::{result::ZipError, ZipArchive};
+
+use crate::document_loaders::{LoaderError, Metadata};
+
+/// An XML element, with its name stripped of its prefix.
+#[derive(Debug, Default)]
+pub(crate) struct Element {
+    pub name: String,
+    attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    /// The value of the attribute `name`, such as `r:id`, or of the first one with that name
+    /// without its prefix, such as `val` for `w:val`.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        let local = |n: &str| n.rsplit_once(':').map_or(n, |(_, local)| local).to_string();
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .or_else(|| self.attributes.iter().find(|(n, _)| local(n) == name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|e| e.name == name)
+    }
+
+    /// The elements named `name` under this one, in document order, without looking inside them.
+    pub fn descendants<'a>(&'a self, name: &str) -> Vec<&'a Element> {
+        self.descendants_named(&[name])
+    }
+
+    /// The elements with any of `names` under this one, in document order, without looking inside
+    /// them.
+    pub fn descendants_named<'a>(&'a self, names: &[&str]) -> Vec<&'a Element> {
+        let mut found = Vec::new();
+        for element in self.elements() {
+            if names.contains(&element.name.as_str()) {
+                found.push(element);
+            } else {
+                found.extend(element.descendants_named(names));
+            }
+        }
+        found
+    }
+
+    /// The text of the element, including that of the elements under it.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.children {
+            match node {
+                Node::Text(t) => text.push_str(t),
+                Node::Element(element) => text.push_str(&element.text()),
+            }
+        }
+        text
+    }
+}
+
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Parses `xml` into its root element.
+pub(crate) fn parse(xml: &str) -> Result<Element, LoaderError> {
+    let invalid = |e: xmlparser::Error| LoaderError::OtherError(format!("Invalid XML: {}", e));
+    // The root is kept under a placeholder, so that the stack is never empty.
+    let mut stack = vec![Element::default()];
+    for token in Tokenizer::from(xml) {
+        match token.map_err(invalid)? {
+            Token::ElementStart { local, .. } => stack.push(Element {
+                name: local.to_string(),
+                ..Default::default()
+            }),
+            Token::Attribute {
+                prefix,
+                local,
+                value,
+                ..
+            } => {
+                let name = match prefix.as_str() {
+                    "" => local.to_string(),
+                    prefix => format!("{}:{}", prefix, local),
+                };
+                if let Some(element) = stack.last_mut() {
+                    element.attributes.push((name, unescape(value.as_str())));
+                }
+            }
+            Token::ElementEnd { end, .. } => {
+                if let ElementEnd::Close(_, local) = end {
+                    if stack.last().map(|e| e.name.as_str()) != Some(local.as_str()) {
+                        return Err(LoaderError::OtherError(format!(
+                            "Invalid XML: unexpected closing tag {}",
+                            local
+                        )));
+                    }
+                }
+                if !matches!(end, ElementEnd::Open) && stack.len() > 1 {
+                    let element = stack.pop().unwrap_or_default();
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(Node::Element(element));
+                    }
+                }
+            }
+            Token::Text { text } => {
+                if let Some(element) = stack.last_mut() {
+                    element.children.push(Node::Text(unescape(text.as_str())));
+                }
+            }
+            Token::Cdata { text, .. } => {
+                if let Some(element) = stack.last_mut() {
+                    element.children.push(Node::Text(text.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    let document = stack
+        .pop()
+        .filter(|_| stack.is_empty())
+        .ok_or_else(|| LoaderError::OtherError("Invalid XML: unclosed element".to_string()))?;
+    document
+        .children
+        .into_iter()
+        .find_map(|node| match node {
+            Node::Element(root) => Some(root),
+            Node::Text(_) => None,
+        })
+        .ok_or_else(|| LoaderError::OtherError("XML without a root element".to_string()))
+}
+
+/// An Office package.
+pub(crate) struct Package<R> {
+    archive: ZipArchive<R>,
+}
+
+impl<R: Read + Seek> Package<R> {
+    pub fn new(reader: R) -> Result<Self, LoaderError> {
+        Ok(Self {
+            archive: ZipArchive::new(reader)?,
+        })
+    }
+
+    /// The part at `path`, parsed, or `None` if the package has no such part.
+    pub fn part(&mut self, path: &str) -> Result<Option<Element>, LoaderError> {
+        let mut xml = String::new();
+        match self.archive.by_name(path) {
+            Ok(mut file) => file.read_to_string(&mut xml)?,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        parse(&xml).map(Some)
+    }
+
+    pub fn required_part(&mut self, path: &str) -> Result<Element, LoaderError> {
+        self.part(path)?.ok_or_else(|| {
+            LoaderError::LoadDocumentError(format!("The document has no part {}", path))
+        })
+    }
+
+    /// The relationships of the part at `path`, by id, as their type and the path of their
+    /// target.
+    pub fn relationships(
+        &mut self,
+        path: &str,
+    ) -> Result<HashMap<String, (String, String)>, LoaderError> {
+        let (directory, file) = path.rsplit_once('/').unwrap_or(("", path));
+        let rels_path = if directory.is_empty() {
+            format!("_rels/{}.rels", file)
+        } else {
+            format!("{}/_rels/{}.rels", directory, file)
+        };
+        let Some(rels) = self.part(&rels_path)? else {
+            return Ok(HashMap::new());
+        };
+        Ok(rels
+            .elements()
+            .filter_map(|r| {
+                let target = resolve(directory, r.attr("Target")?);
+                Some((
+                    r.attr("Id")?.to_string(),
+                    (r.attr("Type").unwrap_or_default().to_string(), target),
+                ))
+            })
+            .collect())
+    }
+
+    /// The title and author of the document, from its core properties.
+    pub fn properties(&mut self) -> Result<Metadata, LoaderError> {
+        let mut properties = Metadata::new();
+        if let Some(core) = self.part("docProps/core.xml")? {
+            for (name, key) in [("title", "title"), ("creator", "author")] {
+                let value = core.child(name).map(|e| e.text());
+                if let Some(value) = value.filter(|v| !v.trim().is_empty()) {
+                    properties.insert(key.to_string(), Value::from(value.trim()));
+                }
+            }
+        }
+        Ok(properties)
+    }
+}
+
+/// The path in the package of `target`, relative to `directory` unless it starts with `/`.
+fn resolve(directory: &str, target: &str) -> String {
+    if let Some(absolute) = target.strip_prefix('/') {
+        return absolute.to_string();
+    }
+    let mut segments: Vec<&str> = directory.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                segments.pop();
+            }
+            "." | "" => {}
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Renders rows as a Markdown table, the first row being the header.
+pub(crate) fn markdown_table(rows: &[Vec<String>]) -> String {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let line = |cells: &[String]| {
+        let cells: Vec<String> = (0..columns)
+            .map(|i| {
+                cells
+                    .get(i)
+                    .map_or(String::new(), |c| c.replace('|', "\\|").replace('\n', " "))
+            })
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    for (i, row) in rows.iter().enumerate() {
+        lines.push(line(row));
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(columns)));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+pub(crate) fn package_of(parts: &[(&str, &str)]) -> std::io::Cursor<Vec<u8>> {
+    use std::io::Write;
+
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (path, xml) in parts {
+        writer
+            .start_file(*path, zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(xml.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let root = parse(
+            r#"<?xml version="1.0"?><w:p a:val="x &amp; y"><w:t>1 &lt; 2</w:t><w:br/><w:t><![CDATA[<3]]></w:t></w:p>"#,
+        )
+        .unwrap();
+        assert_eq!(root.name, "p");
+        assert_eq!(root.attr("val"), Some("x & y"));
+        assert_eq!(root.descendants("t").len(), 2);
+        assert_eq!(root.text(), "1 < 2<3");
+        assert!(parse("<a><b></a>").is_err());
+        assert_eq!(unescape("&#65;&#x42;&unknown;"), "AB&unknown;");
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            resolve("ppt/slides", "../notesSlides/n1.xml"),
+            "ppt/notesSlides/n1.xml"
+        );
+        assert_eq!(
+            resolve("xl", "worksheets/sheet1.xml"),
+            "xl/worksheets/sheet1.xml"
+        );
+        assert_eq!(
+            resolve("xl", "/xl/worksheets/sheet1.xml"),
+            "xl/worksheets/sheet1.xml"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+    pin::Pin,
+};
+
+use async_trait::async_trait;
+use futures::{stream, Stream};
+use serde_json::Value;
+
+use super::ooxml::{Element, Node, Package};
+use crate::{
+    document_loaders::{process_doc_stream, Loader, LoaderError, Metadata, TextSplitter},
+    schema::Document,
+};
+
+/// Loads PowerPoint presentations, one document per slide.
+///
+/// The text of a slide is followed by its speaker notes, unless they are turned off. Each
+/// document has the `slide` number, counting from 1, and the `title` of its slide in its metadata.
+#[derive(Debug, Clone)]
+pub struct PptxLoader<R> {
+    reader: R,
+    include_notes: bool,
+    source: Option<String>,
+}
+
+impl<R: Read + Seek> PptxLoader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            include_notes: true,
+            source: None,
+        }
+    }
+
+    /// Whether the speaker notes follow the text of their slide. Defaults to true.
+    pub fn with_notes(mut self, include_notes: bool) -> Self {
+        self.include_notes = include_notes;
+        self
+    }
+
+    /// Stored as `source` in the metadata of every document.
+    pub fn with_source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl PptxLoader<BufReader<File>> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoaderError> {
+        let source = path.as_ref().to_string_lossy().to_string();
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        Ok(Self::new(reader).with_source(source))
+    }
+}
+
+/// The type of the placeholder a shape fills, if any.
+fn placeholder_type(shape: &Element) -> Option<&str> {
+    let properties = shape.elements().find(|e| e.name.starts_with("nv"))?;
+    let placeholder = properties.child("nvPr")?.child("ph")?;
+    // Placeholders without a type are body placeholders.
+    Some(placeholder.attr("type").unwrap_or("body"))
+}
+
+/// The paragraphs under `element`, one per line, leaving out the empty ones.
+fn paragraphs(element: &Element) -> String {
+    element
+        .descendants("p")
+        .into_iter()
+        .filter_map(|paragraph| {
+            let mut text = String::new();
+            for node in &paragraph.children {
+                if let Node::Element(child) = node {
+                    match child.name.as_str() {
+                        "r" | "fld" => {
+                            text.push_str(&child.child("t").map(Element::text).unwrap_or_default())
+                        }
+                        "br" => text.push('\n'),
+                        _ => {}
+                    }
+                }
+            }
+            let text = text.trim();
+            (!text.is_empty()).then(|| text.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The shapes and graphic frames, such as tables, of a slide in reading order.
+fn shapes(slide: &Element) -> Vec<&Element> {
+    slide.descendants_named(&["sp", "graphicFrame"])
+}
+
+#[async_trait]
+impl<R: Read + Seek + Send + Sync + 'static> Loader for PptxLoader<R> {
+    async fn load(
+        self,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let mut package = Package::new(self.reader)?;
+        let presentation = package.required_part("ppt/presentation.xml")?;
+        let relationships = package.relationships("ppt/presentation.xml")?;
+        let properties = package.properties()?;
+
+        let mut documents = Vec::new();
+        let slide_ids = presentation
+            .child("sldIdLst")
+            .map(|list| {
+                list.elements()
+                    .filter_map(|s| s.attr("r:id"))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for (i, id) in slide_ids.into_iter().enumerate() {
+            let Some((_, path)) = relationships.get(id) else {
+                continue;
+            };
+            let slide = package.required_part(path)?;
+            let slide_shapes = shapes(&slide);
+            let title = slide_shapes
+                .iter()
+                .find(|s| matches!(placeholder_type(s), Some("title" | "ctrTitle")))
+                .map(|s| paragraphs(s))
+                .filter(|t| !t.is_empty());
+            let mut content = slide_shapes
+                .iter()
+                .map(|s| paragraphs(s))
+                .filter(|t| !t.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+
+            if self.include_notes {
+                let notes_path = package
+                    .relationships(path)?
+                    .into_values()
+                    .find(|(kind, _)| kind.ends_with("/notesSlide"))
+                    .map(|(_, target)| target);
+                if let Some(notes) = match notes_path {
+                    Some(notes_path) => package.part(&notes_path)?,
+                    None => None,
+                } {
+                    let notes = shapes(&notes)
+                        .into_iter()
+                        .filter(|s| placeholder_type(s) == Some("body"))
+                        .map(paragraphs)
+                        .filter(|t| !t.is_empty())
+                        .collect::<Vec<_>>()
+                        .join("\n\n");
+                    if !notes.is_empty() {
+                        content.push_str(&format!("\n\nNotes:\n{}", notes));
+                    }
+                }
+            }
+
+            let mut metadata = properties.clone();
+            metadata.insert("slide".to_string(), Value::from(i + 1));
+            match title {
+                Some(title) => metadata.insert("title".to_string(), Value::from(title)),
+                None => metadata.remove("title"),
+            };
+            if let Some(source) = &self.source {
+                metadata.insert("source".to_string(), Value::from(source.as_str()));
+            }
+            documents.push(Ok(Document::new(content).with_metadata(metadata)));
+        }
+
+        let stream = stream::iter(documents);
+        Ok(Box::pin(stream))
+    }
+
+    async fn load_and_split<TS: TextSplitter + 'static>(
+        self,
+        splitter: TS,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let doc_stream = self.load().await?;
+        let stream = process_doc_stream(doc_stream, splitter).await;
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::super::ooxml::package_of;
+    use super::*;
+
+    const PRESENTATION: &str = r#"<p:presentation xmlns:p="p" xmlns:r="r"><p:sldIdLst><p:sldId id="257" r:id="rId3"/><p:sldId id="256" r:id="rId2"/></p:sldIdLst></p:presentation>"#;
+    const RELS: &str = r#"<Relationships><Relationship Id="rId2" Type="slide" Target="slides/slide1.xml"/><Relationship Id="rId3" Type="slide" Target="slides/slide2.xml"/></Relationships>"#;
+    const SLIDE: &str = r#"<p:sld xmlns:p="p" xmlns:a="a"><p:cSld><p:spTree>
+<p:sp><p:nvSpPr><p:cNvPr id="2" name="Title 1"/><p:cNvSpPr/><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>Quarterly </a:t></a:r><a:r><a:t>results</a:t></a:r></a:p></p:txBody></p:sp>
+<p:graphicFrame><p:nvGraphicFramePr><p:cNvPr id="4" name="Table 3"/><p:cNvGraphicFramePr/><p:nvPr/></p:nvGraphicFramePr><a:graphic><a:graphicData><a:tbl><a:tr><a:tc><a:txBody><a:p><a:r><a:t>Q1</a:t></a:r></a:p></a:txBody></a:tc><a:tc><a:txBody><a:p><a:r><a:t>+4%</a:t></a:r></a:p></a:txBody></a:tc></a:tr></a:tbl></a:graphicData></a:graphic></p:graphicFrame>
+<p:sp><p:nvSpPr><p:cNvPr id="3" name="Content 2"/><p:cNvSpPr/><p:nvPr><p:ph idx="1"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>Revenue up</a:t></a:r></a:p><a:p/><a:p><a:r><a:t>Costs down</a:t></a:r></a:p></p:txBody></p:sp>
+</p:spTree></p:cSld></p:sld>"#;
+    const SLIDE_RELS: &str = r#"<Relationships><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesSlide" Target="../notesSlides/notesSlide1.xml"/></Relationships>"#;
+    const NOTES: &str = r#"<p:notes xmlns:p="p" xmlns:a="a"><p:cSld><p:spTree>
+<p:sp><p:nvSpPr><p:cNvPr id="2" name="Slide Image"/><p:cNvSpPr/><p:nvPr><p:ph type="sldImg"/></p:nvPr></p:nvSpPr></p:sp>
+<p:sp><p:nvSpPr><p:cNvPr id="3" name="Notes"/><p:cNvSpPr/><p:nvPr><p:ph type="body" idx="1"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>Mention the new office.</a:t></a:r></a:p></p:txBody></p:sp>
+</p:spTree></p:cSld></p:notes>"#;
+    const CLOSING: &str = r#"<p:sld xmlns:p="p" xmlns:a="a"><p:cSld><p:spTree><p:sp><p:nvSpPr><p:cNvPr id="2" name="Text"/><p:cNvSpPr/><p:nvPr/></p:nvSpPr><p:txBody><a:p><a:r><a:t>Thanks</a:t></a:r></a:p></p:txBody></p:sp></p:spTree></p:cSld></p:sld>"#;
+
+    fn presentation() -> std::io::Cursor<Vec<u8>> {
+        package_of(&[
+            ("ppt/presentation.xml", PRESENTATION),
+            ("ppt/_rels/presentation.xml.rels", RELS),
+            ("ppt/slides/slide1.xml", SLIDE),
+            ("ppt/slides/_rels/slide1.xml.rels", SLIDE_RELS),
+            ("ppt/notesSlides/notesSlide1.xml", NOTES),
+            ("ppt/slides/slide2.xml", CLOSING),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_pptx_loader() {
+        let documents = PptxLoader::new(presentation())
+            .load()
+            .await
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+
+        // Slides are in the order of the presentation, not of their files.
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].page_content, "Thanks");
+        let metadata = documents[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata["slide"], Value::from(1));
+        assert!(!metadata.contains_key("title"));
+        assert_eq!(
+            documents[1].page_content,
+            "Quarterly results\n\nQ1\n+4%\n\nRevenue up\nCosts down\n\nNotes:\nMention the new office."
+        );
+        assert_eq!(
+            documents[1].metadata.as_ref().unwrap()["title"],
+            Value::from("Quarterly results")
+        );
+
+        let documents = PptxLoader::new(presentation())
+            .with_notes(false)
+            .load()
+            .await
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            documents[1].page_content,
+            "Quarterly results\n\nQ1\n+4%\n\nRevenue up\nCosts down"
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+    pin::Pin,
+};
+
+use async_trait::async_trait;
+use futures::{stream, Stream};
+use serde_json::Value;
+
+use super::ooxml::{Element, Package};
+use crate::{
+    document_loaders::{process_doc_stream, Loader, LoaderError, Metadata, TextSplitter},
+    schema::Document,
+};
+
+/// Loads the rows of Excel workbooks, one document per row.
+///
+/// Like the `CsvLoader`, the first row of each sheet names the columns, and every other row makes
+/// a document with a `column: value` line per selected column. Each document has the `sheet` and
+/// the `row` number of its row in its metadata. Cells are loaded with the value Excel stored, so
+/// dates are numbers.
+#[derive(Debug, Clone)]
+pub struct XlsxLoader<R> {
+    reader: R,
+    columns: Option<Vec<String>>,
+    sheets: Option<Vec<String>>,
+    header_row: bool,
+    source: Option<String>,
+}
+
+impl<R: Read + Seek> XlsxLoader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            columns: None,
+            sheets: None,
+            header_row: true,
+            source: None,
+        }
+    }
+
+    /// The columns that go into the documents. Defaults to all of them.
+    pub fn with_columns(mut self, columns: Vec<String>) -> Self {
+        self.columns = Some(columns);
+        self
+    }
+
+    /// The sheets to load, by name. Defaults to all of them.
+    pub fn with_sheets(mut self, sheets: Vec<String>) -> Self {
+        self.sheets = Some(sheets);
+        self
+    }
+
+    /// Whether the first row of each sheet names the columns. Without a header row, the columns
+    /// are named by their letter. Defaults to true.
+    pub fn with_header_row(mut self, header_row: bool) -> Self {
+        self.header_row = header_row;
+        self
+    }
+
+    /// Stored as `source` in the metadata of every document.
+    pub fn with_source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl XlsxLoader<BufReader<File>> {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoaderError> {
+        let source = path.as_ref().to_string_lossy().to_string();
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        Ok(Self::new(reader).with_source(source))
+    }
+}
+
+/// The index of the column of a cell reference such as `AB12`.
+fn column_index(reference: &str) -> Option<usize> {
+    let letters = reference
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase() as usize - 'A' as usize + 1);
+    letters
+        .fold(None, |index: Option<usize>, letter| {
+            Some(index.unwrap_or(0) * 26 + letter)
+        })
+        .map(|index| index - 1)
+}
+
+fn column_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push((b'A' + (index % 26) as u8) as char);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.iter().rev().collect()
+}
+
+/// The text of a string item, leaving out its phonetic runs.
+fn string_item(item: &Element) -> String {
+    item.elements()
+        .map(|e| match e.name.as_str() {
+            "t" => e.text(),
+            "r" => e.child("t").map(Element::text).unwrap_or_default(),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+fn cell_value(cell: &Element, shared_strings: &[String]) -> String {
+    let value = cell.child("v").map(Element::text);
+    match cell.attr("t") {
+        Some("s") => value
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .and_then(|i| shared_strings.get(i).cloned())
+            .unwrap_or_default(),
+        Some("inlineStr") => cell.child("is").map(string_item).unwrap_or_default(),
+        Some("b") => match value.as_deref() {
+            Some("1") => "TRUE".to_string(),
+            Some(_) => "FALSE".to_string(),
+            None => String::new(),
+        },
+        _ => value.unwrap_or_default(),
+    }
+}
+
+/// The rows of a sheet, with their number, as the values of their cells by column.
+fn rows(sheet: &Element, shared_strings: &[String]) -> Vec<(u64, Vec<String>)> {
+    let Some(data) = sheet.child("sheetData") else {
+        return Vec::new();
+    };
+    let mut rows = Vec::new();
+    for (i, row) in data.elements().filter(|e| e.name == "row").enumerate() {
+        let number = row
+            .attr("r")
+            .and_then(|r| r.parse().ok())
+            .unwrap_or(i as u64 + 1);
+        let mut values: Vec<String> = Vec::new();
+        for cell in row.elements().filter(|e| e.name == "c") {
+            let index = cell
+                .attr("r")
+                .and_then(column_index)
+                .unwrap_or(values.len());
+            if values.len() <= index {
+                values.resize(index + 1, String::new());
+            }
+            values[index] = cell_value(cell, shared_strings);
+        }
+        rows.push((number, values));
+    }
+    rows
+}
+
+#[async_trait]
+impl<R: Read + Seek + Send + Sync + 'static> Loader for XlsxLoader<R> {
+    async fn load(
+        self,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let mut package = Package::new(self.reader)?;
+        let workbook = package.required_part("xl/workbook.xml")?;
+        let relationships = package.relationships("xl/workbook.xml")?;
+        let shared_strings: Vec<String> = package
+            .part("xl/sharedStrings.xml")?
+            .map(|sst| {
+                sst.elements()
+                    .filter(|e| e.name == "si")
+                    .map(string_item)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let properties = package.properties()?;
+
+        let mut documents = Vec::new();
+        for sheet in workbook.descendants("sheet") {
+            let name = sheet.attr("name").unwrap_or_default().to_string();
+            if self.sheets.as_ref().is_some_and(|s| !s.contains(&name)) {
+                continue;
+            }
+            let Some((_, path)) = sheet.attr("r:id").and_then(|id| relationships.get(id)) else {
+                continue;
+            };
+            let sheet_rows = rows(&package.required_part(path)?, &shared_strings);
+
+            let mut sheet_rows = sheet_rows.into_iter();
+            let headers: Vec<String> = match self.header_row {
+                true => sheet_rows.next().map(|(_, h)| h).unwrap_or_default(),
+                false => Vec::new(),
+            };
+            for (number, values) in sheet_rows {
+                let mut content = String::new();
+                for (i, value) in values.iter().enumerate() {
+                    let header = headers
+                        .get(i)
+                        .filter(|h| !h.is_empty())
+                        .cloned()
+                        .unwrap_or_else(|| column_name(i));
+                    if value.is_empty()
+                        || self.columns.as_ref().is_some_and(|c| !c.contains(&header))
+                    {
+                        continue;
+                    }
+                    content.push_str(&format!("{}: {}\n", header, value));
+                }
+                if content.is_empty() {
+                    continue;
+                }
+
+                let mut metadata = properties.clone();
+                metadata.insert("sheet".to_string(), Value::from(name.as_str()));
+                metadata.insert("row".to_string(), Value::from(number));
+                if let Some(source) = &self.source {
+                    metadata.insert("source".to_string(), Value::from(source.as_str()));
+                }
+                documents.push(Ok(Document::new(content).with_metadata(metadata)));
+            }
+        }
+
+        let stream = stream::iter(documents);
+        Ok(Box::pin(stream))
+    }
+
+    async fn load_and_split<TS: TextSplitter + 'static>(
+        self,
+        splitter: TS,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
+        LoaderError,
+    > {
+        let doc_stream = self.load().await?;
+        let stream = process_doc_stream(doc_stream, splitter).await;
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::super::ooxml::package_of;
+    use super::*;
+
+    const WORKBOOK: &str = r#"<workbook xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="People" sheetId="1" r:id="rId1"/><sheet name="Empty" sheetId="2" r:id="rId2"/></sheets></workbook>"#;
+    const RELS: &str = r#"<Relationships><Relationship Id="rId1" Type="worksheet" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Type="worksheet" Target="/xl/worksheets/sheet2.xml"/></Relationships>"#;
+    const SHARED_STRINGS: &str = r#"<sst><si><t>name</t></si><si><t>city</t></si><si><r><t>Jane </t></r><r><t>Smith</t></r></si><si><t>London</t></si></sst>"#;
+    const SHEET: &str = r#"<worksheet><sheetData>
+<row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c><c r="C1" t="inlineStr"><is><t>age</t></is></c></row>
+<row r="2"><c r="A2" t="s"><v>2</v></c><c r="C2"><v>32</v></c></row>
+<row r="4"><c r="B4" t="s"><v>3</v></c><c r="D4" t="b"><v>1</v></c></row>
+</sheetData></worksheet>"#;
+
+    fn workbook() -> std::io::Cursor<Vec<u8>> {
+        package_of(&[
+            ("xl/workbook.xml", WORKBOOK),
+            ("xl/_rels/workbook.xml.rels", RELS),
+            ("xl/sharedStrings.xml", SHARED_STRINGS),
+            ("xl/worksheets/sheet1.xml", SHEET),
+            (
+                "xl/worksheets/sheet2.xml",
+                "<worksheet><sheetData/></worksheet>",
+            ),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_xlsx_loader() {
+        let documents = XlsxLoader::new(workbook())
+            .load()
+            .await
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].page_content, "name: Jane Smith\nage: 32\n");
+        let metadata = documents[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata["sheet"], Value::from("People"));
+        assert_eq!(metadata["row"], Value::from(2));
+        assert_eq!(documents[1].page_content, "city: London\nD: TRUE\n");
+        assert_eq!(
+            documents[1].metadata.as_ref().unwrap()["row"],
+            Value::from(4)
+        );
+
+        let documents = XlsxLoader::new(workbook())
+            .with_columns(vec!["city".to_string()])
+            .load()
+            .await
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].page_content, "city: London\n");
+    }
+
+    #[test]
+    fn test_column_names() {
+        assert_eq!(column_index("A1"), Some(0));
+        assert_eq!(column_index("AB12"), Some(27));
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(27), "AB");
+        assert_eq!(column_name(701), "ZZ");
+    }
+}
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 mod text_loader;
 pub use text_loader::*;
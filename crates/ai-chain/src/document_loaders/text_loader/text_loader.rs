@@ -4,9 +4,8 @@ use async_trait::async_trait;
 use futures::{stream, Stream};
 
 use crate::{
-    document_loaders::{process_doc_stream, Loader, LoaderError},
-    schemas::Document,
-    text_splitter::TextSplitter,
+    document_loaders::{process_doc_stream, Loader, LoaderError, Metadata, TextSplitter},
+    schema::Document,
 };
 
 #[derive(Debug, Clone)]
@@ -25,9 +24,9 @@ impl TextLoader {
 #[async_trait]
 impl Loader for TextLoader {
     async fn load(
-        mut self,
+        self,
     ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<Document, LoaderError>> + Send + 'static>>,
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
         LoaderError,
     > {
         let doc = Document::new(self.content);
@@ -36,10 +35,10 @@ impl Loader for TextLoader {
     }
 
     async fn load_and_split<TS: TextSplitter + 'static>(
-        mut self,
+        self,
         splitter: TS,
     ) -> Result<
-        Pin<Box<dyn Stream<Item = Result<Document, LoaderError>> + Send + 'static>>,
+        Pin<Box<dyn Stream<Item = Result<Document<Metadata>, LoaderError>> + Send + 'static>>,
         LoaderError,
     > {
         let doc_stream = self.load().await?;
@@ -52,7 +51,8 @@ impl Loader for TextLoader {
 mod tests {
     use futures_util::StreamExt;
 
-    use crate::text_splitter::TokenSplitter;
+    use crate::chains::tests::ByteTokenizer;
+    use crate::document_loaders::TokenSplitter;
 
     use super::*;
 
@@ -78,14 +78,16 @@ The west of Texas divides the border between Mexico and New Mexico. It is very b
         }
 
         let loader = TextLoader::new(mocked_file_content.to_string());
-        let splitter = TokenSplitter::default();
-
-        let mut documents = loader.load_and_split(splitter).await.unwrap();
-
-        while let Some(doc) = documents.next().await {
-            println!("{:?}", doc.unwrap());
-            println!("/n");
-            println!("-----------------------");
-        }
+        let splitter = TokenSplitter::new(ByteTokenizer, 100, 0);
+
+        let chunks = loader
+            .load_and_split(splitter)
+            .await
+            .unwrap()
+            .map(|doc| doc.unwrap().page_content)
+            .collect::<Vec<_>>()
+            .await;
+        // One chunk per 100 bytes.
+        assert_eq!(chunks.len(), mocked_file_content.len().div_ceil(100));
     }
 }
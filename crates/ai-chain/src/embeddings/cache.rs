@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+/// The `EmbeddingCacheError` enum represents errors that can occur when reading or writing an
+/// embedding cache.
+#[derive(Debug, Error)]
+pub enum EmbeddingCacheError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Corrupt cache entry: {0}")]
+    Corrupt(String),
+}
+
+/// Stores embedding vectors by their [`cache_key`](super::cache_key).
+#[async_trait]
+pub trait EmbeddingCache: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Vec<f32>>, EmbeddingCacheError>;
+
+    async fn put(&self, key: &str, vector: &[f32]) -> Result<(), EmbeddingCacheError>;
+}
+
+/// Keeps vectors in memory for the life of the cache. Entries are never evicted.
+#[derive(Debug, Default)]
+pub struct InMemoryEmbeddingCache {
+    vectors: RwLock<HashMap<String, Vec<f32>>>,
+}
+
+impl InMemoryEmbeddingCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of cached vectors.
+    pub fn len(&self) -> usize {
+        self.vectors.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl EmbeddingCache for InMemoryEmbeddingCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<f32>>, EmbeddingCacheError> {
+        Ok(self.vectors.read().unwrap().get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, vector: &[f32]) -> Result<(), EmbeddingCacheError> {
+        self.vectors
+            .write()
+            .unwrap()
+            .insert(key.to_string(), vector.to_vec());
+        Ok(())
+    }
+}
+
+/// Keeps each vector in a file of its own in a directory, as little-endian `f32`s.
+///
+/// The directory is created on the first write. Entries are written to a temporary file first and
+/// then renamed, so a crash never leaves a partial entry behind.
+#[derive(Debug, Clone)]
+pub struct FileEmbeddingCache {
+    directory: PathBuf,
+}
+
+impl FileEmbeddingCache {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory.join(format!("{}.f32", key))
+    }
+}
+
+#[async_trait]
+impl EmbeddingCache for FileEmbeddingCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<f32>>, EmbeddingCacheError> {
+        let bytes = match tokio::fs::read(self.path(key)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if bytes.len() % 4 != 0 {
+            return Err(EmbeddingCacheError::Corrupt(key.to_string()));
+        }
+        Ok(Some(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        ))
+    }
+
+    async fn put(&self, key: &str, vector: &[f32]) -> Result<(), EmbeddingCacheError> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let temporary = self
+            .directory
+            .join(format!("{}.{}.tmp", key, uuid::Uuid::new_v4()));
+        tokio::fs::write(&temporary, bytes).await?;
+        tokio::fs::rename(&temporary, self.path(key)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_cache_round_trips_vectors() {
+        let directory = std::env::temp_dir().join(format!("ai-chain-{}", uuid::Uuid::new_v4()));
+        let cache = FileEmbeddingCache::new(&directory);
+        assert!(cache.get("a").await.unwrap().is_none());
+        cache.put("a", &[0.5, -1.25, 3.0]).await.unwrap();
+
+        let reopened = FileEmbeddingCache::new(&directory);
+        assert_eq!(
+            reopened.get("a").await.unwrap(),
+            Some(vec![0.5, -1.25, 3.0])
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
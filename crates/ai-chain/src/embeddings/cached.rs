@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use thiserror::Error;
+
+use super::{cache_key, EmbeddingCache, EmbeddingCacheError, InMemoryEmbeddingCache};
+use crate::traits::{Embeddings, EmbeddingsError};
+
+/// The `CachedEmbeddingsError` enum represents errors that can occur when embedding through
+/// `CachedEmbeddings`.
+#[derive(Debug, Error)]
+pub enum CachedEmbeddingsError<E>
+where
+    E: std::error::Error,
+{
+    #[error("EmbeddingsError: {0}")]
+    Embeddings(E),
+    #[error("EmbeddingCacheError: {0}")]
+    Cache(#[from] EmbeddingCacheError),
+    #[error("Expected {expected} embeddings, got {actual}")]
+    Count { expected: usize, actual: usize },
+}
+
+impl<E> EmbeddingsError for CachedEmbeddingsError<E> where E: std::error::Error {}
+
+/// Batches, retries and caches the requests of another `Embeddings`.
+///
+/// Vectors are cached by the hash of the model name and the text, so the model name must change
+/// whenever the inner embeddings compute different vectors.
+pub struct CachedEmbeddings<E, C = InMemoryEmbeddingCache> {
+    inner: E,
+    cache: C,
+    model: String,
+    batch_size: usize,
+    concurrency: usize,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl<E> CachedEmbeddings<E> {
+    /// Wraps `inner`, the embeddings of `model`, with an in-memory cache. Texts are embedded 100
+    /// at a time, four batches at once, and failed batches are retried twice.
+    pub fn new<S: Into<String>>(inner: E, model: S) -> Self {
+        Self {
+            inner,
+            cache: InMemoryEmbeddingCache::new(),
+            model: model.into(),
+            batch_size: 100,
+            concurrency: 4,
+            retries: 2,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl<E, C> CachedEmbeddings<E, C> {
+    /// Replaces the cache.
+    pub fn with_cache<D: EmbeddingCache>(self, cache: D) -> CachedEmbeddings<E, D> {
+        CachedEmbeddings {
+            inner: self.inner,
+            cache,
+            model: self.model,
+            batch_size: self.batch_size,
+            concurrency: self.concurrency,
+            retries: self.retries,
+            retry_delay: self.retry_delay,
+        }
+    }
+
+    /// Sets the most texts sent to the inner embeddings in one call.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets how many batches are embedded at the same time.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets how often a failed call is retried, and the delay before the first retry. The delay
+    /// doubles with every retry.
+    pub fn with_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+}
+
+impl<E, C> CachedEmbeddings<E, C>
+where
+    E: Embeddings + Sync,
+    C: EmbeddingCache,
+{
+    async fn with_retry<T, F, Fut>(&self, call: F) -> Result<T, CachedEmbeddingsError<E::Error>>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, E::Error>>,
+    {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match call().await {
+                Ok(value) => return Ok(value),
+                Err(_) if attempt < self.retries => {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(CachedEmbeddingsError::Embeddings(e)),
+            }
+        }
+    }
+
+    async fn embed_batch(
+        &self,
+        batch: Vec<String>,
+    ) -> Result<Vec<Vec<f32>>, CachedEmbeddingsError<E::Error>> {
+        let vectors = self
+            .with_retry(|| self.inner.embed_texts(batch.clone()))
+            .await?;
+        if vectors.len() != batch.len() {
+            return Err(CachedEmbeddingsError::Count {
+                expected: batch.len(),
+                actual: vectors.len(),
+            });
+        }
+        Ok(vectors)
+    }
+}
+
+#[async_trait]
+impl<E, C> Embeddings for CachedEmbeddings<E, C>
+where
+    E: Embeddings + Send + Sync,
+    C: EmbeddingCache,
+{
+    type Error = CachedEmbeddingsError<E::Error>;
+
+    async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Self::Error> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        // The texts missing from the cache, each once, with the positions they fill.
+        let mut missing: Vec<(String, String)> = Vec::new();
+        let mut positions: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, text) in texts.into_iter().enumerate() {
+            let key = cache_key(&self.model, "document", &text);
+            if !positions.contains_key(&key) {
+                if let Some(vector) = self.cache.get(&key).await? {
+                    vectors.push(Some(vector));
+                    continue;
+                }
+                missing.push((key.clone(), text));
+            }
+            positions.entry(key).or_default().push(i);
+            vectors.push(None);
+        }
+
+        // Every batch is cached as soon as it is embedded, so a failed call only loses the
+        // batches that failed. No new batch is started after a failure.
+        let failed = &AtomicBool::new(false);
+        let batches: Vec<Vec<(String, String)>> =
+            missing.chunks(self.batch_size).map(<[_]>::to_vec).collect();
+        let mut embedded = futures::stream::iter(batches)
+            .map(|batch| async move {
+                if failed.load(Ordering::SeqCst) {
+                    return (batch, None);
+                }
+                let texts = batch.iter().map(|(_, text)| text.clone()).collect();
+                let result = self.embed_batch(texts).await;
+                (batch, Some(result))
+            })
+            .buffer_unordered(self.concurrency);
+        let mut error = None;
+        while let Some((batch, result)) = embedded.next().await {
+            match result {
+                None => {}
+                Some(Ok(batch_vectors)) => {
+                    for ((key, _), vector) in batch.iter().zip(batch_vectors) {
+                        self.cache.put(key, &vector).await?;
+                        for &i in &positions[key] {
+                            vectors[i] = Some(vector.clone());
+                        }
+                    }
+                }
+                Some(Err(e)) => {
+                    failed.store(true, Ordering::SeqCst);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        if let Some(e) = error {
+            return Err(e);
+        }
+        Ok(vectors.into_iter().map(Option::unwrap_or_default).collect())
+    }
+
+    async fn embed_query(&self, query: String) -> Result<Vec<f32>, Self::Error> {
+        let key = cache_key(&self.model, "query", &query);
+        if let Some(vector) = self.cache.get(&key).await? {
+            return Ok(vector);
+        }
+        let vector = self
+            .with_retry(|| self.inner.embed_query(query.clone()))
+            .await?;
+        self.cache.put(&key, &vector).await?;
+        Ok(vector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::chains::tests::StoreError;
+
+    /// Embeds each text as its length, and fails the first `failures` calls and every batch
+    /// holding the text "fail".
+    #[derive(Default)]
+    struct LengthEmbeddings {
+        batches: Mutex<Vec<usize>>,
+        failures: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Embeddings for LengthEmbeddings {
+        type Error = StoreError;
+
+        async fn embed_texts(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, StoreError> {
+            if self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+                || texts.iter().any(|t| t == "fail")
+            {
+                return Err(StoreError);
+            }
+            self.batches.lock().unwrap().push(texts.len());
+            Ok(texts.iter().map(|t| vec![t.len() as f32]).collect())
+        }
+
+        async fn embed_query(&self, query: String) -> Result<Vec<f32>, StoreError> {
+            Ok(vec![query.len() as f32])
+        }
+    }
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_caches_texts() {
+        let embeddings =
+            CachedEmbeddings::new(LengthEmbeddings::default(), "length").with_batch_size(2);
+        let vectors = embeddings
+            .embed_texts(texts(&["a", "bb", "a", "ccc", "dddd"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            vectors,
+            vec![vec![1.0], vec![2.0], vec![1.0], vec![3.0], vec![4.0]]
+        );
+        assert_eq!(*embeddings.inner().batches.lock().unwrap(), vec![2, 2]);
+
+        let vectors = embeddings
+            .embed_texts(texts(&["bb", "eeeee"]))
+            .await
+            .unwrap();
+        assert_eq!(vectors, vec![vec![2.0], vec![5.0]]);
+        assert_eq!(*embeddings.inner().batches.lock().unwrap(), vec![2, 2, 1]);
+        assert_eq!(embeddings.cache().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batches_are_retried() {
+        let inner = LengthEmbeddings {
+            failures: AtomicUsize::new(2),
+            ..Default::default()
+        };
+        let embeddings =
+            CachedEmbeddings::new(inner, "length").with_retries(2, Duration::from_millis(1));
+        assert_eq!(
+            embeddings.embed_texts(texts(&["a"])).await.unwrap(),
+            vec![vec![1.0]]
+        );
+
+        embeddings.inner().failures.store(2, Ordering::SeqCst);
+        let embeddings = embeddings.with_retries(1, Duration::from_millis(1));
+        assert!(matches!(
+            embeddings.embed_texts(texts(&["bb"])).await,
+            Err(CachedEmbeddingsError::Embeddings(StoreError))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batches_before_a_failure_stay_cached() {
+        let embeddings = CachedEmbeddings::new(LengthEmbeddings::default(), "length")
+            .with_batch_size(1)
+            .with_concurrency(1)
+            .with_retries(0, Duration::from_millis(1));
+        assert!(embeddings
+            .embed_texts(texts(&["a", "fail", "ccc"]))
+            .await
+            .is_err());
+        assert_eq!(*embeddings.inner().batches.lock().unwrap(), vec![1]);
+        assert_eq!(embeddings.cache().len(), 1);
+
+        let vectors = embeddings.embed_texts(texts(&["a", "ccc"])).await.unwrap();
+        assert_eq!(vectors, vec![vec![1.0], vec![3.0]]);
+        assert_eq!(*embeddings.inner().batches.lock().unwrap(), vec![1, 1]);
+    }
+}
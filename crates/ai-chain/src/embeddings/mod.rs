@@ -0,0 +1,31 @@
+//! Wrappers that make any `Embeddings` cheaper and more robust to use.
+//!
+//! [`CachedEmbeddings`] wraps another `Embeddings`. It splits large inputs into batches the
+//! provider accepts, embeds a few batches at a time, retries failed batches, and caches vectors so
+//! that a text is never embedded twice by the same model. The cache is an [`EmbeddingCache`]:
+//! [`InMemoryEmbeddingCache`] for the life of the process, or [`FileEmbeddingCache`] to keep the
+//! vectors across runs.
+
+use sha2::{Digest, Sha256};
+
+pub mod cache;
+pub mod cached;
+
+pub use cache::{EmbeddingCache, EmbeddingCacheError, FileEmbeddingCache, InMemoryEmbeddingCache};
+pub use cached::{CachedEmbeddings, CachedEmbeddingsError};
+
+/// The cache key of the embedding of `text` by `model`: the hex SHA-256 of both.
+///
+/// `kind` separates document and query embeddings, which some models compute differently.
+pub fn cache_key(model: &str, kind: &str, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [model, kind, text] {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -80,6 +80,26 @@ macro_rules! executor {
         use ai_chain::traits::Executor;
         ai_chain_qwen::chatgpt::Executor::new_with_options($options)
     }};
+    (anthropic) => {{
+        use ai_chain::traits::Executor;
+        ai_chain_anthropic::Executor::new()
+    }};
+    (anthropic, $options:expr) => {{
+        use ai_chain::traits::Executor;
+        ai_chain_anthropic::Executor::new_with_options($options)
+    }};
+    (candle, $options:expr) => {{
+        use ai_chain::traits::Executor;
+        ai_chain_candle::Executor::new_with_options($options)
+    }};
+    (ollama) => {{
+        use ai_chain::traits::Executor;
+        ai_chain_ollama::Executor::new()
+    }};
+    (ollama, $options:expr) => {{
+        use ai_chain::traits::Executor;
+        ai_chain_ollama::Executor::new_with_options($options)
+    }};
     (gemma, $options:expr) => {{
         use ai_chain::traits::Executor;
         ai_chain_gemma::Executor::new_with_options($options)
@@ -24,7 +24,9 @@
 // Core components
 pub mod agents;
 pub mod chains;
+pub mod document_loaders;
 pub mod document_stores;
+pub mod embeddings;
 pub mod executor;
 pub mod frame;
 pub mod options;
@@ -32,12 +34,14 @@ pub mod output;
 pub mod parameters;
 pub mod parsing;
 pub mod prompt;
+pub mod retrieval;
 pub mod schema;
 pub mod serialization;
 pub mod step;
 pub mod tokens;
 pub mod tools;
 pub mod traits;
+pub mod vector_dump;
 
 // Utilities and tools
 pub mod summarization;
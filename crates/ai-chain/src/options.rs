@@ -5,6 +5,7 @@ use std::{collections::HashMap, env::VarError, ffi::OsStr};
 use serde::{Deserialize, Serialize};
 use strum_macros::EnumDiscriminants;
 
+use crate::prompt::ChatTemplate;
 use crate::tokens::Token;
 
 /// A collection of options that can be used to configure a model.
@@ -317,6 +318,16 @@ impl ModelRef {
 pub struct TokenBias(Vec<(Token, f32)>); // TODO: Serialize to a JSON object of str(F32) =>
 
 impl TokenBias {
+    /// Creates a token bias from pairs of a token and the value added to its logit.
+    pub fn new(biases: Vec<(Token, f32)>) -> Self {
+        Self(biases)
+    }
+
+    /// Returns the biased tokens together with their bias.
+    pub fn iter(&self) -> impl Iterator<Item = &(Token, f32)> {
+        self.0.iter()
+    }
+
     /// Returns the token bias as a hashmap where the keys are i32 and the value f32. If the type doesn't match returns None
     pub fn as_i32_f32_hashmap(&self) -> Option<HashMap<i32, f32>> {
         let mut map = HashMap::new();
@@ -410,6 +421,8 @@ pub enum Opt {
     UseMmap(bool),
     // Force the system to keep the model in memory for ai-chain-llama.
     UseMlock(bool),
+    /// The chat template local models use to turn chat prompts into raw text.
+    ChatTemplate(ChatTemplate),
 }
 
 // Helper function to extract environment variables
@@ -466,6 +479,7 @@ opt_parse_str!(RepeatPenaltyLastN);
 opt_parse_str!(TfsZ);
 opt_parse_str!(PenalizeNl);
 opt_parse_str!(NBatch);
+opt_parse_str!(ChatTemplate);
 
 macro_rules! opt_from_env {
     ($opt:ident, $v:ident) => {
@@ -508,7 +522,8 @@ pub fn options_from_env() -> Result<Options, VarError> {
         RepeatPenaltyLastN,
         TfsZ,
         PenalizeNl,
-        NBatch
+        NBatch,
+        ChatTemplate
     );
     Ok(opts.build())
 }
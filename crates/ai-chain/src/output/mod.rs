@@ -7,6 +7,7 @@ use crate::{prompt::Data, traits::ExecutorError};
 use thiserror;
 use tokio::sync::mpsc;
 
+pub(crate) use stream::SegmentCollector;
 pub use stream::{OutputStream, StreamSegment};
 pub use tokio_stream::{Stream, StreamExt};
 
@@ -55,40 +55,56 @@ impl OutputStream {
     }
 
     pub(super) async fn into_data(self) -> Result<Data<String>, ExecutorError> {
-        let mut messages = ChatMessageCollection::new();
-        let mut current_role = None;
-        let mut current_body = Vec::new();
-
+        let mut collector = SegmentCollector::default();
         let mut stream = self.receiver;
-
         while let Some(segment) = stream.recv().await {
-            match segment {
-                StreamSegment::Role(role) => {
-                    if let Some(role) = current_role {
-                        if !current_body.is_empty() {
-                            let body = current_body.join("");
-                            messages.add_message(ChatMessage::new(role, body));
-                            current_body.clear();
-                        }
+            collector.push(segment)?;
+        }
+        Ok(collector.finish())
+    }
+}
+
+/// Assembles stream segments into the data they represent: a chat if the stream had roles,
+/// otherwise text.
+#[derive(Default)]
+pub(crate) struct SegmentCollector {
+    messages: ChatMessageCollection<String>,
+    current_role: Option<ChatRole>,
+    current_body: Vec<String>,
+}
+
+impl SegmentCollector {
+    /// Adds a segment, returning the error if it is an error segment.
+    pub(crate) fn push(&mut self, segment: StreamSegment) -> Result<(), ExecutorError> {
+        match segment {
+            StreamSegment::Role(role) => {
+                if let Some(role) = self.current_role.take() {
+                    if !self.current_body.is_empty() {
+                        let body = self.current_body.join("");
+                        self.messages.add_message(ChatMessage::new(role, body));
+                        self.current_body.clear();
                     }
-                    current_role = Some(role);
-                }
-                StreamSegment::Content(text) => {
-                    current_body.push(text);
                 }
-                StreamSegment::Err(err) => return Err(err),
+                self.current_role = Some(role);
             }
+            StreamSegment::Content(text) => {
+                self.current_body.push(text);
+            }
+            StreamSegment::Err(err) => return Err(err),
         }
+        Ok(())
+    }
 
-        let body = current_body.join("");
+    pub(crate) fn finish(mut self) -> Data<String> {
+        let body = self.current_body.join("");
         // Handle any remaining message
-        if let Some(role) = current_role {
-            if !current_body.is_empty() {
-                messages.add_message(ChatMessage::new(role, body));
+        if let Some(role) = self.current_role {
+            if !self.current_body.is_empty() {
+                self.messages.add_message(ChatMessage::new(role, body));
             }
-            Ok(messages.into())
+            self.messages.into()
         } else {
-            Ok(Data::text(body))
+            Data::text(body)
         }
     }
 }
@@ -234,6 +234,31 @@ pub fn extract_labeled_text(text: &str) -> Vec<(String, String)> {
     extracted_labels
 }
 
+/// Finds the numbers written as `[n]` in `text`, such as the citations of an answer or a ranking,
+/// and returns them as indices from 0 in the order they first appear. Numbers outside `1..=count`
+/// are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use ai_chain::parsing::find_numbered_references;
+/// assert_eq!(find_numbered_references("[3] > [1] > [3] > [9]", 3), vec![2, 0]);
+/// ```
+pub fn find_numbered_references(text: &str, count: usize) -> Vec<usize> {
+    let mut references = Vec::new();
+    for part in text.split('[').skip(1) {
+        let Some((number, _)) = part.split_once(']') else {
+            continue;
+        };
+        if let Ok(n) = number.trim().parse::<usize>() {
+            if (1..=count).contains(&n) && !references.contains(&(n - 1)) {
+                references.push(n - 1);
+            }
+        }
+    }
+    references
+}
+
 /// Finds labeled text
 ///
 /// This function looks for patterns such as `**label**: text.
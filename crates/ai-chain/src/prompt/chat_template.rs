@@ -0,0 +1,310 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use tera::{Context, Tera};
+use thiserror::Error;
+
+use super::{ChatRole, Conversation, Data, Prompt};
+
+/// An error rendering a chat template.
+#[derive(Debug, Error)]
+pub enum ChatTemplateError {
+    #[error("unable to render the chat template: {0}")]
+    Render(#[from] tera::Error),
+    #[error("unknown chat template `{0}`")]
+    UnknownTemplate(String),
+}
+
+const CHATML: &str = "{% for message in messages %}<|im_start|>{{ message.role }}\n{{ message.content }}<|im_end|>\n{% endfor %}{% if add_generation_prompt %}<|im_start|>assistant\n{% endif %}";
+
+const LLAMA2: &str = "{% for message in messages %}{% if message.role == \"user\" %}{{ bos_token }}[INST] {% if loop.index0 == 1 and messages.0.role == \"system\" %}<<SYS>>\n{{ messages.0.content }}\n<</SYS>>\n\n{% endif %}{{ message.content | trim }} [/INST]{% elif message.role == \"assistant\" %} {{ message.content | trim }} {{ eos_token }}{% endif %}{% endfor %}";
+
+const LLAMA3: &str = "{{ bos_token }}{% for message in messages %}<|start_header_id|>{{ message.role }}<|end_header_id|>\n\n{{ message.content | trim }}<|eot_id|>{% endfor %}{% if add_generation_prompt %}<|start_header_id|>assistant<|end_header_id|>\n\n{% endif %}";
+
+const GEMMA: &str = "{{ bos_token }}{% for message in messages %}{% if message.role != \"system\" %}<start_of_turn>{% if message.role == \"assistant\" %}model{% else %}user{% endif %}\n{% if loop.index0 == 1 and messages.0.role == \"system\" %}{{ messages.0.content | trim }}\n\n{% endif %}{{ message.content | trim }}<end_of_turn>\n{% endif %}{% endfor %}{% if add_generation_prompt %}<start_of_turn>model\n{% endif %}";
+
+const MISTRAL: &str = "{{ bos_token }}{% for message in messages %}{% if message.role == \"user\" %}[INST] {% if loop.index0 == 1 and messages.0.role == \"system\" %}{{ messages.0.content | trim }}\n\n{% endif %}{{ message.content | trim }} [/INST]{% elif message.role == \"assistant\" %}{{ message.content | trim }}{{ eos_token }}{% endif %}{% endfor %}";
+
+const FALCON: &str = "{% for message in messages %}{% if message.role == \"system\" %}{{ message.content }}\n\n{% elif message.role == \"assistant\" %}Falcon: {{ message.content }}\n\n{% else %}User: {{ message.content }}\n\n{% endif %}{% endfor %}{% if add_generation_prompt %}Falcon:{% endif %}";
+
+/// A template that turns a `Conversation` into the raw prompt an instruction-tuned model was
+/// trained on.
+///
+/// Templates use the Tera syntax, which is close to the Jinja templates shipped with many models.
+/// They are rendered with these variables:
+/// - `messages`: the messages, each with a `role` (`system`, `user`, `assistant` or the name of
+///   another role) and a `content`.
+/// - `bos_token` and `eos_token`: the special tokens of the template.
+/// - `add_generation_prompt`: whether the prompt should end with the start of an assistant turn.
+///
+/// Executors of local models take a template through `Opt::ChatTemplate` and also stop
+/// generating at its stop sequences.
+///
+/// # Examples
+/// ```
+/// use ai_chain::prompt::{ChatMessage, ChatMessageCollection, ChatTemplate};
+/// let chat = ChatMessageCollection::for_vector(vec![
+///     ChatMessage::system("Be brief.".to_string()),
+///     ChatMessage::user("Hi!".to_string()),
+/// ]);
+/// assert_eq!(
+///     ChatTemplate::chatml().render(&chat, true).unwrap(),
+///     "<|im_start|>system\nBe brief.<|im_end|>\n<|im_start|>user\nHi!<|im_end|>\n<|im_start|>assistant\n"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChatTemplate {
+    template: String,
+    #[serde(default)]
+    bos_token: String,
+    #[serde(default)]
+    eos_token: String,
+    #[serde(default)]
+    stop_sequences: Vec<String>,
+}
+
+impl ChatTemplate {
+    /// Creates a template from Tera source, without special tokens or stop sequences.
+    pub fn new<S: Into<String>>(template: S) -> Self {
+        Self {
+            template: template.into(),
+            bos_token: String::new(),
+            eos_token: String::new(),
+            stop_sequences: Vec::new(),
+        }
+    }
+
+    pub fn with_bos_token<S: Into<String>>(mut self, bos_token: S) -> Self {
+        self.bos_token = bos_token.into();
+        self
+    }
+
+    pub fn with_eos_token<S: Into<String>>(mut self, eos_token: S) -> Self {
+        self.eos_token = eos_token.into();
+        self
+    }
+
+    pub fn with_stop_sequences<I, S>(mut self, stop_sequences: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.stop_sequences = stop_sequences.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The ChatML format used by Qwen, Yi and many fine-tunes.
+    pub fn chatml() -> Self {
+        Self::new(CHATML)
+            .with_eos_token("<|im_end|>")
+            .with_stop_sequences(["<|im_end|>"])
+    }
+
+    /// The format of the Llama 2 chat models. The system message goes into the first user turn.
+    pub fn llama2() -> Self {
+        Self::new(LLAMA2)
+            .with_bos_token("<s>")
+            .with_eos_token("</s>")
+            .with_stop_sequences(["</s>"])
+    }
+
+    /// The format of the Llama 3 instruct models.
+    pub fn llama3() -> Self {
+        Self::new(LLAMA3)
+            .with_bos_token("<|begin_of_text|>")
+            .with_eos_token("<|eot_id|>")
+            .with_stop_sequences(["<|eot_id|>"])
+    }
+
+    /// The format of the Gemma instruct models, which have no system role: the system message is
+    /// prepended to the first user turn.
+    pub fn gemma() -> Self {
+        Self::new(GEMMA)
+            .with_bos_token("<bos>")
+            .with_eos_token("<end_of_turn>")
+            .with_stop_sequences(["<end_of_turn>"])
+    }
+
+    /// The format of the Mistral and Mixtral instruct models.
+    pub fn mistral() -> Self {
+        Self::new(MISTRAL)
+            .with_bos_token("<s>")
+            .with_eos_token("</s>")
+            .with_stop_sequences(["</s>"])
+    }
+
+    /// The `User:`/`Falcon:` format of the Falcon instruct models.
+    pub fn falcon() -> Self {
+        Self::new(FALCON)
+            .with_eos_token("<|endoftext|>")
+            .with_stop_sequences(["\nUser:", "<|endoftext|>"])
+    }
+
+    /// Picks the built-in template matching the Jinja chat template of a model, such as the
+    /// `tokenizer.chat_template` of a GGUF file, by looking for the markers of each format.
+    pub fn detect(jinja_template: &str) -> Option<Self> {
+        let has = |marker: &str| jinja_template.contains(marker);
+        if has("<|im_start|>") {
+            Some(Self::chatml())
+        } else if has("<|start_header_id|>") {
+            Some(Self::llama3())
+        } else if has("<start_of_turn>") {
+            Some(Self::gemma())
+        } else if has("<<SYS>>") {
+            Some(Self::llama2())
+        } else if has("[INST]") {
+            Some(Self::mistral())
+        } else {
+            None
+        }
+    }
+
+    pub fn bos_token(&self) -> &str {
+        &self.bos_token
+    }
+
+    pub fn eos_token(&self) -> &str {
+        &self.eos_token
+    }
+
+    pub fn stop_sequences(&self) -> &[String] {
+        &self.stop_sequences
+    }
+
+    /// Renders `chat`, ending with the start of an assistant turn if `add_generation_prompt` is set.
+    pub fn render(
+        &self,
+        chat: &Conversation,
+        add_generation_prompt: bool,
+    ) -> Result<String, ChatTemplateError> {
+        #[derive(Serialize)]
+        struct Message<'a> {
+            role: String,
+            content: &'a str,
+        }
+        let messages: Vec<Message> = chat
+            .iter()
+            .map(|message| Message {
+                role: match message.role() {
+                    ChatRole::System => "system".to_string(),
+                    ChatRole::User => "user".to_string(),
+                    ChatRole::Assistant => "assistant".to_string(),
+                    ChatRole::Other(role) => role.to_lowercase(),
+                },
+                content: message.body(),
+            })
+            .collect();
+        let mut context = Context::new();
+        context.insert("messages", &messages);
+        context.insert("bos_token", &self.bos_token);
+        context.insert("eos_token", &self.eos_token);
+        context.insert("add_generation_prompt", &add_generation_prompt);
+        Ok(Tera::one_off(&self.template, &context, false)?)
+    }
+
+    /// Turns a prompt into raw text: chat prompts are rendered with a generation prompt and text
+    /// prompts are passed through unchanged.
+    pub fn format_prompt(&self, prompt: &Prompt) -> Result<String, ChatTemplateError> {
+        match prompt {
+            Data::Chat(chat) => self.render(chat, true),
+            Data::Text(text) => Ok(text.clone()),
+        }
+    }
+}
+
+impl FromStr for ChatTemplate {
+    type Err = ChatTemplateError;
+
+    /// Looks up a built-in template by name: `chatml`, `llama2`, `llama3`, `gemma`, `mistral` or
+    /// `falcon`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "chatml" => Ok(Self::chatml()),
+            "llama2" => Ok(Self::llama2()),
+            "llama3" => Ok(Self::llama3()),
+            "gemma" => Ok(Self::gemma()),
+            "mistral" => Ok(Self::mistral()),
+            "falcon" => Ok(Self::falcon()),
+            _ => Err(ChatTemplateError::UnknownTemplate(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::{ChatMessage, ChatMessageCollection};
+
+    fn chat() -> Conversation {
+        ChatMessageCollection::for_vector(vec![
+            ChatMessage::system("Be brief.".to_string()),
+            ChatMessage::user("Hi!".to_string()),
+            ChatMessage::assistant("Hello.".to_string()),
+            ChatMessage::user("Bye!".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_llama2_puts_system_message_in_first_turn() {
+        assert_eq!(
+            ChatTemplate::llama2().render(&chat(), true).unwrap(),
+            "<s>[INST] <<SYS>>\nBe brief.\n<</SYS>>\n\nHi! [/INST] Hello. </s><s>[INST] Bye! [/INST]"
+        );
+    }
+
+    #[test]
+    fn test_llama3_and_gemma() {
+        assert_eq!(
+            ChatTemplate::llama3().render(&chat(), true).unwrap(),
+            "<|begin_of_text|><|start_header_id|>system<|end_header_id|>\n\nBe brief.<|eot_id|>\
+             <|start_header_id|>user<|end_header_id|>\n\nHi!<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\nHello.<|eot_id|>\
+             <|start_header_id|>user<|end_header_id|>\n\nBye!<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+        assert_eq!(
+            ChatTemplate::gemma().render(&chat(), true).unwrap(),
+            "<bos><start_of_turn>user\nBe brief.\n\nHi!<end_of_turn>\n\
+             <start_of_turn>model\nHello.<end_of_turn>\n\
+             <start_of_turn>user\nBye!<end_of_turn>\n<start_of_turn>model\n"
+        );
+    }
+
+    #[test]
+    fn test_mistral_and_falcon() {
+        assert_eq!(
+            ChatTemplate::mistral().render(&chat(), true).unwrap(),
+            "<s>[INST] Be brief.\n\nHi! [/INST]Hello.</s>[INST] Bye! [/INST]"
+        );
+        assert_eq!(
+            ChatTemplate::falcon().render(&chat(), true).unwrap(),
+            "Be brief.\n\nUser: Hi!\n\nFalcon: Hello.\n\nUser: Bye!\n\nFalcon:"
+        );
+    }
+
+    #[test]
+    fn test_custom_template_and_lookup() {
+        let template = ChatTemplate::new(
+            "{{ bos_token }}{% for m in messages %}{{ m.role }}: {{ m.content }}\n{% endfor %}",
+        )
+        .with_bos_token("^");
+        assert_eq!(
+            template.format_prompt(&Data::Chat(chat())).unwrap(),
+            "^system: Be brief.\nuser: Hi!\nassistant: Hello.\nuser: Bye!\n"
+        );
+        assert_eq!(
+            template
+                .format_prompt(&Data::text("raw".to_string()))
+                .unwrap(),
+            "raw"
+        );
+        assert_eq!(
+            "Llama-3".parse::<ChatTemplate>().unwrap(),
+            ChatTemplate::llama3()
+        );
+        assert_eq!(
+            ChatTemplate::detect("{{ '<|im_start|>' + message['role'] }}"),
+            Some(ChatTemplate::chatml())
+        );
+    }
+}
@@ -2,6 +2,7 @@
 //! Contains the `prompt!` macro, Prompts and PromptTemplates.
 
 mod chat;
+mod chat_template;
 mod model;
 mod serialization;
 mod string_template;
@@ -9,6 +10,7 @@ mod string_template;
 pub use string_template::{StringTemplate, StringTemplateError};
 
 pub use chat::{ChatMessage, ChatMessageCollection, ChatRole};
+pub use chat_template::{ChatTemplate, ChatTemplateError};
 pub use model::Data;
 
 /// A prompt template.
@@ -0,0 +1,238 @@
+//! An in-process BM25 keyword index over `Document`s.
+//!
+//! Embedding search is good at meaning but weak at exact strings, such as identifiers and error
+//! codes. `Bm25Index` ranks documents by the words they share with the query instead. It implements
+//! `VectorStore` for any `Embeddings`, so it can be used wherever a vector store is expected, and
+//! combined with one in an [`EnsembleRetriever`](super::EnsembleRetriever).
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::schema::{Document, EmptyMetadata};
+use crate::traits::{Embeddings, VectorStore, VectorStoreError};
+
+/// The error type of `Bm25Index`. Searching the index can't fail.
+#[derive(Debug, Error)]
+pub enum Bm25Error {}
+
+impl VectorStoreError for Bm25Error {}
+
+/// Splits text into lowercase terms.
+///
+/// Terms are runs of alphanumeric characters, `_`, `-` and `.`, without leading or trailing `-`
+/// and `.`, so that identifiers like `ERR_CONN-42` and `v1.2.3` are kept whole.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '.'))
+        .map(|term| term.trim_matches(|c| c == '-' || c == '.'))
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+struct Indexed<M>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    document: Document<M>,
+    term_frequencies: HashMap<String, u32>,
+    length: usize,
+}
+
+struct Inner<M>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    documents: Vec<Indexed<M>>,
+    document_frequencies: HashMap<String, u32>,
+    total_length: usize,
+}
+
+/// A BM25 keyword index.
+///
+/// Documents are scored with the Okapi BM25 formula, with `k1 = 1.2` and `b = 0.75` unless set
+/// with `with_parameters`.
+pub struct Bm25Index<M = EmptyMetadata>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    inner: RwLock<Inner<M>>,
+    k1: f32,
+    b: f32,
+}
+
+impl<M> Default for Bm25Index<M>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn default() -> Self {
+        Self {
+            inner: RwLock::new(Inner {
+                documents: Vec::new(),
+                document_frequencies: HashMap::new(),
+                total_length: 0,
+            }),
+            k1: 1.2,
+            b: 0.75,
+        }
+    }
+}
+
+impl<M> Bm25Index<M>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned + Clone,
+{
+    /// Creates an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the term frequency saturation `k1` and the length normalization `b`.
+    pub fn with_parameters(mut self, k1: f32, b: f32) -> Self {
+        self.k1 = k1;
+        self.b = b;
+        self
+    }
+
+    /// Adds documents to the index and returns their ids.
+    pub fn insert(&self, documents: Vec<Document<M>>) -> Vec<String> {
+        let mut inner = self.inner.write().unwrap();
+        let mut ids = Vec::with_capacity(documents.len());
+        for document in documents {
+            let terms = tokenize(&document.page_content);
+            let mut term_frequencies = HashMap::new();
+            for term in &terms {
+                *term_frequencies.entry(term.clone()).or_insert(0) += 1;
+            }
+            for term in term_frequencies.keys() {
+                *inner.document_frequencies.entry(term.clone()).or_insert(0) += 1;
+            }
+            inner.total_length += terms.len();
+            ids.push(inner.documents.len().to_string());
+            inner.documents.push(Indexed {
+                document,
+                term_frequencies,
+                length: terms.len(),
+            });
+        }
+        ids
+    }
+
+    /// Returns up to `limit` documents sharing terms with `query` with their scores, best first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(Document<M>, f32)> {
+        let inner = self.inner.read().unwrap();
+        if inner.documents.is_empty() {
+            return Vec::new();
+        }
+        let count = inner.documents.len() as f32;
+        let average_length = (inner.total_length as f32 / count).max(1.0);
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut scored: Vec<(usize, f32)> = inner
+            .documents
+            .iter()
+            .enumerate()
+            .filter_map(|(i, indexed)| {
+                let score: f32 = terms
+                    .iter()
+                    .filter_map(|term| {
+                        let tf = *indexed.term_frequencies.get(term)? as f32;
+                        let df = inner.document_frequencies[term] as f32;
+                        let idf = (1.0 + (count - df + 0.5) / (df + 0.5)).ln();
+                        let norm = 1.0 - self.b + self.b * indexed.length as f32 / average_length;
+                        Some(idf * tf * (self.k1 + 1.0) / (tf + self.k1 * norm))
+                    })
+                    .sum();
+                (score > 0.0).then_some((i, score))
+            })
+            .collect();
+        // Stable, so ties keep the order of insertion.
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(i, score)| (inner.documents[i].document.clone(), score))
+            .collect()
+    }
+
+    /// The number of documents in the index.
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl<E, M> VectorStore<E, M> for Bm25Index<M>
+where
+    E: Embeddings,
+    M: serde::Serialize + serde::de::DeserializeOwned + Clone + Send + Sync,
+{
+    type Error = Bm25Error;
+
+    async fn add_texts(&self, texts: Vec<String>) -> Result<Vec<String>, Self::Error> {
+        Ok(self.insert(texts.into_iter().map(Document::new).collect()))
+    }
+
+    async fn add_documents(&self, documents: Vec<Document<M>>) -> Result<Vec<String>, Self::Error> {
+        Ok(self.insert(documents))
+    }
+
+    async fn similarity_search(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<Document<M>>, Self::Error> {
+        Ok(self
+            .search(&query, limit as usize)
+            .into_iter()
+            .map(|(document, _)| document)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> Bm25Index {
+        let index = Bm25Index::new();
+        index.insert(vec![
+            Document::new("The connection failed with ERR_CONN-42.".to_string()),
+            Document::new("Connection pooling keeps connections open.".to_string()),
+            Document::new("Release v1.2.3 fixes the login page.".to_string()),
+        ]);
+        index
+    }
+
+    #[test]
+    fn test_tokenize_keeps_identifiers() {
+        assert_eq!(
+            tokenize("Failed: ERR_CONN-42 in v1.2.3."),
+            vec!["failed", "err_conn-42", "in", "v1.2.3"]
+        );
+    }
+
+    #[test]
+    fn test_search_ranks_exact_matches_first() {
+        let index = index();
+        let results = index.search("what is err_conn-42", 10);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].0.page_content,
+            "The connection failed with ERR_CONN-42."
+        );
+
+        let results = index.search("connection v1.2.3", 10);
+        assert_eq!(results.len(), 3);
+        assert!(results[0].1 >= results[1].1 && results[1].1 >= results[2].1);
+        assert!(index.search("nothing matches", 10).is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+//! Hybrid retrieval that merges the results of a keyword index and a vector store.
+//!
+//! The rankings are merged with reciprocal rank fusion: a document at rank `r` (counting from 1) of
+//! a ranking with weight `w` gets `w / (k + r)`, and the scores of the same document add up across
+//! rankings. Documents are the same when their `page_content` is equal.
+
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::schema::{Document, EmptyMetadata};
+use crate::traits::{Embeddings, VectorStore, VectorStoreError};
+
+/// The `k` constant of reciprocal rank fusion used unless set otherwise.
+pub const DEFAULT_RRF_K: f32 = 60.0;
+
+/// Merges rankings with weighted reciprocal rank fusion.
+///
+/// Each ranking comes with its weight. Returns every document found, with its fused score, best
+/// first; ties keep the order in which the documents were first seen.
+pub fn reciprocal_rank_fusion<M>(
+    rankings: Vec<(f32, Vec<Document<M>>)>,
+    k: f32,
+) -> Vec<(Document<M>, f32)>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let mut fused: Vec<(Document<M>, f32)> = Vec::new();
+    for (weight, ranking) in rankings {
+        for (rank, document) in ranking.into_iter().enumerate() {
+            let score = weight / (k + rank as f32 + 1.0);
+            match fused
+                .iter_mut()
+                .find(|(d, _)| d.page_content == document.page_content)
+            {
+                Some((_, total)) => *total += score,
+                None => fused.push((document, score)),
+            }
+        }
+    }
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1));
+    fused
+}
+
+/// The error type of `EnsembleRetriever`.
+#[derive(Debug, Error)]
+pub enum EnsembleRetrieverError<K, V>
+where
+    K: std::error::Error,
+    V: std::error::Error,
+{
+    #[error("keyword store error: {0}")]
+    Keyword(K),
+    #[error("vector store error: {0}")]
+    Vector(V),
+}
+
+impl<K, V> VectorStoreError for EnsembleRetrieverError<K, V>
+where
+    K: std::error::Error,
+    V: std::error::Error,
+{
+}
+
+/// Combines a keyword store, usually a [`Bm25Index`](super::Bm25Index), with a vector store.
+///
+/// Both stores are searched for each query and their results are merged with reciprocal rank
+/// fusion. Added documents go to both stores. It implements `VectorStore`, so it can be used by
+/// `VectorStoreTool` and the retrieval chains like any vector store.
+pub struct EnsembleRetriever<E, K, V, M = EmptyMetadata> {
+    keyword: K,
+    vector: V,
+    keyword_weight: f32,
+    vector_weight: f32,
+    k: f32,
+    candidates: Option<u32>,
+    _embeddings: PhantomData<fn() -> (E, M)>,
+}
+
+impl<E, K, V, M> EnsembleRetriever<E, K, V, M>
+where
+    E: Embeddings,
+    K: VectorStore<E, M>,
+    V: VectorStore<E, M>,
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Creates an ensemble that weighs both stores equally.
+    pub fn new(keyword: K, vector: V) -> Self {
+        Self {
+            keyword,
+            vector,
+            keyword_weight: 1.0,
+            vector_weight: 1.0,
+            k: DEFAULT_RRF_K,
+            candidates: None,
+            _embeddings: PhantomData,
+        }
+    }
+
+    /// Sets the weights of the keyword and the vector results.
+    pub fn with_weights(mut self, keyword_weight: f32, vector_weight: f32) -> Self {
+        self.keyword_weight = keyword_weight;
+        self.vector_weight = vector_weight;
+        self
+    }
+
+    /// Sets the `k` constant of reciprocal rank fusion. Smaller values favor the top ranks more.
+    pub fn with_rrf_k(mut self, k: f32) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Sets how many results are fetched from each store before fusion. Defaults to the number of
+    /// results asked for.
+    pub fn with_candidates(mut self, candidates: u32) -> Self {
+        self.candidates = Some(candidates);
+        self
+    }
+
+    pub fn keyword_store(&self) -> &K {
+        &self.keyword
+    }
+
+    pub fn vector_store(&self) -> &V {
+        &self.vector
+    }
+
+    /// Returns up to `limit` documents for `query` with their fused scores, best first.
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: u32,
+    ) -> Result<Vec<(Document<M>, f32)>, EnsembleRetrieverError<K::Error, V::Error>> {
+        let candidates = self.candidates.unwrap_or(limit).max(limit);
+        let (keyword, vector) = futures::join!(
+            self.keyword
+                .similarity_search(query.to_string(), candidates),
+            self.vector.similarity_search(query.to_string(), candidates)
+        );
+        let keyword = keyword.map_err(EnsembleRetrieverError::Keyword)?;
+        let vector = vector.map_err(EnsembleRetrieverError::Vector)?;
+        let mut fused = reciprocal_rank_fusion(
+            vec![(self.keyword_weight, keyword), (self.vector_weight, vector)],
+            self.k,
+        );
+        fused.truncate(limit as usize);
+        Ok(fused)
+    }
+}
+
+#[async_trait]
+impl<E, K, V, M> VectorStore<E, M> for EnsembleRetriever<E, K, V, M>
+where
+    E: Embeddings,
+    K: VectorStore<E, M> + Send + Sync,
+    V: VectorStore<E, M> + Send + Sync,
+    K::Error: Send,
+    V::Error: Send,
+    M: serde::Serialize + serde::de::DeserializeOwned + Clone + Send + Sync,
+{
+    type Error = EnsembleRetrieverError<K::Error, V::Error>;
+
+    async fn add_texts(&self, texts: Vec<String>) -> Result<Vec<String>, Self::Error> {
+        self.keyword
+            .add_texts(texts.clone())
+            .await
+            .map_err(EnsembleRetrieverError::Keyword)?;
+        self.vector
+            .add_texts(texts)
+            .await
+            .map_err(EnsembleRetrieverError::Vector)
+    }
+
+    async fn add_documents(&self, documents: Vec<Document<M>>) -> Result<Vec<String>, Self::Error> {
+        self.keyword
+            .add_documents(documents.clone())
+            .await
+            .map_err(EnsembleRetrieverError::Keyword)?;
+        self.vector
+            .add_documents(documents)
+            .await
+            .map_err(EnsembleRetrieverError::Vector)
+    }
+
+    async fn similarity_search(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<Document<M>>, Self::Error> {
+        Ok(self
+            .search(&query, limit)
+            .await?
+            .into_iter()
+            .map(|(document, _)| document)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::{NoEmbeddings, WordStore};
+    use crate::retrieval::Bm25Index;
+
+    fn documents(texts: &[&str]) -> Vec<Document> {
+        texts.iter().map(|t| Document::new(t.to_string())).collect()
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion_adds_up_weighted_ranks() {
+        let fused = reciprocal_rank_fusion(
+            vec![
+                (1.0, documents(&["a", "b", "c"])),
+                (2.0, documents(&["c", "d"])),
+            ],
+            1.0,
+        );
+        let order: Vec<_> = fused.iter().map(|(d, _)| d.page_content.as_str()).collect();
+        assert_eq!(order, vec!["c", "d", "a", "b"]);
+        assert_eq!(fused[0].1, 1.0 / 4.0 + 2.0 / 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_finds_keyword_and_vector_matches() {
+        let ensemble: EnsembleRetriever<NoEmbeddings, _, _> =
+            EnsembleRetriever::new(Bm25Index::new(), WordStore::default()).with_weights(2.0, 1.0);
+        ensemble
+            .add_texts(vec![
+                "login fails with E1234".to_string(),
+                "how to reset a login password".to_string(),
+            ])
+            .await
+            .unwrap();
+        let results = ensemble.search("e1234 login", 2).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0.page_content, "login fails with E1234");
+    }
+}
@@ -0,0 +1,93 @@
+//! Hypothetical document embeddings (HyDE) search with an answer instead of the question.
+//!
+//! Questions and the passages that answer them are often far apart in embedding space. The
+//! `HydeRetriever` has the LLM write a passage that could answer the query, and searches for the
+//! documents most similar to that passage. The passage doesn't need to be correct, only to look like
+//! the documents being searched.
+
+use async_trait::async_trait;
+
+use super::{Retriever, RetrieverError};
+use crate::schema::Document;
+use crate::step::Step;
+use crate::traits::Executor;
+use crate::{prompt, Parameters};
+
+/// Retrieves the documents most similar to an LLM-written answer to the query.
+///
+/// The step gets the query in `{{question}}`.
+pub struct HydeRetriever<R, X> {
+    inner: R,
+    executor: X,
+    step: Step,
+}
+
+impl<R, X> HydeRetriever<R, X> {
+    pub fn new(inner: R, executor: X) -> Self {
+        Self {
+            inner,
+            executor,
+            step: Step::for_prompt_template(prompt!(
+                "You write passages that answer questions.",
+                "Write a short passage that answers the following question.\n\nQuestion: {{question}}\n\nPassage:"
+            )),
+        }
+    }
+
+    /// Replaces the step that writes the hypothetical passage, for example to match the style of
+    /// the documents being searched.
+    pub fn with_step(mut self, step: Step) -> Self {
+        self.step = step;
+        self
+    }
+}
+
+#[async_trait]
+impl<R, X, M> Retriever<M> for HydeRetriever<R, X>
+where
+    R: Retriever<M>,
+    X: Executor + Send + Sync,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync,
+{
+    async fn retrieve(&self, query: &str, limit: u32) -> Result<Vec<Document<M>>, RetrieverError> {
+        let prompt = self
+            .step
+            .format(&Parameters::new().with("question", query))?;
+        let passage = self
+            .executor
+            .execute(self.step.options(), &prompt)
+            .await?
+            .to_immediate()
+            .await?
+            .primary_textual_output()
+            .filter(|passage| !passage.trim().is_empty())
+            .unwrap_or_else(|| query.to_string());
+        self.inner.retrieve(&passage, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::{ScriptedExecutor, WordStore};
+    use crate::retrieval::VectorStoreRetriever;
+
+    #[tokio::test]
+    async fn test_searches_with_the_hypothetical_passage() {
+        let store = WordStore::default();
+        store.0.lock().unwrap().extend([
+            "Paris is the capital of France".to_string(),
+            "Berlin has many museums".to_string(),
+        ]);
+        let retriever = HydeRetriever::new(
+            VectorStoreRetriever::new(store),
+            ScriptedExecutor {
+                reply: |_| "Paris".to_string(),
+                max_tokens: 1000,
+            },
+        );
+        let documents = retriever.retrieve("French capital?", 5).await.unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].page_content, "Paris is the capital of France");
+    }
+}
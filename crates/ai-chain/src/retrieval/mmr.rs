@@ -0,0 +1,139 @@
+//! Maximal marginal relevance re-ranks results for diversity.
+//!
+//! A plain similarity search often returns several near-identical passages. MMR picks documents
+//! one at a time, each maximizing `lambda * sim(query, doc) - (1 - lambda) * max sim(doc, picked)`,
+//! so that every pick is relevant but different from the ones before it.
+
+use async_trait::async_trait;
+
+use super::{cosine_similarity, Retriever, RetrieverError};
+use crate::schema::Document;
+use crate::traits::Embeddings;
+
+/// Selects up to `limit` of `embeddings` by maximal marginal relevance to `query`.
+///
+/// Returns the indices of the selected embeddings in the order they were picked. `lambda` is
+/// between 0 (only diversity) and 1 (only relevance).
+pub fn maximal_marginal_relevance(
+    query: &[f32],
+    embeddings: &[Vec<f32>],
+    limit: usize,
+    lambda: f32,
+) -> Vec<usize> {
+    let relevance: Vec<f32> = embeddings
+        .iter()
+        .map(|e| cosine_similarity(query, e))
+        .collect();
+    let mut selected: Vec<usize> = Vec::new();
+    while selected.len() < limit.min(embeddings.len()) {
+        let best = (0..embeddings.len())
+            .filter(|i| !selected.contains(i))
+            .map(|i| {
+                let redundancy = selected
+                    .iter()
+                    .map(|&j| cosine_similarity(&embeddings[i], &embeddings[j]))
+                    .reduce(f32::max)
+                    .unwrap_or(0.0);
+                (i, lambda * relevance[i] - (1.0 - lambda) * redundancy)
+            })
+            .fold(None, |best: Option<(usize, f32)>, (i, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((i, score)),
+            });
+        match best {
+            Some((i, _)) => selected.push(i),
+            None => break,
+        }
+    }
+    selected
+}
+
+/// Fetches candidates from another retriever and re-ranks them by maximal marginal relevance.
+///
+/// The candidates and the query are embedded with `embeddings`, which should be the model the
+/// documents were indexed with.
+pub struct MmrRetriever<R, E> {
+    inner: R,
+    embeddings: E,
+    fetch_k: u32,
+    lambda: f32,
+}
+
+impl<R, E> MmrRetriever<R, E> {
+    /// Creates a retriever that re-ranks 20 candidates with `lambda = 0.5`.
+    pub fn new(inner: R, embeddings: E) -> Self {
+        Self {
+            inner,
+            embeddings,
+            fetch_k: 20,
+            lambda: 0.5,
+        }
+    }
+
+    /// Sets how many candidates are fetched from the inner retriever. At least `limit` are.
+    pub fn with_fetch_k(mut self, fetch_k: u32) -> Self {
+        self.fetch_k = fetch_k;
+        self
+    }
+
+    /// Sets the trade-off between relevance (1) and diversity (0).
+    pub fn with_lambda(mut self, lambda: f32) -> Self {
+        self.lambda = lambda;
+        self
+    }
+}
+
+#[async_trait]
+impl<R, E, M> Retriever<M> for MmrRetriever<R, E>
+where
+    R: Retriever<M>,
+    E: Embeddings + Send + Sync,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync,
+{
+    async fn retrieve(&self, query: &str, limit: u32) -> Result<Vec<Document<M>>, RetrieverError> {
+        let candidates = self.inner.retrieve(query, self.fetch_k.max(limit)).await?;
+        if candidates.is_empty() {
+            return Ok(candidates);
+        }
+        let query_embedding = self
+            .embeddings
+            .embed_query(query.to_string())
+            .await
+            .map_err(|e| RetrieverError::Embeddings(e.to_string()))?;
+        let embeddings = self
+            .embeddings
+            .embed_texts(candidates.iter().map(|d| d.page_content.clone()).collect())
+            .await
+            .map_err(|e| RetrieverError::Embeddings(e.to_string()))?;
+        let order =
+            maximal_marginal_relevance(&query_embedding, &embeddings, limit as usize, self.lambda);
+        let mut candidates: Vec<Option<Document<M>>> = candidates.into_iter().map(Some).collect();
+        Ok(order
+            .into_iter()
+            .filter_map(|i| candidates[i].take())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mmr_prefers_diverse_results() {
+        let query = [1.0, 0.0];
+        let embeddings = vec![vec![1.0, 0.1], vec![1.0, 0.11], vec![0.6, 0.8]];
+        assert_eq!(
+            maximal_marginal_relevance(&query, &embeddings, 2, 1.0),
+            vec![0, 1]
+        );
+        assert_eq!(
+            maximal_marginal_relevance(&query, &embeddings, 2, 0.3),
+            vec![0, 2]
+        );
+        assert_eq!(
+            maximal_marginal_relevance(&query, &embeddings, 5, 0.5).len(),
+            3
+        );
+    }
+}
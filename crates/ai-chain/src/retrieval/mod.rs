@@ -0,0 +1,117 @@
+//! Retrieval finds the documents relevant to a query.
+//!
+//! Vector stores find documents by meaning. This module adds a BM25 keyword index, which finds
+//! exact terms such as identifiers and error codes, and an ensemble retriever that merges keyword
+//! and vector results with reciprocal rank fusion. Both implement `VectorStore`, so they can be used
+//! anywhere a vector store is expected.
+//!
+//! The [`Retriever`] trait is the extension point for smarter strategies than a plain similarity
+//! search. [`VectorStoreRetriever`] wraps any vector store, and the other retrievers build on it:
+//!
+//! - [`MmrRetriever`] re-ranks the results by maximal marginal relevance, for diverse results.
+//! - [`MultiQueryRetriever`] has the LLM write variants of the query and merges their results.
+//! - [`HydeRetriever`] has the LLM write a hypothetical answer and searches with that instead.
+//! - [`ParentDocumentRetriever`] searches small chunks but returns the documents they came from.
+//! - [`RerankingRetriever`] reorders the results of another retriever with a [`Reranker`].
+
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+use crate::schema::{Document, EmptyMetadata};
+use crate::traits::{Embeddings, ExecutorError, VectorStore};
+
+pub mod bm25;
+pub mod ensemble;
+pub mod hyde;
+pub mod mmr;
+pub mod multi_query;
+pub mod parent_document;
+pub mod rerank;
+
+pub use bm25::{Bm25Error, Bm25Index};
+pub use ensemble::{reciprocal_rank_fusion, EnsembleRetriever, EnsembleRetrieverError};
+pub use hyde::HydeRetriever;
+pub use mmr::{maximal_marginal_relevance, MmrRetriever};
+pub use multi_query::MultiQueryRetriever;
+pub use parent_document::{ParentDocumentRetriever, ParentId};
+pub use rerank::{LlmReranker, RerankStrategy, Reranker, RerankingRetriever};
+
+/// The `RetrieverError` enum represents errors that can occur when retrieving documents.
+#[derive(Debug, Error)]
+pub enum RetrieverError {
+    #[error("VectorStoreError: {0}")]
+    VectorStore(String),
+    #[error("EmbeddingsError: {0}")]
+    Embeddings(String),
+    #[error("DocumentStoreError: {0}")]
+    DocumentStore(String),
+    #[error("RerankerError: {0}")]
+    Reranker(String),
+    #[error("ExecutorError: {0}")]
+    Executor(#[from] ExecutorError),
+    #[error("Error formatting: {0}")]
+    Format(#[from] crate::prompt::StringTemplateError),
+}
+
+/// Finds the documents relevant to a query.
+#[async_trait]
+pub trait Retriever<M = EmptyMetadata>: Send + Sync
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Returns up to `limit` documents relevant to `query`, most relevant first.
+    async fn retrieve(&self, query: &str, limit: u32) -> Result<Vec<Document<M>>, RetrieverError>;
+}
+
+/// Retrieves documents with the similarity search of a vector store.
+pub struct VectorStoreRetriever<E, V, M = EmptyMetadata> {
+    store: V,
+    _embeddings: PhantomData<fn() -> (E, M)>,
+}
+
+impl<E, V, M> VectorStoreRetriever<E, V, M>
+where
+    E: Embeddings,
+    V: VectorStore<E, M>,
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    pub fn new(store: V) -> Self {
+        Self {
+            store,
+            _embeddings: PhantomData,
+        }
+    }
+
+    pub fn store(&self) -> &V {
+        &self.store
+    }
+}
+
+#[async_trait]
+impl<E, V, M> Retriever<M> for VectorStoreRetriever<E, V, M>
+where
+    E: Embeddings,
+    V: VectorStore<E, M> + Send + Sync,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync,
+{
+    async fn retrieve(&self, query: &str, limit: u32) -> Result<Vec<Document<M>>, RetrieverError> {
+        self.store
+            .similarity_search(query.to_string(), limit)
+            .await
+            .map_err(|e| RetrieverError::VectorStore(e.to_string()))
+    }
+}
+
+/// The cosine similarity of two vectors, or 0 if one of them is zero.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
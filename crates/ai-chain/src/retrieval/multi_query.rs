@@ -0,0 +1,155 @@
+//! Multi-query retrieval searches with several phrasings of the query.
+//!
+//! The wording of a question decides which documents a similarity search finds. The
+//! `MultiQueryRetriever` has the LLM write variants of the query, retrieves documents for the query
+//! and each variant, and merges the results with reciprocal rank fusion.
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{reciprocal_rank_fusion, Retriever, RetrieverError};
+use crate::schema::Document;
+use crate::step::Step;
+use crate::traits::Executor;
+use crate::{prompt, Parameters};
+
+/// Retrieves documents for the query and LLM-written variants of it.
+///
+/// The step gets the query in `{{question}}` and the number of variants in `{{count}}`, and should
+/// answer with one variant per line.
+pub struct MultiQueryRetriever<R, X> {
+    inner: R,
+    executor: X,
+    step: Step,
+    count: usize,
+}
+
+impl<R, X> MultiQueryRetriever<R, X> {
+    /// Creates a retriever that searches with the query and three variants of it.
+    pub fn new(inner: R, executor: X) -> Self {
+        Self {
+            inner,
+            executor,
+            step: Step::for_prompt_template(prompt!(
+                "You help a search engine find documents by rewriting questions.",
+                "Write {{count}} different versions of the following question, to find relevant documents in a vector database. Respond only with the questions, one per line.\n\nQuestion: {{question}}"
+            )),
+            count: 3,
+        }
+    }
+
+    /// Sets how many variants the LLM writes.
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Replaces the step that writes the variants.
+    pub fn with_step(mut self, step: Step) -> Self {
+        self.step = step;
+        self
+    }
+}
+
+lazy_static! {
+    /// A numbered or bulleted list marker, such as `1.`, `2)`, `-` or `*`, followed by whitespace.
+    static ref LIST_MARKER: Regex = Regex::new(r"^(?:\d+[.)]|[-*])\s+").unwrap();
+}
+
+/// Reads one query per line, without list markers such as `1.` or `-`.
+fn parse_queries(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            LIST_MARKER.find(line).map_or(line, |m| &line[m.end()..])
+        })
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[async_trait]
+impl<R, X, M> Retriever<M> for MultiQueryRetriever<R, X>
+where
+    R: Retriever<M>,
+    X: Executor + Send + Sync,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync,
+{
+    async fn retrieve(&self, query: &str, limit: u32) -> Result<Vec<Document<M>>, RetrieverError> {
+        let parameters = Parameters::new()
+            .with("question", query)
+            .with("count", self.count.to_string());
+        let prompt = self.step.format(&parameters)?;
+        let output = self
+            .executor
+            .execute(self.step.options(), &prompt)
+            .await?
+            .to_immediate()
+            .await?
+            .primary_textual_output()
+            .unwrap_or_default();
+        let mut queries = vec![query.to_string()];
+        queries.extend(
+            parse_queries(&output)
+                .into_iter()
+                .filter(|q| q != query)
+                .take(self.count),
+        );
+
+        let rankings = join_all(queries.iter().map(|q| self.inner.retrieve(q, limit))).await;
+        let rankings = rankings
+            .into_iter()
+            .map(|ranking| ranking.map(|documents| (1.0, documents)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(
+            reciprocal_rank_fusion(rankings, super::ensemble::DEFAULT_RRF_K)
+                .into_iter()
+                .take(limit as usize)
+                .map(|(document, _)| document)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::{ScriptedExecutor, WordStore};
+    use crate::retrieval::VectorStoreRetriever;
+
+    #[test]
+    fn test_parse_queries_strips_list_markers() {
+        assert_eq!(
+            parse_queries("1. first one\n\n- second\n3) third"),
+            vec!["first one", "second", "third"]
+        );
+        assert_eq!(
+            parse_queries("2020 revenue\n2. 2021 revenue\n-5 degrees\n* 3.5 stars"),
+            vec!["2020 revenue", "2021 revenue", "-5 degrees", "3.5 stars"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_results_of_all_variants_are_merged() {
+        let store = WordStore::default();
+        store.0.lock().unwrap().extend([
+            "cats purr".to_string(),
+            "dogs bark".to_string(),
+            "birds sing".to_string(),
+        ]);
+        let retriever = MultiQueryRetriever::new(
+            VectorStoreRetriever::new(store),
+            ScriptedExecutor {
+                reply: |_| "1. dogs\n2. birds".to_string(),
+                max_tokens: 1000,
+            },
+        );
+        let documents = retriever.retrieve("cats", 10).await.unwrap();
+        let contents: Vec<_> = documents.iter().map(|d| d.page_content.as_str()).collect();
+        assert_eq!(contents, vec!["cats purr", "dogs bark", "birds sing"]);
+        assert_eq!(retriever.retrieve("cats", 1).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,218 @@
+//! Parent-document retrieval searches small chunks but returns whole documents.
+//!
+//! Small chunks embed precisely but lack context, while whole documents give the LLM context but
+//! embed poorly. The `ParentDocumentRetriever` gets the best of both: it splits every document into
+//! chunks, indexes the chunks in a vector store with the id of their document, keeps the documents
+//! in a `DocumentStore`, and answers queries with the documents of the best matching chunks.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::{Retriever, RetrieverError};
+use crate::document_stores::document_store::DocumentStore;
+use crate::schema::{Document, EmptyMetadata};
+use crate::traits::{Embeddings, VectorStore};
+
+/// The metadata of a chunk: the id of the document it was split from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParentId<T> {
+    pub parent_id: T,
+}
+
+/// Splits `text` into chunks of at most `chunk_size` characters, at whitespace.
+///
+/// Words longer than `chunk_size` become chunks of their own.
+fn split_into_chunks(text: &str, chunk_size: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.len() + 1 + word.len() > chunk_size {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Indexes chunks of documents in `children` and returns the documents from `parents`.
+pub struct ParentDocumentRetriever<E, V, S, T, M = EmptyMetadata> {
+    children: V,
+    parents: RwLock<S>,
+    chunk_size: usize,
+    fetch_k: u32,
+    _embeddings: PhantomData<fn() -> E>,
+    _documents: PhantomData<fn() -> (T, M)>,
+}
+
+impl<E, V, S, T, M> ParentDocumentRetriever<E, V, S, T, M>
+where
+    E: Embeddings,
+    V: VectorStore<E, ParentId<T>>,
+    S: DocumentStore<T, M>,
+    T: Clone + Eq + Hash + Serialize + DeserializeOwned + Send + Sync,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    /// Creates a retriever that splits documents into chunks of 400 characters.
+    pub fn new(children: V, parents: S) -> Self {
+        Self {
+            children,
+            parents: RwLock::new(parents),
+            chunk_size: 400,
+            fetch_k: 20,
+            _embeddings: PhantomData,
+            _documents: PhantomData,
+        }
+    }
+
+    /// Sets the maximum number of characters of a chunk.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets how many chunks are searched for to find the documents. At least the number of
+    /// documents asked for are.
+    pub fn with_fetch_k(mut self, fetch_k: u32) -> Self {
+        self.fetch_k = fetch_k;
+        self
+    }
+
+    /// Splits the documents into chunks, indexes the chunks and stores the documents. Returns the
+    /// ids of the documents in the document store.
+    pub async fn add_documents(
+        &self,
+        documents: Vec<Document<M>>,
+    ) -> Result<Vec<T>, RetrieverError> {
+        let mut ids = Vec::with_capacity(documents.len());
+        for document in documents {
+            let chunks = split_into_chunks(&document.page_content, self.chunk_size);
+            ids.push(self.add_document_with_chunks(document, chunks).await?);
+        }
+        Ok(ids)
+    }
+
+    /// Stores `document` and indexes the given chunks of it, for documents that need a splitter
+    /// aware of their format. Returns the id of the document in the document store.
+    pub async fn add_document_with_chunks(
+        &self,
+        document: Document<M>,
+        chunks: Vec<String>,
+    ) -> Result<T, RetrieverError> {
+        let id = {
+            let mut parents = self.parents.write().await;
+            let id = parents
+                .next_id()
+                .await
+                .map_err(|e| RetrieverError::DocumentStore(e.to_string()))?;
+            parents
+                .insert(&HashMap::from([(id.clone(), document)]))
+                .await
+                .map_err(|e| RetrieverError::DocumentStore(e.to_string()))?;
+            id
+        };
+        let chunks = chunks
+            .into_iter()
+            .map(|chunk| Document {
+                page_content: chunk,
+                metadata: Some(ParentId {
+                    parent_id: id.clone(),
+                }),
+            })
+            .collect();
+        self.children
+            .add_documents(chunks)
+            .await
+            .map_err(|e| RetrieverError::VectorStore(e.to_string()))?;
+        Ok(id)
+    }
+}
+
+#[async_trait]
+impl<E, V, S, T, M> Retriever<M> for ParentDocumentRetriever<E, V, S, T, M>
+where
+    E: Embeddings,
+    V: VectorStore<E, ParentId<T>> + Send + Sync,
+    S: DocumentStore<T, M> + Send + Sync,
+    T: Clone + Eq + Hash + Serialize + DeserializeOwned + Send + Sync,
+    M: Serialize + DeserializeOwned + Send + Sync,
+{
+    async fn retrieve(&self, query: &str, limit: u32) -> Result<Vec<Document<M>>, RetrieverError> {
+        let chunks = self
+            .children
+            .similarity_search(query.to_string(), self.fetch_k.max(limit))
+            .await
+            .map_err(|e| RetrieverError::VectorStore(e.to_string()))?;
+        let mut ids: Vec<T> = Vec::new();
+        for chunk in chunks {
+            if let Some(ParentId { parent_id }) = chunk.metadata {
+                if !ids.contains(&parent_id) {
+                    ids.push(parent_id);
+                }
+            }
+        }
+        ids.truncate(limit as usize);
+
+        let parents = self.parents.read().await;
+        let mut documents = Vec::with_capacity(ids.len());
+        for id in &ids {
+            if let Some(document) = parents
+                .get(id)
+                .await
+                .map_err(|e| RetrieverError::DocumentStore(e.to_string()))?
+            {
+                documents.push(document);
+            }
+        }
+        Ok(documents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::NoEmbeddings;
+    use crate::document_stores::in_memory_document_store::InMemoryDocumentStore;
+    use crate::retrieval::Bm25Index;
+
+    #[test]
+    fn test_split_into_chunks_packs_words() {
+        assert_eq!(
+            split_into_chunks("aa bb cc dddddddd e", 5),
+            vec!["aa bb", "cc", "dddddddd", "e"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_returns_the_parents_of_matching_chunks() {
+        let retriever: ParentDocumentRetriever<NoEmbeddings, _, _, usize> =
+            ParentDocumentRetriever::new(Bm25Index::new(), InMemoryDocumentStore::new())
+                .with_chunk_size(20);
+        let ids = retriever
+            .add_documents(vec![
+                Document::new("The kettle boils water. Tea needs hot water.".to_string()),
+                Document::new("Bikes have two wheels. Cars have four wheels.".to_string()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![0, 1]);
+
+        let documents = retriever.retrieve("water kettle", 5).await.unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(
+            documents[0].page_content,
+            "The kettle boils water. Tea needs hot water."
+        );
+        assert_eq!(retriever.retrieve("wheels", 5).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,329 @@
+//! Rerankers score retrieved documents against the query in a second, more precise stage.
+//!
+//! A first-stage retriever compares the query with each document independently of how it was
+//! phrased, which is fast but coarse. A `Reranker` looks at the query and a document together, so
+//! it ranks better but is too slow to run on the whole corpus. The `RerankingRetriever` combines
+//! both: it fetches candidates from any retriever and returns the best ones according to the
+//! reranker.
+//!
+//! The `LlmReranker` prompts an LLM through any `Executor`. Cross-encoder models running locally
+//! are provided by the `ai-chain-onnx` crate.
+
+use async_trait::async_trait;
+use futures::future::join_all;
+
+use super::{Retriever, RetrieverError};
+use crate::parsing::find_numbered_references;
+use crate::schema::{Document, EmptyMetadata};
+use crate::step::Step;
+use crate::traits::Executor;
+use crate::{prompt, Parameters};
+
+/// Scores how relevant documents are to a query.
+#[async_trait]
+pub trait Reranker<M = EmptyMetadata>: Send + Sync
+where
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync + 'static,
+{
+    /// Returns the score of each of `documents`, in their order. Higher scores are more relevant.
+    async fn score(
+        &self,
+        query: &str,
+        documents: &[Document<M>],
+    ) -> Result<Vec<f32>, RetrieverError>;
+
+    /// Returns `documents` with their scores, most relevant first. Fails if `score` doesn't return
+    /// one score per document.
+    async fn rerank(
+        &self,
+        query: &str,
+        documents: Vec<Document<M>>,
+    ) -> Result<Vec<(Document<M>, f32)>, RetrieverError> {
+        let scores = self.score(query, &documents).await?;
+        if scores.len() != documents.len() {
+            return Err(RetrieverError::Reranker(format!(
+                "Expected {} scores, got {}",
+                documents.len(),
+                scores.len()
+            )));
+        }
+        let mut scored: Vec<_> = documents.into_iter().zip(scores).collect();
+        // Stable, so ties keep the order of the first stage.
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(scored)
+    }
+}
+
+/// How the `LlmReranker` prompts the LLM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RerankStrategy {
+    /// Rate each document on its own, with one call per document.
+    #[default]
+    Pointwise,
+    /// Rank all documents at once, with a single call.
+    Listwise,
+}
+
+/// Reranks documents by asking an LLM.
+///
+/// The pointwise step gets the query in `{{question}}` and a document in `{{text}}` and should
+/// answer with a score from 0 to 10. The listwise step gets the query in `{{question}}` and the
+/// numbered documents in `{{context}}` and should answer with the numbers of the documents, most
+/// relevant first, like `[2] > [1] > [3]`.
+pub struct LlmReranker<X> {
+    executor: X,
+    strategy: RerankStrategy,
+    pointwise: Step,
+    listwise: Step,
+}
+
+impl<X> LlmReranker<X> {
+    /// Creates a pointwise reranker.
+    pub fn new(executor: X) -> Self {
+        Self {
+            executor,
+            strategy: RerankStrategy::default(),
+            pointwise: Step::for_prompt_template(prompt!(
+                "You judge how relevant documents are to search queries.",
+                "Query: {{question}}\n\nDocument:\n\n{{text}}\n\nHow relevant is the document to the query, from 0 (not at all) to 10 (answers it)? Respond only with the number."
+            )),
+            listwise: Step::for_prompt_template(prompt!(
+                "You rank documents by how relevant they are to search queries.",
+                "Query: {{question}}\n\nDocuments:\n\n{{context}}\n\nRank the documents from most to least relevant to the query. Respond only with their numbers, like [2] > [1] > [3]."
+            )),
+        }
+    }
+
+    /// Sets how the LLM is prompted.
+    pub fn with_strategy(mut self, strategy: RerankStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Replaces the step that rates a single document.
+    pub fn with_pointwise_step(mut self, step: Step) -> Self {
+        self.pointwise = step;
+        self
+    }
+
+    /// Replaces the step that ranks all documents.
+    pub fn with_listwise_step(mut self, step: Step) -> Self {
+        self.listwise = step;
+        self
+    }
+}
+
+impl<X: Executor> LlmReranker<X> {
+    async fn complete(
+        &self,
+        step: &Step,
+        parameters: &Parameters,
+    ) -> Result<String, RetrieverError> {
+        let prompt = step.format(parameters)?;
+        Ok(self
+            .executor
+            .execute(step.options(), &prompt)
+            .await?
+            .to_immediate()
+            .await?
+            .primary_textual_output()
+            .unwrap_or_default())
+    }
+}
+
+/// Reads the first number in `output`, such as `7` in `Score: 7/10`.
+fn parse_score(output: &str) -> Option<f32> {
+    output
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|part| part.trim_matches('.'))
+        .find(|part| !part.is_empty())
+        .and_then(|part| part.parse().ok())
+}
+
+#[async_trait]
+impl<X, M> Reranker<M> for LlmReranker<X>
+where
+    X: Executor + Send + Sync,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync + 'static,
+{
+    async fn score(
+        &self,
+        query: &str,
+        documents: &[Document<M>],
+    ) -> Result<Vec<f32>, RetrieverError> {
+        match self.strategy {
+            RerankStrategy::Pointwise => {
+                let outputs = join_all(documents.iter().map(|document| {
+                    let parameters = Parameters::new_with_text(document.page_content.as_str())
+                        .with("question", query);
+                    async move { self.complete(&self.pointwise, &parameters).await }
+                }))
+                .await;
+                outputs
+                    .into_iter()
+                    .map(|output| Ok(parse_score(&output?).unwrap_or(0.0)))
+                    .collect()
+            }
+            RerankStrategy::Listwise => {
+                let context = documents
+                    .iter()
+                    .enumerate()
+                    .map(|(i, document)| format!("[{}] {}", i + 1, document.page_content))
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                let parameters = Parameters::new()
+                    .with("question", query)
+                    .with("context", context);
+                let output = self.complete(&self.listwise, &parameters).await?;
+                // Ranked documents score from the count down to 1, the ones left out 0.
+                let mut scores = vec![0.0; documents.len()];
+                for (position, i) in find_numbered_references(&output, documents.len())
+                    .into_iter()
+                    .enumerate()
+                {
+                    scores[i] = (documents.len() - position) as f32;
+                }
+                Ok(scores)
+            }
+        }
+    }
+}
+
+/// Fetches candidates from another retriever and returns the best ones according to a reranker.
+pub struct RerankingRetriever<R, K> {
+    inner: R,
+    reranker: K,
+    fetch_k: u32,
+}
+
+impl<R, K> RerankingRetriever<R, K> {
+    /// Creates a retriever that reranks 20 candidates.
+    pub fn new(inner: R, reranker: K) -> Self {
+        Self {
+            inner,
+            reranker,
+            fetch_k: 20,
+        }
+    }
+
+    /// Sets how many candidates are fetched from the inner retriever. At least `limit` are.
+    pub fn with_fetch_k(mut self, fetch_k: u32) -> Self {
+        self.fetch_k = fetch_k;
+        self
+    }
+
+    pub fn reranker(&self) -> &K {
+        &self.reranker
+    }
+}
+
+#[async_trait]
+impl<R, K, M> Retriever<M> for RerankingRetriever<R, K>
+where
+    R: Retriever<M>,
+    K: Reranker<M>,
+    M: serde::Serialize + serde::de::DeserializeOwned + Send + Sync + 'static,
+{
+    async fn retrieve(&self, query: &str, limit: u32) -> Result<Vec<Document<M>>, RetrieverError> {
+        let candidates = self.inner.retrieve(query, self.fetch_k.max(limit)).await?;
+        if candidates.is_empty() {
+            return Ok(candidates);
+        }
+        Ok(self
+            .reranker
+            .rerank(query, candidates)
+            .await?
+            .into_iter()
+            .take(limit as usize)
+            .map(|(document, _)| document)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chains::tests::{ScriptedExecutor, WordStore};
+    use crate::retrieval::VectorStoreRetriever;
+
+    fn documents(texts: &[&str]) -> Vec<Document> {
+        texts.iter().map(|t| Document::new(t.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_score() {
+        assert_eq!(parse_score("Score: 7/10"), Some(7.0));
+        assert_eq!(parse_score("8.5."), Some(8.5));
+        assert_eq!(parse_score("irrelevant"), None);
+    }
+
+    #[tokio::test]
+    async fn test_llm_reranker_strategies() {
+        let documents = documents(&["tea is brewed", "kettles boil water", "bikes"]);
+        let pointwise = LlmReranker::new(ScriptedExecutor {
+            reply: |prompt| if prompt.contains("kettles") { "9" } else { "2" }.to_string(),
+            max_tokens: 1000,
+        });
+        assert_eq!(
+            pointwise.score("boil", &documents).await.unwrap(),
+            vec![2.0, 9.0, 2.0]
+        );
+
+        let listwise = LlmReranker::new(ScriptedExecutor {
+            reply: |_| "[2] > [1]".to_string(),
+            max_tokens: 1000,
+        })
+        .with_strategy(RerankStrategy::Listwise);
+        let reranked = listwise.rerank("boil", documents).await.unwrap();
+        let order: Vec<_> = reranked
+            .iter()
+            .map(|(d, _)| d.page_content.as_str())
+            .collect();
+        assert_eq!(order, vec!["kettles boil water", "tea is brewed", "bikes"]);
+    }
+
+    /// Scores only the first document.
+    struct ShortReranker;
+
+    #[async_trait]
+    impl Reranker for ShortReranker {
+        async fn score(
+            &self,
+            _query: &str,
+            _documents: &[Document],
+        ) -> Result<Vec<f32>, RetrieverError> {
+            Ok(vec![1.0])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rerank_requires_a_score_per_document() {
+        assert!(matches!(
+            ShortReranker.rerank("q", documents(&["a", "b"])).await,
+            Err(RetrieverError::Reranker(_))
+        ));
+        assert_eq!(
+            ShortReranker.rerank("q", documents(&["a"])).await.unwrap()[0].1,
+            1.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reranking_retriever_keeps_the_best_candidates() {
+        let store = WordStore::default();
+        store.0.lock().unwrap().extend([
+            "water for tea".to_string(),
+            "the kettle boils water".to_string(),
+        ]);
+        let retriever = RerankingRetriever::new(
+            VectorStoreRetriever::new(store),
+            LlmReranker::new(ScriptedExecutor {
+                reply: |prompt| if prompt.contains("kettle") { "9" } else { "1" }.to_string(),
+                max_tokens: 1000,
+            }),
+        );
+        let documents = retriever.retrieve("water", 1).await.unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].page_content, "the kettle boils water");
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! This schema is used to store documents in vector stores. It is used to store the document's content and metadata.
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Document<M = EmptyMetadata>
 where
     M: serde::Serialize + serde::de::DeserializeOwned,
@@ -21,9 +21,14 @@ where
             metadata: None,
         }
     }
+
+    pub fn with_metadata(mut self, metadata: M) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct EmptyMetadata;
 
 impl From<()> for EmptyMetadata {
@@ -61,3 +66,41 @@ impl<'de> Deserialize<'de> for EmptyMetadata {
         deserializer.deserialize_unit(EmptyMetadataVisitor)
     }
 }
+
+/// A document together with the id and the vector it is stored under in a vector store.
+///
+/// Records are what [`crate::traits::ExportableVectorStore`] exports and imports, so documents
+/// can move between stores, or be dumped with [`crate::vector_dump`], without being embedded
+/// again.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct VectorRecord<M = EmptyMetadata> {
+    pub id: String,
+    pub vector: Vec<f32>,
+    pub page_content: String,
+    pub metadata: Option<M>,
+}
+
+impl<M> VectorRecord<M>
+where
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    pub fn new(id: String, vector: Vec<f32>, document: Document<M>) -> Self {
+        VectorRecord {
+            id,
+            vector,
+            page_content: document.page_content,
+            metadata: document.metadata,
+        }
+    }
+
+    /// Splits the record into its document and its vector.
+    pub fn into_document(self) -> (Document<M>, Vec<f32>) {
+        (
+            Document {
+                page_content: self.page_content,
+                metadata: self.metadata,
+            },
+            self.vector,
+        )
+    }
+}
@@ -1,21 +1,60 @@
 //! Steps are individual LLM invocations in a chain. They are a combination of a prompt and a configuration.
 //!
 //! Steps are used to set the per-invocation settings for a prompt. Useful when you want to change the settings for a specific prompt in a chain.
+use std::fmt;
+use std::sync::Arc;
+
 use crate::frame::{FormatAndExecuteError, Frame};
 use crate::options::Opt;
 use crate::options::Options;
+use crate::output::parser::OutputParser;
 use crate::output::Output;
 use crate::prompt::{Prompt, StringTemplateError};
 use crate::traits::Executor;
 use crate::{chains::sequential, prompt, Parameters};
 
 use serde::Deserialize;
-use serde::Serialize;
+use serde::{ser::Error as _, Serialize, Serializer};
+
+/// The output parser of a step, shared between its clones.
+#[derive(Clone)]
+pub(crate) struct StepOutputParser(pub(crate) Arc<dyn OutputParser>);
+
+impl fmt::Debug for StepOutputParser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OutputParser")
+    }
+}
+
+/// Output parsers can't be stored, so a step with one fails to serialize instead of coming back
+/// without it.
+fn refuse_output_parser<S: Serializer>(
+    _parser: &Option<StepOutputParser>,
+    _serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Err(S::Error::custom(
+        "a step with an output parser can't be serialized; store it without the parser and set the parser again after loading",
+    ))
+}
+
 #[derive(derive_builder::Builder, Debug, Clone, Serialize, Deserialize)]
 /// A step in a chain of LLM invocations. It is a combination of a prompt and a configuration.
 pub struct Step {
     pub(crate) prompt: prompt::PromptTemplate,
     pub(crate) options: Options,
+    /// The parameter that holds the output of this step for the later steps of a sequential chain.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub(crate) output_key: Option<String>,
+    /// Parses the output of this step before the later steps of a sequential chain see it.
+    #[serde(
+        default,
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "refuse_output_parser"
+    )]
+    #[builder(default, setter(skip))]
+    pub(crate) output_parser: Option<StepOutputParser>,
 }
 
 impl Step {
@@ -23,16 +62,40 @@ impl Step {
         Self {
             prompt,
             options: Options::empty().clone(),
+            output_key: None,
+            output_parser: None,
         }
     }
     pub fn for_prompt_with_streaming(prompt: prompt::PromptTemplate) -> Self {
         let mut options = Options::builder();
         options.add_option(Opt::Stream(true));
         let options = options.build();
-        Self { prompt, options }
+        Self {
+            prompt,
+            options,
+            output_key: None,
+            output_parser: None,
+        }
     }
     pub fn for_prompt_and_options(prompt: prompt::PromptTemplate, options: Options) -> Self {
-        Self { prompt, options }
+        Self {
+            prompt,
+            options,
+            output_key: None,
+            output_parser: None,
+        }
+    }
+    /// Names the output of this step. In a sequential chain every later step can use the output as
+    /// `{{key}}`, and the output can be looked up by `key` in the result of the chain.
+    pub fn with_output_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.output_key = Some(key.into());
+        self
+    }
+    /// Parses the output of this step with `parser` in a sequential chain, before it is passed on
+    /// and returned. A step with an output parser can't be serialized.
+    pub fn with_output_parser<P: OutputParser + 'static>(mut self, parser: P) -> Self {
+        self.output_parser = Some(StepOutputParser(Arc::new(parser)));
+        self
     }
     pub fn prompt(&self) -> &prompt::PromptTemplate {
         &self.prompt
@@ -40,6 +103,12 @@ impl Step {
     pub fn options(&self) -> &Options {
         &self.options
     }
+    pub fn output_key(&self) -> Option<&str> {
+        self.output_key.as_deref()
+    }
+    pub(crate) fn output_parser(&self) -> Option<&dyn OutputParser> {
+        self.output_parser.as_ref().map(|parser| parser.0.as_ref())
+    }
 
     /// Converts this step into a sequential chain with a single step.
     ///
@@ -4,6 +4,7 @@
 
 use crate::{
     chains::map_reduce::{self, MapReduceChainError},
+    chains::refine::{self, RefineChainError},
     frame::FormatAndExecuteError,
     parameters, prompt,
     step::Step,
@@ -13,8 +14,15 @@ use crate::{
 /// A `TextSummarizer` takes a given text and summarizes it using an `Executor`.
 ///
 /// The summarizer is built on top of a `map_reduce::Chain`, which takes care of the summarization process.
+/// Use [`TextSummarizer::refine`] to summarize with a `refine::Chain` instead, which keeps the order of
+/// the text but summarizes one chunk at a time.
 pub struct TextSummarizer {
-    chain: map_reduce::Chain,
+    chain: SummarizerChain,
+}
+
+enum SummarizerChain {
+    MapReduce(map_reduce::Chain),
+    Refine(refine::Chain),
 }
 
 impl Default for TextSummarizer {
@@ -29,7 +37,7 @@ impl Default for TextSummarizer {
         ));
 
         TextSummarizer {
-            chain: map_reduce::Chain::new(map_prompt, reduce_prompt),
+            chain: SummarizerChain::MapReduce(map_reduce::Chain::new(map_prompt, reduce_prompt)),
         }
     }
 }
@@ -39,11 +47,20 @@ impl Default for TextSummarizer {
 pub enum TextSummarizerError {
     #[error("MapReduceChainError: {0}")]
     MapReduceChainError(#[from] MapReduceChainError),
+    #[error("RefineChainError: {0}")]
+    RefineChainError(#[from] RefineChainError),
     #[error("No output was produced")]
     NoOutput,
 }
 
 impl TextSummarizer {
+    /// A summarizer that refines its summary chunk by chunk, in the order of the text.
+    pub fn refine() -> Self {
+        TextSummarizer {
+            chain: SummarizerChain::Refine(refine::Chain::for_summarization()),
+        }
+    }
+
     /// Summarizes the given text using the provided `Executor`.
     ///
     /// Returns the summarized text, or an error if the summarization process fails.
@@ -55,7 +72,13 @@ impl TextSummarizer {
         let params = parameters! {
             "text" => text,
         };
-        let chain_output = self.chain.run(vec![params], parameters!(), exec).await?;
+        let chain = match &self.chain {
+            SummarizerChain::MapReduce(chain) => chain,
+            SummarizerChain::Refine(chain) => {
+                return Ok(chain.run(vec![params], parameters!(), exec).await?.answer);
+            }
+        };
+        let chain_output = chain.run(vec![params], parameters!(), exec).await?;
         chain_output
             .to_immediate()
             .await
@@ -1,3 +1,4 @@
+use super::description::ToolDescription;
 use super::tool::{Tool, ToolError};
 use crate::parsing::{find_yaml, ExtractionError};
 use crate::prompt::StringTemplate;
@@ -86,6 +87,11 @@ where
         serde_yaml::to_string(&output).map_err(|e| e.into())
     }
 
+    /// Returns the descriptions of the available tools, e.g. for executors with native tool use.
+    pub fn descriptions(&self) -> Vec<ToolDescription> {
+        self.tools.iter().map(|t| t.description()).collect()
+    }
+
     /// Generate a YAML-formatted string describing the available tools.
     pub fn describe(&self) -> Result<String, ToolUseError<<T as Tool>::Error>> {
         let des: Vec<_> = self.tools.iter().map(|t| t.description()).collect();
@@ -134,7 +134,6 @@ impl Tool for Scraper {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::test;
 
     #[tokio::test]
     async fn test_scraper_invoke_typed() {
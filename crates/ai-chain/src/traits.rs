@@ -15,7 +15,7 @@ use crate::{
     options::Options,
     output::Output,
     prompt::Prompt,
-    schema::{Document, EmptyMetadata},
+    schema::{Document, EmptyMetadata, VectorRecord},
     tokens::{PromptTokensError, TokenCount, Tokenizer, TokenizerError},
 };
 use async_trait::async_trait;
@@ -160,3 +160,25 @@ where
         limit: u32,
     ) -> Result<Vec<Document<M>>, Self::Error>;
 }
+
+/// A vector store whose documents can be read back with their vectors.
+///
+/// Exported records can be imported into another store, or written with
+/// [`crate::vector_dump`], without calling the embeddings model again. The vectors must come from
+/// the model the target store embeds queries with.
+#[async_trait]
+pub trait ExportableVectorStore<E, M = EmptyMetadata>: VectorStore<E, M>
+where
+    E: Embeddings,
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Every document in the store, with its id and vector.
+    async fn export_records(&self) -> Result<Vec<VectorRecord<M>>, Self::Error>;
+
+    /// Adds `records` with their vectors. Stores that can choose their ids keep the ones of the
+    /// records; the others assign new ones. Returns the ids the records were stored under.
+    async fn import_records(
+        &self,
+        records: Vec<VectorRecord<M>>,
+    ) -> Result<Vec<String>, Self::Error>;
+}
@@ -0,0 +1,268 @@
+//! A portable format for the contents of vector stores.
+//!
+//! A dump is a list of [`VectorRecord`]s: the id, vector, page content and metadata of every
+//! document of a store. Dumps are written as JSON Lines, one record per line, or, with the
+//! `parquet` feature, as Parquet files with an `id`, a `page_content`, a `vector` and a `metadata`
+//! column, the metadata being JSON text.
+//!
+//! Together with [`ExportableVectorStore`], dumps move the documents of a store to another
+//! backend, or snapshot them for tests, without embedding them again:
+//!
+//! ```ignore
+//! let records = hnsw.export_records().await?;
+//! vector_dump::save("corpus.parquet", &records)?;
+//!
+//! let records: Vec<VectorRecord<Source>> = vector_dump::load("corpus.parquet")?;
+//! qdrant.import_records(records).await?;
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use crate::{
+    schema::VectorRecord,
+    traits::{Embeddings, ExportableVectorStore},
+};
+
+#[cfg(feature = "parquet")]
+mod parquet;
+
+#[cfg(feature = "parquet")]
+pub use self::parquet::{read_parquet, write_parquet};
+
+#[derive(Debug, Error)]
+pub enum DumpError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Invalid record on line {line}: {source}")]
+    Json {
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error("Invalid metadata: {0}")]
+    Metadata(serde_json::Error),
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    Arrow(#[from] ai_chain_types::arrow::error::ArrowError),
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    Parquet(#[from] ::parquet::errors::ParquetError),
+    #[error("Invalid dump: {0}")]
+    Invalid(String),
+    #[error("Unknown dump format for {0:?}, expected a .jsonl or a .parquet file")]
+    UnknownFormat(PathBuf),
+    #[error("Parquet dumps need the `parquet` feature of ai-chain")]
+    ParquetDisabled,
+}
+
+/// The file formats of dumps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// JSON Lines, one record per line.
+    Jsonl,
+    /// Parquet, which needs the `parquet` feature.
+    Parquet,
+}
+
+impl DumpFormat {
+    /// The format of `path`, from its extension: `.jsonl` or `.ndjson`, or `.parquet`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "jsonl" | "ndjson" => Some(DumpFormat::Jsonl),
+            "parquet" => Some(DumpFormat::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `records` to `writer` as JSON Lines.
+pub fn write_jsonl<M, W>(records: &[VectorRecord<M>], mut writer: W) -> Result<(), DumpError>
+where
+    M: Serialize,
+    W: Write,
+{
+    for record in records {
+        serde_json::to_writer(&mut writer, record).map_err(|e| {
+            if e.is_io() {
+                DumpError::Io(e.into())
+            } else {
+                DumpError::Metadata(e)
+            }
+        })?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads records written as JSON Lines. Blank lines are skipped.
+pub fn read_jsonl<M, R>(reader: R) -> Result<Vec<VectorRecord<M>>, DumpError>
+where
+    M: DeserializeOwned,
+    R: BufRead,
+{
+    let mut records = Vec::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|source| DumpError::Json {
+            line: n + 1,
+            source,
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn format_of(path: &Path) -> Result<DumpFormat, DumpError> {
+    DumpFormat::from_path(path).ok_or_else(|| DumpError::UnknownFormat(path.to_path_buf()))
+}
+
+/// Writes `records` to `path`, in the format its extension names.
+pub fn save<M, P>(path: P, records: &[VectorRecord<M>]) -> Result<(), DumpError>
+where
+    M: Serialize,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    match format_of(path)? {
+        DumpFormat::Jsonl => write_jsonl(records, BufWriter::new(File::create(path)?)),
+        #[cfg(feature = "parquet")]
+        DumpFormat::Parquet => write_parquet(records, File::create(path)?),
+        #[cfg(not(feature = "parquet"))]
+        DumpFormat::Parquet => Err(DumpError::ParquetDisabled),
+    }
+}
+
+/// Reads the records of the dump at `path`, in the format its extension names.
+pub fn load<M, P>(path: P) -> Result<Vec<VectorRecord<M>>, DumpError>
+where
+    M: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    match format_of(path)? {
+        DumpFormat::Jsonl => read_jsonl(BufReader::new(File::open(path)?)),
+        #[cfg(feature = "parquet")]
+        DumpFormat::Parquet => read_parquet(File::open(path)?),
+        #[cfg(not(feature = "parquet"))]
+        DumpFormat::Parquet => Err(DumpError::ParquetDisabled),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MigrationError<S, T>
+where
+    S: std::error::Error,
+    T: std::error::Error,
+{
+    #[error("Unable to export the records: {0}")]
+    Export(S),
+    #[error("Unable to import the records: {0}")]
+    Import(T),
+}
+
+/// Copies every document of `source`, with its vector, to `target`. Returns the ids of the
+/// documents in `target`.
+pub async fn migrate<E1, E2, M, S, T>(
+    source: &S,
+    target: &T,
+) -> Result<Vec<String>, MigrationError<S::Error, T::Error>>
+where
+    E1: Embeddings,
+    E2: Embeddings,
+    M: Serialize + DeserializeOwned,
+    S: ExportableVectorStore<E1, M>,
+    T: ExportableVectorStore<E2, M>,
+{
+    let records = source
+        .export_records()
+        .await
+        .map_err(MigrationError::Export)?;
+    target
+        .import_records(records)
+        .await
+        .map_err(MigrationError::Import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub(super) struct Source {
+        pub source: String,
+    }
+
+    pub(super) fn records() -> Vec<VectorRecord<Source>> {
+        vec![
+            VectorRecord {
+                id: "1".to_string(),
+                vector: vec![0.5, -1.0, 2.0],
+                page_content: "first".to_string(),
+                metadata: Some(Source {
+                    source: "wiki".to_string(),
+                }),
+            },
+            VectorRecord {
+                id: "2".to_string(),
+                vector: vec![0.0, 0.25, 1.0],
+                page_content: "second\nline".to_string(),
+                metadata: None,
+            },
+        ]
+    }
+
+    pub(super) fn assert_same(a: &[VectorRecord<Source>], b: &[VectorRecord<Source>]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!(
+                (&a.id, &a.vector, &a.page_content, &a.metadata),
+                (&b.id, &b.vector, &b.page_content, &b.metadata)
+            );
+        }
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let mut buffer = Vec::new();
+        write_jsonl(&records(), &mut buffer).unwrap();
+        assert_eq!(buffer.iter().filter(|&&b| b == b'\n').count(), 2);
+
+        let read: Vec<VectorRecord<Source>> = read_jsonl(&buffer[..]).unwrap();
+        assert_same(&read, &records());
+
+        let error = read_jsonl::<Source, _>("\n{\"id\": 3}\n".as_bytes()).unwrap_err();
+        assert!(matches!(error, DumpError::Json { line: 2, .. }));
+
+        // A full buffer fails like a full disk.
+        let mut full = [0u8; 4];
+        let error = write_jsonl(&records(), &mut full[..]).unwrap_err();
+        assert!(matches!(error, DumpError::Io(e) if e.kind() == std::io::ErrorKind::WriteZero));
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            DumpFormat::from_path(Path::new("a/b.ndjson")),
+            Some(DumpFormat::Jsonl)
+        );
+        assert_eq!(
+            DumpFormat::from_path(Path::new("b.parquet")),
+            Some(DumpFormat::Parquet)
+        );
+        assert!(matches!(
+            save::<Source, _>("dump.csv", &[]),
+            Err(DumpError::UnknownFormat(_))
+        ));
+    }
+}
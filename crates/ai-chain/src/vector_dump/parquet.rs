@@ -0,0 +1,124 @@
+//! Parquet dumps, written through the Arrow support of `ai-chain-types`.
+
+use std::{io::Write, sync::Arc};
+
+use ai_chain_types::arrow::{
+    array::{Array, ArrayRef, Float32Array, ListArray, StringArray},
+    datatypes::{DataType, Field, Float32Type, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::Compression,
+    file::{properties::WriterProperties, reader::ChunkReader},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::DumpError;
+use crate::schema::VectorRecord;
+
+/// The number of records per row group.
+const BATCH_ROWS: usize = 8192;
+
+fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("page_content", DataType::Utf8, false),
+        Field::new_list("vector", Field::new("item", DataType::Float32, true), false),
+        Field::new("metadata", DataType::Utf8, true),
+    ]))
+}
+
+/// Writes `records` to `writer` as a Parquet file, compressed with Snappy.
+pub fn write_parquet<M, W>(records: &[VectorRecord<M>], writer: W) -> Result<(), DumpError>
+where
+    M: Serialize,
+    W: Write + Send,
+{
+    let schema = schema();
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(BATCH_ROWS)
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, schema.clone(), Some(properties))?;
+    for batch in records.chunks(BATCH_ROWS) {
+        let metadata = batch
+            .iter()
+            .map(|r| r.metadata.as_ref().map(serde_json::to_string).transpose())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(DumpError::Metadata)?;
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(batch.iter().map(|r| &r.id))),
+            Arc::new(StringArray::from_iter_values(
+                batch.iter().map(|r| &r.page_content),
+            )),
+            Arc::new(ListArray::from_iter_primitive::<Float32Type, _, _>(
+                batch
+                    .iter()
+                    .map(|r| Some(r.vector.iter().copied().map(Some))),
+            )),
+            Arc::new(StringArray::from(metadata)),
+        ];
+        writer.write(&RecordBatch::try_new(schema.clone(), columns)?)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T, DumpError> {
+    batch
+        .column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<T>())
+        .ok_or_else(|| DumpError::Invalid(format!("missing or mistyped column {}", name)))
+}
+
+/// Reads the records of a Parquet dump, such as a `std::fs::File`.
+pub fn read_parquet<M, R>(reader: R) -> Result<Vec<VectorRecord<M>>, DumpError>
+where
+    M: DeserializeOwned,
+    R: ChunkReader + 'static,
+{
+    let mut records = Vec::new();
+    for batch in ParquetRecordBatchReaderBuilder::try_new(reader)?.build()? {
+        let batch = batch?;
+        let ids = column::<StringArray>(&batch, "id")?;
+        let contents = column::<StringArray>(&batch, "page_content")?;
+        let vectors = column::<ListArray>(&batch, "vector")?;
+        let metadata = column::<StringArray>(&batch, "metadata")?;
+        for row in 0..batch.num_rows() {
+            let vector = vectors.value(row);
+            let vector = vector
+                .as_any()
+                .downcast_ref::<Float32Array>()
+                .ok_or_else(|| DumpError::Invalid("vectors are not f32".to_string()))?;
+            records.push(VectorRecord {
+                id: ids.value(row).to_string(),
+                vector: vector.values().to_vec(),
+                page_content: contents.value(row).to_string(),
+                metadata: match metadata.is_null(row) {
+                    true => None,
+                    false => Some(
+                        serde_json::from_str(metadata.value(row)).map_err(DumpError::Metadata)?,
+                    ),
+                },
+            });
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{assert_same, records, Source};
+    use super::*;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("ai-chain-dump-{}.parquet", std::process::id()));
+        super::super::save(&path, &records()).unwrap();
+        let read: Vec<VectorRecord<Source>> = super::super::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_same(&read, &records());
+    }
+}